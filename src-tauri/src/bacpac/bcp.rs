@@ -0,0 +1,1073 @@
+//! Reader and writer for the BCP native format used for table data inside a BACPAC.
+//!
+//! Rows are stored back to back with no delimiters. Each field is written in
+//! the order of the table's non-computed columns, preceded by a length prefix
+//! whose width depends on the column type:
+//!
+//! * fixed-width types: no prefix when the column is NOT NULL, otherwise one
+//!   byte holding the data length, or `0xFF` for NULL
+//! * `char`, `varchar`, `nchar`, `nvarchar`, `binary`, `varbinary`: two bytes
+//! * `text`, `ntext`, `image`: four bytes
+//! * `(max)` types, `xml`, `sql_variant` and CLR types: eight bytes
+//!
+//! An all-ones prefix marks NULL. Values use the same little-endian layouts
+//! as TDS, and non-Unicode text the code page of the column's collation.
+
+use super::model::{Column, SqlType};
+use anyhow::{anyhow, bail, Result};
+use chrono::{
+    DateTime, Duration, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Timelike,
+};
+use encoding_rs::{EncoderResult, Encoding};
+use std::fmt;
+use std::io::{ErrorKind, Read, Write};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    /// Unscaled value and scale; used for `decimal`, `numeric` and money types.
+    Decimal {
+        value: i128,
+        scale: u8,
+    },
+    String(String),
+    Binary(Vec<u8>),
+    Guid(uuid::Uuid),
+    Date(NaiveDate),
+    Time(NaiveTime),
+    DateTime(NaiveDateTime),
+    DateTimeOffset(DateTime<FixedOffset>),
+}
+
+impl Value {
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            Value::Null => serde_json::Value::Null,
+            Value::Bool(b) => serde_json::Value::Bool(*b),
+            Value::Int(i) => serde_json::Value::from(*i),
+            Value::Float(f) => serde_json::Number::from_f64(*f)
+                .map(serde_json::Value::Number)
+                .unwrap_or(serde_json::Value::Null),
+            // Decimals are kept as strings so no precision is lost
+            _ => serde_json::Value::String(self.to_string()),
+        }
+    }
+
+    /// Parses text, as written by `Display`, into a value for `column`.
+    pub fn parse(text: &str, column: &Column) -> Result<Value> {
+        let invalid = || anyhow!("'{}' is not a valid {}", text, column.sql_type.name());
+        let trimmed = text.trim();
+        let value = match column.sql_type {
+            SqlType::Bit => match trimmed.to_ascii_lowercase().as_str() {
+                "1" | "true" => Value::Bool(true),
+                "0" | "false" => Value::Bool(false),
+                _ => return Err(invalid()),
+            },
+            SqlType::TinyInt | SqlType::SmallInt | SqlType::Int | SqlType::BigInt => {
+                Value::Int(trimmed.parse().map_err(|_| invalid())?)
+            }
+            SqlType::Real | SqlType::Float => Value::Float(trimmed.parse().map_err(|_| invalid())?),
+            SqlType::Decimal | SqlType::Numeric | SqlType::Money | SqlType::SmallMoney => {
+                let (value, scale) = parse_decimal(trimmed).ok_or_else(invalid)?;
+                Value::Decimal { value, scale }
+            }
+            SqlType::UniqueIdentifier => {
+                Value::Guid(uuid::Uuid::parse_str(trimmed).map_err(|_| invalid())?)
+            }
+            SqlType::Date => {
+                Value::Date(NaiveDate::parse_from_str(trimmed, "%Y-%m-%d").map_err(|_| invalid())?)
+            }
+            SqlType::Time => Value::Time(
+                NaiveTime::parse_from_str(trimmed, "%H:%M:%S%.f").map_err(|_| invalid())?,
+            ),
+            SqlType::DateTime | SqlType::DateTime2 | SqlType::SmallDateTime => {
+                let parsed = ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"]
+                    .iter()
+                    .find_map(|format| NaiveDateTime::parse_from_str(trimmed, format).ok())
+                    .or_else(|| {
+                        NaiveDate::parse_from_str(trimmed, "%Y-%m-%d")
+                            .ok()
+                            .and_then(|d| d.and_hms_opt(0, 0, 0))
+                    })
+                    .ok_or_else(invalid)?;
+                Value::DateTime(parsed)
+            }
+            SqlType::DateTimeOffset => Value::DateTimeOffset(
+                DateTime::parse_from_rfc3339(trimmed)
+                    .or_else(|_| DateTime::parse_from_str(trimmed, "%Y-%m-%d %H:%M:%S%.f %:z"))
+                    .map_err(|_| invalid())?,
+            ),
+            SqlType::Binary
+            | SqlType::VarBinary
+            | SqlType::Image
+            | SqlType::Timestamp
+            | SqlType::SqlVariant
+            | SqlType::HierarchyId
+            | SqlType::Geography
+            | SqlType::Geometry => {
+                let digits = trimmed
+                    .strip_prefix("0x")
+                    .or_else(|| trimmed.strip_prefix("0X"))
+                    .unwrap_or(trimmed);
+                Value::Binary(hex::decode(digits).map_err(|_| invalid())?)
+            }
+            SqlType::Char
+            | SqlType::VarChar
+            | SqlType::Text
+            | SqlType::NChar
+            | SqlType::NVarChar
+            | SqlType::NText
+            | SqlType::Xml => Value::String(text.to_string()),
+        };
+        Ok(value)
+    }
+}
+
+/// Splits `-12.50` into its unscaled value and scale, `(-1250, 2)`.
+pub fn parse_decimal(text: &str) -> Option<(i128, u8)> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let (int_part, frac_part) = digits.split_once('.').unwrap_or((digits, ""));
+    if int_part.is_empty() && frac_part.is_empty()
+        || !int_part
+            .chars()
+            .chain(frac_part.chars())
+            .all(|c| c.is_ascii_digit())
+        || frac_part.len() > 38
+    {
+        return None;
+    }
+    let unscaled: i128 = format!("{}{}", int_part, frac_part).parse().ok()?;
+    Some((
+        if negative { -unscaled } else { unscaled },
+        frac_part.len() as u8,
+    ))
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => Ok(()),
+            Value::Bool(b) => write!(f, "{}", if *b { 1 } else { 0 }),
+            Value::Int(i) => write!(f, "{}", i),
+            Value::Float(v) => write!(f, "{}", v),
+            Value::Decimal { value, scale } => write!(f, "{}", format_decimal(*value, *scale)),
+            Value::String(s) => write!(f, "{}", s),
+            Value::Binary(bytes) => write!(f, "0x{}", hex::encode_upper(bytes)),
+            Value::Guid(guid) => write!(f, "{}", guid.to_string().to_uppercase()),
+            Value::Date(d) => write!(f, "{}", d.format("%Y-%m-%d")),
+            Value::Time(t) => write!(f, "{}", t.format("%H:%M:%S%.f")),
+            Value::DateTime(dt) => write!(f, "{}", dt.format("%Y-%m-%dT%H:%M:%S%.f")),
+            Value::DateTimeOffset(dt) => write!(f, "{}", dt.format("%Y-%m-%dT%H:%M:%S%.f%:z")),
+        }
+    }
+}
+
+pub fn format_decimal(value: i128, scale: u8) -> String {
+    let digits = value.unsigned_abs().to_string();
+    let scale = scale as usize;
+    let sign = if value < 0 { "-" } else { "" };
+    if scale == 0 {
+        return format!("{}{}", sign, digits);
+    }
+    let padded = format!("{:0>width$}", digits, width = scale + 1);
+    let (int_part, frac_part) = padded.split_at(padded.len() - scale);
+    format!("{}{}.{}", sign, int_part, frac_part)
+}
+
+/// Longest `char(n)`, `varchar(n)`, `binary(n)` or `varbinary(n)` value, in bytes.
+const MAX_IN_ROW_LEN: usize = 8000;
+/// Longest `max`, `text`, `image` or `xml` value, in bytes.
+const MAX_LOB_LEN: usize = i32::MAX as usize;
+
+/// How a single column is laid out in the data file.
+#[derive(Debug, Clone)]
+pub struct FieldLayout {
+    pub sql_type: SqlType,
+    pub prefix_len: usize,
+    pub precision: u8,
+    pub scale: u8,
+    /// Code page of `char`, `varchar` and `text` data.
+    pub encoding: &'static Encoding,
+    /// Longest data a length prefix may announce for the column.
+    pub max_len: usize,
+}
+
+impl FieldLayout {
+    pub fn for_column(column: &Column) -> Result<Self> {
+        let prefix_len = match column.sql_type {
+            SqlType::Char
+            | SqlType::VarChar
+            | SqlType::NChar
+            | SqlType::NVarChar
+            | SqlType::Binary
+            | SqlType::VarBinary
+                if !column.is_max =>
+            {
+                2
+            }
+            SqlType::Char
+            | SqlType::VarChar
+            | SqlType::NChar
+            | SqlType::NVarChar
+            | SqlType::Binary
+            | SqlType::VarBinary
+            | SqlType::Xml
+            | SqlType::SqlVariant
+            | SqlType::HierarchyId
+            | SqlType::Geography
+            | SqlType::Geometry => 8,
+            SqlType::Text | SqlType::NText | SqlType::Image => 4,
+            _ if column.nullable => 1,
+            _ => 0,
+        };
+
+        let mut layout = FieldLayout {
+            sql_type: column.sql_type,
+            prefix_len,
+            precision: column.precision,
+            scale: column.scale,
+            encoding: code_page_encoding(column.code_page),
+            max_len: MAX_LOB_LEN,
+        };
+        layout.max_len = match prefix_len {
+            2 => {
+                let unit = match column.sql_type {
+                    SqlType::NChar | SqlType::NVarChar => 2,
+                    _ => 1,
+                };
+                column.length.map_or(MAX_IN_ROW_LEN, |n| n as usize * unit)
+            }
+            4 | 8 => MAX_LOB_LEN,
+            _ => layout.fixed_len().unwrap_or(u8::MAX as usize),
+        };
+        Ok(layout)
+    }
+
+    /// Shortest data a variable precision date or time type can have.
+    fn min_len(&self) -> Option<usize> {
+        match self.sql_type {
+            SqlType::Time => Some(time_len(0)),
+            SqlType::DateTime2 => Some(time_len(0) + 3),
+            SqlType::DateTimeOffset => Some(time_len(0) + 5),
+            _ => self.fixed_len(),
+        }
+    }
+
+    /// Data length of a fixed-width type.
+    fn fixed_len(&self) -> Option<usize> {
+        let len = match self.sql_type {
+            SqlType::Bit | SqlType::TinyInt => 1,
+            SqlType::SmallInt => 2,
+            SqlType::Int | SqlType::Real | SqlType::SmallMoney | SqlType::SmallDateTime => 4,
+            SqlType::BigInt
+            | SqlType::Float
+            | SqlType::Money
+            | SqlType::DateTime
+            | SqlType::Timestamp => 8,
+            SqlType::UniqueIdentifier => 16,
+            SqlType::Decimal | SqlType::Numeric => 19,
+            SqlType::Date => 3,
+            SqlType::Time => time_len(self.scale),
+            SqlType::DateTime2 => time_len(self.scale) + 3,
+            SqlType::DateTimeOffset => time_len(self.scale) + 5,
+            _ => return None,
+        };
+        Some(len)
+    }
+}
+
+pub fn time_len(scale: u8) -> usize {
+    match scale {
+        0..=2 => 3,
+        3..=4 => 4,
+        _ => 5,
+    }
+}
+
+/// Streams rows out of one or more BCP native data files.
+pub struct BcpReader<R: Read> {
+    reader: R,
+    fields: Vec<FieldLayout>,
+    buffer: Vec<u8>,
+}
+
+impl<R: Read> BcpReader<R> {
+    pub fn new(reader: R, columns: &[&Column]) -> Result<Self> {
+        let fields = columns
+            .iter()
+            .map(|c| FieldLayout::for_column(c))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(BcpReader {
+            reader,
+            fields,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Reads the next row, or `None` at a clean end of file.
+    pub fn next_row(&mut self) -> Result<Option<Vec<Value>>> {
+        let mut row = Vec::with_capacity(self.fields.len());
+        for index in 0..self.fields.len() {
+            let field = self.fields[index].clone();
+            match self.read_field(&field, index == 0)? {
+                Some(value) => row.push(value),
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(row))
+    }
+
+    /// Returns `None` only when `at_row_start` and the file has ended.
+    fn read_field(&mut self, field: &FieldLayout, at_row_start: bool) -> Result<Option<Value>> {
+        let length = if field.prefix_len == 0 {
+            field.fixed_len().ok_or_else(|| {
+                anyhow!(
+                    "{} cannot be stored without a prefix",
+                    field.sql_type.name()
+                )
+            })?
+        } else {
+            let mut prefix = [0u8; 8];
+            let prefix = &mut prefix[..field.prefix_len];
+            if !self.fill(prefix, at_row_start)? {
+                return Ok(None);
+            }
+            let mut raw = [0u8; 8];
+            raw[..prefix.len()].copy_from_slice(prefix);
+            let length = u64::from_le_bytes(raw);
+            let null_marker = if field.prefix_len == 8 {
+                u64::MAX
+            } else {
+                (1u64 << (field.prefix_len * 8)) - 1
+            };
+            if length == null_marker {
+                return Ok(Some(Value::Null));
+            }
+            usize::try_from(length)
+                .ok()
+                .filter(|&length| length <= field.max_len)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Field length {} exceeds the {} bytes a {} column can hold",
+                        length,
+                        field.max_len,
+                        field.sql_type.name()
+                    )
+                })?
+        };
+
+        self.buffer.resize(length, 0);
+        let mut data = std::mem::take(&mut self.buffer);
+        let at_start = at_row_start && field.prefix_len == 0;
+        if !self.fill(&mut data, at_start)? {
+            self.buffer = data;
+            return Ok(None);
+        }
+        let value = decode_value(field, &data);
+        self.buffer = data;
+        value.map(Some)
+    }
+
+    /// Fills `buf` completely. A clean end of file before the first byte is
+    /// only acceptable at the start of a row.
+    fn fill(&mut self, buf: &mut [u8], eof_allowed: bool) -> Result<bool> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            match self.reader.read(&mut buf[filled..]) {
+                Ok(0) if filled == 0 && eof_allowed => return Ok(false),
+                Ok(0) => return Err(anyhow!("Unexpected end of BCP data")),
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Writes rows in BCP native format, the inverse of [`BcpReader`].
+pub struct BcpWriter<W: Write> {
+    writer: W,
+    fields: Vec<FieldLayout>,
+    names: Vec<String>,
+}
+
+impl<W: Write> BcpWriter<W> {
+    pub fn new(writer: W, columns: &[&Column]) -> Result<Self> {
+        let fields = columns
+            .iter()
+            .map(|c| FieldLayout::for_column(c))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(BcpWriter {
+            writer,
+            fields,
+            names: columns.iter().map(|c| c.name.clone()).collect(),
+        })
+    }
+
+    pub fn write_row(&mut self, row: &[Value]) -> Result<()> {
+        if row.len() != self.fields.len() {
+            return Err(anyhow!(
+                "Expected {} values but found {}",
+                self.fields.len(),
+                row.len()
+            ));
+        }
+        for ((field, name), value) in self.fields.iter().zip(&self.names).zip(row) {
+            if value.is_null() {
+                if field.prefix_len == 0 {
+                    return Err(anyhow!("Column {} does not allow NULL", name));
+                }
+                self.writer.write_all(&vec![0xFF; field.prefix_len])?;
+                continue;
+            }
+            let data = encode_value(field, value).map_err(|e| anyhow!("Column {}: {}", name, e))?;
+            if field.prefix_len > 0 {
+                let length = data.len() as u64;
+                if field.prefix_len < 8 && length >= (1u64 << (field.prefix_len * 8)) - 1 {
+                    return Err(anyhow!(
+                        "Column {}: value of {} bytes is too long",
+                        name,
+                        length
+                    ));
+                }
+                self.writer
+                    .write_all(&length.to_le_bytes()[..field.prefix_len])?;
+            }
+            self.writer.write_all(&data)?;
+        }
+        Ok(())
+    }
+}
+
+fn encode_value(field: &FieldLayout, value: &Value) -> Result<Vec<u8>> {
+    let mismatch = || anyhow!("cannot store {:?} as {}", value, field.sql_type.name());
+    let int = |min: i64, max: i64| match value {
+        Value::Int(i) if (min..=max).contains(i) => Ok(*i),
+        Value::Bool(b) => Ok(*b as i64),
+        Value::Int(i) => Err(anyhow!(
+            "{} is out of range for {}",
+            i,
+            field.sql_type.name()
+        )),
+        _ => Err(mismatch()),
+    };
+    let decimal = |scale: u8| match value {
+        Value::Decimal { value, scale: from } => rescale(*value, *from, scale),
+        Value::Int(i) => rescale(*i as i128, 0, scale),
+        _ => Err(mismatch()),
+    };
+
+    let data = match field.sql_type {
+        SqlType::Bit => vec![(int(0, 1)? != 0) as u8],
+        SqlType::TinyInt => vec![int(0, 255)? as u8],
+        SqlType::SmallInt => (int(i16::MIN as i64, i16::MAX as i64)? as i16)
+            .to_le_bytes()
+            .to_vec(),
+        SqlType::Int => (int(i32::MIN as i64, i32::MAX as i64)? as i32)
+            .to_le_bytes()
+            .to_vec(),
+        SqlType::BigInt => int(i64::MIN, i64::MAX)?.to_le_bytes().to_vec(),
+        SqlType::Real | SqlType::Float => {
+            let number = match value {
+                Value::Float(f) => *f,
+                Value::Int(i) => *i as f64,
+                Value::Decimal { value, scale } => format_decimal(*value, *scale).parse()?,
+                _ => return Err(mismatch()),
+            };
+            if field.sql_type == SqlType::Real {
+                (number as f32).to_le_bytes().to_vec()
+            } else {
+                number.to_le_bytes().to_vec()
+            }
+        }
+        SqlType::SmallMoney => i32::try_from(decimal(4)?)?.to_le_bytes().to_vec(),
+        SqlType::Money => {
+            let money = i64::try_from(decimal(4)?)?;
+            let mut data = ((money >> 32) as i32).to_le_bytes().to_vec();
+            data.extend_from_slice(&(money as u32).to_le_bytes());
+            data
+        }
+        SqlType::Decimal | SqlType::Numeric => {
+            let unscaled = decimal(field.scale)?;
+            if !fits_precision(unscaled, field.precision) {
+                bail!(
+                    "{} does not fit in decimal({}, {})",
+                    format_decimal(unscaled, field.scale),
+                    field.precision,
+                    field.scale
+                );
+            }
+            let mut data = vec![field.precision, field.scale, (unscaled >= 0) as u8];
+            data.extend_from_slice(&unscaled.unsigned_abs().to_le_bytes());
+            data
+        }
+        SqlType::UniqueIdentifier => match value {
+            Value::Guid(guid) => guid.to_bytes_le().to_vec(),
+            Value::String(s) => uuid::Uuid::parse_str(s)?.to_bytes_le().to_vec(),
+            _ => return Err(mismatch()),
+        },
+        SqlType::DateTime => {
+            let Value::DateTime(dt) = value else {
+                return Err(mismatch());
+            };
+            // Whole days before or after 1900-01-01, then the time of day,
+            // which stays positive for dates before 1900 too
+            let mut days = (dt.date() - sql_epoch()).num_days();
+            let nanos = dt.time().num_seconds_from_midnight() as i64 * 1_000_000_000
+                + dt.time().nanosecond().min(999_999_999) as i64;
+            let mut ticks = ((nanos as i128 * 3 + 5_000_000) / 10_000_000) as u32;
+            // 23:59:59.999 rounds up to midnight of the next day
+            if ticks >= DATETIME_TICKS_PER_DAY {
+                days += 1;
+                ticks -= DATETIME_TICKS_PER_DAY;
+            }
+            let mut data = i32::try_from(days)?.to_le_bytes().to_vec();
+            data.extend_from_slice(&ticks.to_le_bytes());
+            data
+        }
+        SqlType::SmallDateTime => {
+            let Value::DateTime(dt) = value else {
+                return Err(mismatch());
+            };
+            let since_epoch = *dt - sql_epoch().and_hms_opt(0, 0, 0).expect("valid time");
+            let days = u16::try_from(since_epoch.num_days())?;
+            let minutes = (since_epoch - Duration::days(days as i64)).num_minutes() as u16;
+            let mut data = days.to_le_bytes().to_vec();
+            data.extend_from_slice(&minutes.to_le_bytes());
+            data
+        }
+        SqlType::Date => match value {
+            Value::Date(d) => encode_date(*d),
+            Value::DateTime(dt) => encode_date(dt.date()),
+            _ => return Err(mismatch()),
+        },
+        SqlType::Time => match value {
+            Value::Time(t) => encode_time(*t, field.scale),
+            _ => return Err(mismatch()),
+        },
+        SqlType::DateTime2 => match value {
+            Value::DateTime(dt) => {
+                let mut data = encode_time(dt.time(), field.scale);
+                data.extend(encode_date(dt.date()));
+                data
+            }
+            Value::Date(d) => {
+                let mut data = encode_time(NaiveTime::MIN, field.scale);
+                data.extend(encode_date(*d));
+                data
+            }
+            _ => return Err(mismatch()),
+        },
+        SqlType::DateTimeOffset => {
+            let Value::DateTimeOffset(dt) = value else {
+                return Err(mismatch());
+            };
+            let utc = dt.naive_utc();
+            let mut data = encode_time(utc.time(), field.scale);
+            data.extend(encode_date(utc.date()));
+            data.extend_from_slice(&((dt.offset().local_minus_utc() / 60) as i16).to_le_bytes());
+            data
+        }
+        SqlType::Char | SqlType::VarChar | SqlType::Text => {
+            encode_ansi(&value.to_string(), field.encoding)
+        }
+        SqlType::NChar | SqlType::NVarChar | SqlType::NText | SqlType::Xml => value
+            .to_string()
+            .encode_utf16()
+            .flat_map(|u| u.to_le_bytes())
+            .collect(),
+        SqlType::Binary
+        | SqlType::VarBinary
+        | SqlType::Image
+        | SqlType::Timestamp
+        | SqlType::SqlVariant
+        | SqlType::HierarchyId
+        | SqlType::Geography
+        | SqlType::Geometry => match value {
+            Value::Binary(bytes) => bytes.clone(),
+            _ => return Err(mismatch()),
+        },
+    };
+    Ok(data)
+}
+
+/// Changes the scale of an unscaled decimal, truncating extra digits.
+/// Fails when the result doesn't fit in 128 bits.
+pub fn rescale(value: i128, from: u8, to: u8) -> Result<i128> {
+    if to >= from {
+        10i128
+            .checked_pow((to - from) as u32)
+            .and_then(|factor| value.checked_mul(factor))
+            .ok_or_else(|| {
+                anyhow!(
+                    "{} is too large to keep {} decimal places",
+                    format_decimal(value, from),
+                    to
+                )
+            })
+    } else {
+        Ok(10i128
+            .checked_pow((from - to) as u32)
+            .map_or(0, |factor| value / factor))
+    }
+}
+
+/// Whether an unscaled decimal has at most `precision` digits.
+pub fn fits_precision(value: i128, precision: u8) -> bool {
+    10u128
+        .checked_pow(precision as u32)
+        .is_none_or(|limit| value.unsigned_abs() < limit)
+}
+
+fn encode_date(date: NaiveDate) -> Vec<u8> {
+    let days = (date - NaiveDate::from_ymd_opt(1, 1, 1).expect("valid date")).num_days() as u32;
+    days.to_le_bytes()[..3].to_vec()
+}
+
+fn encode_time(time: NaiveTime, scale: u8) -> Vec<u8> {
+    let nanos = time.num_seconds_from_midnight() as u64 * 1_000_000_000 + time.nanosecond() as u64;
+    let units = nanos / 10u64.pow(9 - scale.min(7) as u32);
+    units.to_le_bytes()[..time_len(scale)].to_vec()
+}
+
+/// Encodes text in a column's code page; characters outside it become `?`,
+/// as SQL Server does.
+fn encode_ansi(text: &str, encoding: &'static Encoding) -> Vec<u8> {
+    let mut encoder = encoding.new_encoder();
+    let mut data = Vec::with_capacity(text.len());
+    let mut rest = text;
+    loop {
+        let needed = encoder
+            .max_buffer_length_from_utf8_without_replacement(rest.len())
+            .unwrap_or(rest.len() * 4);
+        data.reserve(needed);
+        let (result, read) =
+            encoder.encode_from_utf8_to_vec_without_replacement(rest, &mut data, true);
+        rest = &rest[read..];
+        match result {
+            EncoderResult::InputEmpty => return data,
+            EncoderResult::OutputFull => {}
+            EncoderResult::Unmappable(_) => data.push(b'?'),
+        }
+    }
+}
+
+fn decode_value(field: &FieldLayout, data: &[u8]) -> Result<Value> {
+    if let (Some(min), Some(max)) = (field.min_len(), field.fixed_len()) {
+        // Variable precision types may be shorter than their maximum width
+        if !(min..=max).contains(&data.len()) {
+            return Err(anyhow!(
+                "Expected {} bytes for {} but found {}",
+                if min == max {
+                    max.to_string()
+                } else {
+                    format!("{} to {}", min, max)
+                },
+                field.sql_type.name(),
+                data.len()
+            ));
+        }
+    }
+
+    let value = match field.sql_type {
+        SqlType::Bit => Value::Bool(data[0] != 0),
+        SqlType::TinyInt => Value::Int(data[0] as i64),
+        SqlType::SmallInt => Value::Int(i16::from_le_bytes(data.try_into()?) as i64),
+        SqlType::Int => Value::Int(i32::from_le_bytes(data.try_into()?) as i64),
+        SqlType::BigInt => Value::Int(i64::from_le_bytes(data.try_into()?)),
+        SqlType::Real => Value::Float(f32::from_le_bytes(data.try_into()?) as f64),
+        SqlType::Float => Value::Float(f64::from_le_bytes(data.try_into()?)),
+        SqlType::SmallMoney => Value::Decimal {
+            value: i32::from_le_bytes(data.try_into()?) as i128,
+            scale: 4,
+        },
+        SqlType::Money => {
+            let high = i32::from_le_bytes(data[0..4].try_into()?) as i64;
+            let low = u32::from_le_bytes(data[4..8].try_into()?) as i64;
+            Value::Decimal {
+                value: ((high << 32) | low) as i128,
+                scale: 4,
+            }
+        }
+        SqlType::Decimal | SqlType::Numeric => {
+            let scale = data[1];
+            let positive = data[2] == 1;
+            let magnitude = u128::from_le_bytes(data[3..19].try_into()?) as i128;
+            Value::Decimal {
+                value: if positive { magnitude } else { -magnitude },
+                scale,
+            }
+        }
+        SqlType::UniqueIdentifier => Value::Guid(uuid::Uuid::from_bytes_le(data.try_into()?)),
+        SqlType::DateTime => {
+            let days = i32::from_le_bytes(data[0..4].try_into()?);
+            let ticks = u32::from_le_bytes(data[4..8].try_into()?);
+            Value::DateTime(decode_datetime(days, ticks)?)
+        }
+        SqlType::SmallDateTime => {
+            let days = u16::from_le_bytes(data[0..2].try_into()?);
+            let minutes = u16::from_le_bytes(data[2..4].try_into()?);
+            Value::DateTime(
+                sql_epoch()
+                    .and_hms_opt(0, 0, 0)
+                    .ok_or_else(|| anyhow!("Invalid smalldatetime"))?
+                    + Duration::days(days as i64)
+                    + Duration::minutes(minutes as i64),
+            )
+        }
+        SqlType::Date => Value::Date(decode_date(data)?),
+        SqlType::Time => Value::Time(decode_time(data, field.scale)?),
+        SqlType::DateTime2 => {
+            let split = data.len() - 3;
+            let time = decode_time(&data[..split], field.scale)?;
+            let date = decode_date(&data[split..])?;
+            Value::DateTime(date.and_time(time))
+        }
+        SqlType::DateTimeOffset => {
+            let split = data.len() - 5;
+            let time = decode_time(&data[..split], field.scale)?;
+            let date = decode_date(&data[split..split + 3])?;
+            let offset_minutes = i16::from_le_bytes(data[split + 3..].try_into()?);
+            let offset = FixedOffset::east_opt(offset_minutes as i32 * 60)
+                .ok_or_else(|| anyhow!("Invalid time zone offset {}", offset_minutes))?;
+            Value::DateTimeOffset(offset.from_utc_datetime(&date.and_time(time)))
+        }
+        SqlType::Char | SqlType::VarChar | SqlType::Text => {
+            Value::String(decode_ansi(data, field.encoding))
+        }
+        SqlType::NChar | SqlType::NVarChar | SqlType::NText | SqlType::Xml => {
+            Value::String(decode_utf16(data)?)
+        }
+        SqlType::Binary
+        | SqlType::VarBinary
+        | SqlType::Image
+        | SqlType::Timestamp
+        | SqlType::SqlVariant
+        | SqlType::HierarchyId
+        | SqlType::Geography
+        | SqlType::Geometry => Value::Binary(data.to_vec()),
+    };
+    Ok(value)
+}
+
+/// `datetime` time of day is counted in 1/300 of a second.
+const DATETIME_TICKS_PER_DAY: u32 = 300 * 86_400;
+
+/// 1900-01-01, day zero for `datetime` and `smalldatetime`.
+fn sql_epoch() -> NaiveDate {
+    NaiveDate::from_ymd_opt(1900, 1, 1).expect("valid date")
+}
+
+fn decode_datetime(days: i32, ticks: u32) -> Result<NaiveDateTime> {
+    // Ticks are 1/300 of a second
+    let nanos = ticks as i64 * 10_000_000 / 3;
+    Ok(sql_epoch()
+        .and_hms_opt(0, 0, 0)
+        .ok_or_else(|| anyhow!("Invalid datetime"))?
+        + Duration::days(days as i64)
+        + Duration::nanoseconds(nanos))
+}
+
+fn decode_date(data: &[u8]) -> Result<NaiveDate> {
+    let days = u32::from_le_bytes([data[0], data[1], data[2], 0]);
+    NaiveDate::from_ymd_opt(1, 1, 1)
+        .and_then(|d| d.checked_add_signed(Duration::days(days as i64)))
+        .ok_or_else(|| anyhow!("Invalid date value {}", days))
+}
+
+fn decode_time(data: &[u8], scale: u8) -> Result<NaiveTime> {
+    let mut raw = [0u8; 8];
+    raw[..data.len()].copy_from_slice(data);
+    let units = u64::from_le_bytes(raw);
+    let nanos = units * 10u64.pow(9 - scale.min(7) as u32);
+    NaiveTime::from_num_seconds_from_midnight_opt(
+        (nanos / 1_000_000_000) as u32,
+        (nanos % 1_000_000_000) as u32,
+    )
+    .ok_or_else(|| anyhow!("Invalid time value {}", units))
+}
+
+fn decode_utf16(data: &[u8]) -> Result<String> {
+    if !data.len().is_multiple_of(2) {
+        return Err(anyhow!("Odd byte count in Unicode data"));
+    }
+    let units: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    Ok(String::from_utf16_lossy(&units))
+}
+
+/// The decoder for a Windows code page. encoding_rs has no OEM code pages such
+/// as 437 and 850, so their rare SQL_ collations are read as Windows-1252.
+pub fn code_page_encoding(code_page: u16) -> &'static Encoding {
+    match code_page {
+        874 => encoding_rs::WINDOWS_874,
+        932 => encoding_rs::SHIFT_JIS,
+        936 => encoding_rs::GBK,
+        949 => encoding_rs::EUC_KR,
+        950 => encoding_rs::BIG5,
+        1250 => encoding_rs::WINDOWS_1250,
+        1251 => encoding_rs::WINDOWS_1251,
+        1253 => encoding_rs::WINDOWS_1253,
+        1254 => encoding_rs::WINDOWS_1254,
+        1255 => encoding_rs::WINDOWS_1255,
+        1256 => encoding_rs::WINDOWS_1256,
+        1257 => encoding_rs::WINDOWS_1257,
+        1258 => encoding_rs::WINDOWS_1258,
+        65001 => encoding_rs::UTF_8,
+        _ => encoding_rs::WINDOWS_1252,
+    }
+}
+
+fn decode_ansi(data: &[u8], encoding: &'static Encoding) -> String {
+    encoding.decode_without_bom_handling(data).0.into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(name: &str, sql_type: SqlType, nullable: bool) -> Column {
+        Column {
+            name: name.to_string(),
+            sql_type,
+            type_name: sql_type.name().to_string(),
+            length: None,
+            is_max: false,
+            precision: 18,
+            scale: 2,
+            nullable,
+            identity: false,
+            identity_seed: None,
+            identity_increment: None,
+            computed: false,
+            expression: None,
+            collation: None,
+            code_page: 1252,
+            default: None,
+            description: None,
+        }
+    }
+
+    #[test]
+    fn test_decode_rows_with_nulls() {
+        let id = column("Id", SqlType::Int, false);
+        let name = column("Name", SqlType::NVarChar, true);
+        let amount = column("Amount", SqlType::Decimal, true);
+        let columns = vec![&id, &name, &amount];
+
+        let mut data = Vec::new();
+        // Row 1: 7, N'Zoë', 12.34
+        data.extend_from_slice(&7i32.to_le_bytes());
+        let name_bytes: Vec<u8> = "Zoë".encode_utf16().flat_map(|u| u.to_le_bytes()).collect();
+        data.extend_from_slice(&(name_bytes.len() as u16).to_le_bytes());
+        data.extend_from_slice(&name_bytes);
+        data.push(19);
+        data.extend_from_slice(&[18, 2, 1]);
+        data.extend_from_slice(&1234u128.to_le_bytes());
+        // Row 2: 8, NULL, NULL
+        data.extend_from_slice(&8i32.to_le_bytes());
+        data.extend_from_slice(&[0xFF, 0xFF]);
+        data.push(0xFF);
+
+        let mut reader = BcpReader::new(data.as_slice(), &columns).unwrap();
+        let first = reader.next_row().unwrap().unwrap();
+        assert_eq!(first[0], Value::Int(7));
+        assert_eq!(first[1], Value::String("Zoë".to_string()));
+        assert_eq!(first[2].to_string(), "12.34");
+
+        let second = reader.next_row().unwrap().unwrap();
+        assert_eq!(second, vec![Value::Int(8), Value::Null, Value::Null]);
+        assert!(reader.next_row().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_truncated_row_is_an_error() {
+        let id = column("Id", SqlType::BigInt, false);
+        let mut reader = BcpReader::new(&[1u8, 2, 3][..], &[&id]).unwrap();
+        assert!(reader.next_row().is_err());
+
+        // Length prefixes too short for the date part of the value
+        for sql_type in [SqlType::Time, SqlType::DateTime2, SqlType::DateTimeOffset] {
+            let value = column("Value", sql_type, true);
+            let mut reader = BcpReader::new(&[2u8, 0, 0][..], &[&value]).unwrap();
+            let error = reader.next_row().unwrap_err().to_string();
+            assert!(error.contains("but found 2"), "{}", error);
+        }
+
+        // A length prefix beyond the declared varchar(3)
+        let mut code = column("Code", SqlType::VarChar, false);
+        code.length = Some(3);
+        let mut reader = BcpReader::new(&[0x60u8, 0xEA, b'a'][..], &[&code]).unwrap();
+        let error = reader.next_row().unwrap_err().to_string();
+        assert!(error.contains("exceeds the 3 bytes"), "{}", error);
+    }
+
+    #[test]
+    fn test_text_uses_the_collation_code_page() {
+        let mut name = column("Name", SqlType::VarChar, false);
+        name.code_page = 1251;
+        let mut data = Vec::new();
+        let mut writer = BcpWriter::new(&mut data, &[&name]).unwrap();
+        writer
+            .write_row(&[Value::String("Привет, 世界".to_string())])
+            .unwrap();
+        assert_eq!(&data[2..], b"\xcf\xf0\xe8\xe2\xe5\xf2, ??");
+
+        let mut reader = BcpReader::new(data.as_slice(), &[&name]).unwrap();
+        assert_eq!(
+            reader.next_row().unwrap().unwrap(),
+            vec![Value::String("Привет, ??".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_decimal_overflow_is_an_error() {
+        assert_eq!(rescale(12345, 2, 4).unwrap(), 1234500);
+        assert_eq!(rescale(12345, 4, 2).unwrap(), 123);
+        assert!(rescale(i128::MAX / 10, 0, 2).is_err());
+        assert!(rescale(1, 0, 200).is_err());
+        assert_eq!(rescale(1, 200, 0).unwrap(), 0);
+
+        let mut amount = column("Amount", SqlType::Decimal, false);
+        amount.precision = 5;
+        let mut writer = BcpWriter::new(Vec::new(), &[&amount]).unwrap();
+        assert!(writer
+            .write_row(&[Value::Decimal {
+                value: 99999,
+                scale: 2
+            }])
+            .is_ok());
+        assert!(writer
+            .write_row(&[Value::Decimal {
+                value: 100000,
+                scale: 2
+            }])
+            .is_err());
+    }
+
+    #[test]
+    fn test_decode_date_time_types() {
+        let mut datetime = column("Created", SqlType::DateTime, false);
+        datetime.scale = 0;
+        let mut dt2 = column("Updated", SqlType::DateTime2, false);
+        dt2.scale = 7;
+
+        let mut data = Vec::new();
+        // 2000-01-01 00:00:01
+        data.extend_from_slice(&36524i32.to_le_bytes());
+        data.extend_from_slice(&300u32.to_le_bytes());
+        // 0001-01-02 00:00:00.5
+        data.extend_from_slice(&5_000_000u64.to_le_bytes()[..5]);
+        data.extend_from_slice(&[1, 0, 0]);
+
+        let mut reader = BcpReader::new(data.as_slice(), &[&datetime, &dt2]).unwrap();
+        let row = reader.next_row().unwrap().unwrap();
+        assert_eq!(row[0].to_string(), "2000-01-01T00:00:01");
+        assert_eq!(row[1].to_string(), "0001-01-02T00:00:00.500");
+    }
+
+    #[test]
+    fn test_write_then_read_round_trip() {
+        let id = column("Id", SqlType::BigInt, false);
+        let name = column("Name", SqlType::VarChar, true);
+        let amount = column("Amount", SqlType::Money, true);
+        let mut created = column("Created", SqlType::DateTimeOffset, true);
+        created.scale = 3;
+        let columns = vec![&id, &name, &amount, &created];
+
+        let created_at = DateTime::parse_from_rfc3339("2024-02-29T13:45:10.125+02:00").unwrap();
+        let rows = vec![
+            vec![
+                Value::Int(-42),
+                Value::String("Café €5".to_string()),
+                Value::Decimal {
+                    value: -1_234_567,
+                    scale: 2,
+                },
+                Value::DateTimeOffset(created_at),
+            ],
+            vec![Value::Int(1), Value::Null, Value::Null, Value::Null],
+        ];
+
+        let mut data = Vec::new();
+        let mut writer = BcpWriter::new(&mut data, &columns).unwrap();
+        for row in &rows {
+            writer.write_row(row).unwrap();
+        }
+
+        let mut reader = BcpReader::new(data.as_slice(), &columns).unwrap();
+        let first = reader.next_row().unwrap().unwrap();
+        assert_eq!(first[0], rows[0][0]);
+        assert_eq!(first[1], rows[0][1]);
+        assert_eq!(first[2].to_string(), "-12345.6700");
+        assert_eq!(first[3], rows[0][3]);
+        assert_eq!(reader.next_row().unwrap().unwrap(), rows[1]);
+        assert!(reader.next_row().unwrap().is_none());
+
+        let mut writer = BcpWriter::new(Vec::new(), &[&id]).unwrap();
+        assert!(writer.write_row(&[Value::Null]).is_err());
+    }
+
+    #[test]
+    fn test_datetime_rounding_carries_into_the_next_day() {
+        let mut created = column("Created", SqlType::DateTime, false);
+        created.scale = 3;
+        let last_moment = NaiveDate::from_ymd_opt(2024, 12, 31)
+            .unwrap()
+            .and_hms_milli_opt(23, 59, 59, 999)
+            .unwrap();
+        let mut data = Vec::new();
+        let mut writer = BcpWriter::new(&mut data, &[&created]).unwrap();
+        writer.write_row(&[Value::DateTime(last_moment)]).unwrap();
+
+        let mut reader = BcpReader::new(data.as_slice(), &[&created]).unwrap();
+        assert_eq!(
+            reader.next_row().unwrap().unwrap(),
+            vec![Value::DateTime(
+                NaiveDate::from_ymd_opt(2025, 1, 1)
+                    .unwrap()
+                    .and_hms_opt(0, 0, 0)
+                    .unwrap()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_datetime_before_1900_keeps_its_time() {
+        let created = column("Created", SqlType::DateTime, false);
+        let before_epoch = NaiveDate::from_ymd_opt(1899, 12, 31)
+            .unwrap()
+            .and_hms_milli_opt(12, 0, 0, 500)
+            .unwrap();
+        let mut data = Vec::new();
+        let mut writer = BcpWriter::new(&mut data, &[&created]).unwrap();
+        writer.write_row(&[Value::DateTime(before_epoch)]).unwrap();
+        assert_eq!(data[..4], (-1i32).to_le_bytes());
+
+        let mut reader = BcpReader::new(data.as_slice(), &[&created]).unwrap();
+        assert_eq!(
+            reader.next_row().unwrap().unwrap(),
+            vec![Value::DateTime(before_epoch)]
+        );
+    }
+
+    #[test]
+    fn test_format_decimal() {
+        assert_eq!(format_decimal(-5, 2), "-0.05");
+        assert_eq!(format_decimal(123456, 4), "12.3456");
+        assert_eq!(format_decimal(42, 0), "42");
+    }
+}
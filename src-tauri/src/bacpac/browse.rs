@@ -0,0 +1,162 @@
+use super::bcp::Value;
+use super::model::Table;
+use super::BacpacArchive;
+use crate::models::{FilterOperator, RowFilter, TableRowsPage, TableRowsRequest};
+use anyhow::{anyhow, Result};
+
+/// Largest page the browser will return in one call.
+pub const MAX_PAGE_SIZE: u64 = 1000;
+
+pub(crate) struct CompiledFilter {
+    index: usize,
+    operator: FilterOperator,
+    value: String,
+}
+
+impl CompiledFilter {
+    pub(crate) fn matches(&self, row: &[Value]) -> bool {
+        let value = &row[self.index];
+        if value.is_null() {
+            return false;
+        }
+        let text = value.to_string().to_lowercase();
+        match self.operator {
+            FilterOperator::Equals => text == self.value,
+            FilterOperator::Contains => text.contains(&self.value),
+        }
+    }
+}
+
+/// Reads one page of matching rows. Only the rows up to the end of the page
+/// are decoded; the rest of the table is never read.
+pub fn read_page(
+    archive: &mut BacpacArchive,
+    table: &Table,
+    request: &TableRowsRequest,
+) -> Result<TableRowsPage> {
+    let data_columns = table.data_columns();
+    let selected: Vec<usize> = if request.columns.is_empty() {
+        (0..data_columns.len()).collect()
+    } else {
+        request
+            .columns
+            .iter()
+            .map(|name| column_index(table, name))
+            .collect::<Result<_>>()?
+    };
+    let filters = compile_filters(table, &request.filters)?;
+
+    let limit = request.limit.clamp(1, MAX_PAGE_SIZE);
+    let mut matched = 0u64;
+    let mut rows = Vec::new();
+    let mut has_more = false;
+
+    archive.visit_rows(table, |row| {
+        if !filters.iter().all(|f| f.matches(&row)) {
+            return Ok(true);
+        }
+        matched += 1;
+        if matched <= request.offset {
+            return Ok(true);
+        }
+        if rows.len() as u64 == limit {
+            has_more = true;
+            return Ok(false);
+        }
+        rows.push(selected.iter().map(|&i| row[i].to_json()).collect());
+        Ok(true)
+    })?;
+
+    Ok(TableRowsPage {
+        columns: selected
+            .iter()
+            .map(|&i| data_columns[i].name.clone())
+            .collect(),
+        rows,
+        offset: request.offset,
+        has_more,
+    })
+}
+
+pub(crate) fn compile_filters(table: &Table, filters: &[RowFilter]) -> Result<Vec<CompiledFilter>> {
+    filters
+        .iter()
+        .map(|filter| {
+            Ok(CompiledFilter {
+                index: column_index(table, &filter.column)?,
+                operator: filter.operator,
+                value: filter.value.to_lowercase(),
+            })
+        })
+        .collect()
+}
+
+fn column_index(table: &Table, name: &str) -> Result<usize> {
+    table.data_column_index(name).ok_or_else(|| {
+        anyhow!(
+            "Column {} not found in {} (computed columns are not stored in a BACPAC)",
+            name,
+            table.display_name()
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bacpac::model::tests::MODEL_XML;
+    use crate::bacpac::test_support::write_package;
+
+    fn request(offset: u64, limit: u64, filters: Vec<RowFilter>) -> TableRowsRequest {
+        TableRowsRequest {
+            bacpac_path: String::new(),
+            table: "dbo.Customers".to_string(),
+            offset,
+            limit,
+            columns: vec!["Email".to_string()],
+            filters,
+        }
+    }
+
+    #[test]
+    fn test_read_page_with_offset_and_filter() {
+        let dir = tempfile::tempdir().unwrap();
+        let bacpac = dir.path().join("sample.bacpac");
+
+        let mut data = Vec::new();
+        for id in 1..=5i32 {
+            data.extend_from_slice(&id.to_le_bytes());
+            let email: Vec<u8> = format!("user{}@example.com", id)
+                .encode_utf16()
+                .flat_map(|u| u.to_le_bytes())
+                .collect();
+            data.extend_from_slice(&(email.len() as u16).to_le_bytes());
+            data.extend_from_slice(&email);
+            data.push(0xFF);
+        }
+        write_package(
+            &bacpac,
+            MODEL_XML,
+            &[("Data/dbo.Customers/TableData-000-00000.BCP", data)],
+        );
+
+        let mut archive = BacpacArchive::open(&bacpac).unwrap();
+        let model = archive.read_model().unwrap();
+        let table = model.find_table("dbo.Customers").unwrap();
+
+        let page = read_page(&mut archive, table, &request(1, 2, vec![])).unwrap();
+        assert_eq!(page.columns, vec!["Email"]);
+        assert_eq!(page.rows.len(), 2);
+        assert_eq!(page.rows[0][0], "user2@example.com");
+        assert!(page.has_more);
+
+        let filter = RowFilter {
+            column: "email".to_string(),
+            operator: FilterOperator::Contains,
+            value: "USER5".to_string(),
+        };
+        let page = read_page(&mut archive, table, &request(0, 10, vec![filter])).unwrap();
+        assert_eq!(page.rows.len(), 1);
+        assert!(!page.has_more);
+    }
+}
@@ -0,0 +1,683 @@
+//! Builds new BACPACs from data held outside SQL Server: a folder of CSV
+//! files described by a manifest, or a SQLite database.
+
+use super::bcp::{BcpWriter, Value};
+use super::model::{Column, DatabaseModel, ForeignKey, Index, SqlType, Table};
+use super::origin::{self, OriginInfo};
+use super::{sha256_hex, PackageWriter, DATA_PREFIX, METADATA_ENTRY, MODEL_ENTRY, ORIGIN_ENTRY};
+use anyhow::{anyhow, Context, Result};
+use rusqlite::types::ValueRef;
+use rusqlite::{Connection, OpenFlags};
+use serde::Deserialize;
+use std::path::Path;
+
+/// Describes the CSV files of a folder, e.g.
+///
+/// ```json
+/// { "tables": [{ "file": "customers.csv", "name": "Customers",
+///     "primary_key": ["Id"],
+///     "columns": [{ "name": "Id", "type": "int", "nullable": false }] }] }
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct CsvManifest {
+    pub tables: Vec<CsvTable>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CsvTable {
+    pub file: String,
+    #[serde(default = "default_schema")]
+    pub schema: String,
+    pub name: String,
+    pub columns: Vec<CsvColumn>,
+    #[serde(default)]
+    pub primary_key: Vec<String>,
+    #[serde(default)]
+    pub foreign_keys: Vec<CsvForeignKey>,
+    /// Text that stands for NULL, e.g. `NULL` or `\N`. Without it, empty
+    /// fields are NULL except in text columns, which keep the empty string.
+    #[serde(default)]
+    pub null_value: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CsvColumn {
+    pub name: String,
+    /// SQL Server type declaration such as `nvarchar(100)` or `decimal(10, 2)`.
+    #[serde(rename = "type")]
+    pub sql_type: String,
+    #[serde(default = "default_nullable")]
+    pub nullable: bool,
+    #[serde(default)]
+    pub identity: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CsvForeignKey {
+    pub columns: Vec<String>,
+    /// `schema.table` or a table in `dbo`.
+    pub references: String,
+    pub referenced_columns: Vec<String>,
+}
+
+fn default_schema() -> String {
+    "dbo".to_string()
+}
+
+fn default_nullable() -> bool {
+    true
+}
+
+pub struct BuildSummary {
+    pub tables: usize,
+    pub rows: u64,
+}
+
+/// Writes a complete BACPAC for `model`. `write_table` streams each table's
+/// rows into the writer and returns how many it wrote.
+fn write_package(
+    model: &DatabaseModel,
+    database_name: &str,
+    target: &Path,
+    mut write_table: impl FnMut(&Table, &mut BcpWriter<&mut PackageWriter>) -> Result<u64>,
+) -> Result<BuildSummary> {
+    let model_xml = model.to_xml();
+    let mut writer = PackageWriter::create(target)?;
+    let result = (|| -> Result<BuildSummary> {
+        writer.add_entry(MODEL_ENTRY, model_xml.as_bytes())?;
+        writer.add_entry(
+            METADATA_ENTRY,
+            origin::dac_metadata_xml(database_name, "1.0.0.0", None).as_bytes(),
+        )?;
+
+        let mut summary = BuildSummary { tables: 0, rows: 0 };
+        for table in &model.tables {
+            writer.start_entry(&format!(
+                "{}{}/TableData-000-00000.BCP",
+                DATA_PREFIX,
+                table.display_name()
+            ))?;
+            let mut bcp = BcpWriter::new(&mut writer, &table.data_columns())?;
+            summary.rows += write_table(table, &mut bcp)
+                .with_context(|| format!("Failed to write {}", table.display_name()))?;
+            summary.tables += 1;
+        }
+
+        let checksums = [("/model.xml", sha256_hex(model_xml.as_bytes()))];
+        writer.add_entry(
+            ORIGIN_ENTRY,
+            OriginInfo::new_export().to_xml(&checksums).as_bytes(),
+        )?;
+        Ok(summary)
+    })();
+
+    match result {
+        Ok(summary) => {
+            writer.finish()?;
+            Ok(summary)
+        }
+        Err(e) => {
+            writer.abandon();
+            Err(e)
+        }
+    }
+}
+
+fn empty_model(tables: Vec<Table>) -> DatabaseModel {
+    DatabaseModel {
+        schema_provider: None,
+        collation_lcid: Some(1033),
+        collation_case_sensitive: false,
+        tables,
+    }
+}
+
+fn primary_key(table_name: &str, columns: Vec<String>) -> Option<Index> {
+    (!columns.is_empty()).then(|| Index {
+        name: Some(format!("PK_{}", table_name)),
+        columns,
+        unique: true,
+        is_constraint: true,
+        filter: None,
+        included_columns: Vec::new(),
+    })
+}
+
+/// Builds a BACPAC from the CSV files listed in `manifest`. Every file needs
+/// a header row naming its columns.
+pub fn from_csv(
+    csv_dir: &Path,
+    manifest: &CsvManifest,
+    database_name: &str,
+    target: &Path,
+    mut progress: impl FnMut(String),
+) -> Result<BuildSummary> {
+    let mut tables = Vec::new();
+    for entry in &manifest.tables {
+        let columns = entry
+            .columns
+            .iter()
+            .map(|c| {
+                let mut column = Column::from_declaration(&c.name, &c.sql_type, c.nullable)
+                    .map_err(|e| anyhow!("{}.{}: {}", entry.name, c.name, e))?;
+                column.identity = c.identity;
+                Ok(column)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let table = Table {
+            schema: entry.schema.clone(),
+            name: entry.name.clone(),
+            columns,
+            primary_key: primary_key(&entry.name, entry.primary_key.clone()),
+            indexes: Vec::new(),
+            foreign_keys: entry
+                .foreign_keys
+                .iter()
+                .map(|fk| ForeignKey {
+                    name: None,
+                    columns: fk.columns.clone(),
+                    referenced_table: if fk.references.contains('.') {
+                        fk.references.clone()
+                    } else {
+                        format!("dbo.{}", fk.references)
+                    },
+                    referenced_columns: fk.referenced_columns.clone(),
+                })
+                .collect(),
+            description: None,
+        };
+        for column in table.primary_key.iter().flat_map(|pk| &pk.columns) {
+            if table.data_column_index(column).is_none() {
+                return Err(anyhow!(
+                    "Primary key column {} is not a column of {}",
+                    column,
+                    entry.name
+                ));
+            }
+        }
+        tables.push(table);
+    }
+    let model = empty_model(tables);
+
+    write_package(&model, database_name, target, |table, writer| {
+        let entry = manifest
+            .tables
+            .iter()
+            .find(|t| t.schema == table.schema && t.name == table.name)
+            .ok_or_else(|| anyhow!("No manifest entry for {}", table.display_name()))?;
+        let path = csv_dir.join(&entry.file);
+        progress(format!("Loading {}", path.display()));
+
+        let mut reader = csv::Reader::from_path(&path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        let headers = reader.headers()?.clone();
+        let columns = table.data_columns();
+        let positions = columns
+            .iter()
+            .map(|column| {
+                headers
+                    .iter()
+                    .position(|h| h.trim().eq_ignore_ascii_case(&column.name))
+                    .ok_or_else(|| anyhow!("{} has no column {}", entry.file, column.name))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut rows = 0;
+        for record in reader.records() {
+            let record = record?;
+            let line = record.position().map(|p| p.line()).unwrap_or(0);
+            let row = columns
+                .iter()
+                .zip(&positions)
+                .map(|(column, &position)| match record.get(position) {
+                    None => Ok(Value::Null),
+                    Some(text) if entry.null_value.as_deref() == Some(text) => Ok(Value::Null),
+                    Some("") if entry.null_value.is_none() && !column.sql_type.is_string() => {
+                        Ok(Value::Null)
+                    }
+                    Some(text) => {
+                        Value::parse(text, column).and_then(|value| check_length(value, column))
+                    }
+                })
+                .collect::<Result<Vec<_>>>()
+                .with_context(|| format!("{} line {}", entry.file, line))?;
+            writer
+                .write_row(&row)
+                .with_context(|| format!("{} line {}", entry.file, line))?;
+            rows += 1;
+        }
+        Ok(rows)
+    })
+}
+
+/// Rejects a value longer than its column's declared length; SQL Server would
+/// refuse the whole import over it.
+fn check_length(value: Value, column: &Column) -> Result<Value> {
+    let Some(limit) = column.length.filter(|_| !column.is_max) else {
+        return Ok(value);
+    };
+    let (length, unit) = match &value {
+        Value::String(text) if matches!(column.sql_type, SqlType::NChar | SqlType::NVarChar) => {
+            (text.encode_utf16().count(), "characters")
+        }
+        Value::String(text) => (text.chars().count(), "characters"),
+        Value::Binary(bytes) => (bytes.len(), "bytes"),
+        _ => return Ok(value),
+    };
+    if length > limit as usize {
+        return Err(anyhow!(
+            "Column {} allows {} {}, the value has {}",
+            column.name,
+            limit,
+            unit,
+            length
+        ));
+    }
+    Ok(value)
+}
+
+/// Builds a BACPAC from the tables of a SQLite database. Column types are
+/// mapped from the declared SQLite types.
+pub fn from_sqlite(
+    sqlite_path: &Path,
+    table_names: &[String],
+    schema: &str,
+    database_name: &str,
+    target: &Path,
+    mut progress: impl FnMut(String),
+) -> Result<BuildSummary> {
+    let conn = Connection::open_with_flags(sqlite_path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .with_context(|| format!("Failed to open {}", sqlite_path.display()))?;
+
+    let available: Vec<String> = conn
+        .prepare(
+            "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name",
+        )?
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+    let selected: Vec<String> = if table_names.is_empty() {
+        available
+    } else {
+        table_names
+            .iter()
+            .map(|name| {
+                available
+                    .iter()
+                    .find(|t| t.eq_ignore_ascii_case(name))
+                    .cloned()
+                    .ok_or_else(|| anyhow!("Table {} not found in the SQLite database", name))
+            })
+            .collect::<Result<_>>()?
+    };
+
+    let mut tables = Vec::new();
+    for name in &selected {
+        tables.push(sqlite_table(&conn, name, schema, &selected)?);
+    }
+    let model = empty_model(tables);
+
+    write_package(&model, database_name, target, |table, writer| {
+        progress(format!("Loading {}", table.name));
+        let columns = table.data_columns();
+        let select = format!(
+            "SELECT {} FROM {}",
+            columns
+                .iter()
+                .map(|c| quote_sqlite(&c.name))
+                .collect::<Vec<_>>()
+                .join(", "),
+            quote_sqlite(&table.name)
+        );
+        let mut stmt = conn.prepare(&select)?;
+        let mut result = stmt.query([])?;
+        let mut rows = 0;
+        while let Some(row) = result.next()? {
+            let values = columns
+                .iter()
+                .enumerate()
+                .map(|(i, column)| from_sqlite_value(row.get_ref(i)?, column))
+                .collect::<Result<Vec<_>>>()
+                .with_context(|| format!("Row {} of {}", rows + 1, table.name))?;
+            writer.write_row(&values)?;
+            rows += 1;
+        }
+        Ok(rows)
+    })
+}
+
+fn sqlite_table(conn: &Connection, name: &str, schema: &str, selected: &[String]) -> Result<Table> {
+    // (name, declared type, not null, primary key position)
+    let info: Vec<(String, String, bool, i64)> = conn
+        .prepare(&format!("PRAGMA table_info({})", quote_sqlite(name)))?
+        .query_map([], |row| {
+            Ok((row.get(1)?, row.get(2)?, row.get(3)?, row.get(5)?))
+        })?
+        .collect::<Result<_, _>>()?;
+
+    let key = primary_key_columns(&info);
+
+    let columns = info
+        .iter()
+        .map(|(column, declared, not_null, pk)| {
+            let mut column = sqlite_column(column, declared);
+            column.nullable = !*not_null && *pk == 0;
+            column
+        })
+        .collect();
+
+    // One row per column pair; rows sharing an id form one foreign key
+    let mut foreign_keys: Vec<ForeignKey> = Vec::new();
+    let mut last_id = None;
+    let rows: Vec<(i64, i64, String, String, Option<String>)> = conn
+        .prepare(&format!("PRAGMA foreign_key_list({})", quote_sqlite(name)))?
+        .query_map([], |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+            ))
+        })?
+        .collect::<Result<_, _>>()?;
+    for (id, seq, referenced, from, to) in rows {
+        // Constraints to tables that are left out cannot be kept
+        let Some(referenced) = selected
+            .iter()
+            .find(|t| t.eq_ignore_ascii_case(&referenced))
+        else {
+            continue;
+        };
+        if last_id != Some(id) {
+            foreign_keys.push(ForeignKey {
+                name: None,
+                columns: Vec::new(),
+                referenced_table: format!("{}.{}", schema, referenced),
+                referenced_columns: Vec::new(),
+            });
+            last_id = Some(id);
+        }
+        let to = match to {
+            Some(to) => to,
+            // `REFERENCES Parent` without columns means the parent's primary key
+            None => sqlite_primary_key(conn, referenced)?
+                .into_iter()
+                .nth(seq as usize)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Foreign key from {}.{} references {} without naming columns, but {} has no matching primary key column",
+                        name,
+                        from,
+                        referenced,
+                        referenced
+                    )
+                })?,
+        };
+        let foreign_key = foreign_keys.last_mut().expect("pushed above");
+        foreign_key.columns.push(from);
+        foreign_key.referenced_columns.push(to);
+    }
+
+    Ok(Table {
+        schema: schema.to_string(),
+        name: name.to_string(),
+        columns,
+        primary_key: primary_key(name, key),
+        indexes: Vec::new(),
+        foreign_keys,
+        description: None,
+    })
+}
+
+/// Primary key columns in key order, from `PRAGMA table_info` rows.
+fn primary_key_columns(info: &[(String, String, bool, i64)]) -> Vec<String> {
+    let mut key: Vec<(i64, String)> = info
+        .iter()
+        .filter(|(_, _, _, pk)| *pk > 0)
+        .map(|(column, _, _, pk)| (*pk, column.clone()))
+        .collect();
+    key.sort();
+    key.into_iter().map(|(_, column)| column).collect()
+}
+
+fn sqlite_primary_key(conn: &Connection, table: &str) -> Result<Vec<String>> {
+    let info: Vec<(String, String, bool, i64)> = conn
+        .prepare(&format!("PRAGMA table_info({})", quote_sqlite(table)))?
+        .query_map([], |row| {
+            Ok((row.get(1)?, row.get(2)?, row.get(3)?, row.get(5)?))
+        })?
+        .collect::<Result<_, _>>()?;
+    Ok(primary_key_columns(&info))
+}
+
+/// Maps a declared SQLite type to a SQL Server column, following SQLite's
+/// affinity rules for names it does not recognise. Names with NUMERIC
+/// affinity can hold any value, so they become `nvarchar(max)`.
+fn sqlite_column(name: &str, declared: &str) -> Column {
+    let upper = declared.trim().to_ascii_uppercase();
+    let base = upper.split('(').next().unwrap_or("").trim();
+    let length = upper.find('(').map(|start| &upper[start..]);
+    let declaration = match base {
+        "BOOLEAN" | "BOOL" | "BIT" => "bit".to_string(),
+        "TINYINT" | "SMALLINT" | "INT" | "BIGINT" | "UNIQUEIDENTIFIER" | "DATE"
+        | "DATETIMEOFFSET" => base.to_lowercase(),
+        "DATETIME" | "TIMESTAMP" => "datetime2".to_string(),
+        "DATETIME2" => format!("datetime2{}", length.unwrap_or_default()),
+        "TIME" => "time".to_string(),
+        "DECIMAL" | "NUMERIC" if length.is_some() => {
+            format!("decimal{}", length.unwrap_or_default())
+        }
+        "VARCHAR" | "NVARCHAR" | "CHAR" | "NCHAR" => match length {
+            Some(length) => format!("{}{}", base.to_lowercase(), length),
+            // Without a length SQLite keeps text of any size
+            None if base.starts_with('N') => "nvarchar(max)".to_string(),
+            None => "varchar(max)".to_string(),
+        },
+        _ if base.contains("INT") => "bigint".to_string(),
+        _ if base.contains("CHAR") || base.contains("CLOB") || base.contains("TEXT") => {
+            "nvarchar(max)".to_string()
+        }
+        _ if base.contains("BLOB") || base.is_empty() => "varbinary(max)".to_string(),
+        _ if base.contains("REAL") || base.contains("FLOA") || base.contains("DOUB") => {
+            "float".to_string()
+        }
+        _ => "nvarchar(max)".to_string(),
+    };
+    Column::from_declaration(name, &declaration, true).unwrap_or_else(|_| {
+        Column::from_declaration(name, "nvarchar(max)", true).expect("valid declaration")
+    })
+}
+
+fn from_sqlite_value(value: ValueRef, column: &Column) -> Result<Value> {
+    let value = match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(i) => match column.sql_type {
+            SqlType::Bit => Value::Bool(i != 0),
+            SqlType::Float | SqlType::Real => Value::Float(i as f64),
+            t if t.is_string() => Value::String(i.to_string()),
+            _ => Value::Int(i),
+        },
+        ValueRef::Real(f) => match column.sql_type {
+            SqlType::Decimal | SqlType::Numeric => Value::parse(&f.to_string(), column)?,
+            t if t.is_string() => Value::String(f.to_string()),
+            _ => Value::Float(f),
+        },
+        ValueRef::Text(bytes) => Value::parse(&String::from_utf8_lossy(bytes), column)?,
+        ValueRef::Blob(bytes) => Value::Binary(bytes.to_vec()),
+    };
+    Ok(value)
+}
+
+fn quote_sqlite(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bacpac::BacpacArchive;
+
+    fn read_all(archive: &mut BacpacArchive, table: &Table) -> Vec<Vec<Value>> {
+        let mut rows = Vec::new();
+        archive
+            .visit_rows(table, |row| {
+                rows.push(row);
+                Ok(true)
+            })
+            .unwrap();
+        rows
+    }
+
+    #[test]
+    fn test_from_csv() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("people.csv"),
+            "Id,Name,Born\n1,\"Smith, Ann\",1990-04-01\n2,Bo,\n",
+        )
+        .unwrap();
+        let manifest: CsvManifest = serde_json::from_str(
+            r#"{"tables": [{"file": "people.csv", "schema": "hr", "name": "People",
+                "primary_key": ["Id"],
+                "columns": [
+                    {"name": "Id", "type": "int", "nullable": false},
+                    {"name": "Name", "type": "nvarchar(50)"},
+                    {"name": "Born", "type": "date"}
+                ]}]}"#,
+        )
+        .unwrap();
+
+        let target = dir.path().join("people.bacpac");
+        let summary = from_csv(dir.path(), &manifest, "People", &target, |_| {}).unwrap();
+        assert_eq!(summary.rows, 2);
+
+        let mut archive = BacpacArchive::open(&target).unwrap();
+        let model = archive.read_model().unwrap();
+        let table = model.find_table("hr.People").unwrap();
+        assert_eq!(table.primary_key.as_ref().unwrap().columns, vec!["Id"]);
+        let rows = read_all(&mut archive, table);
+        assert_eq!(rows[0][1], Value::String("Smith, Ann".to_string()));
+        assert_eq!(rows[0][2].to_string(), "1990-04-01");
+        assert_eq!(rows[1][2], Value::Null);
+
+        let origin = archive.read_entry_string(ORIGIN_ENTRY).unwrap();
+        let model_xml = archive.read_entry(MODEL_ENTRY).unwrap();
+        assert!(origin.contains(&sha256_hex(&model_xml)));
+    }
+
+    #[test]
+    fn test_from_sqlite() {
+        let dir = tempfile::tempdir().unwrap();
+        let sqlite = dir.path().join("source.sqlite");
+        let conn = Connection::open(&sqlite).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE Parents (Id INTEGER PRIMARY KEY, Name TEXT NOT NULL, Score REAL);
+             CREATE TABLE Children (Id INTEGER PRIMARY KEY, ParentId INTEGER REFERENCES Parents(Id), Price DECIMAL(8, 2));
+             CREATE TABLE Toys (Id INTEGER PRIMARY KEY, ParentId INTEGER REFERENCES Parents, Code VARCHAR(10), Colour NCHAR(5), Notes CHAR, Made DATETIME2);
+             INSERT INTO Parents VALUES (1, 'Ann', 2.5), (2, 'Bo', NULL);
+             INSERT INTO Children VALUES (10, 1, '3.75');
+             INSERT INTO Toys VALUES (20, 2, 'ab-1', 'red', 'Oak', '2024-03-01 10:30:00');",
+        )
+        .unwrap();
+        drop(conn);
+
+        let target = dir.path().join("sqlite.bacpac");
+        let summary = from_sqlite(&sqlite, &[], "dbo", "Family", &target, |_| {}).unwrap();
+        assert_eq!((summary.tables, summary.rows), (3, 4));
+
+        let mut archive = BacpacArchive::open(&target).unwrap();
+        let model = archive.read_model().unwrap();
+        let children = model.find_table("Children").unwrap();
+        assert_eq!(children.columns[2].type_declaration(), "decimal(8, 2)");
+        assert_eq!(children.foreign_keys[0].referenced_table, "dbo.Parents");
+        let rows = read_all(&mut archive, children);
+        assert_eq!(rows[0][2].to_string(), "3.75");
+
+        let parents = model.find_table("Parents").unwrap();
+        assert!(!parents.columns[0].nullable);
+        let rows = read_all(&mut archive, parents);
+        assert_eq!(rows[0][2], Value::Float(2.5));
+
+        let toys = model.find_table("Toys").unwrap();
+        let fk = &toys.foreign_keys[0];
+        assert_eq!(
+            (fk.columns.as_slice(), fk.referenced_columns.as_slice()),
+            (&["ParentId".to_string()][..], &["Id".to_string()][..])
+        );
+        let declarations: Vec<_> = toys.columns[2..]
+            .iter()
+            .map(|c| c.type_declaration())
+            .collect();
+        assert_eq!(
+            declarations,
+            ["varchar(10)", "nchar(5)", "varchar(max)", "datetime2(7)"]
+        );
+        let rows = read_all(&mut archive, toys);
+        assert_eq!(rows[0][5].to_string(), "2024-03-01T10:30:00");
+    }
+
+    #[test]
+    fn test_csv_value_longer_than_its_column() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("codes.csv"), "Code\nab\nabcd\n").unwrap();
+        let manifest: CsvManifest = serde_json::from_str(
+            r#"{"tables": [{"file": "codes.csv", "name": "Codes",
+                "columns": [{"name": "Code", "type": "varchar(3)"}]}]}"#,
+        )
+        .unwrap();
+        let target = dir.path().join("codes.bacpac");
+        let Err(error) = from_csv(dir.path(), &manifest, "Codes", &target, |_| {}) else {
+            panic!("a value longer than varchar(3) was accepted");
+        };
+        assert_eq!(
+            format!("{:#}", error),
+            "Failed to write dbo.Codes: codes.csv line 3: Column Code allows 3 characters, the value has 4"
+        );
+    }
+
+    #[test]
+    fn test_csv_empty_strings_and_null_marker() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("notes.csv"),
+            "Id,Note,Due\n1,,\n2,NULL,NULL\n",
+        )
+        .unwrap();
+        let manifest = |null_value: &str| -> CsvManifest {
+            serde_json::from_str(&format!(
+                r#"{{"tables": [{{"file": "notes.csv", "name": "Notes", {}
+                    "columns": [
+                        {{"name": "Id", "type": "int", "nullable": false}},
+                        {{"name": "Note", "type": "nvarchar(20)"}},
+                        {{"name": "Due", "type": "nvarchar(20)"}}
+                    ]}}]}}"#,
+                null_value
+            ))
+            .unwrap()
+        };
+
+        let target = dir.path().join("default.bacpac");
+        from_csv(dir.path(), &manifest(""), "Notes", &target, |_| {}).unwrap();
+        let mut archive = BacpacArchive::open(&target).unwrap();
+        let model = archive.read_model().unwrap();
+        let rows = read_all(&mut archive, model.find_table("Notes").unwrap());
+        assert_eq!(rows[0][1], Value::String(String::new()));
+        assert_eq!(rows[1][1], Value::String("NULL".to_string()));
+
+        let target = dir.path().join("marked.bacpac");
+        from_csv(
+            dir.path(),
+            &manifest(r#""null_value": "NULL","#),
+            "Notes",
+            &target,
+            |_| {},
+        )
+        .unwrap();
+        let mut archive = BacpacArchive::open(&target).unwrap();
+        let model = archive.read_model().unwrap();
+        let rows = read_all(&mut archive, model.find_table("Notes").unwrap());
+        assert_eq!(rows[0][1], Value::String(String::new()));
+        assert_eq!(rows[1][1], Value::Null);
+        assert_eq!(rows[1][2], Value::Null);
+    }
+}
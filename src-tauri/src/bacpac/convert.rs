@@ -0,0 +1,160 @@
+use super::origin::{self, OriginInfo};
+use super::{
+    is_data_entry, sha256_hex, BacpacArchive, PackageWriter, CONTENT_TYPES_ENTRY, METADATA_ENTRY,
+    MODEL_ENTRY, ORIGIN_ENTRY,
+};
+use anyhow::{anyhow, Result};
+use std::path::Path;
+
+pub struct DacpacOptions {
+    pub dac_name: Option<String>,
+    pub dac_version: Option<String>,
+}
+
+pub struct DacpacSummary {
+    pub copied_entries: usize,
+    pub dropped_data_entries: usize,
+}
+
+/// Builds a schema-only DACPAC from a BACPAC: `model.xml` is kept byte for
+/// byte, `Data/` is dropped and the package metadata is rewritten to describe
+/// a DACPAC.
+pub fn bacpac_to_dacpac(
+    source: &Path,
+    target: &Path,
+    options: &DacpacOptions,
+    mut progress: impl FnMut(String),
+) -> Result<DacpacSummary> {
+    if source == target {
+        return Err(anyhow!(
+            "The DACPAC must be written to a different file than the BACPAC"
+        ));
+    }
+
+    progress(format!("Reading {}", source.display()));
+    let mut archive = BacpacArchive::open(source)?;
+
+    let model = archive.read_entry(MODEL_ENTRY)?;
+    let origin_xml = archive.read_entry_string(ORIGIN_ENTRY)?;
+    let mut origin = OriginInfo::parse(&origin_xml)?;
+
+    let (source_name, source_version) = if archive.has_entry(METADATA_ENTRY) {
+        origin::parse_dac_metadata(&archive.read_entry_string(METADATA_ENTRY)?)?
+    } else {
+        (String::new(), "1.0.0.0".to_string())
+    };
+    let dac_name = options
+        .dac_name
+        .clone()
+        .filter(|name| !name.is_empty())
+        .unwrap_or(source_name);
+    let dac_version = options
+        .dac_version
+        .clone()
+        .filter(|version| !version.is_empty())
+        .unwrap_or(source_version);
+
+    let mut writer = PackageWriter::create(target)?;
+    let result = (|| -> Result<DacpacSummary> {
+        writer.add_entry(MODEL_ENTRY, &model)?;
+
+        let mut summary = DacpacSummary {
+            copied_entries: 1,
+            dropped_data_entries: 0,
+        };
+        for name in archive.entry_names() {
+            if is_data_entry(&name) {
+                summary.dropped_data_entries += 1;
+                continue;
+            }
+            if [
+                MODEL_ENTRY,
+                ORIGIN_ENTRY,
+                METADATA_ENTRY,
+                CONTENT_TYPES_ENTRY,
+            ]
+            .contains(&name.as_str())
+                || name.ends_with('/')
+            {
+                continue;
+            }
+            archive.copy_entry_to(&name, &mut writer)?;
+            summary.copied_entries += 1;
+        }
+        progress(format!(
+            "Dropped {} data entries",
+            summary.dropped_data_entries
+        ));
+
+        writer.add_entry(
+            METADATA_ENTRY,
+            origin::dac_metadata_xml(&dac_name, &dac_version, None).as_bytes(),
+        )?;
+
+        origin.contains_exported_data = false;
+        let checksums = [("/model.xml", sha256_hex(&model))];
+        writer.add_entry(ORIGIN_ENTRY, origin.to_xml(&checksums).as_bytes())?;
+
+        Ok(summary)
+    })();
+
+    match result {
+        Ok(summary) => {
+            writer.finish()?;
+            progress(format!("Wrote {}", target.display()));
+            Ok(summary)
+        }
+        Err(e) => {
+            writer.abandon();
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bacpac::test_support::write_package;
+
+    const MODEL_XML: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<DataSchemaModel FileFormatVersion="1.2" SchemaVersion="2.9" DspName="Microsoft.Data.Tools.Schema.Sql.Sql150DatabaseSchemaProvider" CollationLcid="1033" CollationCaseSensitive="False" xmlns="http://schemas.microsoft.com/sqlserver/dac/Serialization/2012/02">
+  <Model />
+</DataSchemaModel>"#;
+
+    #[test]
+    fn test_bacpac_to_dacpac_drops_data_and_rewrites_origin() {
+        let dir = tempfile::tempdir().unwrap();
+        let bacpac = dir.path().join("sample.bacpac");
+        let dacpac = dir.path().join("sample.dacpac");
+        write_package(
+            &bacpac,
+            MODEL_XML,
+            &[("Data/dbo.Customers/TableData-000-00000.BCP", vec![1, 2, 3])],
+        );
+
+        let options = DacpacOptions {
+            dac_name: None,
+            dac_version: Some("2.0.0.0".to_string()),
+        };
+        let summary = bacpac_to_dacpac(&bacpac, &dacpac, &options, |_| {}).unwrap();
+        assert_eq!(summary.dropped_data_entries, 1);
+
+        let mut output = BacpacArchive::open(&dacpac).unwrap();
+        assert!(!output.entry_names().iter().any(|name| is_data_entry(name)));
+        assert!(output.has_entry(CONTENT_TYPES_ENTRY));
+        assert_eq!(
+            output.read_entry(MODEL_ENTRY).unwrap(),
+            MODEL_XML.as_bytes()
+        );
+
+        let origin_xml = output.read_entry_string(ORIGIN_ENTRY).unwrap();
+        let origin = OriginInfo::parse(&origin_xml).unwrap();
+        assert!(!origin.contains_exported_data);
+        assert!(origin_xml.contains(&sha256_hex(MODEL_XML.as_bytes())));
+
+        let metadata = output.read_entry_string(METADATA_ENTRY).unwrap();
+        let (name, version) = origin::parse_dac_metadata(&metadata).unwrap();
+        assert_eq!(name, "Sample");
+        assert_eq!(version, "2.0.0.0");
+    }
+}
@@ -0,0 +1,568 @@
+//! Dependency graph of the objects in a package model.
+//!
+//! Edges come from the `References` recorded in model.xml, from object names
+//! found in SQL bodies (views, routines, triggers and expressions), and from
+//! containment: a column depends on the table or view it belongs to.
+
+use super::model::{element_type, model_elements, model_node, parse_document, split_name};
+use crate::models::{DependencyEntry, DependencySource, ModelObject};
+use anyhow::Result;
+use roxmltree::Node;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Relationships that only place an object in a schema or give it an owner.
+const IGNORED_RELATIONSHIPS: &[&str] = &["Schema", "Authorizer"];
+
+struct GraphNode {
+    parts: Vec<String>,
+    object_type: String,
+    parent: Option<usize>,
+    children: Vec<usize>,
+}
+
+pub struct DependencyGraph {
+    nodes: Vec<GraphNode>,
+    index: HashMap<Vec<String>, usize>,
+    depends_on: Vec<Vec<(usize, DependencySource)>>,
+    depended_by: Vec<Vec<(usize, DependencySource)>>,
+}
+
+fn name_key(parts: &[String]) -> Vec<String> {
+    parts.iter().map(|p| p.to_lowercase()).collect()
+}
+
+impl DependencyGraph {
+    pub fn parse(xml: &str) -> Result<Self> {
+        let doc = parse_document(xml)?;
+        let model = model_node(&doc)?;
+
+        let mut graph = DependencyGraph {
+            nodes: Vec::new(),
+            index: HashMap::new(),
+            depends_on: Vec::new(),
+            depended_by: Vec::new(),
+        };
+        for element in model_elements(model) {
+            graph.register(element, None);
+        }
+
+        let mut bodies = Vec::new();
+        for element in model_elements(model) {
+            if let Some(owner) = graph.node_for(element) {
+                graph.collect_references(element, owner, &mut bodies);
+            }
+        }
+        for (owner, body) in bodies {
+            for parts in sql_identifiers(&body) {
+                if let Some(target) = graph.resolve_body_name(owner, &parts) {
+                    graph.add_edge(owner, target, DependencySource::SqlBody);
+                }
+            }
+        }
+        Ok(graph)
+    }
+
+    fn node_for(&self, element: Node) -> Option<usize> {
+        let name = element.attribute("Name")?;
+        self.index.get(&name_key(&split_name(name))).copied()
+    }
+
+    /// Adds a node for every named element, nested ones included.
+    fn register(&mut self, element: Node, parent: Option<usize>) {
+        let mut owner = parent;
+        if let (Some(name), Some(object_type)) = (element.attribute("Name"), element_type(element))
+        {
+            let parts = split_name(name);
+            let key = name_key(&parts);
+            if !self.index.contains_key(&key) {
+                let id = self.nodes.len();
+                self.nodes.push(GraphNode {
+                    parts,
+                    object_type: object_type
+                        .strip_prefix("Sql")
+                        .unwrap_or(object_type)
+                        .to_string(),
+                    parent,
+                    children: Vec::new(),
+                });
+                self.depends_on.push(Vec::new());
+                self.depended_by.push(Vec::new());
+                self.index.insert(key, id);
+                if let Some(parent) = parent {
+                    self.nodes[parent].children.push(id);
+                }
+                owner = Some(id);
+            }
+        }
+        for child in element.descendants().skip(1).filter(|n| {
+            n.tag_name().name() == "Element"
+                && n.ancestors()
+                    .skip(1)
+                    .find(|a| a.tag_name().name() == "Element")
+                    == Some(element)
+        }) {
+            self.register(child, owner);
+        }
+    }
+
+    /// Records the references and SQL bodies of `element`, attributing them to `owner`
+    /// until a nested named element takes over.
+    fn collect_references(
+        &mut self,
+        element: Node,
+        owner: usize,
+        bodies: &mut Vec<(usize, String)>,
+    ) {
+        for child in element.children().filter(|n| n.is_element()) {
+            match child.tag_name().name() {
+                "Element" => match self.node_for(child) {
+                    Some(nested) if nested != owner => {
+                        self.add_edge(nested, owner, DependencySource::Containment);
+                        self.collect_references(child, nested, bodies);
+                    }
+                    _ => self.collect_references(child, owner, bodies),
+                },
+                "Relationship" => {
+                    if child
+                        .attribute("Name")
+                        .is_some_and(|name| IGNORED_RELATIONSHIPS.contains(&name))
+                    {
+                        continue;
+                    }
+                    self.collect_references(child, owner, bodies);
+                }
+                "References" => {
+                    if child.attribute("ExternalSource").is_some() {
+                        continue;
+                    }
+                    let target = child
+                        .attribute("Name")
+                        .and_then(|name| self.index.get(&name_key(&split_name(name))).copied());
+                    if let Some(target) = target {
+                        self.add_edge(owner, target, DependencySource::Reference);
+                    }
+                }
+                "Property" => {
+                    let is_script = child
+                        .attribute("Name")
+                        .is_some_and(|name| name.ends_with("Script"));
+                    let body = child
+                        .children()
+                        .find(|n| n.tag_name().name() == "Value")
+                        .and_then(|n| n.text())
+                        .or_else(|| child.attribute("Value"));
+                    if let (true, Some(body)) = (is_script, body) {
+                        bodies.push((owner, body.to_string()));
+                    }
+                }
+                _ => self.collect_references(child, owner, bodies),
+            }
+        }
+    }
+
+    fn add_edge(&mut self, from: usize, to: usize, source: DependencySource) {
+        if from == to || self.depends_on[from].iter().any(|(t, _)| *t == to) {
+            return;
+        }
+        self.depends_on[from].push((to, source));
+        self.depended_by[to].push((from, source));
+    }
+
+    fn is_related(&self, a: usize, b: usize) -> bool {
+        let ancestors = |mut node: usize| {
+            let mut chain = vec![node];
+            while let Some(parent) = self.nodes[node].parent {
+                chain.push(parent);
+                node = parent;
+            }
+            chain
+        };
+        ancestors(a).contains(&b) || ancestors(b).contains(&a)
+    }
+
+    /// Resolves a name found in a SQL body the way SQL Server would: a one-part
+    /// name in the owner's schema and then `dbo`.
+    fn resolve_body_name(&self, owner: usize, parts: &[String]) -> Option<usize> {
+        let key = name_key(parts);
+        let target = match key.as_slice() {
+            [name] => {
+                let schema = self.nodes[owner].parts[0].to_lowercase();
+                self.index
+                    .get(&vec![schema, name.clone()])
+                    .or_else(|| self.index.get(&vec!["dbo".to_string(), name.clone()]))
+                    .copied()
+            }
+            [_, _] | [_, _, _] => self.index.get(&key).copied(),
+            _ => None,
+        }?;
+        (!self.is_related(owner, target)).then_some(target)
+    }
+
+    fn display_name(&self, node: usize) -> String {
+        self.nodes[node].parts.join(".")
+    }
+
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.index.get(&name_key(&split_name(name.trim()))).copied()
+    }
+
+    pub fn object(&self, node: usize) -> ModelObject {
+        ModelObject {
+            name: self.display_name(node),
+            object_type: self.nodes[node].object_type.clone(),
+        }
+    }
+
+    /// Top-level objects, sorted by name.
+    pub fn objects(&self) -> Vec<ModelObject> {
+        let mut objects: Vec<ModelObject> = (0..self.nodes.len())
+            .filter(|&node| self.nodes[node].parent.is_none())
+            .map(|node| self.object(node))
+            .collect();
+        objects.sort_by_key(|o| o.name.to_lowercase());
+        objects
+    }
+
+    fn descendants(&self, node: usize) -> Vec<usize> {
+        let mut found = vec![node];
+        let mut position = 0;
+        while position < found.len() {
+            found.extend(self.nodes[found[position]].children.iter().copied());
+            position += 1;
+        }
+        found
+    }
+
+    /// Everything `node` depends on, including what its columns and other parts depend on.
+    pub fn depends_on(&self, node: usize, max_depth: Option<u32>) -> Vec<DependencyEntry> {
+        let parts = self.descendants(node);
+        self.walk(node, parts, max_depth, |current| {
+            self.descendants(current)
+                .into_iter()
+                .flat_map(|part| self.depends_on[part].iter().copied())
+                .collect()
+        })
+    }
+
+    /// Everything that depends on `node`, directly or through one of its parts.
+    pub fn depended_by(&self, node: usize, max_depth: Option<u32>) -> Vec<DependencyEntry> {
+        self.walk(node, vec![node], max_depth, |current| {
+            self.depended_by[current].clone()
+        })
+    }
+
+    fn walk(
+        &self,
+        start: usize,
+        skip: Vec<usize>,
+        max_depth: Option<u32>,
+        neighbours: impl Fn(usize) -> Vec<(usize, DependencySource)>,
+    ) -> Vec<DependencyEntry> {
+        let mut visited: HashSet<usize> = skip.into_iter().collect();
+        let mut queue = VecDeque::from([(start, 0)]);
+        let mut entries = Vec::new();
+
+        while let Some((current, depth)) = queue.pop_front() {
+            if max_depth.is_some_and(|max| depth >= max) {
+                continue;
+            }
+            for (next, source) in neighbours(current) {
+                if !visited.insert(next) {
+                    continue;
+                }
+                let object = self.object(next);
+                entries.push(DependencyEntry {
+                    name: object.name,
+                    object_type: object.object_type,
+                    depth: depth + 1,
+                    via: self.display_name(current),
+                    source,
+                });
+                queue.push_back((next, depth + 1));
+            }
+        }
+
+        entries.sort_by(|a, b| {
+            a.depth
+                .cmp(&b.depth)
+                .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+        });
+        entries
+    }
+}
+
+/// Multi-part identifiers in a T-SQL body, skipping comments, string literals
+/// and variables.
+fn sql_identifiers(body: &str) -> Vec<Vec<String>> {
+    let chars: Vec<char> = body.chars().collect();
+    let mut names = Vec::new();
+    let mut current: Vec<String> = Vec::new();
+    let mut expecting_part = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let part = match c {
+            '-' if chars.get(i + 1) == Some(&'-') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+                None
+            }
+            '/' if chars.get(i + 1) == Some(&'*') => {
+                let mut depth = 0;
+                while i < chars.len() {
+                    if chars[i] == '/' && chars.get(i + 1) == Some(&'*') {
+                        depth += 1;
+                        i += 2;
+                    } else if chars[i] == '*' && chars.get(i + 1) == Some(&'/') {
+                        depth -= 1;
+                        i += 2;
+                        if depth == 0 {
+                            break;
+                        }
+                    } else {
+                        i += 1;
+                    }
+                }
+                None
+            }
+            '\'' => {
+                i += 1;
+                while i < chars.len() {
+                    if chars[i] == '\'' {
+                        if chars.get(i + 1) == Some(&'\'') {
+                            i += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                    i += 1;
+                }
+                i += 1;
+                None
+            }
+            '[' | '"' => {
+                let close = if c == '[' { ']' } else { '"' };
+                let mut part = String::new();
+                i += 1;
+                while i < chars.len() {
+                    if chars[i] == close {
+                        if chars.get(i + 1) == Some(&close) {
+                            i += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                    part.push(chars[i]);
+                    i += 1;
+                }
+                i += 1;
+                Some(part)
+            }
+            '@' => {
+                while i < chars.len() && (chars[i] == '@' || is_identifier_char(chars[i])) {
+                    i += 1;
+                }
+                None
+            }
+            c if c.is_alphabetic() || c == '_' || c == '#' => {
+                let start = i;
+                while i < chars.len() && is_identifier_char(chars[i]) {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                if chars.get(i) == Some(&'\'') && word.eq_ignore_ascii_case("n") {
+                    // N'unicode literal'
+                    None
+                } else {
+                    Some(word)
+                }
+            }
+            c if c.is_ascii_digit() => {
+                while i < chars.len() && (is_identifier_char(chars[i]) || chars[i] == '.') {
+                    i += 1;
+                }
+                None
+            }
+            '.' if !current.is_empty() => {
+                if expecting_part {
+                    // `db..table` skips the schema
+                    current.push(String::new());
+                }
+                expecting_part = true;
+                i += 1;
+                continue;
+            }
+            _ => {
+                i += 1;
+                None
+            }
+        };
+
+        match part {
+            Some(part) if expecting_part => current.push(part),
+            Some(part) => {
+                finish(&mut current, &mut names);
+                current.push(part);
+            }
+            None => finish(&mut current, &mut names),
+        }
+        expecting_part = false;
+    }
+    finish(&mut current, &mut names);
+    names
+}
+
+/// Keeps a complete name; `db..table` names the default schema of another database.
+fn finish(current: &mut Vec<String>, names: &mut Vec<Vec<String>>) {
+    if !current.is_empty() && current.iter().all(|p| !p.is_empty()) {
+        names.push(std::mem::take(current));
+    }
+    current.clear();
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '#' | '$' | '@')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODEL_XML: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<DataSchemaModel FileFormatVersion="1.2" SchemaVersion="2.9" DspName="Microsoft.Data.Tools.Schema.Sql.Sql150DatabaseSchemaProvider" xmlns="http://schemas.microsoft.com/sqlserver/dac/Serialization/2012/02">
+  <Model>
+    <Element Type="SqlScalarFunction" Name="[dbo].[EmailDomain]">
+      <Property Name="BodyScript">
+        <Value><![CDATA[BEGIN RETURN SUBSTRING(@email, CHARINDEX('@', @email) + 1, 200) END]]></Value>
+      </Property>
+      <Relationship Name="Schema">
+        <Entry><References ExternalSource="BuiltIns" Name="[dbo]" /></Entry>
+      </Relationship>
+    </Element>
+    <Element Type="SqlTable" Name="[dbo].[Customers]">
+      <Relationship Name="Columns">
+        <Entry>
+          <Element Type="SqlSimpleColumn" Name="[dbo].[Customers].[Id]" />
+        </Entry>
+        <Entry>
+          <Element Type="SqlSimpleColumn" Name="[dbo].[Customers].[Email]" />
+        </Entry>
+        <Entry>
+          <Element Type="SqlComputedColumn" Name="[dbo].[Customers].[Domain]">
+            <Property Name="ExpressionScript">
+              <Value><![CDATA[([dbo].[EmailDomain]([Email]))]]></Value>
+            </Property>
+            <Relationship Name="ExpressionDependencies">
+              <Entry><References Name="[dbo].[Customers].[Email]" /></Entry>
+            </Relationship>
+          </Element>
+        </Entry>
+      </Relationship>
+    </Element>
+    <Element Type="SqlTable" Name="[sales].[Orders]">
+      <Relationship Name="Columns">
+        <Entry>
+          <Element Type="SqlSimpleColumn" Name="[sales].[Orders].[CustomerId]" />
+        </Entry>
+      </Relationship>
+    </Element>
+    <Element Type="SqlForeignKeyConstraint" Name="[sales].[FK_Orders_Customers]">
+      <Relationship Name="Columns">
+        <Entry><References Name="[sales].[Orders].[CustomerId]" /></Entry>
+      </Relationship>
+      <Relationship Name="DefiningTable">
+        <Entry><References Name="[sales].[Orders]" /></Entry>
+      </Relationship>
+      <Relationship Name="ForeignColumns">
+        <Entry><References Name="[dbo].[Customers].[Id]" /></Entry>
+      </Relationship>
+      <Relationship Name="ForeignTable">
+        <Entry><References Name="[dbo].[Customers]" /></Entry>
+      </Relationship>
+    </Element>
+    <Element Type="SqlView" Name="[dbo].[CustomerEmails]">
+      <Property Name="QueryScript">
+        <Value><![CDATA[SELECT c.[Email] FROM [dbo].[Customers] AS c]]></Value>
+      </Property>
+      <Relationship Name="QueryDependencies">
+        <Entry><References Name="[dbo].[Customers]" /></Entry>
+        <Entry><References Name="[dbo].[Customers].[Email]" /></Entry>
+      </Relationship>
+    </Element>
+    <Element Type="SqlProcedure" Name="[dbo].[ListEmails]">
+      <Property Name="BodyScript">
+        <Value><![CDATA[
+-- Reads from dbo.Customers through the view
+SELECT Email FROM CustomerEmails WHERE Email <> N'dbo.Orders';
+]]></Value>
+      </Property>
+    </Element>
+  </Model>
+</DataSchemaModel>"#;
+
+    fn names(entries: &[DependencyEntry]) -> Vec<(&str, u32, DependencySource)> {
+        entries
+            .iter()
+            .map(|e| (e.name.as_str(), e.depth, e.source))
+            .collect()
+    }
+
+    #[test]
+    fn test_dependency_graph() {
+        let graph = DependencyGraph::parse(MODEL_XML).unwrap();
+        assert_eq!(graph.objects().len(), 6);
+
+        let email = graph.find("dbo.Customers.[Email]").unwrap();
+        assert_eq!(
+            names(&graph.depended_by(email, None)),
+            vec![
+                ("dbo.CustomerEmails", 1, DependencySource::Reference),
+                ("dbo.Customers.Domain", 1, DependencySource::Reference),
+                ("dbo.ListEmails", 2, DependencySource::SqlBody),
+            ]
+        );
+        assert_eq!(graph.depended_by(email, Some(1)).len(), 2);
+
+        let customers = graph.find("[dbo].[Customers]").unwrap();
+        let dependents = graph.depended_by(customers, None);
+        assert!(dependents
+            .iter()
+            .any(|e| e.name == "sales.FK_Orders_Customers" && e.depth == 1));
+        assert!(dependents
+            .iter()
+            .any(|e| e.name == "dbo.Customers.Id" && e.source == DependencySource::Containment));
+        // The comment and the string literal don't count as references
+        let procedure = graph.find("dbo.ListEmails").unwrap();
+        assert_eq!(
+            names(&graph.depends_on(procedure, None)),
+            vec![
+                ("dbo.CustomerEmails", 1, DependencySource::SqlBody),
+                ("dbo.Customers", 2, DependencySource::Reference),
+                ("dbo.Customers.Email", 2, DependencySource::Reference),
+                ("dbo.EmailDomain", 3, DependencySource::SqlBody),
+            ]
+        );
+
+        // A table depends on what its computed columns call
+        let table_dependencies = graph.depends_on(customers, None);
+        assert_eq!(
+            names(&table_dependencies),
+            vec![("dbo.EmailDomain", 1, DependencySource::SqlBody)]
+        );
+        assert_eq!(table_dependencies[0].via, "dbo.Customers");
+    }
+
+    #[test]
+    fn test_sql_identifiers() {
+        let names = sql_identifiers(
+            "SELECT [a]]b].c, x FROM db..t /* [skip] /* nested */ */ WHERE @v = 'it''s' AND y.z = 1.5",
+        );
+        let joined: Vec<String> = names.iter().map(|n| n.join("|")).collect();
+        assert_eq!(
+            joined,
+            vec!["SELECT", "a]b|c", "x", "FROM", "WHERE", "AND", "y|z"]
+        );
+    }
+}
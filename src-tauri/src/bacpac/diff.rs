@@ -0,0 +1,462 @@
+//! Row-level comparison of one table between two BACPACs.
+//!
+//! Only the primary key and a SHA-256 of each row of the older export are
+//! held in memory, so memory grows with the row count rather than the row
+//! width; the keys of every row must still fit in RAM. Each call
+//! scans both exports; differences are numbered inserted and changed rows
+//! first, in the newer export's order, then deleted rows in the older one's.
+
+use super::bcp::Value;
+use super::model::Table;
+use super::BacpacArchive;
+use crate::models::{ColumnChange, RowChangeKind, RowDiff, TableDiffPage, TableDiffRequest};
+use anyhow::{anyhow, Result};
+use chrono::{Datelike, NaiveDateTime, NaiveTime, Timelike};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+/// Largest page returned in one call.
+pub const MAX_PAGE_SIZE: u64 = 1000;
+
+/// Column positions of one export, lined up with the other export.
+struct Layout {
+    key: Vec<usize>,
+    common: Vec<usize>,
+}
+
+impl Layout {
+    fn new(table: &Table, key_columns: &[String], common: &[String]) -> Result<Self> {
+        let position = |name: &String| {
+            table
+                .data_column_index(name)
+                .ok_or_else(|| anyhow!("Column {} not found in {}", name, table.display_name()))
+        };
+        Ok(Layout {
+            key: key_columns.iter().map(position).collect::<Result<_>>()?,
+            common: common.iter().map(position).collect::<Result<_>>()?,
+        })
+    }
+
+    fn key(&self, row: &[Value]) -> Vec<u8> {
+        let mut key = Vec::new();
+        for &i in &self.key {
+            encode(&row[i], &mut key);
+        }
+        key
+    }
+
+    fn digest(&self, row: &[Value]) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        let mut bytes = Vec::new();
+        for &i in &self.common {
+            bytes.clear();
+            encode(&row[i], &mut bytes);
+            hasher.update(&bytes);
+        }
+        hasher.finalize().into()
+    }
+
+    fn key_json(&self, row: &[Value]) -> Vec<serde_json::Value> {
+        self.key.iter().map(|&i| row[i].to_json()).collect()
+    }
+
+    fn values_json(&self, row: &[Value]) -> Vec<serde_json::Value> {
+        self.common.iter().map(|&i| row[i].to_json()).collect()
+    }
+}
+
+/// Appends a canonical encoding of `value`: a tag byte per variant, then a
+/// fixed-width or length-prefixed body, so equal values always encode alike
+/// and a row's encodings can be concatenated unambiguously. Decimals drop
+/// trailing zeros first, so 1.50 and 1.5 match.
+fn encode(value: &Value, out: &mut Vec<u8>) {
+    fn bytes(out: &mut Vec<u8>, data: &[u8]) {
+        out.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        out.extend_from_slice(data);
+    }
+    fn time(out: &mut Vec<u8>, time: NaiveTime) {
+        out.extend_from_slice(&time.num_seconds_from_midnight().to_le_bytes());
+        out.extend_from_slice(&time.nanosecond().to_le_bytes());
+    }
+    fn datetime(out: &mut Vec<u8>, value: NaiveDateTime) {
+        out.extend_from_slice(&value.date().num_days_from_ce().to_le_bytes());
+        time(out, value.time());
+    }
+    match value {
+        Value::Null => out.push(0),
+        Value::Bool(b) => out.extend_from_slice(&[1, *b as u8]),
+        Value::Int(i) => {
+            out.push(2);
+            out.extend_from_slice(&i.to_le_bytes());
+        }
+        Value::Float(f) => {
+            out.push(3);
+            // -0.0 equals 0.0 and every NaN is the same missing value
+            let f = if *f == 0.0 {
+                0.0
+            } else if f.is_nan() {
+                f64::NAN
+            } else {
+                *f
+            };
+            out.extend_from_slice(&f.to_bits().to_le_bytes());
+        }
+        Value::Decimal { value, scale } => {
+            let (mut value, mut scale) = (*value, *scale);
+            while scale > 0 && value % 10 == 0 {
+                value /= 10;
+                scale -= 1;
+            }
+            out.push(4);
+            out.push(scale);
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        Value::String(text) => {
+            out.push(5);
+            bytes(out, text.as_bytes());
+        }
+        Value::Binary(data) => {
+            out.push(6);
+            bytes(out, data);
+        }
+        Value::Guid(guid) => {
+            out.push(7);
+            out.extend_from_slice(guid.as_bytes());
+        }
+        Value::Date(date) => {
+            out.push(8);
+            out.extend_from_slice(&date.num_days_from_ce().to_le_bytes());
+        }
+        Value::Time(value) => {
+            out.push(9);
+            time(out, *value);
+        }
+        Value::DateTime(value) => {
+            out.push(10);
+            datetime(out, *value);
+        }
+        Value::DateTimeOffset(value) => {
+            out.push(11);
+            datetime(out, value.naive_utc());
+            out.extend_from_slice(&value.offset().local_minus_utc().to_le_bytes());
+        }
+    }
+}
+
+struct LeftRow {
+    position: u64,
+    digest: [u8; 32],
+    seen: bool,
+}
+
+/// Compares `table` in two exports and returns one page of differences.
+pub fn diff_table(
+    left: &mut BacpacArchive,
+    left_table: &Table,
+    right: &mut BacpacArchive,
+    right_table: &Table,
+    request: &TableDiffRequest,
+) -> Result<TableDiffPage> {
+    let primary_key = right_table
+        .primary_key
+        .as_ref()
+        .filter(|pk| !pk.columns.is_empty())
+        .ok_or_else(|| {
+            anyhow!(
+                "{} has no primary key, so its rows cannot be matched",
+                right_table.display_name()
+            )
+        })?;
+    let key_columns = primary_key.columns.clone();
+
+    let left_names: Vec<String> = left_table
+        .data_columns()
+        .iter()
+        .map(|c| c.name.clone())
+        .collect();
+    let right_names: Vec<String> = right_table
+        .data_columns()
+        .iter()
+        .map(|c| c.name.clone())
+        .collect();
+    let contains =
+        |names: &[String], name: &String| names.iter().any(|n| n.eq_ignore_ascii_case(name));
+    let common: Vec<String> = right_names
+        .iter()
+        .filter(|n| contains(&left_names, n))
+        .cloned()
+        .collect();
+    let added_columns = right_names
+        .iter()
+        .filter(|n| !contains(&left_names, n))
+        .cloned()
+        .collect();
+    let removed_columns = left_names
+        .iter()
+        .filter(|n| !contains(&right_names, n))
+        .cloned()
+        .collect();
+
+    let left_layout = Layout::new(left_table, &key_columns, &common)?;
+    let right_layout = Layout::new(right_table, &key_columns, &common)?;
+
+    // Pass 1: fingerprint every row of the older export
+    let mut left_rows: HashMap<Vec<u8>, LeftRow> = HashMap::new();
+    let mut position = 0u64;
+    left.visit_rows(left_table, |row| {
+        let key = left_layout.key(&row);
+        let entry = LeftRow {
+            position,
+            digest: left_layout.digest(&row),
+            seen: false,
+        };
+        position += 1;
+        if left_rows.insert(key, entry).is_some() {
+            return Err(anyhow!(
+                "Duplicate primary key {:?} in {}",
+                left_layout.key_json(&row),
+                request.left_path
+            ));
+        }
+        Ok(true)
+    })?;
+
+    let limit = request.limit.clamp(1, MAX_PAGE_SIZE);
+    let in_page =
+        |index: u64| index >= request.offset && index < request.offset.saturating_add(limit);
+
+    let mut page = TableDiffPage {
+        key_columns,
+        columns: common,
+        added_columns,
+        removed_columns,
+        inserted: 0,
+        deleted: 0,
+        changed: 0,
+        rows: Vec::new(),
+        offset: request.offset,
+        has_more: false,
+    };
+    // Changed rows on the page wait for their old values from pass 3
+    let mut pending: HashMap<Vec<u8>, (usize, Vec<Value>)> = HashMap::new();
+
+    // Pass 2: walk the newer export
+    right.visit_rows(right_table, |row| {
+        let key = right_layout.key(&row);
+        let index = page.inserted + page.changed;
+        match left_rows.get_mut(&key) {
+            None => {
+                if in_page(index) {
+                    page.rows.push(RowDiff {
+                        kind: RowChangeKind::Inserted,
+                        key: right_layout.key_json(&row),
+                        values: right_layout.values_json(&row),
+                        changes: Vec::new(),
+                    });
+                }
+                page.inserted += 1;
+            }
+            Some(existing) => {
+                if existing.seen {
+                    return Err(anyhow!(
+                        "Duplicate primary key {:?} in {}",
+                        right_layout.key_json(&row),
+                        request.right_path
+                    ));
+                }
+                existing.seen = true;
+                if existing.digest != right_layout.digest(&row) {
+                    if in_page(index) {
+                        page.rows.push(RowDiff {
+                            kind: RowChangeKind::Changed,
+                            key: right_layout.key_json(&row),
+                            values: Vec::new(),
+                            changes: Vec::new(),
+                        });
+                        pending.insert(key, (page.rows.len() - 1, row));
+                    }
+                    page.changed += 1;
+                }
+            }
+        }
+        Ok(true)
+    })?;
+
+    let mut deleted: Vec<(u64, Vec<u8>)> = left_rows
+        .into_iter()
+        .filter(|(_, row)| !row.seen)
+        .map(|(key, row)| (row.position, key))
+        .collect();
+    deleted.sort_unstable();
+    page.deleted = deleted.len() as u64;
+
+    let first_deleted = page.inserted + page.changed;
+    let mut deleted_on_page: HashMap<Vec<u8>, usize> = HashMap::new();
+    for (n, (_, key)) in deleted.into_iter().enumerate() {
+        if in_page(first_deleted + n as u64) {
+            deleted_on_page.insert(key, deleted_on_page.len());
+        }
+    }
+    let mut deleted_rows: Vec<Option<RowDiff>> = (0..deleted_on_page.len()).map(|_| None).collect();
+
+    // Pass 3: fetch old values for the rows on this page
+    if !pending.is_empty() || !deleted_on_page.is_empty() {
+        let mut remaining = pending.len() + deleted_on_page.len();
+        left.visit_rows(left_table, |row| {
+            let key = left_layout.key(&row);
+            if let Some((slot, new_row)) = pending.remove(&key) {
+                page.rows[slot].changes = page
+                    .columns
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(n, column)| {
+                        let old = &row[left_layout.common[n]];
+                        let new = &new_row[right_layout.common[n]];
+                        (old != new).then(|| ColumnChange {
+                            column: column.clone(),
+                            old_value: old.to_json(),
+                            new_value: new.to_json(),
+                        })
+                    })
+                    .collect();
+                remaining -= 1;
+            } else if let Some(&slot) = deleted_on_page.get(&key) {
+                deleted_rows[slot] = Some(RowDiff {
+                    kind: RowChangeKind::Deleted,
+                    key: left_layout.key_json(&row),
+                    values: left_layout.values_json(&row),
+                    changes: Vec::new(),
+                });
+                remaining -= 1;
+            }
+            Ok(remaining > 0)
+        })?;
+    }
+    page.rows.extend(deleted_rows.into_iter().flatten());
+
+    page.has_more =
+        request.offset.saturating_add(limit) < page.inserted + page.changed + page.deleted;
+    Ok(page)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bacpac::model::tests::MODEL_XML;
+    use crate::bacpac::test_support::write_package;
+
+    fn customers(rows: &[(i32, &str)]) -> Vec<u8> {
+        let mut data = Vec::new();
+        for (id, email) in rows {
+            data.extend_from_slice(&id.to_le_bytes());
+            let email: Vec<u8> = email.encode_utf16().flat_map(|u| u.to_le_bytes()).collect();
+            data.extend_from_slice(&(email.len() as u16).to_le_bytes());
+            data.extend_from_slice(&email);
+            data.push(0xFF);
+        }
+        data
+    }
+
+    #[test]
+    fn test_encoding_is_canonical() {
+        let encoded = |values: &[Value]| {
+            let mut out = Vec::new();
+            values.iter().for_each(|value| encode(value, &mut out));
+            out
+        };
+        let decimal = |value, scale| Value::Decimal { value, scale };
+        assert_eq!(encoded(&[decimal(150, 2)]), encoded(&[decimal(15, 1)]));
+        assert_ne!(encoded(&[decimal(15, 1)]), encoded(&[decimal(15, 0)]));
+        assert_ne!(
+            encoded(&[Value::Int(1)]),
+            encoded(&[Value::String("1".into())])
+        );
+        let text = |s: &str| Value::String(s.to_string());
+        assert_ne!(
+            encoded(&[text("a"), text("bc")]),
+            encoded(&[text("ab"), text("c")])
+        );
+        assert_eq!(
+            encoded(&[Value::Float(-0.0)]),
+            encoded(&[Value::Float(0.0)])
+        );
+    }
+
+    #[test]
+    fn test_diff_table() {
+        let dir = tempfile::tempdir().unwrap();
+        let left_path = dir.path().join("left.bacpac");
+        let right_path = dir.path().join("right.bacpac");
+        let entry = "Data/dbo.Customers/TableData-000-00000.BCP";
+        write_package(
+            &left_path,
+            MODEL_XML,
+            &[(
+                entry,
+                customers(&[(1, "a@b.com"), (2, "c@d.com"), (3, "e@f.com")]),
+            )],
+        );
+        write_package(
+            &right_path,
+            MODEL_XML,
+            &[(
+                entry,
+                customers(&[(1, "a@b.com"), (3, "new@f.com"), (4, "g@h.com")]),
+            )],
+        );
+
+        let mut left = BacpacArchive::open(&left_path).unwrap();
+        let mut right = BacpacArchive::open(&right_path).unwrap();
+        let left_model = left.read_model().unwrap();
+        let right_model = right.read_model().unwrap();
+        let table = "dbo.Customers";
+        let mut request = TableDiffRequest {
+            left_path: String::new(),
+            right_path: String::new(),
+            table: table.to_string(),
+            offset: 0,
+            limit: 10,
+        };
+
+        let page = diff_table(
+            &mut left,
+            left_model.find_table(table).unwrap(),
+            &mut right,
+            right_model.find_table(table).unwrap(),
+            &request,
+        )
+        .unwrap();
+        assert_eq!((page.inserted, page.changed, page.deleted), (1, 1, 1));
+        assert_eq!(page.rows[0].kind, RowChangeKind::Changed);
+        assert_eq!(page.rows[0].changes[0].column, "Email");
+        assert_eq!(page.rows[0].changes[0].new_value, "new@f.com");
+        assert_eq!(page.rows[1].kind, RowChangeKind::Inserted);
+        assert_eq!(page.rows[2].kind, RowChangeKind::Deleted);
+        assert_eq!(page.rows[2].key, vec![serde_json::json!(2)]);
+        assert!(!page.has_more);
+
+        request.offset = 2;
+        request.limit = 1;
+        let page = diff_table(
+            &mut left,
+            left_model.find_table(table).unwrap(),
+            &mut right,
+            right_model.find_table(table).unwrap(),
+            &request,
+        )
+        .unwrap();
+        assert_eq!(page.rows.len(), 1);
+        assert_eq!(page.rows[0].kind, RowChangeKind::Deleted);
+
+        request.offset = u64::MAX;
+        let page = diff_table(
+            &mut left,
+            left_model.find_table(table).unwrap(),
+            &mut right,
+            right_model.find_table(table).unwrap(),
+            &request,
+        )
+        .unwrap();
+        assert!(page.rows.is_empty() && !page.has_more);
+    }
+}
@@ -0,0 +1,593 @@
+//! Data dictionary and ER diagram generation from a package model.
+
+use super::escape_xml;
+use super::model::{DatabaseModel, Table};
+use std::collections::{HashMap, HashSet};
+
+/// Tables of the model in the given schemas, or all tables.
+pub fn tables_in_schemas<'a>(model: &'a DatabaseModel, schemas: &[String]) -> Vec<&'a Table> {
+    let mut tables: Vec<&Table> = model
+        .tables
+        .iter()
+        .filter(|t| schemas.is_empty() || schemas.iter().any(|s| s.eq_ignore_ascii_case(&t.schema)))
+        .collect();
+    tables.sort_by_key(|t| (t.schema.to_lowercase(), t.name.to_lowercase()));
+    tables
+}
+
+struct ColumnRow {
+    name: String,
+    data_type: String,
+    nullable: &'static str,
+    default: String,
+    key: String,
+    description: String,
+}
+
+fn column_rows(table: &Table) -> Vec<ColumnRow> {
+    let primary_key: HashSet<&str> = table
+        .primary_key
+        .iter()
+        .flat_map(|pk| pk.columns.iter().map(|c| c.as_str()))
+        .collect();
+    let foreign: HashSet<&str> = table
+        .foreign_keys
+        .iter()
+        .flat_map(|fk| fk.columns.iter().map(|c| c.as_str()))
+        .collect();
+
+    table
+        .columns
+        .iter()
+        .map(|column| {
+            let mut keys = Vec::new();
+            if primary_key.contains(column.name.as_str()) {
+                keys.push("PK");
+            }
+            if foreign.contains(column.name.as_str()) {
+                keys.push("FK");
+            }
+            let mut data_type = if column.computed {
+                format!("AS {}", column.expression.as_deref().unwrap_or(""))
+            } else {
+                column.type_declaration()
+            };
+            if column.identity {
+                data_type.push_str(" IDENTITY");
+            }
+            ColumnRow {
+                name: column.name.clone(),
+                data_type,
+                nullable: if column.nullable { "Yes" } else { "No" },
+                default: column.default.clone().unwrap_or_default(),
+                key: keys.join(", "),
+                description: column.description.clone().unwrap_or_default(),
+            }
+        })
+        .collect()
+}
+
+fn index_rows(table: &Table) -> Vec<[String; 3]> {
+    table
+        .primary_key
+        .iter()
+        .chain(&table.indexes)
+        .map(|index| {
+            let kind = match (index.is_constraint, index.unique) {
+                (true, _)
+                    if table
+                        .primary_key
+                        .as_ref()
+                        .is_some_and(|pk| std::ptr::eq(pk, index)) =>
+                {
+                    "Primary key"
+                }
+                (true, _) => "Unique constraint",
+                (false, true) => "Unique index",
+                (false, false) => "Index",
+            };
+            [
+                index.name.clone().unwrap_or_default(),
+                index.columns.join(", "),
+                kind.to_string(),
+            ]
+        })
+        .collect()
+}
+
+fn foreign_key_rows(table: &Table) -> Vec<[String; 3]> {
+    table
+        .foreign_keys
+        .iter()
+        .map(|fk| {
+            [
+                fk.name.clone().unwrap_or_default(),
+                fk.columns.join(", "),
+                format!(
+                    "{} ({})",
+                    fk.referenced_table,
+                    fk.referenced_columns.join(", ")
+                ),
+            ]
+        })
+        .collect()
+}
+
+fn markdown_cell(text: &str) -> String {
+    text.replace('|', "\\|")
+        .replace("\r\n", "<br>")
+        .replace('\n', "<br>")
+}
+
+/// Heading slug as GitHub derives it: lower case, punctuation other than
+/// `-` and `_` dropped, spaces turned into hyphens.
+fn slug(heading: &str) -> String {
+    heading
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | ' '))
+        .map(|c| if c == ' ' { '-' } else { c })
+        .collect()
+}
+
+/// Anchors of the table headings, numbered like GitHub does when slugs repeat.
+fn anchors(database_name: &str, tables: &[&Table]) -> Vec<String> {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut unique = |heading: &str| {
+        let slug = slug(heading);
+        let count = seen.entry(slug.clone()).or_insert(0);
+        *count += 1;
+        match *count {
+            1 => slug,
+            n => format!("{}-{}", slug, n - 1),
+        }
+    };
+    unique(&format!("Data dictionary: {}", database_name));
+    unique("Entity relationships");
+    tables
+        .iter()
+        .map(|table| unique(&table.display_name()))
+        .collect()
+}
+
+pub fn markdown(database_name: &str, tables: &[&Table], er_diagram: &str) -> String {
+    let anchors = anchors(database_name, tables);
+    let mut md = format!("# Data dictionary: {}\n\n", database_name);
+    md.push_str(&format!("{} tables.\n\n", tables.len()));
+    for (table, anchor) in tables.iter().zip(&anchors) {
+        md.push_str(&format!("- [{}](#{})\n", table.display_name(), anchor));
+    }
+
+    md.push_str("\n## Entity relationships\n\n```mermaid\n");
+    md.push_str(er_diagram);
+    md.push_str("```\n");
+
+    for table in tables {
+        md.push_str(&format!("\n## {}\n\n", table.display_name()));
+        if let Some(description) = &table.description {
+            md.push_str(&format!("{}\n\n", description));
+        }
+
+        md.push_str("| Column | Type | Nullable | Default | Key | Description |\n");
+        md.push_str("|---|---|---|---|---|---|\n");
+        for row in column_rows(table) {
+            md.push_str(&format!(
+                "| {} | {} | {} | {} | {} | {} |\n",
+                markdown_cell(&row.name),
+                markdown_cell(&row.data_type),
+                row.nullable,
+                markdown_cell(&row.default),
+                row.key,
+                markdown_cell(&row.description)
+            ));
+        }
+
+        let indexes = index_rows(table);
+        if !indexes.is_empty() {
+            md.push_str("\n**Indexes**\n\n| Name | Columns | Kind |\n|---|---|---|\n");
+            for [name, columns, kind] in indexes {
+                md.push_str(&format!(
+                    "| {} | {} | {} |\n",
+                    markdown_cell(&name),
+                    markdown_cell(&columns),
+                    kind
+                ));
+            }
+        }
+
+        let foreign_keys = foreign_key_rows(table);
+        if !foreign_keys.is_empty() {
+            md.push_str("\n**Foreign keys**\n\n| Name | Columns | References |\n|---|---|---|\n");
+            for [name, columns, references] in foreign_keys {
+                md.push_str(&format!(
+                    "| {} | {} | {} |\n",
+                    markdown_cell(&name),
+                    markdown_cell(&columns),
+                    markdown_cell(&references)
+                ));
+            }
+        }
+    }
+    md
+}
+
+fn html_table(out: &mut String, headers: &[&str], rows: Vec<Vec<String>>) {
+    out.push_str("<table>\n<thead><tr>");
+    for header in headers {
+        out.push_str(&format!("<th>{}</th>", header));
+    }
+    out.push_str("</tr></thead>\n<tbody>\n");
+    for row in rows {
+        out.push_str("<tr>");
+        for cell in row {
+            out.push_str(&format!("<td>{}</td>", escape_xml(&cell)));
+        }
+        out.push_str("</tr>\n");
+    }
+    out.push_str("</tbody>\n</table>\n");
+}
+
+pub fn html(database_name: &str, tables: &[&Table], er_diagram: &str) -> String {
+    let title = format!("Data dictionary: {}", escape_xml(database_name));
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n",
+        title
+    );
+    out.push_str(
+        "<style>\n\
+         body { font-family: system-ui, sans-serif; margin: 2rem; color: #1f2937; }\n\
+         table { border-collapse: collapse; margin: 0.5rem 0 1.5rem; }\n\
+         th, td { border: 1px solid #d1d5db; padding: 0.3rem 0.6rem; text-align: left; vertical-align: top; }\n\
+         th { background: #f3f4f6; }\n\
+         </style>\n",
+    );
+    let anchors = anchors(database_name, tables);
+    out.push_str(&format!("</head>\n<body>\n<h1>{}</h1>\n<ul>\n", title));
+    for (table, anchor) in tables.iter().zip(&anchors) {
+        out.push_str(&format!(
+            "<li><a href=\"#{}\">{}</a></li>\n",
+            escape_xml(anchor),
+            escape_xml(&table.display_name())
+        ));
+    }
+    // Drawn here rather than by mermaid so the page works offline
+    out.push_str("</ul>\n<h2>Entity relationships</h2>\n");
+    out.push_str(&er_svg(tables));
+    out.push_str("<details>\n<summary>Mermaid source</summary>\n<pre>\n");
+    out.push_str(&escape_xml(er_diagram));
+    out.push_str("</pre>\n</details>\n");
+
+    for (table, anchor) in tables.iter().zip(&anchors) {
+        out.push_str(&format!(
+            "<h2 id=\"{}\">{}</h2>\n",
+            escape_xml(anchor),
+            escape_xml(&table.display_name())
+        ));
+        if let Some(description) = &table.description {
+            out.push_str(&format!("<p>{}</p>\n", escape_xml(description)));
+        }
+        html_table(
+            &mut out,
+            &[
+                "Column",
+                "Type",
+                "Nullable",
+                "Default",
+                "Key",
+                "Description",
+            ],
+            column_rows(table)
+                .into_iter()
+                .map(|r| {
+                    vec![
+                        r.name,
+                        r.data_type,
+                        r.nullable.to_string(),
+                        r.default,
+                        r.key,
+                        r.description,
+                    ]
+                })
+                .collect(),
+        );
+        let indexes = index_rows(table);
+        if !indexes.is_empty() {
+            out.push_str("<h3>Indexes</h3>\n");
+            html_table(
+                &mut out,
+                &["Name", "Columns", "Kind"],
+                indexes.into_iter().map(Vec::from).collect(),
+            );
+        }
+        let foreign_keys = foreign_key_rows(table);
+        if !foreign_keys.is_empty() {
+            out.push_str("<h3>Foreign keys</h3>\n");
+            html_table(
+                &mut out,
+                &["Name", "Columns", "References"],
+                foreign_keys.into_iter().map(Vec::from).collect(),
+            );
+        }
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// Inline SVG of the tables on a grid, with a line from each foreign key to
+/// the table it references.
+fn er_svg(tables: &[&Table]) -> String {
+    const CHAR_WIDTH: usize = 7;
+    const LINE_HEIGHT: usize = 18;
+    const GAP: usize = 40;
+
+    let per_row = (tables.len() as f64).sqrt().ceil().max(1.0) as usize;
+    let lines: Vec<Vec<String>> =
+        tables
+            .iter()
+            .map(|table| {
+                std::iter::once(table.display_name())
+                    .chain(column_rows(table).into_iter().zip(&table.columns).map(
+                        |(row, column)| {
+                            let data_type = if column.computed {
+                                "computed".to_string()
+                            } else {
+                                column.type_declaration()
+                            };
+                            match row.key.as_str() {
+                                "" => format!("{} {}", row.name, data_type),
+                                key => format!("{} {} ({})", row.name, data_type, key),
+                            }
+                        },
+                    ))
+                    .collect()
+            })
+            .collect();
+    let sizes: Vec<(usize, usize)> = lines
+        .iter()
+        .map(|lines| {
+            let longest = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+            (longest * CHAR_WIDTH + 16, lines.len() * LINE_HEIGHT + 8)
+        })
+        .collect();
+
+    let mut column_widths = vec![0; per_row];
+    let mut row_heights = vec![0; tables.len().div_ceil(per_row)];
+    for (i, &(width, height)) in sizes.iter().enumerate() {
+        column_widths[i % per_row] = column_widths[i % per_row].max(width);
+        row_heights[i / per_row] = row_heights[i / per_row].max(height);
+    }
+    let offsets = |sizes: &[usize]| -> Vec<usize> {
+        sizes
+            .iter()
+            .scan(GAP / 2, |next, size| {
+                let offset = *next;
+                *next += size + GAP;
+                Some(offset)
+            })
+            .collect()
+    };
+    let (xs, ys) = (offsets(&column_widths), offsets(&row_heights));
+    let boxes: Vec<(f64, f64, f64, f64)> = sizes
+        .iter()
+        .enumerate()
+        .map(|(i, &(width, height))| {
+            (
+                xs[i % per_row] as f64,
+                ys[i / per_row] as f64,
+                width as f64,
+                height as f64,
+            )
+        })
+        .collect();
+    let total_width = column_widths.iter().sum::<usize>() + GAP * per_row;
+    let total_height = row_heights.iter().sum::<usize>() + GAP * row_heights.len();
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" font-family=\"monospace\" font-size=\"12\">\n",
+        total_width, total_height
+    );
+    svg.push_str("<defs><marker id=\"arrow\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" markerWidth=\"8\" markerHeight=\"8\" orient=\"auto\"><path d=\"M0,0 L10,5 L0,10 z\" fill=\"#6b7280\" /></marker></defs>\n");
+
+    let positions: HashMap<String, usize> = tables
+        .iter()
+        .enumerate()
+        .map(|(i, t)| (t.display_name().to_lowercase(), i))
+        .collect();
+    let center = |(x, y, w, h): (f64, f64, f64, f64)| (x + w / 2.0, y + h / 2.0);
+    for (child, table) in tables.iter().enumerate() {
+        for foreign_key in &table.foreign_keys {
+            let Some(&parent) = positions.get(&foreign_key.referenced_table.to_lowercase()) else {
+                continue;
+            };
+            if parent == child {
+                continue;
+            }
+            let (x1, y1) = center(boxes[child]);
+            let (x2, y2) = center(boxes[parent]);
+            // Stop at the edge of the referenced box so the arrow stays visible
+            let (_, _, w, h) = boxes[parent];
+            let (dx, dy) = (x2 - x1, y2 - y1);
+            let t = (w / 2.0 / dx.abs()).min(h / 2.0 / dy.abs()).min(1.0);
+            svg.push_str(&format!(
+                "<line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"#6b7280\" marker-end=\"url(#arrow)\" />\n",
+                x1,
+                y1,
+                x2 - dx * t,
+                y2 - dy * t
+            ));
+        }
+    }
+
+    for (lines, &(x, y, width, height)) in lines.iter().zip(&boxes) {
+        svg.push_str(&format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#ffffff\" stroke=\"#374151\" />\n",
+            x, y, width, height
+        ));
+        svg.push_str(&format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#f3f4f6\" stroke=\"#374151\" />\n",
+            x, y, width, LINE_HEIGHT + 4
+        ));
+        for (n, line) in lines.iter().enumerate() {
+            svg.push_str(&format!(
+                "<text x=\"{}\" y=\"{}\"{}>{}</text>\n",
+                x + 8.0,
+                y + ((n + 1) * LINE_HEIGHT) as f64,
+                if n == 0 { " font-weight=\"bold\"" } else { "" },
+                escape_xml(line)
+            ));
+        }
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Mermaid-safe identifier for a table or column.
+fn mermaid_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// Mermaid `erDiagram` of the tables and the foreign keys between them.
+pub fn er_diagram(tables: &[&Table]) -> String {
+    // Table name to identifier; names such as a.b_c and a_b.c would map to
+    // the same one, so later tables get a numbered suffix
+    let mut identifiers: HashMap<String, String> = HashMap::new();
+    let mut taken: HashSet<String> = HashSet::new();
+    for table in tables {
+        let base = mermaid_name(&table.display_name());
+        let mut identifier = base.clone();
+        let mut suffix = 1;
+        while !taken.insert(identifier.to_lowercase()) {
+            suffix += 1;
+            identifier = format!("{}_{}", base, suffix);
+        }
+        identifiers.insert(table.display_name().to_lowercase(), identifier);
+    }
+    let identifier = |name: &str| identifiers[&name.to_lowercase()].as_str();
+    let mut diagram = String::from("erDiagram\n");
+
+    for table in tables {
+        diagram.push_str(&format!(
+            "    {}[\"{}\"] {{\n",
+            identifier(&table.display_name()),
+            table.display_name().replace('"', "'")
+        ));
+        for row in column_rows(table) {
+            let column = table.columns.iter().find(|c| c.name == row.name);
+            let data_type = column
+                .filter(|c| !c.computed)
+                .map(|c| c.sql_type.name())
+                .unwrap_or("computed");
+            diagram.push_str(&format!(
+                "        {} {}",
+                data_type,
+                mermaid_name(&row.name)
+            ));
+            if !row.key.is_empty() {
+                diagram.push_str(&format!(" {}", row.key.replace(", ", ",")));
+            }
+            diagram.push('\n');
+        }
+        diagram.push_str("    }\n");
+    }
+
+    for table in tables {
+        for foreign_key in &table.foreign_keys {
+            if !identifiers.contains_key(&foreign_key.referenced_table.to_lowercase()) {
+                continue;
+            }
+            let optional = foreign_key
+                .columns
+                .iter()
+                .any(|name| table.columns.iter().any(|c| &c.name == name && c.nullable));
+            diagram.push_str(&format!(
+                "    {} {}--o{{ {} : \"{}\"\n",
+                identifier(&foreign_key.referenced_table),
+                if optional { "|o" } else { "||" },
+                identifier(&table.display_name()),
+                foreign_key
+                    .name
+                    .clone()
+                    .unwrap_or_else(|| foreign_key.columns.join(", "))
+                    .replace('"', "'")
+            ));
+        }
+    }
+    diagram
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bacpac::model::tests::MODEL_XML;
+
+    #[test]
+    fn test_dictionary_and_diagram() {
+        let model = DatabaseModel::parse(MODEL_XML).unwrap();
+        let tables = tables_in_schemas(&model, &[]);
+        let diagram = er_diagram(&tables);
+        assert!(diagram.contains("dbo_Customers[\"dbo.Customers\"] {"));
+        assert!(diagram.contains("        int Id PK\n"));
+        assert!(diagram.contains("dbo_Customers ||--o{ sales_Orders : \"FK_Orders_Customers\""));
+
+        let md = markdown("Sample", &tables, &diagram);
+        assert!(md.contains("| Email | nvarchar(200) | Yes |  |  | Customer's login |"));
+        assert!(md.contains("| Balance | decimal(10, 2) | Yes | ((0)) |"));
+        assert!(md.contains("| PK_Customers | Id | Primary key |"));
+
+        assert!(md.contains("- [dbo.Customers](#dbocustomers)"));
+
+        let html = html("Sample", &tables, &diagram);
+        assert!(html.contains("<td>Customer&apos;s login</td>"));
+        assert!(html.contains("<h2 id=\"dbocustomers\">dbo.Customers</h2>"));
+        assert!(html.contains("<svg ") && html.contains("marker-end"));
+        assert!(!html.contains("<script"));
+
+        // Filtering by schema drops relationships to tables left out
+        let sales = tables_in_schemas(&model, &["sales".to_string()]);
+        assert_eq!(sales.len(), 1);
+        assert!(!er_diagram(&sales).contains("||--o{"));
+    }
+
+    #[test]
+    fn test_colliding_identifiers_and_unnamed_keys() {
+        let model = DatabaseModel::parse(MODEL_XML).unwrap();
+        let mut first = model.tables[0].clone();
+        (first.schema, first.name) = ("a".to_string(), "b_c".to_string());
+        first.foreign_keys.clear();
+        let mut second = first.clone();
+        (second.schema, second.name) = ("a_b".to_string(), "c".to_string());
+        let diagram = er_diagram(&[&first, &second]);
+        assert!(diagram.contains("a_b_c[\"a.b_c\"]"), "{}", diagram);
+        assert!(diagram.contains("a_b_c_2[\"a_b.c\"]"), "{}", diagram);
+
+        first.primary_key.as_mut().unwrap().name = None;
+        for index in &mut first.indexes {
+            index.name = None;
+            index.is_constraint = true;
+        }
+        let kinds: Vec<String> = index_rows(&first)
+            .into_iter()
+            .map(|[_, _, kind]| kind)
+            .collect();
+        assert_eq!(kinds[0], "Primary key");
+        assert!(kinds[1..].iter().all(|kind| kind == "Unique constraint"));
+    }
+
+    #[test]
+    fn test_anchors_follow_github_slugs() {
+        assert_eq!(slug("dbo.Order Lines"), "dboorder-lines");
+        assert_eq!(slug("sales.Über_Items"), "salesüber_items");
+
+        let model = DatabaseModel::parse(MODEL_XML).unwrap();
+        let mut copy = model.tables[0].clone();
+        copy.schema = "db".to_string();
+        copy.name = "oCustomers".to_string();
+        let tables = vec![&model.tables[0], &copy];
+        assert_eq!(
+            anchors("Sample", &tables),
+            vec!["dbocustomers", "dbocustomers-1"]
+        );
+    }
+}
@@ -0,0 +1,501 @@
+use super::bcp::{fits_precision, format_decimal, rescale, Value};
+use super::model::{Column, SqlType, Table};
+use super::{write_replacing, BacpacArchive};
+use crate::models::DataExportFormat;
+use anyhow::{anyhow, Result};
+use arrow_array::builder::{
+    BinaryBuilder, BooleanBuilder, Date32Builder, Decimal128Builder, Float32Builder,
+    Float64Builder, Int32Builder, Int64Builder, StringBuilder, Time64NanosecondBuilder,
+    TimestampMicrosecondBuilder,
+};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use chrono::{NaiveDate, Timelike};
+use parquet::arrow::ArrowWriter;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const PARQUET_BATCH_SIZE: usize = 8192;
+
+/// Writes one table's rows to `output_dir` and returns the file path and row count.
+pub fn export_table(
+    archive: &mut BacpacArchive,
+    table: &Table,
+    format: DataExportFormat,
+    output_dir: &Path,
+) -> Result<(PathBuf, u64)> {
+    let extension = match format {
+        DataExportFormat::Csv => "csv",
+        DataExportFormat::Jsonl => "jsonl",
+        DataExportFormat::Parquet => "parquet",
+    };
+    let path = output_dir.join(format!(
+        "{}.{}",
+        sanitize_file_name(&table.display_name()),
+        extension
+    ));
+    let columns = table.data_columns();
+
+    let rows = write_replacing(&path, |partial| {
+        let file = File::create(partial)?;
+        match format {
+            DataExportFormat::Csv => write_csv(archive, table, &columns, file),
+            DataExportFormat::Jsonl => write_json_lines(archive, table, &columns, file),
+            DataExportFormat::Parquet => write_parquet(archive, table, &columns, file),
+        }
+    })?;
+
+    Ok((path, rows))
+}
+
+fn write_csv(
+    archive: &mut BacpacArchive,
+    table: &Table,
+    columns: &[&Column],
+    file: File,
+) -> Result<u64> {
+    let mut writer = csv::Writer::from_writer(BufWriter::new(file));
+    writer.write_record(columns.iter().map(|c| c.name.as_str()))?;
+
+    let rows = archive.visit_rows(table, |row| {
+        writer.write_record(row.iter().map(|value| value.to_string()))?;
+        Ok(true)
+    })?;
+
+    writer.flush()?;
+    Ok(rows)
+}
+
+fn write_json_lines(
+    archive: &mut BacpacArchive,
+    table: &Table,
+    columns: &[&Column],
+    file: File,
+) -> Result<u64> {
+    let mut writer = BufWriter::new(file);
+
+    let rows = archive.visit_rows(table, |row| {
+        let object: serde_json::Map<String, serde_json::Value> = columns
+            .iter()
+            .zip(row.iter())
+            .map(|(column, value)| (column.name.clone(), value.to_json()))
+            .collect();
+        serde_json::to_writer(&mut writer, &object)?;
+        writer.write_all(b"\n")?;
+        Ok(true)
+    })?;
+
+    writer.flush()?;
+    Ok(rows)
+}
+
+fn write_parquet(
+    archive: &mut BacpacArchive,
+    table: &Table,
+    columns: &[&Column],
+    file: File,
+) -> Result<u64> {
+    let schema = Arc::new(Schema::new(
+        columns
+            .iter()
+            .map(|c| Field::new(&c.name, arrow_type(c), true))
+            .collect::<Vec<_>>(),
+    ));
+    let mut writer = ArrowWriter::try_new(file, schema.clone(), None)?;
+    let mut batch: Vec<Vec<Value>> = Vec::with_capacity(PARQUET_BATCH_SIZE);
+
+    let rows = archive.visit_rows(table, |row| {
+        batch.push(row);
+        if batch.len() == PARQUET_BATCH_SIZE {
+            writer.write(&record_batch(&schema, &batch)?)?;
+            batch.clear();
+        }
+        Ok(true)
+    })?;
+
+    if !batch.is_empty() {
+        writer.write(&record_batch(&schema, &batch)?)?;
+    }
+    writer.close()?;
+    Ok(rows)
+}
+
+fn arrow_type(column: &Column) -> DataType {
+    match column.sql_type {
+        SqlType::Bit => DataType::Boolean,
+        SqlType::TinyInt | SqlType::SmallInt | SqlType::Int => DataType::Int32,
+        SqlType::BigInt => DataType::Int64,
+        SqlType::Real => DataType::Float32,
+        SqlType::Float => DataType::Float64,
+        SqlType::Decimal | SqlType::Numeric => {
+            DataType::Decimal128(column.precision, column.scale as i8)
+        }
+        SqlType::Money => DataType::Decimal128(19, 4),
+        SqlType::SmallMoney => DataType::Decimal128(10, 4),
+        SqlType::Date => DataType::Date32,
+        SqlType::Time => DataType::Time64(TimeUnit::Nanosecond),
+        SqlType::DateTime | SqlType::DateTime2 | SqlType::SmallDateTime => {
+            DataType::Timestamp(TimeUnit::Microsecond, None)
+        }
+        SqlType::DateTimeOffset => {
+            DataType::Timestamp(TimeUnit::Microsecond, Some("+00:00".into()))
+        }
+        SqlType::Binary
+        | SqlType::VarBinary
+        | SqlType::Image
+        | SqlType::Timestamp
+        | SqlType::SqlVariant
+        | SqlType::HierarchyId
+        | SqlType::Geography
+        | SqlType::Geometry => DataType::Binary,
+        SqlType::Char
+        | SqlType::VarChar
+        | SqlType::Text
+        | SqlType::NChar
+        | SqlType::NVarChar
+        | SqlType::NText
+        | SqlType::Xml
+        | SqlType::UniqueIdentifier => DataType::Utf8,
+    }
+}
+
+fn record_batch(schema: &Arc<Schema>, rows: &[Vec<Value>]) -> Result<RecordBatch> {
+    let arrays = schema
+        .fields()
+        .iter()
+        .enumerate()
+        .map(|(index, field)| build_array(field.data_type(), rows, index))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(RecordBatch::try_new(schema.clone(), arrays)?)
+}
+
+fn build_array(data_type: &DataType, rows: &[Vec<Value>], index: usize) -> Result<ArrayRef> {
+    let values = rows.iter().map(|row| &row[index]);
+    let mismatch = |value: &Value| anyhow!("Unexpected value {:?} for {}", value, data_type);
+
+    let array: ArrayRef = match data_type {
+        DataType::Boolean => {
+            let mut builder = BooleanBuilder::with_capacity(rows.len());
+            for value in values {
+                match value {
+                    Value::Null => builder.append_null(),
+                    Value::Bool(b) => builder.append_value(*b),
+                    other => return Err(mismatch(other)),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        DataType::Int32 => {
+            let mut builder = Int32Builder::with_capacity(rows.len());
+            for value in values {
+                match value {
+                    Value::Null => builder.append_null(),
+                    Value::Int(i) => builder.append_value(*i as i32),
+                    other => return Err(mismatch(other)),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        DataType::Int64 => {
+            let mut builder = Int64Builder::with_capacity(rows.len());
+            for value in values {
+                match value {
+                    Value::Null => builder.append_null(),
+                    Value::Int(i) => builder.append_value(*i),
+                    other => return Err(mismatch(other)),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        DataType::Float32 => {
+            let mut builder = Float32Builder::with_capacity(rows.len());
+            for value in values {
+                match value {
+                    Value::Null => builder.append_null(),
+                    Value::Float(f) => builder.append_value(*f as f32),
+                    other => return Err(mismatch(other)),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        DataType::Float64 => {
+            let mut builder = Float64Builder::with_capacity(rows.len());
+            for value in values {
+                match value {
+                    Value::Null => builder.append_null(),
+                    Value::Float(f) => builder.append_value(*f),
+                    other => return Err(mismatch(other)),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        DataType::Decimal128(precision, scale) => {
+            let mut builder = Decimal128Builder::with_capacity(rows.len());
+            for value in values {
+                match value {
+                    Value::Null => builder.append_null(),
+                    Value::Decimal {
+                        value,
+                        scale: value_scale,
+                    } => {
+                        let value = rescale(*value, *value_scale, *scale as u8)?;
+                        if !fits_precision(value, *precision) {
+                            return Err(anyhow!(
+                                "{} does not fit in {}",
+                                format_decimal(value, *scale as u8),
+                                data_type
+                            ));
+                        }
+                        builder.append_value(value)
+                    }
+                    other => return Err(mismatch(other)),
+                }
+            }
+            Arc::new(
+                builder
+                    .finish()
+                    .with_precision_and_scale(*precision, *scale)?,
+            )
+        }
+        DataType::Date32 => {
+            let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).expect("valid date");
+            let mut builder = Date32Builder::with_capacity(rows.len());
+            for value in values {
+                match value {
+                    Value::Null => builder.append_null(),
+                    Value::Date(d) => builder.append_value((*d - epoch).num_days() as i32),
+                    other => return Err(mismatch(other)),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        DataType::Time64(_) => {
+            let mut builder = Time64NanosecondBuilder::with_capacity(rows.len());
+            for value in values {
+                match value {
+                    Value::Null => builder.append_null(),
+                    Value::Time(t) => builder.append_value(
+                        t.num_seconds_from_midnight() as i64 * 1_000_000_000
+                            + t.nanosecond() as i64,
+                    ),
+                    other => return Err(mismatch(other)),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        DataType::Timestamp(_, timezone) => {
+            let mut builder = TimestampMicrosecondBuilder::with_capacity(rows.len());
+            for value in values {
+                match value {
+                    Value::Null => builder.append_null(),
+                    Value::DateTime(dt) => builder.append_value(dt.and_utc().timestamp_micros()),
+                    Value::DateTimeOffset(dt) => builder.append_value(dt.timestamp_micros()),
+                    other => return Err(mismatch(other)),
+                }
+            }
+            Arc::new(builder.finish().with_timezone_opt(timezone.clone()))
+        }
+        DataType::Binary => {
+            let mut builder = BinaryBuilder::new();
+            for value in values {
+                match value {
+                    Value::Null => builder.append_null(),
+                    Value::Binary(bytes) => builder.append_value(bytes),
+                    other => return Err(mismatch(other)),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        _ => {
+            let mut builder = StringBuilder::new();
+            for value in values {
+                match value {
+                    Value::Null => builder.append_null(),
+                    other => builder.append_value(other.to_string()),
+                }
+            }
+            Arc::new(builder.finish())
+        }
+    };
+    Ok(array)
+}
+
+fn sanitize_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            _ => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bacpac::bcp::BcpWriter;
+    use crate::bacpac::model::tests::MODEL_XML;
+    use crate::bacpac::model::DatabaseModel;
+    use crate::bacpac::test_support::write_package;
+    use arrow_array::{Array, Decimal128Array, Int32Array, StringArray};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    fn customer_rows() -> Vec<Vec<Value>> {
+        vec![
+            vec![
+                Value::Int(1),
+                Value::String("a@b.com".to_string()),
+                Value::Decimal {
+                    value: -1234,
+                    scale: 2,
+                },
+            ],
+            vec![
+                Value::Int(2),
+                Value::String("Zoë \"quoted\", 東京".to_string()),
+                Value::Null,
+            ],
+            vec![
+                Value::Int(3),
+                Value::Null,
+                Value::Decimal {
+                    value: 9_999_999_999,
+                    scale: 2,
+                },
+            ],
+        ]
+    }
+
+    /// A package holding `customer_rows`, opened with the Customers table.
+    fn customers_package(dir: &Path) -> (BacpacArchive, Table) {
+        let model = DatabaseModel::parse(MODEL_XML).unwrap();
+        let table = model.find_table("dbo.Customers").unwrap().clone();
+        let mut data = Vec::new();
+        let mut writer = BcpWriter::new(&mut data, &table.data_columns()).unwrap();
+        for row in customer_rows() {
+            writer.write_row(&row).unwrap();
+        }
+        let bacpac = dir.join("sample.bacpac");
+        write_package(
+            &bacpac,
+            MODEL_XML,
+            &[("Data/dbo.Customers/TableData-000-00000.BCP", data)],
+        );
+        (BacpacArchive::open(&bacpac).unwrap(), table)
+    }
+
+    #[test]
+    fn test_export_table_to_csv() {
+        let dir = tempfile::tempdir().unwrap();
+        let bacpac = dir.path().join("sample.bacpac");
+
+        let mut data = Vec::new();
+        data.extend_from_slice(&1i32.to_le_bytes());
+        let email: Vec<u8> = "a@b.com"
+            .encode_utf16()
+            .flat_map(|u| u.to_le_bytes())
+            .collect();
+        data.extend_from_slice(&(email.len() as u16).to_le_bytes());
+        data.extend_from_slice(&email);
+        data.push(0xFF);
+        write_package(
+            &bacpac,
+            MODEL_XML,
+            &[("Data/dbo.Customers/TableData-000-00000.BCP", data)],
+        );
+
+        let mut archive = BacpacArchive::open(&bacpac).unwrap();
+        let model = archive.read_model().unwrap();
+        let table = model.find_table("dbo.Customers").unwrap();
+        let (path, rows) =
+            export_table(&mut archive, table, DataExportFormat::Csv, dir.path()).unwrap();
+
+        assert_eq!(rows, 1);
+        let csv = std::fs::read_to_string(path).unwrap();
+        assert_eq!(csv, "Id,Email,Balance\n1,a@b.com,\n");
+    }
+
+    #[test]
+    fn test_json_lines_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut archive, table) = customers_package(dir.path());
+        let (path, rows) =
+            export_table(&mut archive, &table, DataExportFormat::Jsonl, dir.path()).unwrap();
+        assert_eq!(rows, 3);
+
+        let columns = table.data_columns();
+        let read: Vec<Vec<Value>> = std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let object: serde_json::Map<String, serde_json::Value> =
+                    serde_json::from_str(line).unwrap();
+                columns
+                    .iter()
+                    .map(|column| match &object[&column.name] {
+                        serde_json::Value::Null => Value::Null,
+                        serde_json::Value::Number(n) => Value::Int(n.as_i64().unwrap()),
+                        serde_json::Value::String(text) => Value::parse(text, column).unwrap(),
+                        other => panic!("unexpected {}", other),
+                    })
+                    .collect()
+            })
+            .collect();
+        assert_eq!(read, customer_rows());
+    }
+
+    #[test]
+    fn test_parquet_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut archive, table) = customers_package(dir.path());
+        let (path, rows) =
+            export_table(&mut archive, &table, DataExportFormat::Parquet, dir.path()).unwrap();
+        assert_eq!(rows, 3);
+
+        let batches = ParquetRecordBatchReaderBuilder::try_new(File::open(path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let mut read = Vec::new();
+        for batch in batches {
+            let ids = batch
+                .column(0)
+                .as_any()
+                .downcast_ref::<Int32Array>()
+                .unwrap();
+            let emails = batch
+                .column(1)
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap();
+            let balances = batch
+                .column(2)
+                .as_any()
+                .downcast_ref::<Decimal128Array>()
+                .unwrap();
+            assert_eq!(balances.precision(), 10);
+            for row in 0..batch.num_rows() {
+                read.push(vec![
+                    Value::Int(ids.value(row) as i64),
+                    if emails.is_null(row) {
+                        Value::Null
+                    } else {
+                        Value::String(emails.value(row).to_string())
+                    },
+                    if balances.is_null(row) {
+                        Value::Null
+                    } else {
+                        Value::Decimal {
+                            value: balances.value(row),
+                            scale: balances.scale() as u8,
+                        }
+                    },
+                ]);
+            }
+        }
+        assert_eq!(read, customer_rows());
+    }
+}
@@ -0,0 +1,588 @@
+//! Offline masking: rewrites chosen columns of a BACPAC's table data.
+//!
+//! Generated values are derived from a SHA-256 of the salt and the original
+//! value, so a value masked with the same rule becomes the same replacement
+//! in every table and foreign keys between masked columns still line up.
+//! Columns that must stay unique are read first so that replacements which
+//! collide, usually after truncation, can be drawn again.
+
+use super::bcp::Value;
+use super::model::{Column, DatabaseModel, Table};
+use super::rewrite::rewrite_package;
+use super::BacpacArchive;
+use crate::models::{MaskRule, MaskStrategy};
+use anyhow::{anyhow, Result};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+const FIRST_NAMES: [&str; 24] = [
+    "Alex", "Blake", "Casey", "Dana", "Eli", "Frankie", "Gray", "Harper", "Indy", "Jordan", "Kai",
+    "Logan", "Morgan", "Noel", "Oakley", "Parker", "Quinn", "Riley", "Sage", "Taylor", "Umi",
+    "Val", "Wren", "Yael",
+];
+
+const LAST_NAMES: [&str; 24] = [
+    "Abbott",
+    "Baker",
+    "Carter",
+    "Dalton",
+    "Ellis",
+    "Fischer",
+    "Garcia",
+    "Hughes",
+    "Ibarra",
+    "Jensen",
+    "Kowalski",
+    "Larsen",
+    "Moreno",
+    "Novak",
+    "Okafor",
+    "Patel",
+    "Quinlan",
+    "Rossi",
+    "Sato",
+    "Tanaka",
+    "Underwood",
+    "Vargas",
+    "Walsh",
+    "Young",
+];
+
+/// Draws tried per value before a unique column is reported as exhausted.
+const MAX_ATTEMPTS: u32 = 100;
+
+pub struct MaskSummary {
+    pub tables: usize,
+    pub columns: usize,
+    pub rows: u64,
+}
+
+struct ColumnMask<'a> {
+    index: usize,
+    column: &'a Column,
+    strategy: &'a MaskStrategy,
+    /// Replacement for `MaskStrategy::Fixed`, parsed for the column type.
+    fixed: Option<Value>,
+    /// The column alone is a primary key or unique index.
+    unique: bool,
+}
+
+/// Redrawn replacements of values that collided in a unique column, per
+/// generator, so every table masks the original the same way.
+type Replacements = HashMap<&'static str, HashMap<String, String>>;
+
+/// Writes a copy of the BACPAC with the columns named in `rules` masked.
+pub fn mask(
+    archive: &mut BacpacArchive,
+    model: &DatabaseModel,
+    rules: &[MaskRule],
+    salt: &str,
+    target: &Path,
+    mut progress: impl FnMut(String),
+) -> Result<MaskSummary> {
+    let plan = plan(model, rules)?;
+    let replacements = deduplicate(archive, model, &plan, salt)?;
+
+    let mut summary = MaskSummary {
+        tables: plan.len(),
+        columns: rules.len(),
+        rows: 0,
+    };
+    summary.rows = rewrite_package(archive, model, target, |archive, table, writer| {
+        let masks = plan.get(&table.display_name());
+        if masks.is_some() {
+            progress(format!("Masking {}", table.display_name()));
+        }
+
+        let mut shuffled = match masks {
+            Some(masks) => shuffle_columns(archive, table, masks, salt)?,
+            None => HashMap::new(),
+        };
+        archive.visit_rows(table, |mut row| {
+            for mask in masks.into_iter().flatten() {
+                let original = std::mem::replace(&mut row[mask.index], Value::Null);
+                row[mask.index] = match mask.strategy {
+                    MaskStrategy::Shuffle => shuffled
+                        .get_mut(&mask.index)
+                        .and_then(|values| values.pop())
+                        .unwrap_or(original),
+                    _ => mask_value(mask, original, salt, &replacements),
+                };
+            }
+            writer.write_row(&row)?;
+            Ok(true)
+        })
+    })?;
+
+    progress(format!(
+        "Masked {} columns in {} tables",
+        summary.columns, summary.tables
+    ));
+    Ok(summary)
+}
+
+/// Resolves the rules against the model and checks each strategy fits its column.
+fn plan<'a>(
+    model: &'a DatabaseModel,
+    rules: &'a [MaskRule],
+) -> Result<HashMap<String, Vec<ColumnMask<'a>>>> {
+    let mut plan: HashMap<String, Vec<ColumnMask>> = HashMap::new();
+    for rule in rules {
+        let table = model
+            .find_table(&rule.table)
+            .ok_or_else(|| anyhow!("Table {} not found in the package", rule.table))?;
+        let index = table.data_column_index(&rule.column).ok_or_else(|| {
+            anyhow!(
+                "Column {} not found in {} (computed columns cannot be masked)",
+                rule.column,
+                table.display_name()
+            )
+        })?;
+        let column = table.data_columns()[index];
+        let describe = || format!("{}.{}", table.display_name(), column.name);
+        let unique = table
+            .primary_key
+            .iter()
+            .chain(table.indexes.iter().filter(|i| i.unique))
+            .any(|key| matches!(key.columns.as_slice(), [only] if only.eq_ignore_ascii_case(&column.name)));
+
+        let fixed = match &rule.strategy {
+            MaskStrategy::Null if !column.nullable => {
+                return Err(anyhow!("{} does not allow NULL", describe()));
+            }
+            MaskStrategy::Null | MaskStrategy::Fixed { .. } if unique => {
+                return Err(anyhow!(
+                    "{} must stay unique; mask it with a generated value or shuffle it",
+                    describe()
+                ));
+            }
+            MaskStrategy::Fixed { value } => {
+                let value =
+                    Value::parse(value, column).map_err(|e| anyhow!("{}: {}", describe(), e))?;
+                let size = match &value {
+                    Value::String(text) => text.chars().count(),
+                    Value::Binary(bytes) => bytes.len(),
+                    _ => 0,
+                };
+                if let Some(length) = column.length.filter(|_| !column.is_max) {
+                    if size > length as usize {
+                        return Err(anyhow!(
+                            "{} holds at most {} characters; the fixed value has {}",
+                            describe(),
+                            length,
+                            size
+                        ));
+                    }
+                }
+                Some(value)
+            }
+            MaskStrategy::Hash
+            | MaskStrategy::FakeName
+            | MaskStrategy::FakeEmail
+            | MaskStrategy::FakePhone
+            | MaskStrategy::KeepFormat
+                if !column.sql_type.is_string() =>
+            {
+                return Err(anyhow!(
+                    "{} is {}; this rule only applies to text columns",
+                    describe(),
+                    column.type_declaration()
+                ));
+            }
+            _ => None,
+        };
+
+        let masks = plan.entry(table.display_name()).or_default();
+        if masks.iter().any(|m| m.index == index) {
+            return Err(anyhow!("{} has more than one rule", describe()));
+        }
+        masks.push(ColumnMask {
+            index,
+            column,
+            strategy: &rule.strategy,
+            fixed,
+            unique,
+        });
+    }
+    Ok(plan)
+}
+
+/// Name of the generator behind a strategy, or `None` if it generates nothing.
+fn generator(strategy: &MaskStrategy) -> Option<&'static str> {
+    match strategy {
+        MaskStrategy::Hash => Some("hash"),
+        MaskStrategy::FakeName => Some("name"),
+        MaskStrategy::FakeEmail => Some("email"),
+        MaskStrategy::FakePhone => Some("phone"),
+        MaskStrategy::KeepFormat => Some("format"),
+        MaskStrategy::Null | MaskStrategy::Fixed { .. } | MaskStrategy::Shuffle => None,
+    }
+}
+
+/// Reads the unique columns with generated replacements and redraws the
+/// replacements that collide.
+fn deduplicate(
+    archive: &mut BacpacArchive,
+    model: &DatabaseModel,
+    plan: &HashMap<String, Vec<ColumnMask>>,
+    salt: &str,
+) -> Result<Replacements> {
+    let mut replacements = Replacements::new();
+    for table in &model.tables {
+        let masks: Vec<(&ColumnMask, &'static str)> = plan
+            .get(&table.display_name())
+            .into_iter()
+            .flatten()
+            .filter(|mask| mask.unique)
+            .filter_map(|mask| Some((mask, generator(mask.strategy)?)))
+            .collect();
+        if masks.is_empty() {
+            continue;
+        }
+
+        let mut seen: Vec<HashSet<String>> = vec![HashSet::new(); masks.len()];
+        archive.visit_rows(table, |row| {
+            for ((mask, name), seen) in masks.iter().zip(seen.iter_mut()) {
+                let original = &row[mask.index];
+                if original.is_null() {
+                    continue;
+                }
+                let key = original.to_string();
+                let known = replacements.get(name).and_then(|r| r.get(&key)).cloned();
+                let mut attempt = 0;
+                loop {
+                    let text = match &known {
+                        Some(text) => fit_length(text.clone(), mask.column),
+                        None => generate(mask, original, salt, attempt),
+                    };
+                    if seen.insert(text.clone()) {
+                        if attempt > 0 {
+                            replacements.entry(name).or_default().insert(key, text);
+                        }
+                        break;
+                    }
+                    attempt += 1;
+                    if known.is_some() || attempt == MAX_ATTEMPTS {
+                        return Err(anyhow!(
+                            "{}.{} must stay unique but its masked values collide; use a longer column or the hash strategy",
+                            table.display_name(),
+                            mask.column.name
+                        ));
+                    }
+                }
+            }
+            Ok(true)
+        })?;
+    }
+    Ok(replacements)
+}
+
+fn mask_value(
+    mask: &ColumnMask,
+    original: Value,
+    salt: &str,
+    replacements: &Replacements,
+) -> Value {
+    if original.is_null() {
+        return original;
+    }
+    match mask.strategy {
+        MaskStrategy::Null => Value::Null,
+        MaskStrategy::Fixed { .. } => mask.fixed.clone().unwrap_or(original),
+        MaskStrategy::Shuffle => original,
+        strategy => {
+            let redrawn = generator(strategy)
+                .and_then(|name| replacements.get(name))
+                .and_then(|r| r.get(&original.to_string()));
+            Value::String(match redrawn {
+                Some(text) => fit_length(text.clone(), mask.column),
+                None => generate(mask, &original, salt, 0),
+            })
+        }
+    }
+}
+
+/// Generated replacement for a text value. Later attempts, used when a unique
+/// column already holds the replacement, mix the attempt into the seed.
+fn generate(mask: &ColumnMask, original: &Value, salt: &str, attempt: u32) -> String {
+    let seed = |purpose: &str| {
+        let mut hasher = Sha256::new();
+        hasher.update(salt.as_bytes());
+        hasher.update(purpose.as_bytes());
+        hasher.update(original.to_string().as_bytes());
+        if attempt > 0 {
+            hasher.update(attempt.to_le_bytes());
+        }
+        hasher.finalize()
+    };
+    let text = match mask.strategy {
+        MaskStrategy::Null | MaskStrategy::Fixed { .. } | MaskStrategy::Shuffle => {
+            original.to_string()
+        }
+        MaskStrategy::Hash => hex::encode(seed("hash")),
+        MaskStrategy::FakeName => {
+            let digest = seed("name");
+            format!(
+                "{} {}",
+                FIRST_NAMES[digest[0] as usize % FIRST_NAMES.len()],
+                LAST_NAMES[digest[1] as usize % LAST_NAMES.len()]
+            )
+        }
+        MaskStrategy::FakeEmail => {
+            let digest = seed("email");
+            format!(
+                "{}.{}{}@example.com",
+                FIRST_NAMES[digest[0] as usize % FIRST_NAMES.len()].to_lowercase(),
+                LAST_NAMES[digest[1] as usize % LAST_NAMES.len()].to_lowercase(),
+                u16::from_le_bytes([digest[2], digest[3]]) % 1000
+            )
+        }
+        MaskStrategy::FakePhone => {
+            let digest = seed("phone");
+            format!(
+                "555-{:03}-{:04}",
+                u16::from_le_bytes([digest[0], digest[1]]) % 1000,
+                u16::from_le_bytes([digest[2], digest[3]]) % 10000
+            )
+        }
+        MaskStrategy::KeepFormat => {
+            let mut random = SplitMix64::new(&seed("format"));
+            original
+                .to_string()
+                .chars()
+                .map(|c| {
+                    if c.is_ascii_digit() {
+                        char::from(b'0' + (random.next() % 10) as u8)
+                    } else {
+                        c
+                    }
+                })
+                .collect()
+        }
+    };
+    fit_length(text, mask.column)
+}
+
+/// Truncates generated text to the column's declared length.
+fn fit_length(text: String, column: &Column) -> String {
+    match column.length {
+        Some(length) if !column.is_max => text.chars().take(length as usize).collect(),
+        _ => text,
+    }
+}
+
+/// Reads the table once to collect and permute the columns to shuffle.
+/// Values are popped from the end while writing.
+fn shuffle_columns(
+    archive: &mut BacpacArchive,
+    table: &Table,
+    masks: &[ColumnMask],
+    salt: &str,
+) -> Result<HashMap<usize, Vec<Value>>> {
+    let indexes: Vec<usize> = masks
+        .iter()
+        .filter(|m| m.strategy == &MaskStrategy::Shuffle)
+        .map(|m| m.index)
+        .collect();
+    if indexes.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let mut columns: HashMap<usize, Vec<Value>> =
+        indexes.iter().map(|&i| (i, Vec::new())).collect();
+    archive.visit_rows(table, |mut row| {
+        for &index in &indexes {
+            let value = std::mem::replace(&mut row[index], Value::Null);
+            columns.entry(index).or_default().push(value);
+        }
+        Ok(true)
+    })?;
+
+    for (index, values) in columns.iter_mut() {
+        let mut hasher = Sha256::new();
+        hasher.update(salt.as_bytes());
+        hasher.update(format!("{}:{}", table.display_name(), index).as_bytes());
+        let mut random = SplitMix64::new(&hasher.finalize());
+        // Fisher-Yates
+        for i in (1..values.len()).rev() {
+            let j = (random.next() % (i as u64 + 1)) as usize;
+            values.swap(i, j);
+        }
+        values.reverse();
+    }
+    Ok(columns)
+}
+
+/// Small seeded generator; masking must be repeatable for a given salt.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn new(seed: &[u8]) -> Self {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&seed[..8]);
+        SplitMix64(u64::from_le_bytes(bytes))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bacpac::model::tests::MODEL_XML;
+    use crate::bacpac::test_support::write_package;
+
+    fn customer(id: i32, email: &str) -> Vec<u8> {
+        let mut data = id.to_le_bytes().to_vec();
+        let email: Vec<u8> = email.encode_utf16().flat_map(|u| u.to_le_bytes()).collect();
+        data.extend_from_slice(&(email.len() as u16).to_le_bytes());
+        data.extend_from_slice(&email);
+        data.push(19);
+        data.extend_from_slice(&[10, 2, 1]);
+        data.extend_from_slice(&(id as u128 * 100).to_le_bytes());
+        data
+    }
+
+    fn rule(column: &str, strategy: MaskStrategy) -> MaskRule {
+        MaskRule {
+            table: "dbo.Customers".to_string(),
+            column: column.to_string(),
+            strategy,
+        }
+    }
+
+    #[test]
+    fn test_mask_columns() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("prod.bacpac");
+        let target = dir.path().join("masked.bacpac");
+        write_package(
+            &source,
+            MODEL_XML,
+            &[(
+                "Data/dbo.Customers/TableData-000-00000.BCP",
+                [customer(1, "jane@corp.com"), customer(2, "joe@corp.com")].concat(),
+            )],
+        );
+
+        let mut archive = BacpacArchive::open(&source).unwrap();
+        let model = archive.read_model().unwrap();
+        let rules = vec![
+            rule("Email", MaskStrategy::FakeEmail),
+            rule(
+                "Balance",
+                MaskStrategy::Fixed {
+                    value: "0.5".to_string(),
+                },
+            ),
+        ];
+        let summary = mask(&mut archive, &model, &rules, "salt", &target, |_| {}).unwrap();
+        assert_eq!(summary.rows, 2);
+
+        let mut masked = BacpacArchive::open(&target).unwrap();
+        let mut rows = Vec::new();
+        masked
+            .visit_rows(model.find_table("dbo.Customers").unwrap(), |row| {
+                rows.push(row);
+                Ok(true)
+            })
+            .unwrap();
+        assert_eq!(rows[0][0], Value::Int(1));
+        let email = rows[0][1].to_string();
+        assert!(email.ends_with("@example.com") && !email.contains("jane"));
+        assert_eq!(rows[1][2].to_string(), "0.50");
+    }
+
+    #[test]
+    fn test_rules_are_checked_against_column_types() {
+        let model = DatabaseModel::parse(MODEL_XML).unwrap();
+        assert!(plan(&model, &[rule("Id", MaskStrategy::Null)]).is_err());
+        assert!(plan(&model, &[rule("Balance", MaskStrategy::FakeName)]).is_err());
+        assert!(plan(&model, &[rule("Domain", MaskStrategy::Hash)]).is_err());
+        assert!(plan(&model, &[rule("Email", MaskStrategy::Shuffle)]).is_ok());
+    }
+
+    #[test]
+    fn test_keep_format_is_repeatable() {
+        let model = DatabaseModel::parse(MODEL_XML).unwrap();
+        let table = model.find_table("dbo.Customers").unwrap();
+        let mask = ColumnMask {
+            index: 1,
+            column: table.data_columns()[1],
+            strategy: &MaskStrategy::KeepFormat,
+            fixed: None,
+            unique: false,
+        };
+        let original = Value::String("AB-1234-x".to_string());
+        let replacements = Replacements::new();
+        let first = mask_value(&mask, original.clone(), "salt", &replacements).to_string();
+        assert_eq!(first.len(), 9);
+        assert!(first.starts_with("AB-") && first.ends_with("-x"));
+        assert_eq!(
+            mask_value(&mask, original, "salt", &replacements).to_string(),
+            first
+        );
+    }
+
+    #[test]
+    fn test_unique_and_length_limits() {
+        let mut model = DatabaseModel::parse(MODEL_XML).unwrap();
+        let fixed = |value: &str| MaskStrategy::Fixed {
+            value: value.to_string(),
+        };
+        assert!(plan(&model, &[rule("Email", fixed("x"))]).is_err());
+        assert!(plan(&model, &[rule("Email", MaskStrategy::Null)]).is_err());
+        assert!(plan(&model, &[rule("Email", MaskStrategy::FakeEmail)]).is_ok());
+
+        model.tables[0].indexes.clear();
+        assert!(plan(&model, &[rule("Email", fixed(&"x".repeat(200)))]).is_ok());
+        let error = plan(&model, &[rule("Email", fixed(&"x".repeat(201)))])
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("at most 200"), "{}", error);
+    }
+
+    #[test]
+    fn test_truncated_values_stay_unique() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("prod.bacpac");
+        let target = dir.path().join("masked.bacpac");
+        let emails: Vec<String> = ('a'..='l').map(|c| c.to_string()).collect();
+        write_package(
+            &source,
+            MODEL_XML,
+            &[(
+                "Data/dbo.Customers/TableData-000-00000.BCP",
+                emails
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(i, email)| customer(i as i32 + 1, email))
+                    .collect(),
+            )],
+        );
+
+        let mut archive = BacpacArchive::open(&source).unwrap();
+        let mut model = archive.read_model().unwrap();
+        // One hex digit per value leaves 16 choices for 12 rows
+        model.tables[0].columns[1].length = Some(1);
+        let rules = vec![rule("Email", MaskStrategy::Hash)];
+        mask(&mut archive, &model, &rules, "salt", &target, |_| {}).unwrap();
+
+        let mut masked = BacpacArchive::open(&target).unwrap();
+        let mut values = HashSet::new();
+        masked
+            .visit_rows(model.find_table("dbo.Customers").unwrap(), |row| {
+                values.insert(row[1].to_string());
+                Ok(true)
+            })
+            .unwrap();
+        assert_eq!(values.len(), 12);
+    }
+}
@@ -0,0 +1,411 @@
+pub mod bcp;
+pub mod browse;
+pub mod builder;
+pub mod convert;
+pub mod dependencies;
+pub mod diff;
+pub mod docs;
+pub mod export;
+pub mod mask;
+pub mod model;
+pub mod origin;
+pub mod preflight;
+pub mod rewrite;
+pub mod script;
+pub mod search;
+pub mod security;
+pub mod sqlite;
+pub mod subset;
+
+use anyhow::{anyhow, Context, Result};
+use bcp::{BcpReader, Value};
+use model::{DatabaseModel, Table};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+pub const MODEL_ENTRY: &str = "model.xml";
+pub const ORIGIN_ENTRY: &str = "Origin.xml";
+pub const METADATA_ENTRY: &str = "DacMetadata.xml";
+pub const CONTENT_TYPES_ENTRY: &str = "[Content_Types].xml";
+pub const DATA_PREFIX: &str = "Data/";
+
+/// XML namespace of model.xml, Origin.xml and DacMetadata.xml.
+pub const DAC_NAMESPACE: &str = "http://schemas.microsoft.com/sqlserver/dac/Serialization/2012/02";
+
+/// A BACPAC or DACPAC opened for reading.
+pub struct BacpacArchive {
+    archive: ZipArchive<BufReader<File>>,
+}
+
+impl BacpacArchive {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file =
+            File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let archive = ZipArchive::new(BufReader::new(file))
+            .with_context(|| format!("{} is not a valid package", path.display()))?;
+
+        let package = BacpacArchive { archive };
+        for required in [MODEL_ENTRY, ORIGIN_ENTRY] {
+            if !package.has_entry(required) {
+                return Err(anyhow!("{} is missing {}", path.display(), required));
+            }
+        }
+
+        Ok(package)
+    }
+
+    pub fn entry_names(&self) -> Vec<String> {
+        self.archive
+            .file_names()
+            .map(|name| name.to_string())
+            .collect()
+    }
+
+    pub fn has_entry(&self, name: &str) -> bool {
+        self.archive.index_for_name(name).is_some()
+    }
+
+    pub fn read_entry(&mut self, name: &str) -> Result<Vec<u8>> {
+        let mut entry = self
+            .archive
+            .by_name(name)
+            .with_context(|| format!("Package entry {} not found", name))?;
+        let mut bytes = Vec::with_capacity(entry.size() as usize);
+        entry.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    pub fn read_entry_string(&mut self, name: &str) -> Result<String> {
+        let bytes = self.read_entry(name)?;
+        decode_xml_text(&bytes).with_context(|| format!("{} is not valid UTF-8", name))
+    }
+
+    /// Copies an entry into `writer` without decompressing it.
+    pub fn copy_entry_to(&mut self, name: &str, writer: &mut PackageWriter) -> Result<()> {
+        let index = self
+            .archive
+            .index_for_name(name)
+            .ok_or_else(|| anyhow!("Package entry {} not found", name))?;
+        let entry = self.archive.by_index_raw(index)?;
+        writer.zip.raw_copy_file(entry)?;
+        writer.entries.insert(name.to_string());
+        Ok(())
+    }
+
+    pub fn read_model(&mut self) -> Result<DatabaseModel> {
+        let xml = self.read_entry_string(MODEL_ENTRY)?;
+        DatabaseModel::parse(&xml)
+    }
+
+    /// BCP files holding a table's rows, in the order they were written.
+    pub fn table_data_entries(&self, table: &Table) -> Vec<String> {
+        let folder = format!("{}{}/", DATA_PREFIX, table.display_name());
+        let mut entries: Vec<String> = self
+            .archive
+            .file_names()
+            .filter(|name| name.starts_with(&folder) && !name.ends_with('/'))
+            .map(|name| name.to_string())
+            .collect();
+        entries.sort();
+        entries
+    }
+
+    /// Uncompressed size of a table's BCP files.
+    pub fn table_data_size(&mut self, table: &Table) -> Result<u64> {
+        let mut total = 0;
+        for name in self.table_data_entries(table) {
+            total += self.archive.by_name(&name)?.size();
+        }
+        Ok(total)
+    }
+
+    /// Uncompressed size of all table data in the package.
+    pub fn data_size(&mut self) -> Result<u64> {
+        let names: Vec<String> = self
+            .entry_names()
+            .into_iter()
+            .filter(|name| is_data_entry(name))
+            .collect();
+        let mut total = 0;
+        for name in names {
+            total += self.archive.by_name(&name)?.size();
+        }
+        Ok(total)
+    }
+
+    /// Streams a table's rows to `visit` straight from the zip entries.
+    /// `visit` returns `false` to stop early. Returns the number of rows read.
+    pub fn visit_rows(
+        &mut self,
+        table: &Table,
+        mut visit: impl FnMut(Vec<Value>) -> Result<bool>,
+    ) -> Result<u64> {
+        let columns = table.data_columns();
+        let mut rows = 0;
+        for name in self.table_data_entries(table) {
+            let entry = self.archive.by_name(&name)?;
+            let mut reader = BcpReader::new(BufReader::new(entry), &columns)?;
+            while let Some(row) = reader.next_row().with_context(|| {
+                format!(
+                    "Failed to decode row {} of {}",
+                    rows + 1,
+                    table.display_name()
+                )
+            })? {
+                rows += 1;
+                if !visit(row)? {
+                    return Ok(rows);
+                }
+            }
+        }
+        Ok(rows)
+    }
+}
+
+pub fn is_data_entry(name: &str) -> bool {
+    name.starts_with(DATA_PREFIX)
+}
+
+/// Writes a new package. Output goes to a temporary file next to the target
+/// and is only moved into place by `finish`, so a failed run never leaves a
+/// truncated package behind.
+pub struct PackageWriter {
+    zip: ZipWriter<File>,
+    entries: BTreeSet<String>,
+    temp_path: PathBuf,
+    target_path: PathBuf,
+}
+
+impl PackageWriter {
+    pub fn create(target_path: &Path) -> Result<Self> {
+        let temp_path = partial_path(target_path)?;
+        let file = File::create(&temp_path)
+            .with_context(|| format!("Failed to create {}", temp_path.display()))?;
+
+        Ok(PackageWriter {
+            zip: ZipWriter::new(file),
+            entries: BTreeSet::new(),
+            temp_path,
+            target_path: target_path.to_path_buf(),
+        })
+    }
+
+    pub fn add_entry(&mut self, name: &str, bytes: &[u8]) -> Result<()> {
+        self.start_entry(name)?;
+        self.zip.write_all(bytes)?;
+        Ok(())
+    }
+
+    /// Starts a new entry; its content is written through the `Write` impl.
+    pub fn start_entry(&mut self, name: &str) -> Result<()> {
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .large_file(true);
+        self.zip.start_file(name, options)?;
+        self.entries.insert(name.to_string());
+        Ok(())
+    }
+
+    /// Writes `[Content_Types].xml` for the entries added so far and moves the
+    /// package into place.
+    pub fn finish(mut self) -> Result<()> {
+        if !self.entries.contains(CONTENT_TYPES_ENTRY) {
+            let content_types = content_types_xml(&self.entries);
+            self.add_entry(CONTENT_TYPES_ENTRY, content_types.as_bytes())?;
+        }
+
+        let file = self.zip.finish()?;
+        file.sync_all()?;
+        drop(file);
+
+        std::fs::rename(&self.temp_path, &self.target_path)
+            .with_context(|| format!("Failed to write {}", self.target_path.display()))?;
+        Ok(())
+    }
+
+    pub fn abandon(self) {
+        drop(self.zip);
+        let _ = std::fs::remove_file(&self.temp_path);
+    }
+}
+
+impl Write for PackageWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.zip.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.zip.flush()
+    }
+}
+
+fn content_types_xml(entries: &BTreeSet<String>) -> String {
+    let extensions: BTreeSet<String> = entries
+        .iter()
+        .filter_map(|name| Path::new(name).extension())
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .collect();
+
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?><Types xmlns=\"http://schemas.openxmlformats.org/package/2006/content-types\">",
+    );
+    for ext in extensions {
+        let content_type = match ext.as_str() {
+            "xml" | "refactorlog" => "text/xml",
+            "sql" => "text/plain",
+            _ => "application/octet-stream",
+        };
+        xml.push_str(&format!(
+            "<Default Extension=\"{}\" ContentType=\"{}\" />",
+            escape_xml(&ext),
+            content_type
+        ));
+    }
+    xml.push_str("</Types>");
+    xml
+}
+
+/// Upper-case hex SHA-256, the form DacFx uses in `Origin.xml` checksums.
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    hex::encode_upper(Sha256::digest(bytes))
+}
+
+pub fn escape_xml(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Package XML is UTF-8, usually with a byte order mark that roxmltree rejects.
+fn decode_xml_text(bytes: &[u8]) -> Result<String> {
+    let bytes = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(bytes);
+    Ok(String::from_utf8(bytes.to_vec())?)
+}
+
+/// Temporary file next to `target` that output is written to before it is
+/// moved into place.
+pub fn partial_path(target: &Path) -> Result<PathBuf> {
+    let mut temp_name = target
+        .file_name()
+        .ok_or_else(|| anyhow!("Invalid output path {}", target.display()))?
+        .to_os_string();
+    temp_name.push(".partial");
+    Ok(target.with_file_name(temp_name))
+}
+
+/// Whether both paths exist and name the same file.
+pub fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Runs `write` against a temporary file next to `target` and moves the file
+/// into place only when it succeeds, so a failed run leaves an existing
+/// `target` untouched.
+pub fn write_replacing<T>(target: &Path, write: impl FnOnce(&Path) -> Result<T>) -> Result<T> {
+    let temp_path = partial_path(target)?;
+    // A leftover from an interrupted run must not be appended to
+    let _ = std::fs::remove_file(&temp_path);
+    let result = write(&temp_path).and_then(|value| {
+        std::fs::rename(&temp_path, target)
+            .with_context(|| format!("Failed to write {}", target.display()))?;
+        Ok(value)
+    });
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_replacing_keeps_the_old_file_on_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("script.sql");
+        std::fs::write(&target, "old").unwrap();
+
+        let failed = write_replacing(&target, |path| -> Result<()> {
+            std::fs::write(path, "half")?;
+            Err(anyhow!("stopped"))
+        });
+        assert!(failed.is_err());
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "old");
+        assert!(!partial_path(&target).unwrap().exists());
+
+        write_replacing(&target, |path| Ok(std::fs::write(path, "new")?)).unwrap();
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "new");
+
+        assert!(same_file(&target, &dir.path().join(".").join("script.sql")));
+        assert!(!same_file(&target, &dir.path().join("missing.sql")));
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test_support {
+    use super::*;
+
+    pub const ORIGIN_XML: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<DacOrigin xmlns="http://schemas.microsoft.com/sqlserver/dac/Serialization/2012/02">
+  <PackageProperties>
+    <Version>3.1.0.0</Version>
+    <ContainsExportedData>true</ContainsExportedData>
+    <StreamVersions>
+      <Version StreamName="Data">2.0.0.0</Version>
+      <Version StreamName="DeploymentContributors">1.0.0.0</Version>
+    </StreamVersions>
+  </PackageProperties>
+  <Operation>
+    <Identity>5e9a2b58-0f7c-4d0e-9a55-1c7c2b2f8f11</Identity>
+    <Start>2024-05-01T10:00:00.0000000+00:00</Start>
+    <End>2024-05-01T10:01:00.0000000+00:00</End>
+    <ProductName>Microsoft.Data.Tools.Schema.Tasks.Sql, Version=162.1.167.1</ProductName>
+    <ProductVersion>162.1.167.1</ProductVersion>
+    <ProductSchema>http://schemas.microsoft.com/sqlserver/dac/Serialization/2012/02</ProductSchema>
+  </Operation>
+  <Server>
+    <ServerVersion>15.0.4153.1</ServerVersion>
+  </Server>
+  <Checksums>
+    <Checksum Uri="/model.xml">00</Checksum>
+  </Checksums>
+  <ModelSchemaVersion>2.9</ModelSchemaVersion>
+</DacOrigin>"#;
+
+    /// Writes a package with the given model and data entries to `path`.
+    pub fn write_package(path: &Path, model_xml: &str, data: &[(&str, Vec<u8>)]) {
+        let mut writer = PackageWriter::create(path).unwrap();
+        writer.add_entry(MODEL_ENTRY, model_xml.as_bytes()).unwrap();
+        writer
+            .add_entry(ORIGIN_ENTRY, ORIGIN_XML.as_bytes())
+            .unwrap();
+        writer
+            .add_entry(
+                METADATA_ENTRY,
+                origin::dac_metadata_xml("Sample", "1.0.0.0", None).as_bytes(),
+            )
+            .unwrap();
+        for (name, bytes) in data {
+            writer.add_entry(name, bytes).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+}
@@ -0,0 +1,1518 @@
+use super::{escape_xml, DAC_NAMESPACE};
+use anyhow::{anyhow, Result};
+use roxmltree::Node;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Schema provider written into packages built by this app (SQL Server 2019).
+pub const DEFAULT_SCHEMA_PROVIDER: &str =
+    "Microsoft.Data.Tools.Schema.Sql.Sql150DatabaseSchemaProvider";
+
+/// Windows-1252, the code page of the Latin1_General collations.
+pub const DEFAULT_CODE_PAGE: u16 = 1252;
+
+/// Collation name prefixes and their code pages, longest first where they overlap.
+const COLLATION_CODE_PAGES: &[(&str, u16)] = &[
+    ("Albanian", 1250),
+    ("Arabic", 1256),
+    ("Azeri_Cyrillic", 1251),
+    ("Azeri_Latin", 1254),
+    ("Bashkir", 1251),
+    ("Bosnian_Cyrillic", 1251),
+    ("Bosnian_Latin", 1250),
+    ("Chinese_Hong_Kong", 950),
+    ("Chinese_Taiwan", 950),
+    ("Chinese_Traditional", 950),
+    ("Chinese", 936),
+    ("Croatian", 1250),
+    ("Cyrillic", 1251),
+    ("Czech", 1250),
+    ("Dari", 1256),
+    ("Estonian", 1257),
+    ("Greek", 1253),
+    ("Hebrew", 1255),
+    ("Hungarian", 1250),
+    ("Japanese", 932),
+    ("Kazakh", 1251),
+    ("Korean", 949),
+    ("Latvian", 1257),
+    ("Lithuanian", 1257),
+    ("Macedonian", 1251),
+    ("Pashto", 1256),
+    ("Persian", 1256),
+    ("Polish", 1250),
+    ("Romanian", 1250),
+    ("Serbian_Cyrillic", 1251),
+    ("Serbian_Latin", 1250),
+    ("Slovak", 1250),
+    ("Slovenian", 1250),
+    ("Tatar", 1251),
+    ("Thai", 874),
+    ("Turkish", 1254),
+    ("Turkmen", 1250),
+    ("Ukrainian", 1251),
+    ("Urdu", 1256),
+    ("Uzbek_Latin", 1254),
+    ("Vietnamese", 1258),
+    ("Yakut", 1251),
+];
+
+/// Code page of non-Unicode data under a collation such as
+/// `Cyrillic_General_CI_AS`, `SQL_Latin1_General_CP1250_CI_AS` or
+/// `Latin1_General_100_CI_AS_SC_UTF8` (65001).
+pub fn collation_code_page(collation: &str) -> u16 {
+    let lower = collation.to_ascii_lowercase();
+    if lower.ends_with("_utf8") {
+        return 65001;
+    }
+    if let Some(rest) = lower.strip_prefix("sql_") {
+        // SQL_Latin1_General_CP1_CI_AS is 1252; others name their code page
+        let code_page = rest
+            .split('_')
+            .find_map(|part| part.strip_prefix("cp")?.parse::<u16>().ok())
+            .filter(|cp| *cp != 1);
+        if let Some(code_page) = code_page {
+            return code_page;
+        }
+    }
+    let name = lower.strip_prefix("sql_").unwrap_or(&lower);
+    COLLATION_CODE_PAGES
+        .iter()
+        .find(|(prefix, _)| name.starts_with(&prefix.to_ascii_lowercase()))
+        .map_or(DEFAULT_CODE_PAGE, |(_, code_page)| *code_page)
+}
+
+/// Code page of a database whose collation is only known by its locale id.
+pub fn lcid_code_page(lcid: u32) -> u16 {
+    match (lcid & 0x3FF, lcid) {
+        (0x04, 0x0804 | 0x1004) => 936,
+        (0x04, _) => 950,
+        (0x1A, 0x0C1A | 0x201A | 0x1C1A) => 1251,
+        (0x02 | 0x19 | 0x22 | 0x23 | 0x2F | 0x3F | 0x44 | 0x6D | 0x85, _) => 1251,
+        (0x05 | 0x0E | 0x15 | 0x18 | 0x1A | 0x1B | 0x1C | 0x24 | 0x42, _) => 1250,
+        (0x08, _) => 1253,
+        (0x1F | 0x2C | 0x43, _) => 1254,
+        (0x0D, _) => 1255,
+        (0x01 | 0x20 | 0x29 | 0x63 | 0x8C, _) => 1256,
+        (0x25..=0x27, _) => 1257,
+        (0x2A, _) => 1258,
+        (0x1E, _) => 874,
+        (0x11, _) => 932,
+        (0x12, _) => 949,
+        _ => DEFAULT_CODE_PAGE,
+    }
+}
+
+/// Base SQL Server type of a column, after resolving user-defined data types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SqlType {
+    Bit,
+    TinyInt,
+    SmallInt,
+    Int,
+    BigInt,
+    Decimal,
+    Numeric,
+    Money,
+    SmallMoney,
+    Float,
+    Real,
+    Date,
+    Time,
+    DateTime,
+    DateTime2,
+    SmallDateTime,
+    DateTimeOffset,
+    Char,
+    VarChar,
+    Text,
+    NChar,
+    NVarChar,
+    NText,
+    Binary,
+    VarBinary,
+    Image,
+    UniqueIdentifier,
+    Xml,
+    Timestamp,
+    SqlVariant,
+    HierarchyId,
+    Geography,
+    Geometry,
+}
+
+impl SqlType {
+    pub fn from_name(name: &str) -> Option<SqlType> {
+        let sql_type = match name.to_ascii_lowercase().as_str() {
+            "bit" => SqlType::Bit,
+            "tinyint" => SqlType::TinyInt,
+            "smallint" => SqlType::SmallInt,
+            "int" => SqlType::Int,
+            "bigint" => SqlType::BigInt,
+            "decimal" => SqlType::Decimal,
+            "numeric" => SqlType::Numeric,
+            "money" => SqlType::Money,
+            "smallmoney" => SqlType::SmallMoney,
+            "float" => SqlType::Float,
+            "real" => SqlType::Real,
+            "date" => SqlType::Date,
+            "time" => SqlType::Time,
+            "datetime" => SqlType::DateTime,
+            "datetime2" => SqlType::DateTime2,
+            "smalldatetime" => SqlType::SmallDateTime,
+            "datetimeoffset" => SqlType::DateTimeOffset,
+            "char" => SqlType::Char,
+            "varchar" => SqlType::VarChar,
+            "text" => SqlType::Text,
+            "nchar" => SqlType::NChar,
+            "nvarchar" => SqlType::NVarChar,
+            "ntext" => SqlType::NText,
+            "binary" => SqlType::Binary,
+            "varbinary" => SqlType::VarBinary,
+            "image" => SqlType::Image,
+            "uniqueidentifier" => SqlType::UniqueIdentifier,
+            "xml" => SqlType::Xml,
+            "timestamp" | "rowversion" => SqlType::Timestamp,
+            "sql_variant" => SqlType::SqlVariant,
+            "hierarchyid" => SqlType::HierarchyId,
+            "geography" => SqlType::Geography,
+            "geometry" => SqlType::Geometry,
+            // sysname is nvarchar(128) NOT NULL
+            "sysname" => SqlType::NVarChar,
+            _ => return None,
+        };
+        Some(sql_type)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SqlType::Bit => "bit",
+            SqlType::TinyInt => "tinyint",
+            SqlType::SmallInt => "smallint",
+            SqlType::Int => "int",
+            SqlType::BigInt => "bigint",
+            SqlType::Decimal => "decimal",
+            SqlType::Numeric => "numeric",
+            SqlType::Money => "money",
+            SqlType::SmallMoney => "smallmoney",
+            SqlType::Float => "float",
+            SqlType::Real => "real",
+            SqlType::Date => "date",
+            SqlType::Time => "time",
+            SqlType::DateTime => "datetime",
+            SqlType::DateTime2 => "datetime2",
+            SqlType::SmallDateTime => "smalldatetime",
+            SqlType::DateTimeOffset => "datetimeoffset",
+            SqlType::Char => "char",
+            SqlType::VarChar => "varchar",
+            SqlType::Text => "text",
+            SqlType::NChar => "nchar",
+            SqlType::NVarChar => "nvarchar",
+            SqlType::NText => "ntext",
+            SqlType::Binary => "binary",
+            SqlType::VarBinary => "varbinary",
+            SqlType::Image => "image",
+            SqlType::UniqueIdentifier => "uniqueidentifier",
+            SqlType::Xml => "xml",
+            SqlType::Timestamp => "timestamp",
+            SqlType::SqlVariant => "sql_variant",
+            SqlType::HierarchyId => "hierarchyid",
+            SqlType::Geography => "geography",
+            SqlType::Geometry => "geometry",
+        }
+    }
+
+    pub fn is_string(&self) -> bool {
+        matches!(
+            self,
+            SqlType::Char
+                | SqlType::VarChar
+                | SqlType::Text
+                | SqlType::NChar
+                | SqlType::NVarChar
+                | SqlType::NText
+                | SqlType::Xml
+        )
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        matches!(
+            self,
+            SqlType::TinyInt
+                | SqlType::SmallInt
+                | SqlType::Int
+                | SqlType::BigInt
+                | SqlType::Decimal
+                | SqlType::Numeric
+                | SqlType::Money
+                | SqlType::SmallMoney
+                | SqlType::Float
+                | SqlType::Real
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Column {
+    pub name: String,
+    pub sql_type: SqlType,
+    /// Type name as written in the model, e.g. a user-defined data type.
+    pub type_name: String,
+    pub length: Option<u32>,
+    pub is_max: bool,
+    pub precision: u8,
+    pub scale: u8,
+    pub nullable: bool,
+    pub identity: bool,
+    /// Identity seed and increment as written in the model; 1 when absent.
+    pub identity_seed: Option<String>,
+    pub identity_increment: Option<String>,
+    pub computed: bool,
+    /// Definition of a computed column.
+    pub expression: Option<String>,
+    pub collation: Option<String>,
+    /// Windows code page of `char`, `varchar` and `text` data: the column's
+    /// collation, else the database's.
+    #[serde(skip)]
+    pub code_page: u16,
+    /// Expression of the column's default constraint.
+    pub default: Option<String>,
+    /// `MS_Description` extended property.
+    pub description: Option<String>,
+}
+
+impl Column {
+    /// Builds a column from a declaration such as `nvarchar(200)`,
+    /// `decimal(10, 2)`, `varbinary(max)` or `datetime2(3)`.
+    pub fn from_declaration(name: &str, declaration: &str, nullable: bool) -> Result<Self> {
+        let declaration = declaration.trim();
+        let (type_name, facets) = match declaration.split_once('(') {
+            Some((type_name, rest)) => (
+                type_name.trim(),
+                rest.strip_suffix(')')
+                    .ok_or_else(|| anyhow!("Invalid type {}", declaration))?
+                    .split(',')
+                    .map(|f| f.trim().to_ascii_lowercase())
+                    .collect::<Vec<_>>(),
+            ),
+            None => (declaration, Vec::new()),
+        };
+        let sql_type = SqlType::from_name(type_name)
+            .ok_or_else(|| anyhow!("Unsupported type {}", declaration))?;
+        let number = |facet: &String| {
+            facet
+                .parse::<u32>()
+                .map_err(|_| anyhow!("Invalid type {}", declaration))
+        };
+        let small = |facet: &String| {
+            number(facet)
+                .and_then(|n| u8::try_from(n).map_err(|_| anyhow!("Invalid type {}", declaration)))
+        };
+
+        let mut column = Column {
+            name: name.to_string(),
+            sql_type,
+            type_name: sql_type.name().to_string(),
+            length: None,
+            is_max: false,
+            precision: 0,
+            scale: 0,
+            nullable,
+            identity: false,
+            identity_seed: None,
+            identity_increment: None,
+            computed: false,
+            expression: None,
+            collation: None,
+            code_page: DEFAULT_CODE_PAGE,
+            default: None,
+            description: None,
+        };
+        match (sql_type, facets.as_slice()) {
+            (SqlType::Decimal | SqlType::Numeric, []) => column.precision = 18,
+            (SqlType::Decimal | SqlType::Numeric, [precision]) => {
+                column.precision = small(precision)?
+            }
+            (SqlType::Decimal | SqlType::Numeric, [precision, scale]) => {
+                column.precision = small(precision)?;
+                column.scale = small(scale)?;
+            }
+            (SqlType::Time | SqlType::DateTime2 | SqlType::DateTimeOffset, []) => column.scale = 7,
+            (SqlType::Time | SqlType::DateTime2 | SqlType::DateTimeOffset, [scale]) => {
+                column.scale = small(scale)?
+            }
+            (SqlType::Char | SqlType::NChar | SqlType::Binary, []) => column.length = Some(1),
+            (
+                SqlType::Char
+                | SqlType::VarChar
+                | SqlType::NChar
+                | SqlType::NVarChar
+                | SqlType::Binary
+                | SqlType::VarBinary,
+                [length],
+            ) if length == "max" => column.is_max = true,
+            (
+                SqlType::Char
+                | SqlType::VarChar
+                | SqlType::NChar
+                | SqlType::NVarChar
+                | SqlType::Binary
+                | SqlType::VarBinary,
+                [length],
+            ) => column.length = Some(number(length)?),
+            (SqlType::VarChar | SqlType::NVarChar | SqlType::VarBinary, []) => {
+                return Err(anyhow!(
+                    "{} needs a length, e.g. {}(100) or {}(max)",
+                    type_name,
+                    type_name,
+                    type_name
+                ));
+            }
+            (_, []) => {}
+            _ => return Err(anyhow!("Invalid type {}", declaration)),
+        }
+        match sql_type {
+            SqlType::Decimal | SqlType::Numeric
+                if !(1..=38).contains(&column.precision) || column.scale > column.precision =>
+            {
+                return Err(anyhow!(
+                    "Invalid type {}: precision must be 1 to 38 and scale at most the precision",
+                    declaration
+                ));
+            }
+            SqlType::Time | SqlType::DateTime2 | SqlType::DateTimeOffset if column.scale > 7 => {
+                return Err(anyhow!(
+                    "Invalid type {}: fractional seconds scale must be 0 to 7",
+                    declaration
+                ));
+            }
+            SqlType::NChar | SqlType::NVarChar
+                if column.length.is_some_and(|n| !(1..=4000).contains(&n)) =>
+            {
+                return Err(anyhow!(
+                    "Invalid type {}: length must be 1 to 4000, or max",
+                    declaration
+                ));
+            }
+            SqlType::Char | SqlType::VarChar | SqlType::Binary | SqlType::VarBinary
+                if column.length.is_some_and(|n| !(1..=8000).contains(&n)) =>
+            {
+                return Err(anyhow!(
+                    "Invalid type {}: length must be 1 to 8000, or max",
+                    declaration
+                ));
+            }
+            _ => {}
+        }
+        if type_name.eq_ignore_ascii_case("sysname") {
+            column.length = Some(128);
+        }
+        Ok(column)
+    }
+
+    /// Type declaration as it would appear in a CREATE TABLE statement.
+    pub fn type_declaration(&self) -> String {
+        match self.sql_type {
+            SqlType::Decimal | SqlType::Numeric => {
+                format!(
+                    "{}({}, {})",
+                    self.sql_type.name(),
+                    self.precision,
+                    self.scale
+                )
+            }
+            SqlType::Time | SqlType::DateTime2 | SqlType::DateTimeOffset => {
+                format!("{}({})", self.sql_type.name(), self.scale)
+            }
+            SqlType::Char
+            | SqlType::VarChar
+            | SqlType::NChar
+            | SqlType::NVarChar
+            | SqlType::Binary
+            | SqlType::VarBinary => {
+                if self.is_max {
+                    format!("{}(max)", self.sql_type.name())
+                } else {
+                    format!("{}({})", self.sql_type.name(), self.length.unwrap_or(1))
+                }
+            }
+            _ => self.sql_type.name().to_string(),
+        }
+    }
+}
+
+/// A primary key, unique constraint or index.
+#[derive(Debug, Clone, Serialize)]
+pub struct Index {
+    pub name: Option<String>,
+    pub columns: Vec<String>,
+    pub unique: bool,
+    /// True for primary key and unique constraints, false for plain indexes.
+    pub is_constraint: bool,
+    /// `WHERE` predicate of a filtered index.
+    pub filter: Option<String>,
+    /// Non-key columns stored in the index leaf level.
+    pub included_columns: Vec<String>,
+}
+
+/// A foreign key from this table's `columns` to `referenced_columns` of
+/// `referenced_table` (`schema.name`).
+#[derive(Debug, Clone, Serialize)]
+pub struct ForeignKey {
+    pub name: Option<String>,
+    pub columns: Vec<String>,
+    pub referenced_table: String,
+    pub referenced_columns: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Table {
+    pub schema: String,
+    pub name: String,
+    pub columns: Vec<Column>,
+    pub primary_key: Option<Index>,
+    pub indexes: Vec<Index>,
+    pub foreign_keys: Vec<ForeignKey>,
+    /// `MS_Description` extended property.
+    pub description: Option<String>,
+}
+
+impl Table {
+    /// `schema.name`, the folder that holds the table's rows under `Data/`.
+    pub fn display_name(&self) -> String {
+        format!("{}.{}", self.schema, self.name)
+    }
+
+    /// Columns present in the BCP data files, in file order. Computed
+    /// columns are not exported.
+    pub fn data_columns(&self) -> Vec<&Column> {
+        self.columns.iter().filter(|c| !c.computed).collect()
+    }
+
+    /// Position of a column within `data_columns`.
+    pub fn data_column_index(&self, name: &str) -> Option<usize> {
+        self.data_columns()
+            .iter()
+            .position(|c| c.name.eq_ignore_ascii_case(name))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DatabaseModel {
+    pub schema_provider: Option<String>,
+    pub collation_lcid: Option<u32>,
+    pub collation_case_sensitive: bool,
+    pub tables: Vec<Table>,
+}
+
+impl DatabaseModel {
+    pub fn parse(xml: &str) -> Result<Self> {
+        let doc = parse_document(xml)?;
+        let root = doc.root_element();
+        let model = model_node(&doc)?;
+
+        let user_types: HashMap<String, Node> = model_elements(model)
+            .filter(|e| element_type(*e) == Some("SqlUserDefinedDataType"))
+            .filter_map(|e| Some((e.attribute("Name")?.to_string(), e)))
+            .collect();
+
+        let mut tables = Vec::new();
+        for element in model_elements(model).filter(|e| element_type(*e) == Some("SqlTable")) {
+            tables.push(parse_table(element, &user_types)?);
+        }
+
+        for element in model_elements(model) {
+            let (table_relationship, is_primary_key, is_constraint) = match element_type(element) {
+                Some("SqlForeignKeyConstraint") => {
+                    add_foreign_key(&mut tables, element);
+                    continue;
+                }
+                Some("SqlDefaultConstraint") => {
+                    add_default(&mut tables, element);
+                    continue;
+                }
+                Some("SqlExtendedProperty") => {
+                    add_description(&mut tables, element);
+                    continue;
+                }
+                Some("SqlPrimaryKeyConstraint") => ("DefiningTable", true, true),
+                Some("SqlUniqueConstraint") => ("DefiningTable", false, true),
+                Some("SqlIndex") => ("IndexedObject", false, false),
+                _ => continue,
+            };
+            let Some(table) = relationship_references(element, table_relationship)
+                .first()
+                .and_then(|name| find_table_mut(&mut tables, name))
+            else {
+                // Indexes on views are not needed for table data
+                continue;
+            };
+
+            let index = Index {
+                name: element.attribute("Name").and_then(|n| split_name(n).pop()),
+                columns: relationship_elements(element, "ColumnSpecifications")
+                    .filter_map(|spec| {
+                        relationship_references(spec, "Column")
+                            .first()
+                            .and_then(|n| split_name(n).pop())
+                    })
+                    .collect(),
+                unique: is_constraint
+                    || property(element, "IsUnique").map(|v| v.eq_ignore_ascii_case("true"))
+                        == Some(true),
+                is_constraint,
+                filter: property(element, "FilterPredicate").map(|s| s.trim().to_string()),
+                included_columns: relationship_references(element, "IncludedColumns")
+                    .into_iter()
+                    .filter_map(|n| split_name(n).pop())
+                    .collect(),
+            };
+
+            if is_primary_key {
+                table.primary_key = Some(index);
+            } else {
+                table.indexes.push(index);
+            }
+        }
+
+        let collation_lcid = root.attribute("CollationLcid").and_then(|s| s.parse().ok());
+        let database_code_page = model_elements(model)
+            .find(|e| element_type(*e) == Some("SqlDatabaseOptions"))
+            .and_then(|options| property(options, "Collation"))
+            .map(collation_code_page)
+            .or(collation_lcid.map(lcid_code_page))
+            .unwrap_or(DEFAULT_CODE_PAGE);
+        for column in tables.iter_mut().flat_map(|t| t.columns.iter_mut()) {
+            column.code_page = column
+                .collation
+                .as_deref()
+                .map_or(database_code_page, collation_code_page);
+        }
+
+        Ok(DatabaseModel {
+            schema_provider: root.attribute("DspName").map(|s| s.to_string()),
+            collation_lcid,
+            collation_case_sensitive: root
+                .attribute("CollationCaseSensitive")
+                .map(|v| v.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+            tables,
+        })
+    }
+
+    /// Resolves table names given by the user; all tables when `names` is empty.
+    pub fn select_tables(&self, names: &[String]) -> Result<Vec<&Table>> {
+        if names.is_empty() {
+            return Ok(self.tables.iter().collect());
+        }
+        names
+            .iter()
+            .map(|name| {
+                self.find_table(name)
+                    .ok_or_else(|| anyhow!("Table {} not found in the package", name))
+            })
+            .collect()
+    }
+
+    /// Renders a `model.xml` describing the tables, their primary keys and
+    /// foreign keys. Used for packages this app builds from scratch.
+    pub fn to_xml(&self) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+        xml.push_str(&format!(
+            "<DataSchemaModel FileFormatVersion=\"1.2\" SchemaVersion=\"2.9\" DspName=\"{}\" CollationLcid=\"{}\" CollationCaseSensitive=\"{}\" xmlns=\"{}\">\n",
+            escape_xml(
+                self.schema_provider
+                    .as_deref()
+                    .unwrap_or(DEFAULT_SCHEMA_PROVIDER)
+            ),
+            self.collation_lcid.unwrap_or(1033),
+            if self.collation_case_sensitive { "True" } else { "False" },
+            DAC_NAMESPACE
+        ));
+        xml.push_str("  <Model>\n");
+        xml.push_str("    <Element Type=\"SqlDatabaseOptions\">\n");
+        xml.push_str(&format!(
+            "      <Property Name=\"Collation\" Value=\"{}\" />\n",
+            if self.collation_case_sensitive {
+                "SQL_Latin1_General_CP1_CS_AS"
+            } else {
+                "SQL_Latin1_General_CP1_CI_AS"
+            }
+        ));
+        xml.push_str("    </Element>\n");
+
+        let mut schemas: Vec<&str> = self
+            .tables
+            .iter()
+            .map(|t| t.schema.as_str())
+            .filter(|s| !s.eq_ignore_ascii_case("dbo"))
+            .collect();
+        schemas.sort_unstable();
+        schemas.dedup();
+        for schema in schemas {
+            xml.push_str(&format!(
+                "    <Element Type=\"SqlSchema\" Name=\"{}\">\n",
+                escape_xml(&quote_name(schema))
+            ));
+            xml.push_str("      <Relationship Name=\"Authorizer\">\n");
+            xml.push_str("        <Entry><References ExternalSource=\"BuiltIns\" Name=\"[dbo]\" /></Entry>\n");
+            xml.push_str("      </Relationship>\n");
+            xml.push_str("    </Element>\n");
+        }
+
+        for table in &self.tables {
+            write_table_xml(&mut xml, table);
+        }
+
+        xml.push_str("  </Model>\n");
+        xml.push_str("</DataSchemaModel>");
+        xml
+    }
+
+    /// Finds a table by `schema.name`, `[schema].[name]` or a bare name in `dbo`.
+    pub fn find_table(&self, name: &str) -> Option<&Table> {
+        let parts = split_name(name);
+        let (schema, table) = match parts.as_slice() {
+            [table] => ("dbo", table.as_str()),
+            [schema, table] => (schema.as_str(), table.as_str()),
+            _ => return None,
+        };
+        self.tables
+            .iter()
+            .find(|t| t.schema.eq_ignore_ascii_case(schema) && t.name.eq_ignore_ascii_case(table))
+    }
+}
+
+fn write_table_xml(xml: &mut String, table: &Table) {
+    let table_name = qualified_name(&table.schema, &table.name);
+    let column_name = |column: &str| format!("{}.{}", table_name, quote_name(column));
+    let references = |xml: &mut String, indent: &str, name: &str, builtin: bool| {
+        xml.push_str(&format!(
+            "{}<Entry><References {}Name=\"{}\" /></Entry>\n",
+            indent,
+            if builtin {
+                "ExternalSource=\"BuiltIns\" "
+            } else {
+                ""
+            },
+            escape_xml(name)
+        ));
+    };
+
+    xml.push_str(&format!(
+        "    <Element Type=\"SqlTable\" Name=\"{}\">\n",
+        escape_xml(&table_name)
+    ));
+    xml.push_str("      <Relationship Name=\"Columns\">\n");
+    for column in table.data_columns() {
+        xml.push_str("        <Entry>\n");
+        xml.push_str(&format!(
+            "          <Element Type=\"SqlSimpleColumn\" Name=\"{}\">\n",
+            escape_xml(&column_name(&column.name))
+        ));
+        if !column.nullable {
+            xml.push_str("            <Property Name=\"IsNullable\" Value=\"False\" />\n");
+        }
+        if column.identity {
+            xml.push_str("            <Property Name=\"IsIdentity\" Value=\"True\" />\n");
+        }
+        xml.push_str("            <Relationship Name=\"TypeSpecifier\">\n");
+        xml.push_str("              <Entry>\n");
+        xml.push_str("                <Element Type=\"SqlTypeSpecifier\">\n");
+        let facet = |xml: &mut String, name: &str, value: String| {
+            xml.push_str(&format!(
+                "                  <Property Name=\"{}\" Value=\"{}\" />\n",
+                name, value
+            ));
+        };
+        match column.sql_type {
+            SqlType::Decimal | SqlType::Numeric => {
+                facet(xml, "Precision", column.precision.to_string());
+                facet(xml, "Scale", column.scale.to_string());
+            }
+            SqlType::Time | SqlType::DateTime2 | SqlType::DateTimeOffset => {
+                facet(xml, "Scale", column.scale.to_string());
+            }
+            _ if column.is_max => facet(xml, "IsMax", "True".to_string()),
+            _ => {
+                if let Some(length) = column.length {
+                    facet(xml, "Length", length.to_string());
+                }
+            }
+        }
+        xml.push_str("                  <Relationship Name=\"Type\">\n");
+        references(
+            xml,
+            "                    ",
+            &quote_name(column.sql_type.name()),
+            true,
+        );
+        xml.push_str("                  </Relationship>\n");
+        xml.push_str("                </Element>\n");
+        xml.push_str("              </Entry>\n");
+        xml.push_str("            </Relationship>\n");
+        xml.push_str("          </Element>\n");
+        xml.push_str("        </Entry>\n");
+    }
+    xml.push_str("      </Relationship>\n");
+    xml.push_str("      <Relationship Name=\"Schema\">\n");
+    references(
+        xml,
+        "        ",
+        &quote_name(&table.schema),
+        table.schema.eq_ignore_ascii_case("dbo"),
+    );
+    xml.push_str("      </Relationship>\n");
+    xml.push_str("    </Element>\n");
+
+    if let Some(primary_key) = table
+        .primary_key
+        .as_ref()
+        .filter(|pk| !pk.columns.is_empty())
+    {
+        let name = primary_key
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("PK_{}", table.name));
+        xml.push_str(&format!(
+            "    <Element Type=\"SqlPrimaryKeyConstraint\" Name=\"{}\">\n",
+            escape_xml(&qualified_name(&table.schema, &name))
+        ));
+        xml.push_str("      <Relationship Name=\"ColumnSpecifications\">\n");
+        for column in &primary_key.columns {
+            xml.push_str("        <Entry>\n");
+            xml.push_str("          <Element Type=\"SqlIndexedColumnSpecification\">\n");
+            xml.push_str("            <Relationship Name=\"Column\">\n");
+            references(xml, "              ", &column_name(column), false);
+            xml.push_str("            </Relationship>\n");
+            xml.push_str("          </Element>\n");
+            xml.push_str("        </Entry>\n");
+        }
+        xml.push_str("      </Relationship>\n");
+        xml.push_str("      <Relationship Name=\"DefiningTable\">\n");
+        references(xml, "        ", &table_name, false);
+        xml.push_str("      </Relationship>\n");
+        xml.push_str("    </Element>\n");
+    }
+
+    for (position, foreign_key) in table.foreign_keys.iter().enumerate() {
+        let parts = split_name(&foreign_key.referenced_table);
+        let [schema, referenced] = parts.as_slice() else {
+            continue;
+        };
+        let referenced_name = qualified_name(schema, referenced);
+        let name = foreign_key
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("FK_{}_{}_{}", table.name, referenced, position + 1));
+        xml.push_str(&format!(
+            "    <Element Type=\"SqlForeignKeyConstraint\" Name=\"{}\">\n",
+            escape_xml(&qualified_name(&table.schema, &name))
+        ));
+        xml.push_str("      <Relationship Name=\"Columns\">\n");
+        for column in &foreign_key.columns {
+            references(xml, "        ", &column_name(column), false);
+        }
+        xml.push_str("      </Relationship>\n");
+        xml.push_str("      <Relationship Name=\"DefiningTable\">\n");
+        references(xml, "        ", &table_name, false);
+        xml.push_str("      </Relationship>\n");
+        xml.push_str("      <Relationship Name=\"ForeignColumns\">\n");
+        for column in &foreign_key.referenced_columns {
+            references(
+                xml,
+                "        ",
+                &format!("{}.{}", referenced_name, quote_name(column)),
+                false,
+            );
+        }
+        xml.push_str("      </Relationship>\n");
+        xml.push_str("      <Relationship Name=\"ForeignTable\">\n");
+        references(xml, "        ", &referenced_name, false);
+        xml.push_str("      </Relationship>\n");
+        xml.push_str("    </Element>\n");
+    }
+}
+
+/// `[name]`, escaping closing brackets.
+pub fn quote_name(name: &str) -> String {
+    format!("[{}]", name.replace(']', "]]"))
+}
+
+/// `[schema].[name]`.
+pub fn qualified_name(schema: &str, name: &str) -> String {
+    format!("{}.{}", quote_name(schema), quote_name(name))
+}
+
+fn parse_table(element: Node, user_types: &HashMap<String, Node>) -> Result<Table> {
+    let name = element
+        .attribute("Name")
+        .ok_or_else(|| anyhow!("SqlTable element without a name"))?;
+    let parts = split_name(name);
+    let (schema, table_name) = match parts.as_slice() {
+        [schema, table] => (schema.clone(), table.clone()),
+        _ => return Err(anyhow!("Unexpected table name {}", name)),
+    };
+
+    let mut columns = Vec::new();
+    for column in relationship_elements(element, "Columns") {
+        let column_name = column
+            .attribute("Name")
+            .and_then(|n| split_name(n).pop())
+            .ok_or_else(|| anyhow!("Column without a name in {}", name))?;
+        let computed = element_type(column) == Some("SqlComputedColumn");
+
+        let mut parsed = Column {
+            name: column_name,
+            sql_type: SqlType::SqlVariant,
+            type_name: String::new(),
+            length: None,
+            is_max: false,
+            precision: 0,
+            scale: 0,
+            nullable: property(column, "IsNullable")
+                .map(|v| !v.eq_ignore_ascii_case("false"))
+                .unwrap_or(true),
+            identity: property(column, "IsIdentity")
+                .map(|v| v.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+            identity_seed: property(column, "IdentitySeed").map(|s| s.trim().to_string()),
+            identity_increment: property(column, "IdentityIncrement").map(|s| s.trim().to_string()),
+            computed,
+            expression: property(column, "ExpressionScript").map(|s| s.trim().to_string()),
+            collation: property(column, "Collation").map(|s| s.to_string()),
+            // Set once the database collation is known
+            code_page: DEFAULT_CODE_PAGE,
+            default: None,
+            description: None,
+        };
+
+        if let Some(specifier) = relationship_elements(column, "TypeSpecifier").next() {
+            apply_type_specifier(&mut parsed, specifier, user_types)
+                .map_err(|e| anyhow!("Column {}.{}: {}", name, parsed.name, e))?;
+        } else if !computed {
+            return Err(anyhow!("Column {}.{} has no type", name, parsed.name));
+        }
+
+        columns.push(parsed);
+    }
+
+    Ok(Table {
+        schema,
+        name: table_name,
+        columns,
+        primary_key: None,
+        indexes: Vec::new(),
+        foreign_keys: Vec::new(),
+        description: None,
+    })
+}
+
+fn add_foreign_key(tables: &mut [Table], element: Node) {
+    let column_names = |relationship: &str| -> Vec<String> {
+        relationship_references(element, relationship)
+            .into_iter()
+            .filter_map(|n| split_name(n).pop())
+            .collect()
+    };
+    let Some(referenced_table) = relationship_references(element, "ForeignTable")
+        .first()
+        .map(|name| split_name(name).join("."))
+    else {
+        return;
+    };
+    let foreign_key = ForeignKey {
+        name: element.attribute("Name").and_then(|n| split_name(n).pop()),
+        columns: column_names("Columns"),
+        referenced_table,
+        referenced_columns: column_names("ForeignColumns"),
+    };
+    if let Some(table) = relationship_references(element, "DefiningTable")
+        .first()
+        .and_then(|name| find_table_mut(tables, name))
+    {
+        table.foreign_keys.push(foreign_key);
+    }
+}
+
+fn add_default(tables: &mut [Table], element: Node) {
+    let Some(expression) = property(element, "DefaultExpressionScript") else {
+        return;
+    };
+    let Some(column_name) = relationship_references(element, "ForColumn")
+        .first()
+        .and_then(|n| split_name(n).pop())
+    else {
+        return;
+    };
+    if let Some(column) = relationship_references(element, "DefiningTable")
+        .first()
+        .and_then(|name| find_table_mut(tables, name))
+        .and_then(|table| table.columns.iter_mut().find(|c| c.name == column_name))
+    {
+        column.default = Some(expression.trim().to_string());
+    }
+}
+
+/// Records `MS_Description` properties of tables and columns.
+fn add_description(tables: &mut [Table], element: Node) {
+    let is_description = element
+        .attribute("Name")
+        .and_then(|n| split_name(n).pop())
+        .is_some_and(|n| n == "MS_Description");
+    let Some(value) = property(element, "Value").filter(|_| is_description) else {
+        return;
+    };
+    let Some(host) = relationship_references(element, "Host")
+        .first()
+        .map(|n| split_name(n))
+    else {
+        return;
+    };
+    let description = Some(unquote_literal(value));
+    match host.as_slice() {
+        [schema, table] => {
+            if let Some(table) = find_table_mut(tables, &qualified_name(schema, table)) {
+                table.description = description;
+            }
+        }
+        [schema, table, column] => {
+            if let Some(column) = find_table_mut(tables, &qualified_name(schema, table))
+                .and_then(|t| t.columns.iter_mut().find(|c| &c.name == column))
+            {
+                column.description = description;
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Text of a T-SQL string literal such as `N'It''s'`.
+fn unquote_literal(literal: &str) -> String {
+    let trimmed = literal.trim();
+    let unprefixed = trimmed
+        .strip_prefix('N')
+        .or_else(|| trimmed.strip_prefix('n'))
+        .unwrap_or(trimmed);
+    match unprefixed
+        .strip_prefix('\'')
+        .and_then(|s| s.strip_suffix('\''))
+    {
+        Some(inner) => inner.replace("''", "'"),
+        None => trimmed.to_string(),
+    }
+}
+
+fn find_table_mut<'a>(tables: &'a mut [Table], name: &str) -> Option<&'a mut Table> {
+    let parts = split_name(name);
+    let [schema, table] = parts.as_slice() else {
+        return None;
+    };
+    tables
+        .iter_mut()
+        .find(|t| &t.schema == schema && &t.name == table)
+}
+
+fn apply_type_specifier(
+    column: &mut Column,
+    specifier: Node,
+    user_types: &HashMap<String, Node>,
+) -> Result<()> {
+    let type_ref = relationship_references(specifier, "Type")
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("type specifier without a type"))?;
+    let type_parts = split_name(type_ref);
+    let type_name = type_parts.last().cloned().unwrap_or_default();
+    column.type_name = if type_parts.len() > 1 {
+        type_parts.join(".")
+    } else {
+        type_name.clone()
+    };
+
+    read_type_facets(column, specifier);
+
+    match SqlType::from_name(&type_name).filter(|_| type_parts.len() == 1) {
+        Some(sql_type) => column.sql_type = sql_type,
+        None => {
+            // A user-defined data type: facets come from its definition
+            let definition = user_types
+                .get(type_ref)
+                .ok_or_else(|| anyhow!("unsupported type {}", type_ref))?;
+            let base_ref = relationship_references(*definition, "Type")
+                .into_iter()
+                .next()
+                .ok_or_else(|| anyhow!("user-defined type {} has no base type", type_ref))?;
+            let base_name = split_name(base_ref).pop().unwrap_or_default();
+            column.sql_type = SqlType::from_name(&base_name)
+                .ok_or_else(|| anyhow!("unsupported base type {}", base_ref))?;
+            read_type_facets(column, *definition);
+            if property(*definition, "IsNullable").map(|v| v.eq_ignore_ascii_case("false"))
+                == Some(true)
+            {
+                column.nullable = false;
+            }
+        }
+    }
+
+    if type_name.eq_ignore_ascii_case("sysname") {
+        column.length = Some(128);
+    }
+
+    match column.sql_type {
+        SqlType::Decimal | SqlType::Numeric if column.precision == 0 => column.precision = 18,
+        SqlType::Time | SqlType::DateTime2 | SqlType::DateTimeOffset
+            if property(specifier, "Scale").is_none() =>
+        {
+            column.scale = 7
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+fn read_type_facets(column: &mut Column, element: Node) {
+    if let Some(length) = property(element, "Length").and_then(|v| v.parse().ok()) {
+        column.length = Some(length);
+    }
+    if let Some(precision) = property(element, "Precision").and_then(|v| v.parse().ok()) {
+        column.precision = precision;
+    }
+    if let Some(scale) = property(element, "Scale").and_then(|v| v.parse().ok()) {
+        column.scale = scale;
+    }
+    if property(element, "IsMax").map(|v| v.eq_ignore_ascii_case("true")) == Some(true) {
+        column.is_max = true;
+    }
+}
+
+pub(crate) fn parse_document(xml: &str) -> Result<roxmltree::Document<'_>> {
+    Ok(roxmltree::Document::parse_with_options(
+        xml,
+        roxmltree::ParsingOptions {
+            allow_dtd: true,
+            ..Default::default()
+        },
+    )?)
+}
+
+/// The `Model` element under the `DataSchemaModel` root.
+pub(crate) fn model_node<'a, 'input>(
+    doc: &'a roxmltree::Document<'input>,
+) -> Result<Node<'a, 'input>> {
+    let root = doc.root_element();
+    if root.tag_name().name() != "DataSchemaModel" {
+        return Err(anyhow!(
+            "model.xml does not contain a DataSchemaModel element"
+        ));
+    }
+    root.children()
+        .find(|n| n.tag_name().name() == "Model")
+        .ok_or_else(|| anyhow!("model.xml does not contain a Model element"))
+}
+
+/// Top-level `Element` nodes of the `Model` element.
+pub(crate) fn model_elements<'a, 'input>(
+    model: Node<'a, 'input>,
+) -> impl Iterator<Item = Node<'a, 'input>> {
+    model
+        .children()
+        .filter(|n| n.is_element() && n.tag_name().name() == "Element")
+}
+
+pub(crate) fn element_type<'a>(element: Node<'a, '_>) -> Option<&'a str> {
+    element.attribute("Type")
+}
+
+/// Value of a `<Property>`, either from its `Value` attribute or its `<Value>` child.
+pub(crate) fn property<'a>(element: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    let property = element
+        .children()
+        .find(|n| n.tag_name().name() == "Property" && n.attribute("Name") == Some(name))?;
+    if let Some(value) = property.attribute("Value") {
+        return Some(value);
+    }
+    property
+        .children()
+        .find(|n| n.tag_name().name() == "Value")
+        .and_then(|n| n.text())
+}
+
+fn relationship_entries<'a, 'input>(
+    element: Node<'a, 'input>,
+    name: &'a str,
+) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    element
+        .children()
+        .filter(move |n| n.tag_name().name() == "Relationship" && n.attribute("Name") == Some(name))
+        .flat_map(|relationship| relationship.children())
+        .filter(|n| n.tag_name().name() == "Entry")
+}
+
+/// Inline `Element` nodes inside a relationship's entries.
+pub(crate) fn relationship_elements<'a, 'input>(
+    element: Node<'a, 'input>,
+    name: &'a str,
+) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    relationship_entries(element, name)
+        .flat_map(|entry| entry.children())
+        .filter(|n| n.tag_name().name() == "Element")
+}
+
+/// Names referenced by a relationship's `References` entries.
+pub(crate) fn relationship_references<'a>(element: Node<'a, '_>, name: &'a str) -> Vec<&'a str> {
+    relationship_entries(element, name)
+        .flat_map(|entry| entry.children())
+        .filter(|n| n.tag_name().name() == "References")
+        .filter_map(|n| n.attribute("Name"))
+        .collect()
+}
+
+/// Splits a possibly bracket-quoted multi-part name such as `[dbo].[My]]Table]`.
+pub fn split_name(name: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut chars = name.chars().peekable();
+    let mut quoted = false;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '[' if !quoted && current.is_empty() => quoted = true,
+            ']' if quoted => {
+                if chars.peek() == Some(&']') {
+                    chars.next();
+                    current.push(']');
+                } else {
+                    quoted = false;
+                }
+            }
+            '.' if !quoted => parts.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    parts.push(current);
+    parts
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub const MODEL_XML: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<DataSchemaModel FileFormatVersion="1.2" SchemaVersion="2.9" DspName="Microsoft.Data.Tools.Schema.Sql.Sql150DatabaseSchemaProvider" CollationLcid="1033" CollationCaseSensitive="False" xmlns="http://schemas.microsoft.com/sqlserver/dac/Serialization/2012/02">
+  <Model>
+    <Element Type="SqlTable" Name="[dbo].[Customers]">
+      <Relationship Name="Columns">
+        <Entry>
+          <Element Type="SqlSimpleColumn" Name="[dbo].[Customers].[Id]">
+            <Property Name="IsNullable" Value="False" />
+            <Property Name="IsIdentity" Value="True" />
+            <Relationship Name="TypeSpecifier">
+              <Entry>
+                <Element Type="SqlTypeSpecifier">
+                  <Relationship Name="Type">
+                    <Entry><References ExternalSource="BuiltIns" Name="[int]" /></Entry>
+                  </Relationship>
+                </Element>
+              </Entry>
+            </Relationship>
+          </Element>
+        </Entry>
+        <Entry>
+          <Element Type="SqlSimpleColumn" Name="[dbo].[Customers].[Email]">
+            <Relationship Name="TypeSpecifier">
+              <Entry>
+                <Element Type="SqlTypeSpecifier">
+                  <Property Name="Length" Value="200" />
+                  <Relationship Name="Type">
+                    <Entry><References ExternalSource="BuiltIns" Name="[nvarchar]" /></Entry>
+                  </Relationship>
+                </Element>
+              </Entry>
+            </Relationship>
+          </Element>
+        </Entry>
+        <Entry>
+          <Element Type="SqlSimpleColumn" Name="[dbo].[Customers].[Balance]">
+            <Relationship Name="TypeSpecifier">
+              <Entry>
+                <Element Type="SqlTypeSpecifier">
+                  <Property Name="Precision" Value="10" />
+                  <Property Name="Scale" Value="2" />
+                  <Relationship Name="Type">
+                    <Entry><References ExternalSource="BuiltIns" Name="[decimal]" /></Entry>
+                  </Relationship>
+                </Element>
+              </Entry>
+            </Relationship>
+          </Element>
+        </Entry>
+        <Entry>
+          <Element Type="SqlComputedColumn" Name="[dbo].[Customers].[Domain]">
+            <Property Name="ExpressionScript">
+              <Value><![CDATA[substring([Email],charindex('@',[Email])+1,200)]]></Value>
+            </Property>
+          </Element>
+        </Entry>
+      </Relationship>
+    </Element>
+    <Element Type="SqlPrimaryKeyConstraint" Name="[dbo].[PK_Customers]">
+      <Relationship Name="ColumnSpecifications">
+        <Entry>
+          <Element Type="SqlIndexedColumnSpecification">
+            <Relationship Name="Column">
+              <Entry><References Name="[dbo].[Customers].[Id]" /></Entry>
+            </Relationship>
+          </Element>
+        </Entry>
+      </Relationship>
+      <Relationship Name="DefiningTable">
+        <Entry><References Name="[dbo].[Customers]" /></Entry>
+      </Relationship>
+    </Element>
+    <Element Type="SqlIndex" Name="[dbo].[Customers].[IX_Customers_Email]">
+      <Property Name="IsUnique" Value="True" />
+      <Relationship Name="ColumnSpecifications">
+        <Entry>
+          <Element Type="SqlIndexedColumnSpecification">
+            <Relationship Name="Column">
+              <Entry><References Name="[dbo].[Customers].[Email]" /></Entry>
+            </Relationship>
+          </Element>
+        </Entry>
+      </Relationship>
+      <Relationship Name="IndexedObject">
+        <Entry><References Name="[dbo].[Customers]" /></Entry>
+      </Relationship>
+    </Element>
+    <Element Type="SqlDefaultConstraint" Name="[dbo].[DF_Customers_Balance]">
+      <Property Name="DefaultExpressionScript">
+        <Value><![CDATA[((0))]]></Value>
+      </Property>
+      <Relationship Name="DefiningTable">
+        <Entry><References Name="[dbo].[Customers]" /></Entry>
+      </Relationship>
+      <Relationship Name="ForColumn">
+        <Entry><References Name="[dbo].[Customers].[Balance]" /></Entry>
+      </Relationship>
+    </Element>
+    <Element Type="SqlExtendedProperty" Name="[SqlColumn].[dbo].[Customers].[Email].[MS_Description]">
+      <Property Name="Value">
+        <Value><![CDATA[N'Customer''s login']]></Value>
+      </Property>
+      <Relationship Name="Host">
+        <Entry><References Name="[dbo].[Customers].[Email]" /></Entry>
+      </Relationship>
+    </Element>
+    <Element Type="SqlTable" Name="[sales].[Orders]">
+      <Relationship Name="Columns">
+        <Entry>
+          <Element Type="SqlSimpleColumn" Name="[sales].[Orders].[OrderId]">
+            <Property Name="IsNullable" Value="False" />
+            <Relationship Name="TypeSpecifier">
+              <Entry>
+                <Element Type="SqlTypeSpecifier">
+                  <Relationship Name="Type">
+                    <Entry><References ExternalSource="BuiltIns" Name="[int]" /></Entry>
+                  </Relationship>
+                </Element>
+              </Entry>
+            </Relationship>
+          </Element>
+        </Entry>
+        <Entry>
+          <Element Type="SqlSimpleColumn" Name="[sales].[Orders].[CustomerId]">
+            <Property Name="IsNullable" Value="False" />
+            <Relationship Name="TypeSpecifier">
+              <Entry>
+                <Element Type="SqlTypeSpecifier">
+                  <Relationship Name="Type">
+                    <Entry><References ExternalSource="BuiltIns" Name="[int]" /></Entry>
+                  </Relationship>
+                </Element>
+              </Entry>
+            </Relationship>
+          </Element>
+        </Entry>
+      </Relationship>
+    </Element>
+    <Element Type="SqlPrimaryKeyConstraint" Name="[sales].[PK_Orders]">
+      <Relationship Name="ColumnSpecifications">
+        <Entry>
+          <Element Type="SqlIndexedColumnSpecification">
+            <Relationship Name="Column">
+              <Entry><References Name="[sales].[Orders].[OrderId]" /></Entry>
+            </Relationship>
+          </Element>
+        </Entry>
+      </Relationship>
+      <Relationship Name="DefiningTable">
+        <Entry><References Name="[sales].[Orders]" /></Entry>
+      </Relationship>
+    </Element>
+    <Element Type="SqlForeignKeyConstraint" Name="[sales].[FK_Orders_Customers]">
+      <Relationship Name="Columns">
+        <Entry><References Name="[sales].[Orders].[CustomerId]" /></Entry>
+      </Relationship>
+      <Relationship Name="DefiningTable">
+        <Entry><References Name="[sales].[Orders]" /></Entry>
+      </Relationship>
+      <Relationship Name="ForeignColumns">
+        <Entry><References Name="[dbo].[Customers].[Id]" /></Entry>
+      </Relationship>
+      <Relationship Name="ForeignTable">
+        <Entry><References Name="[dbo].[Customers]" /></Entry>
+      </Relationship>
+    </Element>
+  </Model>
+</DataSchemaModel>"#;
+
+    #[test]
+    fn test_parse_tables_and_columns() {
+        let model = DatabaseModel::parse(MODEL_XML).unwrap();
+        assert_eq!(model.collation_lcid, Some(1033));
+
+        let table = model.find_table("Customers").unwrap();
+        assert_eq!(table.display_name(), "dbo.Customers");
+        assert_eq!(table.columns.len(), 4);
+        assert_eq!(table.data_columns().len(), 3);
+
+        let id = &table.columns[0];
+        assert_eq!(id.sql_type, SqlType::Int);
+        assert!(id.identity);
+        assert!(!id.nullable);
+
+        let email = &table.columns[1];
+        assert_eq!(email.type_declaration(), "nvarchar(200)");
+        assert!(email.nullable);
+
+        let balance = &table.columns[2];
+        assert_eq!(balance.type_declaration(), "decimal(10, 2)");
+
+        let primary_key = table.primary_key.as_ref().unwrap();
+        assert_eq!(primary_key.name.as_deref(), Some("PK_Customers"));
+        assert_eq!(primary_key.columns, vec!["Id"]);
+        assert_eq!(table.indexes.len(), 1);
+        assert!(table.indexes[0].unique);
+        assert_eq!(table.indexes[0].columns, vec!["Email"]);
+
+        assert_eq!(table.columns[2].default.as_deref(), Some("((0))"));
+        assert_eq!(
+            table.columns[1].description.as_deref(),
+            Some("Customer's login")
+        );
+
+        let orders = model.find_table("sales.Orders").unwrap();
+        let foreign_key = &orders.foreign_keys[0];
+        assert_eq!(foreign_key.columns, vec!["CustomerId"]);
+        assert_eq!(foreign_key.referenced_table, "dbo.Customers");
+        assert_eq!(foreign_key.referenced_columns, vec!["Id"]);
+    }
+
+    #[test]
+    fn test_to_xml_round_trip() {
+        let model = DatabaseModel::parse(MODEL_XML).unwrap();
+        let reparsed = DatabaseModel::parse(&model.to_xml()).unwrap();
+        assert_eq!(reparsed.tables.len(), 2);
+
+        let customers = reparsed.find_table("dbo.Customers").unwrap();
+        // Computed columns have no data and are not written
+        assert_eq!(customers.columns.len(), 3);
+        assert!(customers.columns[0].identity);
+        assert_eq!(customers.columns[2].type_declaration(), "decimal(10, 2)");
+        assert_eq!(customers.primary_key.as_ref().unwrap().columns, vec!["Id"]);
+
+        let orders = reparsed.find_table("sales.Orders").unwrap();
+        assert_eq!(orders.foreign_keys[0].referenced_table, "dbo.Customers");
+    }
+
+    #[test]
+    fn test_column_from_declaration() {
+        let column = Column::from_declaration("Name", "NVARCHAR(50)", true).unwrap();
+        assert_eq!(column.type_declaration(), "nvarchar(50)");
+        let column = Column::from_declaration("Blob", "varbinary(max)", true).unwrap();
+        assert!(column.is_max);
+        let column = Column::from_declaration("Price", "decimal(12, 3)", false).unwrap();
+        assert_eq!((column.precision, column.scale), (12, 3));
+        assert!(Column::from_declaration("Name", "varchar", true).is_err());
+        assert!(Column::from_declaration("Name", "money(4)", true).is_err());
+        for invalid in [
+            "decimal(0)",
+            "decimal(39, 2)",
+            "decimal(5, 6)",
+            "decimal(300)",
+            "datetime2(8)",
+            "time(9)",
+            "nvarchar(4001)",
+            "varchar(0)",
+            "binary(8001)",
+        ] {
+            assert!(
+                Column::from_declaration("Value", invalid, true).is_err(),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_collation_code_pages() {
+        assert_eq!(collation_code_page("SQL_Latin1_General_CP1_CI_AS"), 1252);
+        assert_eq!(collation_code_page("Latin1_General_100_CI_AS"), 1252);
+        assert_eq!(collation_code_page("SQL_Latin1_General_CP1250_CS_AS"), 1250);
+        assert_eq!(collation_code_page("SQL_Ukrainian_Cp1251_CI_AS"), 1251);
+        assert_eq!(collation_code_page("Cyrillic_General_CI_AS"), 1251);
+        assert_eq!(collation_code_page("Chinese_PRC_CI_AS"), 936);
+        assert_eq!(collation_code_page("Chinese_Taiwan_Stroke_CI_AS"), 950);
+        assert_eq!(collation_code_page("Japanese_XJIS_140_CI_AS"), 932);
+        assert_eq!(
+            collation_code_page("Latin1_General_100_CI_AS_SC_UTF8"),
+            65001
+        );
+        assert_eq!(lcid_code_page(1049), 1251);
+        assert_eq!(lcid_code_page(2052), 936);
+        assert_eq!(lcid_code_page(1028), 950);
+        assert_eq!(lcid_code_page(1033), 1252);
+
+        let xml = MODEL_XML.replace("CollationLcid=\"1033\"", "CollationLcid=\"1049\"");
+        let model = DatabaseModel::parse(&xml).unwrap();
+        let email = &model.find_table("dbo.Customers").unwrap().columns[1];
+        assert_eq!(email.code_page, 1251);
+    }
+
+    #[test]
+    fn test_identity_options_and_index_details() {
+        let xml = MODEL_XML
+            .replace(
+                r#"<Property Name="IsIdentity" Value="True" />"#,
+                r#"<Property Name="IsIdentity" Value="True" />
+            <Property Name="IdentitySeed" Value="1000" />
+            <Property Name="IdentityIncrement" Value="5" />"#,
+            )
+            .replace(
+                r#"<Property Name="IsUnique" Value="True" />"#,
+                r#"<Property Name="IsUnique" Value="True" />
+      <Property Name="FilterPredicate">
+        <Value><![CDATA[([Email] IS NOT NULL)]]></Value>
+      </Property>
+      <Relationship Name="IncludedColumns">
+        <Entry><References Name="[dbo].[Customers].[Balance]" /></Entry>
+      </Relationship>"#,
+            );
+        let model = DatabaseModel::parse(&xml).unwrap();
+        let table = model.find_table("dbo.Customers").unwrap();
+        assert_eq!(table.columns[0].identity_seed.as_deref(), Some("1000"));
+        assert_eq!(table.columns[0].identity_increment.as_deref(), Some("5"));
+        let index = &table.indexes[0];
+        assert_eq!(index.filter.as_deref(), Some("([Email] IS NOT NULL)"));
+        assert_eq!(index.included_columns, vec!["Balance"]);
+    }
+
+    #[test]
+    fn test_split_name_handles_escaped_brackets() {
+        assert_eq!(split_name("[dbo].[My]]Table]"), vec!["dbo", "My]Table"]);
+        assert_eq!(split_name("dbo.Customers"), vec!["dbo", "Customers"]);
+    }
+}
@@ -0,0 +1,200 @@
+use super::{escape_xml, DAC_NAMESPACE};
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+
+/// The parts of `Origin.xml` we carry over when writing a new package.
+#[derive(Debug, Clone)]
+pub struct OriginInfo {
+    pub package_version: String,
+    pub contains_exported_data: bool,
+    pub stream_versions: Vec<(String, String)>,
+    pub product_name: String,
+    pub product_version: String,
+    pub product_schema: String,
+    pub server_version: Option<String>,
+    pub model_schema_version: Option<String>,
+}
+
+impl OriginInfo {
+    /// Origin for a package built from scratch by this app.
+    pub fn new_export() -> Self {
+        OriginInfo {
+            package_version: "3.1.0.0".to_string(),
+            contains_exported_data: true,
+            stream_versions: vec![
+                ("Data".to_string(), "2.0.0.0".to_string()),
+                ("DeploymentContributors".to_string(), "1.0.0.0".to_string()),
+            ],
+            product_name: "shapac-tool".to_string(),
+            product_version: env!("CARGO_PKG_VERSION").to_string(),
+            product_schema: DAC_NAMESPACE.to_string(),
+            server_version: None,
+            model_schema_version: Some("2.9".to_string()),
+        }
+    }
+
+    pub fn parse(xml: &str) -> Result<Self> {
+        let doc = roxmltree::Document::parse(xml)?;
+        let root = doc.root_element();
+        if root.tag_name().name() != "DacOrigin" {
+            return Err(anyhow!("Origin.xml does not contain a DacOrigin element"));
+        }
+
+        let text = |name: &str| -> Option<String> {
+            root.descendants()
+                .find(|n| n.tag_name().name() == name)
+                .and_then(|n| n.text())
+                .map(|t| t.trim().to_string())
+        };
+
+        let stream_versions = root
+            .descendants()
+            .find(|n| n.tag_name().name() == "StreamVersions")
+            .map(|streams| {
+                streams
+                    .children()
+                    .filter(|n| n.is_element())
+                    .filter_map(|n| {
+                        Some((
+                            n.attribute("StreamName")?.to_string(),
+                            n.text()?.trim().to_string(),
+                        ))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let package_version = root
+            .descendants()
+            .find(|n| n.tag_name().name() == "PackageProperties")
+            .and_then(|props| props.children().find(|n| n.tag_name().name() == "Version"))
+            .and_then(|n| n.text())
+            .map(|t| t.trim().to_string())
+            .unwrap_or_else(|| "3.1.0.0".to_string());
+
+        Ok(OriginInfo {
+            package_version,
+            contains_exported_data: text("ContainsExportedData")
+                .map(|v| v.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+            stream_versions,
+            product_name: text("ProductName").unwrap_or_default(),
+            product_version: text("ProductVersion").unwrap_or_default(),
+            product_schema: text("ProductSchema").unwrap_or_else(|| DAC_NAMESPACE.to_string()),
+            server_version: text("ServerVersion"),
+            model_schema_version: text("ModelSchemaVersion"),
+        })
+    }
+
+    /// Renders a fresh `Origin.xml` for a package written by this app.
+    /// `checksums` pairs a package URI such as `/model.xml` with its SHA-256.
+    pub fn to_xml(&self, checksums: &[(&str, String)]) -> String {
+        let now = Utc::now().to_rfc3339();
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+        xml.push_str(&format!("<DacOrigin xmlns=\"{}\">\n", DAC_NAMESPACE));
+
+        xml.push_str("  <PackageProperties>\n");
+        xml.push_str(&format!(
+            "    <Version>{}</Version>\n",
+            escape_xml(&self.package_version)
+        ));
+        xml.push_str(&format!(
+            "    <ContainsExportedData>{}</ContainsExportedData>\n",
+            self.contains_exported_data
+        ));
+        if !self.stream_versions.is_empty() {
+            xml.push_str("    <StreamVersions>\n");
+            for (stream, version) in &self.stream_versions {
+                xml.push_str(&format!(
+                    "      <Version StreamName=\"{}\">{}</Version>\n",
+                    escape_xml(stream),
+                    escape_xml(version)
+                ));
+            }
+            xml.push_str("    </StreamVersions>\n");
+        }
+        xml.push_str("  </PackageProperties>\n");
+
+        xml.push_str("  <Operation>\n");
+        xml.push_str(&format!(
+            "    <Identity>{}</Identity>\n",
+            uuid::Uuid::new_v4()
+        ));
+        xml.push_str(&format!("    <Start>{}</Start>\n", now));
+        xml.push_str(&format!("    <End>{}</End>\n", now));
+        xml.push_str(&format!(
+            "    <ProductName>{}</ProductName>\n",
+            escape_xml(&self.product_name)
+        ));
+        xml.push_str(&format!(
+            "    <ProductVersion>{}</ProductVersion>\n",
+            escape_xml(&self.product_version)
+        ));
+        xml.push_str(&format!(
+            "    <ProductSchema>{}</ProductSchema>\n",
+            escape_xml(&self.product_schema)
+        ));
+        xml.push_str("  </Operation>\n");
+
+        if let Some(server_version) = &self.server_version {
+            xml.push_str("  <Server>\n");
+            xml.push_str(&format!(
+                "    <ServerVersion>{}</ServerVersion>\n",
+                escape_xml(server_version)
+            ));
+            xml.push_str("  </Server>\n");
+        }
+
+        xml.push_str("  <Checksums>\n");
+        for (uri, checksum) in checksums {
+            xml.push_str(&format!(
+                "    <Checksum Uri=\"{}\">{}</Checksum>\n",
+                escape_xml(uri),
+                checksum
+            ));
+        }
+        xml.push_str("  </Checksums>\n");
+
+        if let Some(version) = &self.model_schema_version {
+            xml.push_str(&format!(
+                "  <ModelSchemaVersion>{}</ModelSchemaVersion>\n",
+                escape_xml(version)
+            ));
+        }
+
+        xml.push_str("</DacOrigin>");
+        xml
+    }
+}
+
+/// The name and version stored in `DacMetadata.xml`.
+pub fn parse_dac_metadata(xml: &str) -> Result<(String, String)> {
+    let doc = roxmltree::Document::parse(xml)?;
+    let child_text = |name: &str| {
+        doc.root_element()
+            .children()
+            .find(|n| n.tag_name().name() == name)
+            .and_then(|n| n.text())
+            .map(|t| t.trim().to_string())
+    };
+
+    Ok((
+        child_text("Name").unwrap_or_default(),
+        child_text("Version").unwrap_or_else(|| "1.0.0.0".to_string()),
+    ))
+}
+
+pub fn dac_metadata_xml(name: &str, version: &str, description: Option<&str>) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str(&format!("<DacType xmlns=\"{}\">\n", DAC_NAMESPACE));
+    xml.push_str(&format!("  <Name>{}</Name>\n", escape_xml(name)));
+    xml.push_str(&format!("  <Version>{}</Version>\n", escape_xml(version)));
+    if let Some(description) = description {
+        xml.push_str(&format!(
+            "  <Description>{}</Description>\n",
+            escape_xml(description)
+        ));
+    }
+    xml.push_str("</DacType>");
+    xml
+}
@@ -0,0 +1,304 @@
+//! Compatibility checks between a BACPAC and the server it is about to be imported into.
+
+use super::model::{element_type, model_elements, model_node, parse_document, property};
+use super::origin::OriginInfo;
+use super::{BacpacArchive, MODEL_ENTRY, ORIGIN_ENTRY};
+use crate::disk::format_size;
+use crate::models::{ImportPreflight, PreflightWarning};
+use crate::sqlserver::ServerInfo;
+use anyhow::Result;
+
+/// SQL Server Express caps each database at 10 GB of data.
+const EXPRESS_MAX_BYTES: u64 = 10 * 1024 * 1024 * 1024;
+
+/// What the package tells us about where it came from and what it needs.
+#[derive(Debug, Clone, Default)]
+pub struct PackageFacts {
+    pub schema_provider: Option<String>,
+    pub source_server_version: Option<String>,
+    pub data_bytes: u64,
+    pub collation: Option<String>,
+}
+
+/// What we could find out about the target server and database.
+#[derive(Debug, Clone)]
+pub struct TargetFacts {
+    pub server: ServerInfo,
+    pub database_exists: bool,
+    /// User objects in an existing database, when they could be counted.
+    pub user_objects: Option<i32>,
+    pub free_bytes: Option<u64>,
+}
+
+pub fn package_facts(archive: &mut BacpacArchive) -> Result<PackageFacts> {
+    let origin = OriginInfo::parse(&archive.read_entry_string(ORIGIN_ENTRY)?)?;
+    let xml = archive.read_entry_string(MODEL_ENTRY)?;
+    let doc = parse_document(&xml)?;
+    let model = model_node(&doc)?;
+    let collation = model_elements(model)
+        .find(|e| element_type(*e) == Some("SqlDatabaseOptions"))
+        .and_then(|options| property(options, "Collation"))
+        .map(|c| c.to_string());
+
+    Ok(PackageFacts {
+        schema_provider: doc
+            .root_element()
+            .attribute("DspName")
+            .map(|name| name.to_string()),
+        source_server_version: origin.server_version,
+        data_bytes: archive.data_size()?,
+        collation,
+    })
+}
+
+/// Platform a schema provider such as `...Sql160DatabaseSchemaProvider` targets.
+#[derive(Debug, PartialEq)]
+enum Platform {
+    SqlServer(u32),
+    Azure,
+    Synapse,
+    Unknown,
+}
+
+fn provider_platform(provider: &str) -> Platform {
+    let name = provider
+        .rsplit('.')
+        .next()
+        .unwrap_or(provider)
+        .trim_end_matches("DatabaseSchemaProvider");
+    if name.starts_with("SqlAzure") {
+        return Platform::Azure;
+    }
+    if name.starts_with("SqlDw") || name.starts_with("SqlServerless") {
+        return Platform::Synapse;
+    }
+    name.strip_prefix("Sql")
+        .and_then(|version| version.parse::<u32>().ok())
+        .map(|version| Platform::SqlServer(version / 10))
+        .unwrap_or(Platform::Unknown)
+}
+
+fn release_name(major: u32) -> String {
+    let year = match major {
+        9 => "2005",
+        10 => "2008",
+        11 => "2012",
+        12 => "2014",
+        13 => "2016",
+        14 => "2017",
+        15 => "2019",
+        16 => "2022",
+        17 => "2025",
+        _ => return format!("SQL Server {}.x", major),
+    };
+    format!("SQL Server {}", year)
+}
+
+pub fn evaluate(package: &PackageFacts, target: &TargetFacts, database: &str) -> ImportPreflight {
+    let server = &target.server;
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+    let mut warn = |code: &str, message: String| {
+        warnings.push(PreflightWarning {
+            code: code.to_string(),
+            message,
+        })
+    };
+    let target_is_azure = server.is_azure();
+    let target_major = server.major_version();
+
+    let platform = package
+        .schema_provider
+        .as_deref()
+        .map(provider_platform)
+        .unwrap_or(Platform::Unknown);
+    match platform {
+        Platform::SqlServer(major) if !target_is_azure => {
+            if let Some(target_major) = target_major.filter(|t| major > *t) {
+                errors.push(format!(
+                    "The BACPAC targets {} but the server runs {} ({}); sqlpackage will refuse to import it",
+                    release_name(major),
+                    release_name(target_major),
+                    server.product_version
+                ));
+            }
+        }
+        Platform::Azure if !target_is_azure => warn(
+            "azure_model",
+            format!(
+                "The BACPAC was exported from Azure SQL Database; objects that use Azure-only features will fail on {}",
+                server.edition
+            ),
+        ),
+        Platform::Synapse => errors.push(
+            "The package comes from Azure Synapse Analytics, which sqlpackage cannot import into SQL Server"
+                .to_string(),
+        ),
+        _ => {}
+    }
+
+    let source_major = package
+        .source_server_version
+        .as_deref()
+        .and_then(|v| v.split('.').next()?.parse::<u32>().ok());
+    if let (Some(source), Some(target_major), false) = (source_major, target_major, target_is_azure)
+    {
+        // Azure SQL Database reports 12.x, which says nothing about features
+        if source > target_major && errors.is_empty() && platform != Platform::Azure {
+            warn(
+                "source_newer",
+                format!(
+                    "The BACPAC was exported from {} and the server runs {}",
+                    release_name(source),
+                    release_name(target_major)
+                ),
+            );
+        }
+    }
+
+    if target.database_exists {
+        match target.user_objects {
+            Some(0) => warn(
+                "database_exists",
+                format!("Database {} already exists; it is empty, so the import will deploy into it", database),
+            ),
+            Some(count) => errors.push(format!(
+                "Database {} already exists and contains {} objects; sqlpackage only imports into a new or empty database",
+                database, count
+            )),
+            None => warn(
+                "database_exists",
+                format!(
+                    "Database {} already exists and its contents could not be checked; the import fails unless it is empty",
+                    database
+                ),
+            ),
+        }
+    }
+
+    if server.is_express() && package.data_bytes > EXPRESS_MAX_BYTES {
+        errors.push(format!(
+            "The BACPAC holds {} of data, more than the 10 GB SQL Server Express allows per database",
+            format_size(package.data_bytes)
+        ));
+    }
+    match target.free_bytes {
+        Some(free) if package.data_bytes > free => errors.push(format!(
+            "The BACPAC holds {} of data but only {} is free on the server's data volume",
+            format_size(package.data_bytes),
+            format_size(free)
+        )),
+        // Index builds and the transaction log need room on top of the data itself
+        Some(free) if package.data_bytes.saturating_mul(3) / 2 > free => warn(
+            "low_disk_space",
+            format!(
+                "Only {} is free on the server's data volume for {} of data plus indexes and log",
+                format_size(free),
+                format_size(package.data_bytes)
+            ),
+        ),
+        None if !target_is_azure => warn(
+            "disk_space_unknown",
+            format!(
+                "Free space on the server could not be checked; the import needs at least {}",
+                format_size(package.data_bytes)
+            ),
+        ),
+        _ => {}
+    }
+
+    if let Some(collation) = &package.collation {
+        if !server.collation.is_empty() && !collation.eq_ignore_ascii_case(&server.collation) {
+            warn(
+                "collation",
+                format!(
+                    "The database will use collation {} while the server default is {}; comparisons with temp tables may need COLLATE",
+                    collation, server.collation
+                ),
+            );
+        }
+    }
+
+    ImportPreflight {
+        source_schema_provider: package.schema_provider.clone(),
+        source_server_version: package.source_server_version.clone(),
+        target_server_version: server.product_version.clone(),
+        target_edition: server.edition.clone(),
+        database_exists: target.database_exists,
+        data_bytes: package.data_bytes,
+        free_bytes: target.free_bytes,
+        package_collation: package.collation.clone(),
+        server_collation: server.collation.clone(),
+        errors,
+        warnings,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(version: &str, engine_edition: i32) -> TargetFacts {
+        TargetFacts {
+            server: ServerInfo {
+                product_version: version.to_string(),
+                edition: "Developer Edition (64-bit)".to_string(),
+                engine_edition,
+                collation: "SQL_Latin1_General_CP1_CI_AS".to_string(),
+            },
+            database_exists: false,
+            user_objects: None,
+            free_bytes: Some(100 * 1024 * 1024),
+        }
+    }
+
+    #[test]
+    fn test_preflight_checks() {
+        let package = PackageFacts {
+            schema_provider: Some(
+                "Microsoft.Data.Tools.Schema.Sql.Sql160DatabaseSchemaProvider".to_string(),
+            ),
+            source_server_version: Some("16.0.1000.6".to_string()),
+            data_bytes: 80 * 1024 * 1024,
+            collation: Some("Latin1_General_100_CS_AS".to_string()),
+        };
+
+        let plan = evaluate(&package, &target("15.0.4355.3", 3), "Sales");
+        assert_eq!(plan.errors.len(), 1);
+        assert!(plan.errors[0].contains("SQL Server 2022"));
+        let codes: Vec<&str> = plan.warnings.iter().map(|w| w.code.as_str()).collect();
+        assert_eq!(codes, vec!["low_disk_space", "collation"]);
+
+        let mut existing = target("16.0.4003.1", 3);
+        existing.database_exists = true;
+        existing.user_objects = Some(12);
+        existing.free_bytes = Some(1024 * 1024 * 1024);
+        let plan = evaluate(&package, &existing, "Sales");
+        assert_eq!(plan.errors.len(), 1);
+        assert!(plan.errors[0].contains("contains 12 objects"));
+
+        // Azure SQL Database accepts any on-premises model and has no volume to check
+        let mut azure = target("12.0.2000.8", 5);
+        azure.free_bytes = None;
+        azure.server.collation = "Latin1_General_100_CS_AS".to_string();
+        let plan = evaluate(&package, &azure, "Sales");
+        assert!(plan.errors.is_empty());
+        assert!(plan.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_provider_platform() {
+        assert_eq!(
+            provider_platform("Microsoft.Data.Tools.Schema.Sql.Sql150DatabaseSchemaProvider"),
+            Platform::SqlServer(15)
+        );
+        assert_eq!(
+            provider_platform("Microsoft.Data.Tools.Schema.Sql.SqlAzureV12DatabaseSchemaProvider"),
+            Platform::Azure
+        );
+        assert_eq!(
+            provider_platform("Microsoft.Data.Tools.Schema.Sql.SqlDwDatabaseSchemaProvider"),
+            Platform::Synapse
+        );
+    }
+}
@@ -0,0 +1,96 @@
+use super::bcp::BcpWriter;
+use super::model::{DatabaseModel, Table};
+use super::origin::OriginInfo;
+use super::{
+    is_data_entry, sha256_hex, BacpacArchive, PackageWriter, CONTENT_TYPES_ENTRY, DATA_PREFIX,
+    MODEL_ENTRY, ORIGIN_ENTRY,
+};
+use anyhow::{anyhow, Result};
+use std::io::Write;
+use std::path::Path;
+
+/// Zip entry that is only created once the first byte is written, so tables
+/// left without rows get no data file at all.
+pub struct TableDataEntry<'a> {
+    writer: &'a mut PackageWriter,
+    name: String,
+    started: bool,
+}
+
+impl Write for TableDataEntry<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if !self.started {
+            self.writer
+                .start_entry(&self.name)
+                .map_err(std::io::Error::other)?;
+            self.started = true;
+        }
+        self.writer.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Writes a copy of a BACPAC whose table data is produced by `write_table`.
+/// Everything outside `Data/` is copied unchanged and `Origin.xml` is
+/// regenerated with the checksum of `model.xml`. `write_table` returns the
+/// number of rows it wrote.
+pub fn rewrite_package(
+    archive: &mut BacpacArchive,
+    model: &DatabaseModel,
+    target: &Path,
+    mut write_table: impl FnMut(
+        &mut BacpacArchive,
+        &Table,
+        &mut BcpWriter<TableDataEntry<'_>>,
+    ) -> Result<u64>,
+) -> Result<u64> {
+    let model_xml = archive.read_entry(MODEL_ENTRY)?;
+    let origin = OriginInfo::parse(&archive.read_entry_string(ORIGIN_ENTRY)?)?;
+
+    let mut writer = PackageWriter::create(target)?;
+    let result = (|| -> Result<u64> {
+        for name in archive.entry_names() {
+            if is_data_entry(&name)
+                || [ORIGIN_ENTRY, CONTENT_TYPES_ENTRY].contains(&name.as_str())
+                || name.ends_with('/')
+            {
+                continue;
+            }
+            archive.copy_entry_to(&name, &mut writer)?;
+        }
+
+        let mut rows = 0;
+        for table in &model.tables {
+            let entry = TableDataEntry {
+                name: format!(
+                    "{}{}/TableData-000-00000.BCP",
+                    DATA_PREFIX,
+                    table.display_name()
+                ),
+                writer: &mut writer,
+                started: false,
+            };
+            let mut bcp = BcpWriter::new(entry, &table.data_columns())?;
+            rows += write_table(archive, table, &mut bcp)
+                .map_err(|e| anyhow!("{}: {:#}", table.display_name(), e))?;
+        }
+
+        let checksums = [("/model.xml", sha256_hex(&model_xml))];
+        writer.add_entry(ORIGIN_ENTRY, origin.to_xml(&checksums).as_bytes())?;
+        Ok(rows)
+    })();
+
+    match result {
+        Ok(rows) => {
+            writer.finish()?;
+            Ok(rows)
+        }
+        Err(e) => {
+            writer.abandon();
+            Err(e)
+        }
+    }
+}
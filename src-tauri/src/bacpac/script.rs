@@ -0,0 +1,585 @@
+//! T-SQL script generation: CREATE TABLE statements from `model.xml` and
+//! batched INSERTs for the table data.
+
+use super::bcp::{format_decimal, Value};
+use super::model::{qualified_name, quote_name, Column, DatabaseModel, SqlType, Table};
+use super::BacpacArchive;
+use anyhow::{anyhow, Result};
+use chrono::{NaiveDateTime, Timelike};
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+
+/// SQL Server accepts at most 1000 rows in one VALUES clause.
+pub const MAX_BATCH_SIZE: usize = 1000;
+const DEFAULT_BATCH_SIZE: usize = 100;
+
+pub struct ScriptOptions {
+    pub include_schema: bool,
+    pub batch_size: Option<usize>,
+}
+
+pub struct ScriptSummary {
+    pub tables: usize,
+    pub rows: u64,
+}
+
+/// Writes a script for `tables`, each with an optional row limit. Tables are
+/// ordered so referenced tables are created and filled first.
+pub fn write_script(
+    archive: &mut BacpacArchive,
+    model: &DatabaseModel,
+    tables: &[(&Table, Option<u64>)],
+    options: &ScriptOptions,
+    out: &mut impl Write,
+    mut progress: impl FnMut(String),
+) -> Result<ScriptSummary> {
+    let batch_size = options
+        .batch_size
+        .unwrap_or(DEFAULT_BATCH_SIZE)
+        .clamp(1, MAX_BATCH_SIZE);
+    let (ordered, cyclic) = dependency_order(tables);
+    let selected: HashSet<String> = tables.iter().map(|(t, _)| t.display_name()).collect();
+
+    // The BOM lets SSMS and sqlcmd tell the script is UTF-8
+    out.write_all("\u{feff}".as_bytes())?;
+    writeln!(
+        out,
+        "-- Generated by shapac-tool {} on {}",
+        env!("CARGO_PKG_VERSION"),
+        chrono::Utc::now().format("%Y-%m-%d %H:%M:%S UTC")
+    )?;
+    writeln!(
+        out,
+        "-- UTF-8; with older sqlcmd versions run it as sqlcmd -f 65001 -i <file>"
+    )?;
+    // Without the schema the constraints already exist, so they are switched
+    // off around the inserts and checked again afterwards
+    let unchecked: Vec<String> = if options.include_schema {
+        Vec::new()
+    } else {
+        ordered
+            .iter()
+            .filter(|(t, _)| cyclic.contains(&t.display_name()))
+            .map(|(t, _)| qualified_name(&t.schema, &t.name))
+            .collect()
+    };
+    if !cyclic.is_empty() {
+        writeln!(
+            out,
+            "-- Foreign keys form a cycle between {}; {}.",
+            cyclic.join(", "),
+            if options.include_schema {
+                "constraints are added after the data"
+            } else {
+                "their constraints are disabled during the inserts and checked afterwards"
+            }
+        )?;
+    }
+    writeln!(out, "SET NOCOUNT ON;\nGO\n")?;
+
+    if options.include_schema {
+        let mut schemas: Vec<&str> = ordered
+            .iter()
+            .map(|(t, _)| t.schema.as_str())
+            .filter(|s| !s.eq_ignore_ascii_case("dbo"))
+            .collect();
+        schemas.sort_unstable();
+        schemas.dedup();
+        for schema in schemas {
+            writeln!(
+                out,
+                "IF SCHEMA_ID({}) IS NULL EXEC({});\nGO\n",
+                string_literal(schema, true),
+                string_literal(&format!("CREATE SCHEMA {}", quote_name(schema)), true)
+            )?;
+        }
+        for (table, _) in &ordered {
+            write_create_table(out, table)?;
+        }
+    }
+
+    for table in &unchecked {
+        writeln!(out, "ALTER TABLE {} NOCHECK CONSTRAINT ALL;", table)?;
+    }
+    if !unchecked.is_empty() {
+        writeln!(out, "GO\n")?;
+    }
+
+    let mut summary = ScriptSummary { tables: 0, rows: 0 };
+    for (table, max_rows) in &ordered {
+        progress(format!("Scripting {}", table.display_name()));
+        summary.rows += write_inserts(archive, table, *max_rows, batch_size, out)?;
+        summary.tables += 1;
+    }
+
+    for table in &unchecked {
+        writeln!(
+            out,
+            "ALTER TABLE {} WITH CHECK CHECK CONSTRAINT ALL;",
+            table
+        )?;
+    }
+    if !unchecked.is_empty() {
+        writeln!(out, "GO\n")?;
+    }
+
+    if options.include_schema {
+        for (table, _) in &ordered {
+            for foreign_key in &table.foreign_keys {
+                if !selected.contains(&foreign_key.referenced_table) {
+                    continue;
+                }
+                let referenced = model
+                    .find_table(&foreign_key.referenced_table)
+                    .ok_or_else(|| anyhow!("Table {} not found", foreign_key.referenced_table))?;
+                let mut statement = format!(
+                    "ALTER TABLE {} ADD ",
+                    qualified_name(&table.schema, &table.name)
+                );
+                if let Some(name) = &foreign_key.name {
+                    statement.push_str(&format!("CONSTRAINT {} ", quote_name(name)));
+                }
+                statement.push_str(&format!(
+                    "FOREIGN KEY ({}) REFERENCES {} ({});",
+                    column_list(&foreign_key.columns),
+                    qualified_name(&referenced.schema, &referenced.name),
+                    column_list(&foreign_key.referenced_columns)
+                ));
+                writeln!(out, "{}", statement)?;
+            }
+        }
+        writeln!(out, "GO")?;
+    }
+
+    out.flush()?;
+    Ok(summary)
+}
+
+/// Orders tables so each comes after the selected tables it references.
+/// Tables caught in a reference cycle keep their given order and are
+/// returned by name as the second value.
+fn dependency_order<'a>(
+    tables: &[(&'a Table, Option<u64>)],
+) -> (Vec<(&'a Table, Option<u64>)>, Vec<String>) {
+    let names: HashMap<String, usize> = tables
+        .iter()
+        .enumerate()
+        .map(|(i, (t, _))| (t.display_name().to_lowercase(), i))
+        .collect();
+    let parents: Vec<HashSet<usize>> = tables
+        .iter()
+        .enumerate()
+        .map(|(i, (table, _))| {
+            table
+                .foreign_keys
+                .iter()
+                .filter_map(|fk| names.get(&fk.referenced_table.to_lowercase()).copied())
+                .filter(|&parent| parent != i)
+                .collect()
+        })
+        .collect();
+
+    let mut placed = vec![false; tables.len()];
+    let mut order = Vec::with_capacity(tables.len());
+    loop {
+        let ready: Vec<usize> = (0..tables.len())
+            .filter(|&i| !placed[i] && parents[i].iter().all(|&p| placed[p]))
+            .collect();
+        if ready.is_empty() {
+            break;
+        }
+        for i in ready {
+            placed[i] = true;
+            order.push(tables[i]);
+        }
+    }
+
+    let cyclic: Vec<String> = (0..tables.len())
+        .filter(|&i| !placed[i])
+        .map(|i| tables[i].0.display_name())
+        .collect();
+    order.extend((0..tables.len()).filter(|&i| !placed[i]).map(|i| tables[i]));
+    (order, cyclic)
+}
+
+fn write_create_table(out: &mut impl Write, table: &Table) -> Result<()> {
+    let mut definitions: Vec<String> = table
+        .columns
+        .iter()
+        .map(|column| {
+            if column.computed {
+                let expression = column.expression.as_deref().ok_or_else(|| {
+                    anyhow!(
+                        "Computed column {}.{} has no definition in the model",
+                        table.display_name(),
+                        column.name
+                    )
+                })?;
+                return Ok(format!("{} AS {}", quote_name(&column.name), expression));
+            }
+            let mut definition =
+                format!("{} {}", quote_name(&column.name), column.type_declaration());
+            if let Some(collation) = &column.collation {
+                definition.push_str(&format!(" COLLATE {}", collation));
+            }
+            if column.identity {
+                definition.push_str(&format!(
+                    " IDENTITY({}, {})",
+                    column.identity_seed.as_deref().unwrap_or("1"),
+                    column.identity_increment.as_deref().unwrap_or("1")
+                ));
+            }
+            definition.push_str(if column.nullable {
+                " NULL"
+            } else {
+                " NOT NULL"
+            });
+            if let Some(default) = &column.default {
+                definition.push_str(&format!(" DEFAULT {}", default));
+            }
+            Ok(definition)
+        })
+        .collect::<Result<_>>()?;
+
+    if let Some(primary_key) = table
+        .primary_key
+        .as_ref()
+        .filter(|pk| !pk.columns.is_empty())
+    {
+        let mut constraint = String::new();
+        if let Some(name) = &primary_key.name {
+            constraint.push_str(&format!("CONSTRAINT {} ", quote_name(name)));
+        }
+        constraint.push_str(&format!(
+            "PRIMARY KEY ({})",
+            column_list(&primary_key.columns)
+        ));
+        definitions.push(constraint);
+    }
+    for index in table.indexes.iter().filter(|i| i.is_constraint) {
+        let mut constraint = String::new();
+        if let Some(name) = &index.name {
+            constraint.push_str(&format!("CONSTRAINT {} ", quote_name(name)));
+        }
+        constraint.push_str(&format!("UNIQUE ({})", column_list(&index.columns)));
+        definitions.push(constraint);
+    }
+
+    writeln!(
+        out,
+        "CREATE TABLE {} (\n    {}\n);",
+        qualified_name(&table.schema, &table.name),
+        definitions.join(",\n    ")
+    )?;
+    for index in table.indexes.iter().filter(|i| !i.is_constraint) {
+        // CREATE INDEX needs a name even where the model has none
+        let name = index
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("IX_{}_{}", table.name, index.columns.join("_")));
+        let mut statement = format!(
+            "CREATE {}INDEX {} ON {} ({})",
+            if index.unique { "UNIQUE " } else { "" },
+            quote_name(&name),
+            qualified_name(&table.schema, &table.name),
+            column_list(&index.columns)
+        );
+        if !index.included_columns.is_empty() {
+            statement.push_str(&format!(
+                " INCLUDE ({})",
+                column_list(&index.included_columns)
+            ));
+        }
+        if let Some(filter) = &index.filter {
+            statement.push_str(&format!(" WHERE {}", filter));
+        }
+        writeln!(out, "{};", statement)?;
+    }
+    writeln!(out, "GO\n")?;
+    Ok(())
+}
+
+fn write_inserts(
+    archive: &mut BacpacArchive,
+    table: &Table,
+    max_rows: Option<u64>,
+    batch_size: usize,
+    out: &mut impl Write,
+) -> Result<u64> {
+    let columns = table.data_columns();
+    // rowversion values are generated by the server and sql_variant data
+    // has no literal form
+    let scripted: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| !matches!(c.sql_type, SqlType::Timestamp | SqlType::SqlVariant))
+        .map(|(i, _)| i)
+        .collect();
+    let name = qualified_name(&table.schema, &table.name);
+    let insert = format!(
+        "INSERT INTO {} ({}) VALUES",
+        name,
+        scripted
+            .iter()
+            .map(|&i| quote_name(&columns[i].name))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    let identity = columns.iter().any(|c| c.identity);
+
+    writeln!(out, "-- {}", table.display_name())?;
+    if identity {
+        writeln!(out, "SET IDENTITY_INSERT {} ON;", name)?;
+    }
+
+    let mut rows = 0u64;
+    archive.visit_rows(table, |row| {
+        if max_rows.is_some_and(|max| rows >= max) {
+            return Ok(false);
+        }
+        let values = scripted
+            .iter()
+            .map(|&i| sql_literal(&row[i], columns[i]))
+            .collect::<Vec<_>>()
+            .join(", ");
+        if rows.is_multiple_of(batch_size as u64) {
+            if rows > 0 {
+                writeln!(out, ";")?;
+            }
+            write!(out, "{}\n    ({})", insert, values)?;
+        } else {
+            write!(out, ",\n    ({})", values)?;
+        }
+        rows += 1;
+        Ok(true)
+    })?;
+    if rows > 0 {
+        writeln!(out, ";")?;
+    }
+
+    if identity {
+        writeln!(out, "SET IDENTITY_INSERT {} OFF;", name)?;
+    }
+    writeln!(out, "GO\n")?;
+    Ok(rows)
+}
+
+fn column_list(columns: &[String]) -> String {
+    columns
+        .iter()
+        .map(|c| quote_name(c))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// `N'...'` or `'...'` with embedded quotes doubled.
+fn string_literal(value: &str, unicode: bool) -> String {
+    format!(
+        "{}'{}'",
+        if unicode { "N" } else { "" },
+        value.replace('\'', "''")
+    )
+}
+
+/// Fractional seconds with at most seven digits, the precision of datetime2.
+fn fraction(nanos: u32, digits: usize) -> String {
+    if digits == 0 {
+        return String::new();
+    }
+    let units = nanos / 10u32.pow(9 - digits as u32);
+    format!(".{:0width$}", units, width = digits)
+}
+
+fn datetime_literal(value: &NaiveDateTime, digits: usize) -> String {
+    format!(
+        "{}{}",
+        value.format("%Y-%m-%dT%H:%M:%S"),
+        fraction(value.nanosecond(), digits)
+    )
+}
+
+/// Literal for a value of `column`, in a form that does not depend on the
+/// session's language or date format settings.
+fn sql_literal(value: &Value, column: &Column) -> String {
+    let scale = (column.scale as usize).min(7);
+    match value {
+        Value::Null => "NULL".to_string(),
+        Value::Bool(b) => if *b { "1" } else { "0" }.to_string(),
+        Value::Int(i) => i.to_string(),
+        Value::Float(f) => format!("{:e}", f),
+        Value::Decimal { value, scale } => format_decimal(*value, *scale),
+        Value::String(s) => string_literal(
+            s,
+            !matches!(
+                column.sql_type,
+                SqlType::Char | SqlType::VarChar | SqlType::Text
+            ),
+        ),
+        Value::Binary(bytes) => format!("0x{}", hex::encode_upper(bytes)),
+        Value::Guid(guid) => format!("'{}'", guid.to_string().to_uppercase()),
+        Value::Date(d) => format!("'{}'", d.format("%Y-%m-%d")),
+        Value::Time(t) => format!(
+            "'{}{}'",
+            t.format("%H:%M:%S"),
+            fraction(t.nanosecond(), scale)
+        ),
+        Value::DateTime(dt) => {
+            let digits = match column.sql_type {
+                SqlType::DateTime => 3,
+                SqlType::SmallDateTime => 0,
+                _ => scale,
+            };
+            format!("'{}'", datetime_literal(dt, digits))
+        }
+        Value::DateTimeOffset(dt) => format!(
+            "'{}{}'",
+            datetime_literal(&dt.naive_local(), scale),
+            dt.format("%:z")
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bacpac::model::tests::MODEL_XML;
+    use crate::bacpac::model::{ForeignKey, Index};
+    use crate::bacpac::test_support::write_package;
+
+    #[test]
+    fn test_sql_literals() {
+        let nvarchar = Column::from_declaration("Name", "nvarchar(20)", true).unwrap();
+        let varchar = Column::from_declaration("Code", "varchar(20)", true).unwrap();
+        let datetime = Column::from_declaration("At", "datetime", true).unwrap();
+        let dt = chrono::NaiveDate::from_ymd_opt(2024, 1, 2)
+            .unwrap()
+            .and_hms_nano_opt(3, 4, 5, 3_333_333)
+            .unwrap();
+
+        assert_eq!(
+            sql_literal(&Value::String("O'Brien".to_string()), &nvarchar),
+            "N'O''Brien'"
+        );
+        assert_eq!(
+            sql_literal(&Value::String("x".to_string()), &varchar),
+            "'x'"
+        );
+        assert_eq!(
+            sql_literal(&Value::DateTime(dt), &datetime),
+            "'2024-01-02T03:04:05.003'"
+        );
+        assert_eq!(sql_literal(&Value::Float(0.1), &datetime), "1e-1");
+        assert_eq!(sql_literal(&Value::Binary(vec![]), &varchar), "0x");
+    }
+
+    #[test]
+    fn test_write_script_orders_tables_and_batches_rows() {
+        let dir = tempfile::tempdir().unwrap();
+        let bacpac = dir.path().join("sample.bacpac");
+        let mut customers = Vec::new();
+        for id in 1..=3i32 {
+            customers.extend_from_slice(&id.to_le_bytes());
+            customers.extend_from_slice(&[0xFF, 0xFF, 0xFF]);
+        }
+        write_package(
+            &bacpac,
+            MODEL_XML,
+            &[("Data/dbo.Customers/TableData-000-00000.BCP", customers)],
+        );
+
+        let mut archive = BacpacArchive::open(&bacpac).unwrap();
+        let mut model = archive.read_model().unwrap();
+        let customers = &mut model.tables[0];
+        customers.columns[0].identity_seed = Some("1000".to_string());
+        customers.indexes[0].filter = Some("([Email] IS NOT NULL)".to_string());
+        customers.indexes[0].included_columns = vec!["Balance".to_string()];
+        let tables = vec![
+            (model.find_table("sales.Orders").unwrap(), None),
+            (model.find_table("dbo.Customers").unwrap(), Some(2)),
+        ];
+        let options = ScriptOptions {
+            include_schema: true,
+            batch_size: Some(1),
+        };
+        let mut out = Vec::new();
+        let summary =
+            write_script(&mut archive, &model, &tables, &options, &mut out, |_| {}).unwrap();
+        assert_eq!(summary.rows, 2);
+
+        let script = String::from_utf8(out).unwrap();
+        let customers_at = script.find("CREATE TABLE [dbo].[Customers]").unwrap();
+        let orders_at = script.find("CREATE TABLE [sales].[Orders]").unwrap();
+        assert!(customers_at < orders_at);
+        assert!(script.contains("[Domain] AS substring([Email],charindex('@',[Email])+1,200)"));
+        assert!(script.contains("[Id] int IDENTITY(1000, 1) NOT NULL"));
+        assert!(script.contains("[Balance] decimal(10, 2) NULL DEFAULT ((0))"));
+        assert!(script.contains(
+            "ON [dbo].[Customers] ([Email]) INCLUDE ([Balance]) WHERE ([Email] IS NOT NULL);"
+        ));
+        assert!(script.contains("SET IDENTITY_INSERT [dbo].[Customers] ON;"));
+        assert_eq!(script.matches("INSERT INTO [dbo].[Customers]").count(), 2);
+        assert!(script.contains("FOREIGN KEY ([CustomerId]) REFERENCES [dbo].[Customers] ([Id])"));
+    }
+
+    #[test]
+    fn test_cyclic_tables_without_schema_and_unnamed_indexes() {
+        let dir = tempfile::tempdir().unwrap();
+        let bacpac = dir.path().join("sample.bacpac");
+        let mut customers = Vec::new();
+        customers.extend_from_slice(&1i32.to_le_bytes());
+        customers.extend_from_slice(&[0xFF, 0xFF, 0xFF]);
+        write_package(
+            &bacpac,
+            MODEL_XML,
+            &[("Data/dbo.Customers/TableData-000-00000.BCP", customers)],
+        );
+
+        let mut archive = BacpacArchive::open(&bacpac).unwrap();
+        let mut model = archive.read_model().unwrap();
+        let customers = &mut model.tables[0];
+        customers.foreign_keys.push(ForeignKey {
+            name: None,
+            columns: vec!["Id".to_string()],
+            referenced_table: "sales.Orders".to_string(),
+            referenced_columns: vec!["Id".to_string()],
+        });
+        customers.indexes.push(Index {
+            name: None,
+            columns: vec!["Balance".to_string()],
+            unique: false,
+            is_constraint: false,
+            filter: None,
+            included_columns: Vec::new(),
+        });
+        let tables = vec![
+            (model.find_table("dbo.Customers").unwrap(), None),
+            (model.find_table("sales.Orders").unwrap(), None),
+        ];
+
+        let mut script = |include_schema| {
+            let options = ScriptOptions {
+                include_schema,
+                batch_size: None,
+            };
+            let mut out = Vec::new();
+            write_script(&mut archive, &model, &tables, &options, &mut out, |_| {}).unwrap();
+            assert!(out.starts_with(b"\xef\xbb\xbf"));
+            String::from_utf8(out).unwrap()
+        };
+
+        let data_only = script(false);
+        let disabled = data_only
+            .find("ALTER TABLE [dbo].[Customers] NOCHECK CONSTRAINT ALL;")
+            .unwrap();
+        let inserted = data_only.find("INSERT INTO [dbo].[Customers]").unwrap();
+        let enabled = data_only
+            .find("ALTER TABLE [dbo].[Customers] WITH CHECK CHECK CONSTRAINT ALL;")
+            .unwrap();
+        assert!(disabled < inserted && inserted < enabled);
+        assert!(data_only.contains("ALTER TABLE [sales].[Orders] NOCHECK CONSTRAINT ALL;"));
+
+        let with_schema = script(true);
+        assert!(!with_schema.contains("NOCHECK"));
+        assert!(with_schema.contains("CREATE INDEX [IX_Customers_Balance] ON [dbo].[Customers]"));
+    }
+}
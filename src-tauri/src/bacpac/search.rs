@@ -0,0 +1,294 @@
+use super::bcp::{parse_decimal, rescale, Value};
+use super::model::DatabaseModel;
+use super::BacpacArchive;
+use crate::jobs::CancellationToken;
+use crate::models::{BacpacSearchRequest, BacpacSearchResult, MatchMode, SearchMatch};
+use anyhow::{anyhow, Result};
+use regex::{Regex, RegexBuilder};
+
+/// Default cap on reported matches so a broad search stays responsive.
+const DEFAULT_MAX_MATCHES: u64 = 1000;
+
+enum Matcher {
+    Exact {
+        value: String,
+        number: Option<f64>,
+        /// The term as an unscaled value and scale, for exact comparison with
+        /// integers and decimals.
+        decimal: Option<(i128, u8)>,
+    },
+    Substring(String),
+    Regex(Regex),
+}
+
+impl Matcher {
+    fn new(request: &BacpacSearchRequest) -> Result<Self> {
+        let normalize = |s: &str| {
+            if request.case_sensitive {
+                s.to_string()
+            } else {
+                s.to_lowercase()
+            }
+        };
+        let matcher = match request.mode {
+            MatchMode::Exact => Matcher::Exact {
+                value: normalize(&request.value),
+                number: request.value.trim().parse().ok(),
+                decimal: parse_decimal(request.value.trim()),
+            },
+            MatchMode::Substring => Matcher::Substring(normalize(&request.value)),
+            MatchMode::Regex => Matcher::Regex(
+                RegexBuilder::new(&request.value)
+                    .case_insensitive(!request.case_sensitive)
+                    .build()
+                    .map_err(|e| anyhow!("Invalid regular expression: {}", e))?,
+            ),
+        };
+        Ok(matcher)
+    }
+
+    fn matches(&self, value: &Value, case_sensitive: bool) -> bool {
+        let text = value.to_string();
+        match self {
+            Matcher::Exact {
+                value: wanted,
+                number,
+                decimal,
+            } => {
+                // 12.5 should find a decimal stored as 12.50; exact numerics
+                // are compared at a common scale, not through a float
+                let exact = match value {
+                    Value::Int(i) => Some((*i as i128, 0)),
+                    Value::Decimal { value, scale } => Some((*value, *scale)),
+                    _ => None,
+                };
+                if let (Some(found), Some(wanted)) = (exact, decimal) {
+                    return same_decimal(found, *wanted);
+                }
+                if let (Some(wanted), Value::Int(_) | Value::Float(_) | Value::Decimal { .. }) =
+                    (number, value)
+                {
+                    return text.parse::<f64>().ok() == Some(*wanted);
+                }
+                if case_sensitive {
+                    &text == wanted
+                } else {
+                    &text.to_lowercase() == wanted
+                }
+            }
+            Matcher::Substring(wanted) => {
+                if case_sensitive {
+                    text.contains(wanted.as_str())
+                } else {
+                    text.to_lowercase().contains(wanted.as_str())
+                }
+            }
+            Matcher::Regex(regex) => regex.is_match(&text),
+        }
+    }
+}
+
+fn same_decimal((a, a_scale): (i128, u8), (b, b_scale): (i128, u8)) -> bool {
+    let scale = a_scale.max(b_scale);
+    match (rescale(a, a_scale, scale), rescale(b, b_scale, scale)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Case-insensitive wildcard pattern using `*` and `?`.
+fn wildcard(pattern: &Option<String>) -> Result<Option<Regex>> {
+    let Some(pattern) = pattern.as_deref().filter(|p| !p.is_empty()) else {
+        return Ok(None);
+    };
+    let mut expression = String::from("^");
+    for c in pattern.chars() {
+        match c {
+            '*' => expression.push_str(".*"),
+            '?' => expression.push('.'),
+            _ => expression.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    expression.push('$');
+    Ok(Some(
+        RegexBuilder::new(&expression)
+            .case_insensitive(true)
+            .build()?,
+    ))
+}
+
+/// Streams every selected table's rows and reports each string or numeric
+/// column whose value matches.
+pub fn search(
+    archive: &mut BacpacArchive,
+    model: &DatabaseModel,
+    request: &BacpacSearchRequest,
+    cancel: &CancellationToken,
+    mut progress: impl FnMut(String),
+) -> Result<BacpacSearchResult> {
+    let matcher = Matcher::new(request)?;
+    let schema_filter = wildcard(&request.schema_pattern)?;
+    let table_filter = wildcard(&request.table_pattern)?;
+    let max_matches = request.max_matches.unwrap_or(DEFAULT_MAX_MATCHES);
+
+    let tables: Vec<_> = model
+        .tables
+        .iter()
+        .filter(|t| schema_filter.as_ref().is_none_or(|r| r.is_match(&t.schema)))
+        .filter(|t| table_filter.as_ref().is_none_or(|r| r.is_match(&t.name)))
+        .collect();
+
+    let mut result = BacpacSearchResult {
+        matches: Vec::new(),
+        tables_searched: 0,
+        rows_scanned: 0,
+        truncated: false,
+        cancelled: false,
+    };
+
+    for (position, table) in tables.iter().enumerate() {
+        if cancel.is_cancelled() {
+            result.cancelled = true;
+            break;
+        }
+
+        let searchable: Vec<(usize, String)> = table
+            .data_columns()
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.sql_type.is_string() || c.sql_type.is_numeric())
+            .map(|(i, c)| (i, c.name.clone()))
+            .collect();
+        if searchable.is_empty() {
+            continue;
+        }
+
+        progress(format!(
+            "Searching {} ({}/{})",
+            table.display_name(),
+            position + 1,
+            tables.len()
+        ));
+
+        let mut row_number = 0u64;
+        archive.visit_rows(table, |row| {
+            if cancel.is_cancelled() {
+                result.cancelled = true;
+                return Ok(false);
+            }
+            row_number += 1;
+            for (index, column) in &searchable {
+                let value = &row[*index];
+                if value.is_null() || !matcher.matches(value, request.case_sensitive) {
+                    continue;
+                }
+                if result.matches.len() as u64 == max_matches {
+                    result.truncated = true;
+                    return Ok(false);
+                }
+                result.matches.push(SearchMatch {
+                    table: table.display_name(),
+                    column: column.clone(),
+                    row: row_number,
+                    value: value.to_string(),
+                });
+            }
+            Ok(true)
+        })?;
+
+        result.rows_scanned += row_number;
+        result.tables_searched += 1;
+        if result.truncated || result.cancelled {
+            break;
+        }
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(mode: MatchMode, value: &str) -> BacpacSearchRequest {
+        BacpacSearchRequest {
+            job_id: "test".to_string(),
+            bacpac_path: String::new(),
+            value: value.to_string(),
+            mode,
+            case_sensitive: false,
+            schema_pattern: None,
+            table_pattern: None,
+            max_matches: None,
+        }
+    }
+
+    #[test]
+    fn test_matchers() {
+        let exact = Matcher::new(&request(MatchMode::Exact, "12.5")).unwrap();
+        assert!(exact.matches(
+            &Value::Decimal {
+                value: 1250,
+                scale: 2
+            },
+            false
+        ));
+        assert!(!exact.matches(&Value::String("12.5 kg".to_string()), false));
+
+        let tenth = Matcher::new(&request(MatchMode::Exact, "0.1")).unwrap();
+        assert!(tenth.matches(
+            &Value::Decimal {
+                value: 10,
+                scale: 2
+            },
+            false
+        ));
+        assert!(!tenth.matches(
+            &Value::Decimal {
+                value: 11,
+                scale: 2
+            },
+            false
+        ));
+        // Beyond the 15-17 digits a float holds
+        let large = Matcher::new(&request(MatchMode::Exact, "12345678901234567.1")).unwrap();
+        assert!(large.matches(
+            &Value::Decimal {
+                value: 123456789012345671,
+                scale: 1
+            },
+            false
+        ));
+        assert!(!large.matches(
+            &Value::Decimal {
+                value: 123456789012345672,
+                scale: 1
+            },
+            false
+        ));
+        assert!(Matcher::new(&request(MatchMode::Exact, "42"))
+            .unwrap()
+            .matches(
+                &Value::Decimal {
+                    value: 4200,
+                    scale: 2
+                },
+                false
+            ));
+
+        let substring = Matcher::new(&request(MatchMode::Substring, "SMITH")).unwrap();
+        assert!(substring.matches(&Value::String("jane.smith@example.com".to_string()), false));
+
+        let regex = Matcher::new(&request(MatchMode::Regex, r"^\d{3}-\d{4}$")).unwrap();
+        assert!(regex.matches(&Value::String("555-1234".to_string()), false));
+        assert!(Matcher::new(&request(MatchMode::Regex, "(")).is_err());
+    }
+
+    #[test]
+    fn test_wildcard() {
+        let pattern = wildcard(&Some("Cust*".to_string())).unwrap().unwrap();
+        assert!(pattern.is_match("customers"));
+        assert!(!pattern.is_match("OrderCustomers"));
+        assert!(wildcard(&Some(String::new())).unwrap().is_none());
+    }
+}
@@ -0,0 +1,464 @@
+//! Security objects in a package model: principals, role memberships, owners
+//! and permissions, with findings worth reviewing before an import.
+
+use super::model::{
+    element_type, model_elements, model_node, parse_document, property, quote_name,
+    relationship_references, split_name,
+};
+use crate::models::{
+    FindingSeverity, PermissionStatement, RoleMembership, SecurableOwner, SecurityFinding,
+    SecurityPrincipal, SecurityReport,
+};
+use anyhow::Result;
+use roxmltree::Node;
+
+/// Fixed roles that let members change security or the schema.
+const ELEVATED_ROLES: &[&str] = &["db_securityadmin", "db_accessadmin", "db_ddladmin"];
+
+/// Permissions that allow taking over other principals or objects.
+const ELEVATED_PERMISSIONS: &[&str] = &[
+    "IMPERSONATE",
+    "TAKE OWNERSHIP",
+    "ALTER ANY USER",
+    "ALTER ANY ROLE",
+    "ALTER ANY SCHEMA",
+    "ALTER ANY APPLICATION ROLE",
+];
+
+fn display_name(name: &str) -> String {
+    split_name(name).join(".")
+}
+
+fn first_reference(element: Node, relationship: &str) -> Option<String> {
+    relationship_references(element, relationship)
+        .first()
+        .map(|name| display_name(name))
+}
+
+fn is_true(element: Node, name: &str) -> bool {
+    property(element, name).is_some_and(|v| v.eq_ignore_ascii_case("true"))
+}
+
+/// `ViewDefinition` to `VIEW DEFINITION`.
+fn permission_keyword(name: &str) -> String {
+    let mut keyword = String::new();
+    let mut previous_lower = false;
+    for c in name.chars() {
+        if c.is_uppercase() && previous_lower {
+            keyword.push(' ');
+        }
+        previous_lower = c.is_lowercase();
+        keyword.extend(c.to_uppercase());
+    }
+    keyword
+}
+
+fn parse_user(element: Node, name: String) -> SecurityPrincipal {
+    let login = first_reference(element, "Login");
+    let authentication = if login.is_some() {
+        "Login"
+    } else if is_true(element, "WithoutLogin") {
+        "WithoutLogin"
+    } else {
+        match property(element, "AuthenticationType") {
+            Some("1") => "Password",
+            Some("2") | Some("3") => "External",
+            _ if property(element, "Password").is_some() => "Password",
+            _ => "Unknown",
+        }
+    };
+    SecurityPrincipal {
+        name,
+        principal_type: "User".to_string(),
+        authentication: Some(authentication.to_string()),
+        login,
+        default_schema: first_reference(element, "DefaultSchema"),
+        owner: None,
+    }
+}
+
+/// Permission statements are named like `[Grant.Select.Object].[grantee].[grantor]...`.
+fn parse_permission(element: Node) -> Option<PermissionStatement> {
+    let name = element.attribute("Name")?;
+    let header = split_name(name).into_iter().next()?;
+    let mut header_parts = header.split('.');
+    let action_name = header_parts.next()?;
+    let permission = permission_keyword(header_parts.next()?);
+
+    let (action, with_grant_option) = match action_name.to_ascii_lowercase().as_str() {
+        "grantwithgrantoption" => ("GRANT", true),
+        "grant" => ("GRANT", false),
+        "deny" => ("DENY", false),
+        "revoke" => ("REVOKE", false),
+        _ => return None,
+    };
+    let securable_reference = relationship_references(element, "SecuredObject")
+        .first()
+        .map(|name| name.to_string());
+    let scope = header_parts
+        .next()
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| {
+            if securable_reference.is_some() {
+                "Object".to_string()
+            } else {
+                "Database".to_string()
+            }
+        });
+    let grantee = relationship_references(element, "Grantee")
+        .first()
+        .map(|name| name.to_string())?;
+
+    let quoted = |name: &str| {
+        split_name(name)
+            .iter()
+            .map(|part| quote_name(part))
+            .collect::<Vec<_>>()
+            .join(".")
+    };
+    let mut statement = format!("{} {}", action, permission);
+    if let Some(securable) = &securable_reference {
+        if scope.eq_ignore_ascii_case("Database") {
+            statement.push_str(&format!(" ON DATABASE::{}", quoted(securable)));
+        } else {
+            statement.push_str(&format!(
+                " ON {}::{}",
+                permission_keyword(&scope),
+                quoted(securable)
+            ));
+        }
+    }
+    statement.push_str(&format!(" TO {}", quoted(&grantee)));
+    if with_grant_option {
+        statement.push_str(" WITH GRANT OPTION");
+    }
+
+    Some(PermissionStatement {
+        action: action.to_string(),
+        permission,
+        scope,
+        securable: securable_reference.map(|name| display_name(&name)),
+        grantee: display_name(&grantee),
+        with_grant_option,
+        statement,
+    })
+}
+
+pub fn security_report(xml: &str) -> Result<SecurityReport> {
+    let doc = parse_document(xml)?;
+    let model = model_node(&doc)?;
+
+    let mut report = SecurityReport {
+        principals: Vec::new(),
+        role_memberships: Vec::new(),
+        schemas: Vec::new(),
+        keys_and_certificates: Vec::new(),
+        permissions: Vec::new(),
+        findings: Vec::new(),
+    };
+
+    for element in model_elements(model) {
+        let Some(object_type) = element_type(element) else {
+            continue;
+        };
+        let name = element.attribute("Name").map(display_name);
+        match (object_type, name) {
+            ("SqlUser", Some(name)) => report.principals.push(parse_user(element, name)),
+            ("SqlRole" | "SqlApplicationRole", Some(name)) => {
+                report.principals.push(SecurityPrincipal {
+                    name,
+                    principal_type: object_type.trim_start_matches("Sql").to_string(),
+                    authentication: None,
+                    login: None,
+                    default_schema: first_reference(element, "DefaultSchema"),
+                    owner: first_reference(element, "Authorizer"),
+                })
+            }
+            ("SqlRoleMembership", _) => {
+                if let (Some(role), Some(member)) = (
+                    first_reference(element, "Role"),
+                    first_reference(element, "Member"),
+                ) {
+                    report
+                        .role_memberships
+                        .push(RoleMembership { role, member });
+                }
+            }
+            ("SqlSchema", Some(name)) => report.schemas.push(SecurableOwner {
+                name,
+                object_type: "Schema".to_string(),
+                owner: first_reference(element, "Authorizer"),
+            }),
+            ("SqlCertificate" | "SqlAsymmetricKey" | "SqlSymmetricKey", Some(name)) => {
+                report.keys_and_certificates.push(SecurableOwner {
+                    name,
+                    object_type: object_type.trim_start_matches("Sql").to_string(),
+                    owner: first_reference(element, "Authorizer"),
+                })
+            }
+            ("SqlPermissionStatement", _) => {
+                if let Some(permission) = parse_permission(element) {
+                    report.permissions.push(permission);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    report.findings = findings(&report);
+    Ok(report)
+}
+
+fn findings(report: &SecurityReport) -> Vec<SecurityFinding> {
+    let mut findings = Vec::new();
+    let mut add = |severity, principal: &str, message: String| {
+        findings.push(SecurityFinding {
+            severity,
+            principal: principal.to_string(),
+            message,
+        })
+    };
+
+    for membership in &report.role_memberships {
+        if membership.role.eq_ignore_ascii_case("db_owner") {
+            add(
+                FindingSeverity::High,
+                &membership.member,
+                "Member of db_owner".to_string(),
+            );
+        } else if ELEVATED_ROLES
+            .iter()
+            .any(|role| membership.role.eq_ignore_ascii_case(role))
+        {
+            add(
+                FindingSeverity::Medium,
+                &membership.member,
+                format!("Member of {}", membership.role),
+            );
+        }
+    }
+
+    for permission in report.permissions.iter().filter(|p| p.action == "GRANT") {
+        if permission.permission == "CONTROL" {
+            add(
+                FindingSeverity::High,
+                &permission.grantee,
+                format!("Granted CONTROL: {}", permission.statement),
+            );
+        } else if ELEVATED_PERMISSIONS.contains(&permission.permission.as_str()) {
+            add(
+                FindingSeverity::Medium,
+                &permission.grantee,
+                format!(
+                    "Granted {}: {}",
+                    permission.permission, permission.statement
+                ),
+            );
+        }
+        if permission.with_grant_option {
+            add(
+                FindingSeverity::Medium,
+                &permission.grantee,
+                format!("Can grant {} to others", permission.permission),
+            );
+        }
+    }
+
+    for user in report
+        .principals
+        .iter()
+        .filter(|p| p.authentication.as_deref() == Some("WithoutLogin"))
+    {
+        add(
+            FindingSeverity::Medium,
+            &user.name,
+            "User without a login".to_string(),
+        );
+    }
+
+    let users: Vec<&str> = report
+        .principals
+        .iter()
+        .filter(|p| p.principal_type == "User")
+        .map(|p| p.name.as_str())
+        .collect();
+    for schema in &report.schemas {
+        if let Some(owner) = schema.owner.as_deref().filter(|o| users.contains(o)) {
+            add(
+                FindingSeverity::Low,
+                owner,
+                format!("Owns schema {}", schema.name),
+            );
+        }
+    }
+    for key in &report.keys_and_certificates {
+        add(
+            FindingSeverity::Low,
+            key.owner.as_deref().unwrap_or(""),
+            format!("Creates {} {}", key.object_type, key.name),
+        );
+    }
+
+    findings.sort_by_key(|f| f.severity as u8);
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODEL_XML: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<DataSchemaModel FileFormatVersion="1.2" SchemaVersion="2.9" DspName="Microsoft.Data.Tools.Schema.Sql.Sql150DatabaseSchemaProvider" xmlns="http://schemas.microsoft.com/sqlserver/dac/Serialization/2012/02">
+  <Model>
+    <Element Type="SqlUser" Name="[app]">
+      <Relationship Name="DefaultSchema">
+        <Entry><References Name="[sales]" /></Entry>
+      </Relationship>
+      <Relationship Name="Login">
+        <Entry><References Name="[app_login]" /></Entry>
+      </Relationship>
+    </Element>
+    <Element Type="SqlUser" Name="[etl]">
+      <Property Name="WithoutLogin" Value="True" />
+    </Element>
+    <Element Type="SqlRole" Name="[readers]">
+      <Relationship Name="Authorizer">
+        <Entry><References ExternalSource="BuiltIns" Name="[dbo]" /></Entry>
+      </Relationship>
+    </Element>
+    <Element Type="SqlRoleMembership">
+      <Relationship Name="Member">
+        <Entry><References Name="[etl]" /></Entry>
+      </Relationship>
+      <Relationship Name="Role">
+        <Entry><References ExternalSource="BuiltIns" Name="[db_owner]" /></Entry>
+      </Relationship>
+    </Element>
+    <Element Type="SqlSchema" Name="[sales]">
+      <Relationship Name="Authorizer">
+        <Entry><References Name="[app]" /></Entry>
+      </Relationship>
+    </Element>
+    <Element Type="SqlCertificate" Name="[SigningCert]">
+      <Relationship Name="Authorizer">
+        <Entry><References ExternalSource="BuiltIns" Name="[dbo]" /></Entry>
+      </Relationship>
+    </Element>
+    <Element Type="SqlPermissionStatement" Name="[Grant.Select.Object].[readers].[dbo].[sales].[Orders]">
+      <Property Name="Permission" Value="4" />
+      <Relationship Name="Grantee">
+        <Entry><References Name="[readers]" /></Entry>
+      </Relationship>
+      <Relationship Name="SecuredObject">
+        <Entry><References Name="[sales].[Orders]" /></Entry>
+      </Relationship>
+    </Element>
+    <Element Type="SqlPermissionStatement" Name="[GrantWithGrantOption.Control.Schema].[app].[dbo].[sales]">
+      <Relationship Name="Grantee">
+        <Entry><References Name="[app]" /></Entry>
+      </Relationship>
+      <Relationship Name="SecuredObject">
+        <Entry><References Name="[sales]" /></Entry>
+      </Relationship>
+    </Element>
+    <Element Type="SqlPermissionStatement" Name="[Deny.ViewDefinition.Database].[public].[dbo]">
+      <Relationship Name="Grantee">
+        <Entry><References ExternalSource="BuiltIns" Name="[public]" /></Entry>
+      </Relationship>
+    </Element>
+  </Model>
+</DataSchemaModel>"#;
+
+    #[test]
+    fn test_security_report() {
+        let report = security_report(MODEL_XML).unwrap();
+
+        assert_eq!(report.principals.len(), 3);
+        assert_eq!(report.principals[0].login.as_deref(), Some("app_login"));
+        assert_eq!(
+            report.principals[0].default_schema.as_deref(),
+            Some("sales")
+        );
+        assert_eq!(
+            report.principals[1].authentication.as_deref(),
+            Some("WithoutLogin")
+        );
+        assert_eq!(report.principals[2].owner.as_deref(), Some("dbo"));
+
+        let statements: Vec<&str> = report
+            .permissions
+            .iter()
+            .map(|p| p.statement.as_str())
+            .collect();
+        assert_eq!(
+            statements,
+            vec![
+                "GRANT SELECT ON OBJECT::[sales].[Orders] TO [readers]",
+                "GRANT CONTROL ON SCHEMA::[sales] TO [app] WITH GRANT OPTION",
+                "DENY VIEW DEFINITION TO [public]",
+            ]
+        );
+
+        let findings: Vec<(FindingSeverity, &str, &str)> = report
+            .findings
+            .iter()
+            .map(|f| (f.severity, f.principal.as_str(), f.message.as_str()))
+            .collect();
+        assert_eq!(
+            findings,
+            vec![
+                (FindingSeverity::High, "etl", "Member of db_owner"),
+                (
+                    FindingSeverity::High,
+                    "app",
+                    "Granted CONTROL: GRANT CONTROL ON SCHEMA::[sales] TO [app] WITH GRANT OPTION"
+                ),
+                (
+                    FindingSeverity::Medium,
+                    "app",
+                    "Can grant CONTROL to others"
+                ),
+                (FindingSeverity::Medium, "etl", "User without a login"),
+                (FindingSeverity::Low, "app", "Owns schema sales"),
+                (
+                    FindingSeverity::Low,
+                    "dbo",
+                    "Creates Certificate SigningCert"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_multi_word_scope_and_unknown_user_type() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<DataSchemaModel xmlns="http://schemas.microsoft.com/sqlserver/dac/Serialization/2012/02">
+  <Model>
+    <Element Type="SqlUser" Name="[svc]">
+      <Property Name="AuthenticationType" Value="9" />
+    </Element>
+    <Element Type="SqlPermissionStatement" Name="[Grant.Alter.ApplicationRole].[svc].[dbo].[reporting]">
+      <Relationship Name="Grantee">
+        <Entry><References Name="[svc]" /></Entry>
+      </Relationship>
+      <Relationship Name="SecuredObject">
+        <Entry><References Name="[reporting]" /></Entry>
+      </Relationship>
+    </Element>
+  </Model>
+</DataSchemaModel>"#;
+        let report = security_report(xml).unwrap();
+        assert_eq!(
+            report.principals[0].authentication.as_deref(),
+            Some("Unknown")
+        );
+        assert_eq!(
+            report.permissions[0].statement,
+            "GRANT ALTER ON APPLICATION ROLE::[reporting] TO [svc]"
+        );
+        assert!(report
+            .findings
+            .iter()
+            .all(|f| f.message != "User without a login"));
+    }
+}
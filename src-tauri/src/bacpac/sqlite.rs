@@ -0,0 +1,385 @@
+use super::bcp::Value;
+use super::model::{Column, DatabaseModel, SqlType, Table};
+use super::BacpacArchive;
+use anyhow::{anyhow, Context, Result};
+use rusqlite::{params_from_iter, Connection};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Emit a progress line every this many rows within a table.
+const PROGRESS_INTERVAL: u64 = 50_000;
+/// Significant decimal digits a 64-bit float holds exactly.
+const MAX_EXACT_DIGITS: u8 = 15;
+
+pub struct SqliteSummary {
+    pub tables: usize,
+    pub rows: u64,
+}
+
+/// Creates a SQLite database holding the tables and rows of a BACPAC.
+/// Tables in `dbo` keep their name; other schemas are prefixed, e.g.
+/// `sales_Orders`. Computed columns are not copied, so keys and indexes
+/// over them are left out.
+pub fn bacpac_to_sqlite(
+    archive: &mut BacpacArchive,
+    model: &DatabaseModel,
+    tables: &[&Table],
+    target: &Path,
+    mut progress: impl FnMut(String),
+) -> Result<SqliteSummary> {
+    let names = sqlite_table_names(tables)?;
+    let mut conn = Connection::open(target)
+        .with_context(|| format!("Failed to create {}", target.display()))?;
+    conn.execute_batch("PRAGMA journal_mode = OFF; PRAGMA synchronous = OFF;")?;
+
+    let mut summary = SqliteSummary { tables: 0, rows: 0 };
+    for table in tables {
+        let sqlite_name = &names[&table.display_name()];
+        progress(format!("Processing Table '{}'.", table.display_name()));
+
+        if table
+            .primary_key
+            .as_ref()
+            .is_some_and(|key| !stored_columns(table, &key.columns))
+        {
+            progress(format!(
+                "Skipped the primary key of '{}' because it includes a computed column.",
+                table.display_name()
+            ));
+        }
+        conn.execute_batch(&create_table_sql(table, sqlite_name))?;
+
+        let columns = table.data_columns();
+        let insert = format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            quote_ident(sqlite_name),
+            columns
+                .iter()
+                .map(|c| quote_ident(&c.name))
+                .collect::<Vec<_>>()
+                .join(", "),
+            vec!["?"; columns.len()].join(", ")
+        );
+
+        let tx = conn.transaction()?;
+        let rows = {
+            let mut stmt = tx.prepare(&insert)?;
+            let mut copied = 0u64;
+            archive.visit_rows(table, |row| {
+                stmt.execute(params_from_iter(row.iter().map(to_sqlite_value)))?;
+                copied += 1;
+                if copied.is_multiple_of(PROGRESS_INTERVAL) {
+                    progress(format!("Copied {} rows into '{}'.", copied, sqlite_name));
+                }
+                Ok(true)
+            })?
+        };
+        tx.commit()?;
+
+        for (position, index) in table
+            .indexes
+            .iter()
+            .filter(|index| !index.columns.is_empty())
+            .enumerate()
+        {
+            let index_name = format!(
+                "{}_{}",
+                sqlite_name,
+                index
+                    .name
+                    .clone()
+                    .unwrap_or_else(|| format!("index{}", position + 1))
+            );
+            if !stored_columns(table, &index.columns) {
+                progress(format!(
+                    "Skipped index '{}' because it includes a computed column.",
+                    index_name
+                ));
+                continue;
+            }
+            if index.filter.is_some() {
+                // T-SQL predicates are not SQLite expressions
+                progress(format!("Skipped filtered index '{}'.", index_name));
+                continue;
+            }
+            conn.execute_batch(&format!(
+                "CREATE {}INDEX {} ON {} ({})",
+                if index.unique { "UNIQUE " } else { "" },
+                quote_ident(&index_name),
+                quote_ident(sqlite_name),
+                index
+                    .columns
+                    .iter()
+                    .map(|c| quote_ident(c))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+            .with_context(|| format!("Failed to create index {}", index_name))?;
+        }
+
+        progress(format!(
+            "Successfully copied {} rows into '{}'.",
+            rows, sqlite_name
+        ));
+        summary.tables += 1;
+        summary.rows += rows;
+    }
+
+    if model.tables.len() > tables.len() {
+        progress(format!(
+            "Skipped {} tables that were not selected.",
+            model.tables.len() - tables.len()
+        ));
+    }
+
+    Ok(summary)
+}
+
+fn sqlite_table_names(tables: &[&Table]) -> Result<HashMap<String, String>> {
+    let mut names = HashMap::new();
+    let mut used: HashMap<String, String> = HashMap::new();
+    for table in tables {
+        let sqlite_name = if table.schema.eq_ignore_ascii_case("dbo") {
+            table.name.clone()
+        } else {
+            format!("{}_{}", table.schema, table.name)
+        };
+        if let Some(existing) = used.insert(sqlite_name.to_lowercase(), table.display_name()) {
+            return Err(anyhow!(
+                "Tables {} and {} would both be named '{}' in SQLite",
+                existing,
+                table.display_name(),
+                sqlite_name
+            ));
+        }
+        names.insert(table.display_name(), sqlite_name);
+    }
+    Ok(names)
+}
+
+fn create_table_sql(table: &Table, sqlite_name: &str) -> String {
+    let mut definitions: Vec<String> = table
+        .data_columns()
+        .iter()
+        .map(|column| {
+            let declared = sqlite_type(column);
+            format!(
+                "{}{}{}{}",
+                quote_ident(&column.name),
+                if declared.is_empty() { "" } else { " " },
+                declared,
+                if column.nullable { "" } else { " NOT NULL" }
+            )
+        })
+        .collect();
+
+    if let Some(primary_key) = &table.primary_key {
+        if !primary_key.columns.is_empty() && stored_columns(table, &primary_key.columns) {
+            definitions.push(format!(
+                "PRIMARY KEY ({})",
+                primary_key
+                    .columns
+                    .iter()
+                    .map(|c| quote_ident(c))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+    }
+
+    format!(
+        "CREATE TABLE {} (\n    {}\n)",
+        quote_ident(sqlite_name),
+        definitions.join(",\n    ")
+    )
+}
+
+/// Whether all the columns are copied, i.e. none is computed.
+fn stored_columns(table: &Table, columns: &[String]) -> bool {
+    columns
+        .iter()
+        .all(|column| table.data_column_index(column).is_some())
+}
+
+/// Declared type for a column. The names are chosen so that SQLite applies
+/// the right affinity and clients recognise dates. Exact numerics of up to 15
+/// digits are NUMERIC, where every value is stored as an exact number. Wider
+/// ones are left without a type: NUMERIC affinity would round a value that
+/// doesn't fit a 64-bit float, and with no affinity such values stay TEXT
+/// while the rest still compare as numbers.
+fn sqlite_type(column: &Column) -> String {
+    match column.sql_type {
+        SqlType::Bit => "BOOLEAN".to_string(),
+        SqlType::TinyInt | SqlType::SmallInt | SqlType::Int | SqlType::BigInt => {
+            "INTEGER".to_string()
+        }
+        SqlType::Real | SqlType::Float => "REAL".to_string(),
+        SqlType::SmallMoney => "NUMERIC(10, 4)".to_string(),
+        SqlType::Decimal | SqlType::Numeric if column.precision <= MAX_EXACT_DIGITS => {
+            format!("NUMERIC({}, {})", column.precision, column.scale)
+        }
+        SqlType::Decimal | SqlType::Numeric | SqlType::Money => String::new(),
+        SqlType::Date => "DATE".to_string(),
+        SqlType::Time => "TIME".to_string(),
+        SqlType::DateTime | SqlType::DateTime2 | SqlType::SmallDateTime => "DATETIME".to_string(),
+        SqlType::DateTimeOffset => "DATETIMEOFFSET".to_string(),
+        SqlType::Binary
+        | SqlType::VarBinary
+        | SqlType::Image
+        | SqlType::Timestamp
+        | SqlType::SqlVariant
+        | SqlType::HierarchyId
+        | SqlType::Geography
+        | SqlType::Geometry => "BLOB".to_string(),
+        SqlType::Char
+        | SqlType::VarChar
+        | SqlType::Text
+        | SqlType::NChar
+        | SqlType::NVarChar
+        | SqlType::NText
+        | SqlType::Xml
+        | SqlType::UniqueIdentifier => "TEXT".to_string(),
+    }
+}
+
+fn to_sqlite_value(value: &Value) -> rusqlite::types::Value {
+    use rusqlite::types::Value as Sqlite;
+    match value {
+        Value::Null => Sqlite::Null,
+        Value::Bool(b) => Sqlite::Integer(*b as i64),
+        Value::Int(i) => Sqlite::Integer(*i),
+        Value::Float(f) => Sqlite::Real(*f),
+        Value::Binary(bytes) => Sqlite::Blob(bytes.clone()),
+        decimal @ Value::Decimal { value, scale } => {
+            exact_number(*value, *scale).unwrap_or_else(|| Sqlite::Text(decimal.to_string()))
+        }
+        other => Sqlite::Text(other.to_string()),
+    }
+}
+
+/// A decimal as an INTEGER or REAL when that represents it exactly: an
+/// integral value that fits an i64, or one of at most 15 significant digits,
+/// which a 64-bit float round-trips.
+fn exact_number(value: i128, scale: u8) -> Option<rusqlite::types::Value> {
+    use rusqlite::types::Value as Sqlite;
+    let divisor = 10i128.checked_pow(scale as u32)?;
+    if value % divisor == 0 {
+        if let Ok(integer) = i64::try_from(value / divisor) {
+            return Some(Sqlite::Integer(integer));
+        }
+    }
+    let mut digits = value.unsigned_abs();
+    while digits != 0 && digits.is_multiple_of(10) {
+        digits /= 10;
+    }
+    if digits.to_string().len() as u8 > MAX_EXACT_DIGITS {
+        return None;
+    }
+    Value::Decimal { value, scale }
+        .to_string()
+        .parse()
+        .ok()
+        .map(Sqlite::Real)
+}
+
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bacpac::model::tests::MODEL_XML;
+    use crate::bacpac::model::Index;
+    use crate::bacpac::test_support::write_package;
+
+    #[test]
+    fn test_bacpac_to_sqlite() {
+        let dir = tempfile::tempdir().unwrap();
+        let bacpac = dir.path().join("sample.bacpac");
+        let sqlite = dir.path().join("sample.sqlite");
+
+        let mut data = Vec::new();
+        for (id, email) in [(1i32, "a@b.com"), (2, "c@d.com")] {
+            data.extend_from_slice(&id.to_le_bytes());
+            let email: Vec<u8> = email.encode_utf16().flat_map(|u| u.to_le_bytes()).collect();
+            data.extend_from_slice(&(email.len() as u16).to_le_bytes());
+            data.extend_from_slice(&email);
+            data.push(19);
+            data.extend_from_slice(&[10, 2, 1]);
+            data.extend_from_slice(&(id as u128 * 150).to_le_bytes());
+        }
+        write_package(
+            &bacpac,
+            MODEL_XML,
+            &[("Data/dbo.Customers/TableData-000-00000.BCP", data)],
+        );
+
+        let mut archive = BacpacArchive::open(&bacpac).unwrap();
+        let mut model = archive.read_model().unwrap();
+        model.tables[0].indexes.push(Index {
+            name: Some("IX_Customers_Domain".to_string()),
+            columns: vec!["Domain".to_string()],
+            unique: false,
+            is_constraint: false,
+            filter: None,
+            included_columns: Vec::new(),
+        });
+        let tables = model.select_tables(&[]).unwrap();
+        let summary = bacpac_to_sqlite(&mut archive, &model, &tables, &sqlite, |_| {}).unwrap();
+        assert_eq!(summary.rows, 2);
+
+        let conn = Connection::open(&sqlite).unwrap();
+        let total: f64 = conn
+            .query_row("SELECT SUM(Balance) FROM Customers", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(total, 4.5);
+        let mut stmt = conn
+            .prepare("SELECT Id FROM Customers WHERE Balance > 2 ORDER BY Balance DESC")
+            .unwrap();
+        let ids: Vec<i64> = stmt
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .map(|id| id.unwrap())
+            .collect();
+        assert_eq!(ids, vec![2]);
+        let ordered: Vec<String> = conn
+            .prepare("SELECT typeof(Balance) FROM Customers ORDER BY Balance")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .map(|kind| kind.unwrap())
+            .collect();
+        assert_eq!(ordered, vec!["real", "integer"]);
+        let duplicate = conn.execute(
+            "INSERT INTO Customers (Id, Email) VALUES (3, 'a@b.com')",
+            [],
+        );
+        assert!(duplicate.is_err());
+    }
+
+    #[test]
+    fn test_decimals_bind_as_numbers_only_when_exact() {
+        use rusqlite::types::Value as Sqlite;
+        let bind = |value, scale| to_sqlite_value(&Value::Decimal { value, scale });
+        assert_eq!(bind(9900, 2), Sqlite::Integer(99));
+        assert_eq!(bind(10, 2), Sqlite::Real(0.1));
+        assert_eq!(
+            bind(1_234_567_890_123_456_789, 2),
+            Sqlite::Text("12345678901234567.89".to_string())
+        );
+    }
+
+    #[test]
+    fn test_computed_key_columns_are_left_out() {
+        let mut model = DatabaseModel::parse(MODEL_XML).unwrap();
+        let table = &mut model.tables[0];
+        table.primary_key.as_mut().unwrap().columns = vec!["Id".to_string(), "Domain".to_string()];
+        let sql = create_table_sql(table, "Customers");
+        assert!(!sql.contains("PRIMARY KEY"), "{}", sql);
+        Connection::open_in_memory()
+            .unwrap()
+            .execute_batch(&sql)
+            .unwrap();
+    }
+}
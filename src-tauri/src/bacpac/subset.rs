@@ -0,0 +1,392 @@
+//! Data subsetting: keep a selection of rows from some root tables plus
+//! every parent row they reference through foreign keys, so the smaller
+//! BACPAC still imports with all constraints enabled.
+
+use super::bcp::Value;
+use super::browse::{compile_filters, CompiledFilter};
+use super::model::{Column, DatabaseModel, Table};
+use super::rewrite::rewrite_package;
+use super::BacpacArchive;
+use crate::models::{SubsetRoot, SubsetTableResult};
+use anyhow::{anyhow, Result};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+/// Rows kept per table, by position within the table's data.
+type KeptRows = HashMap<String, HashSet<u64>>;
+
+/// Position of a key column within a row, and whether its collation ignores case.
+type KeyColumn = (usize, bool);
+
+/// Keys still to be looked up, per parent table and referenced columns.
+type PendingKeys = HashMap<String, HashMap<Vec<String>, HashSet<String>>>;
+
+struct ParentTracker<'m> {
+    model: &'m DatabaseModel,
+    requested: HashSet<(String, Vec<String>, String)>,
+    pending: PendingKeys,
+}
+
+impl<'m> ParentTracker<'m> {
+    /// Queues the parent rows a kept row refers to.
+    fn add_row(&mut self, table: &Table, row: &[Value]) -> Result<()> {
+        for foreign_key in &table.foreign_keys {
+            let key_columns = key_columns(self.model, table, &foreign_key.columns)?;
+            let Some(key) = row_key(row, &key_columns) else {
+                // A NULL in any column means the row references nothing
+                continue;
+            };
+            let parent = self
+                .model
+                .find_table(&foreign_key.referenced_table)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "{} references {}, which is not in the package",
+                        table.display_name(),
+                        foreign_key.referenced_table
+                    )
+                })?
+                .display_name();
+            let columns = foreign_key.referenced_columns.clone();
+            if self
+                .requested
+                .insert((parent.clone(), columns.clone(), key.clone()))
+            {
+                self.pending
+                    .entry(parent)
+                    .or_default()
+                    .entry(columns)
+                    .or_default()
+                    .insert(key);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes a BACPAC holding the selected root rows and their parents.
+/// Tables that are neither a root nor referenced end up empty.
+pub fn subset(
+    archive: &mut BacpacArchive,
+    model: &DatabaseModel,
+    roots: &[SubsetRoot],
+    target: &Path,
+    mut progress: impl FnMut(String),
+) -> Result<Vec<SubsetTableResult>> {
+    if roots.is_empty() {
+        return Err(anyhow!("Choose at least one table to subset"));
+    }
+
+    let mut kept = KeptRows::new();
+    let mut parents = ParentTracker {
+        model,
+        requested: HashSet::new(),
+        pending: PendingKeys::new(),
+    };
+
+    for root in roots {
+        let table = model
+            .find_table(&root.table)
+            .ok_or_else(|| anyhow!("Table {} not found in the package", root.table))?;
+        progress(format!("Selecting rows from {}", table.display_name()));
+
+        let filters = compile_filters(table, &root.filters)?;
+        let rows = kept.entry(table.display_name()).or_default();
+        let mut selected = 0u64;
+        let mut position = 0u64;
+        archive.visit_rows(table, |row| {
+            if root.max_rows.is_some_and(|max| selected >= max) {
+                return Ok(false);
+            }
+            let current = position;
+            position += 1;
+            if !matches_root(root, &filters, table, current, &row) {
+                return Ok(true);
+            }
+            if rows.insert(current) {
+                parents.add_row(table, &row)?;
+            }
+            selected += 1;
+            Ok(root.max_rows.is_none_or(|max| selected < max))
+        })?;
+    }
+
+    // Keep adding referenced parents until no new rows are pulled in
+    while let Some(name) = parents.pending.keys().next().cloned() {
+        let wanted = parents.pending.remove(&name).unwrap_or_default();
+        let table = model
+            .find_table(&name)
+            .ok_or_else(|| anyhow!("Table {} not found in the package", name))?;
+        progress(format!("Adding referenced rows from {}", name));
+
+        let lookups = wanted
+            .into_iter()
+            .map(|(columns, keys)| Ok((key_columns(model, table, &columns)?, keys)))
+            .collect::<Result<Vec<_>>>()?;
+        let rows = kept.entry(name).or_default();
+        let mut position = 0u64;
+        archive.visit_rows(table, |row| {
+            let current = position;
+            position += 1;
+            let referenced = lookups.iter().any(|(key_columns, keys)| {
+                row_key(&row, key_columns).is_some_and(|key| keys.contains(&key))
+            });
+            if referenced && rows.insert(current) {
+                parents.add_row(table, &row)?;
+            }
+            Ok(true)
+        })?;
+    }
+
+    let mut results = Vec::new();
+    rewrite_package(archive, model, target, |archive, table, writer| {
+        let Some(positions) = kept.get(&table.display_name()).filter(|p| !p.is_empty()) else {
+            return Ok(0);
+        };
+        progress(format!(
+            "Writing {} rows of {}",
+            positions.len(),
+            table.display_name()
+        ));
+        let mut position = 0u64;
+        let mut written = 0u64;
+        archive.visit_rows(table, |row| {
+            if positions.contains(&position) {
+                writer.write_row(&row)?;
+                written += 1;
+            }
+            position += 1;
+            Ok(written < positions.len() as u64)
+        })?;
+        results.push(SubsetTableResult {
+            table: table.display_name(),
+            rows: written,
+        });
+        Ok(written)
+    })?;
+
+    Ok(results)
+}
+
+fn matches_root(
+    root: &SubsetRoot,
+    filters: &[CompiledFilter],
+    table: &Table,
+    position: u64,
+    row: &[Value],
+) -> bool {
+    if !filters.iter().all(|f| f.matches(row)) {
+        return false;
+    }
+    match root.sample_percent {
+        Some(percent) => {
+            // Hash the position so the same request always picks the same rows
+            let mut hasher = Sha256::new();
+            hasher.update(table.display_name().as_bytes());
+            hasher.update([0]);
+            hasher.update(position.to_le_bytes());
+            let digest = hasher.finalize();
+            let bucket = u64::from_le_bytes(digest[..8].try_into().expect("8 bytes"));
+            (bucket % 1_000_000) as f64 / 10_000.0 < percent
+        }
+        None => true,
+    }
+}
+
+fn key_columns(model: &DatabaseModel, table: &Table, columns: &[String]) -> Result<Vec<KeyColumn>> {
+    columns
+        .iter()
+        .map(|name| {
+            let index = table
+                .data_column_index(name)
+                .ok_or_else(|| anyhow!("Column {} not found in {}", name, table.display_name()))?;
+            let column = table.data_columns()[index];
+            Ok((index, ignores_case(model, column)))
+        })
+        .collect()
+}
+
+/// Whether the column's collation, or the database's, compares case-insensitively.
+fn ignores_case(model: &DatabaseModel, column: &Column) -> bool {
+    match column.collation.as_deref() {
+        Some(collation) => collation.to_ascii_uppercase().contains("_CI"),
+        None => !model.collation_case_sensitive,
+    }
+}
+
+/// Comparable form of the key columns of a row, or `None` if any is NULL.
+/// Text is compared as SQL Server would: without trailing spaces, and
+/// case-folded under case-insensitive collations.
+fn row_key(row: &[Value], key_columns: &[KeyColumn]) -> Option<String> {
+    let mut values = Vec::with_capacity(key_columns.len());
+    for &(index, ignore_case) in key_columns {
+        let value = match &row[index] {
+            Value::Null => return None,
+            Value::String(text) => {
+                let text = text.trim_end_matches(' ');
+                Value::String(if ignore_case {
+                    text.to_lowercase()
+                } else {
+                    text.to_string()
+                })
+            }
+            other => other.clone(),
+        };
+        values.push(value);
+    }
+    Some(format!("{:?}", values))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bacpac::model::tests::MODEL_XML;
+    use crate::bacpac::test_support::write_package;
+    use crate::models::{FilterOperator, RowFilter};
+
+    fn customer(id: i32) -> Vec<u8> {
+        let mut data = id.to_le_bytes().to_vec();
+        data.extend_from_slice(&[0xFF, 0xFF, 0xFF]);
+        data
+    }
+
+    fn order(id: i32, customer_id: i32) -> Vec<u8> {
+        let mut data = id.to_le_bytes().to_vec();
+        data.extend_from_slice(&customer_id.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn test_subset_keeps_referenced_parents() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("full.bacpac");
+        let target = dir.path().join("subset.bacpac");
+        write_package(
+            &source,
+            MODEL_XML,
+            &[
+                (
+                    "Data/dbo.Customers/TableData-000-00000.BCP",
+                    (1..=4).flat_map(customer).collect(),
+                ),
+                (
+                    "Data/sales.Orders/TableData-000-00000.BCP",
+                    [order(10, 1), order(11, 3), order(12, 3)].concat(),
+                ),
+            ],
+        );
+
+        let mut archive = BacpacArchive::open(&source).unwrap();
+        let model = archive.read_model().unwrap();
+        let roots = vec![SubsetRoot {
+            table: "sales.Orders".to_string(),
+            filters: vec![RowFilter {
+                column: "OrderId".to_string(),
+                operator: FilterOperator::Equals,
+                value: "11".to_string(),
+            }],
+            sample_percent: None,
+            max_rows: None,
+        }];
+        let results = subset(&mut archive, &model, &roots, &target, |_| {}).unwrap();
+        assert_eq!(results.len(), 2);
+
+        let mut subset = BacpacArchive::open(&target).unwrap();
+        let customers = model.find_table("dbo.Customers").unwrap();
+        let mut ids = Vec::new();
+        subset
+            .visit_rows(customers, |row| {
+                ids.push(row[0].clone());
+                Ok(true)
+            })
+            .unwrap();
+        assert_eq!(ids, vec![Value::Int(3)]);
+
+        let orders = model.find_table("sales.Orders").unwrap();
+        let mut count = 0;
+        subset
+            .visit_rows(orders, |_| {
+                count += 1;
+                Ok(true)
+            })
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn test_sample_is_stable() {
+        let model = DatabaseModel::parse(MODEL_XML).unwrap();
+        let root = SubsetRoot {
+            table: "dbo.Customers".to_string(),
+            filters: Vec::new(),
+            sample_percent: Some(25.0),
+            max_rows: None,
+        };
+        let picked: Vec<u64> = (0..20)
+            .filter(|&position| matches_root(&root, &[], &model.tables[0], position, &[]))
+            .collect();
+        // Fixed by SHA-256, so the same on every platform and release
+        assert_eq!(picked, vec![2, 3, 10]);
+    }
+
+    #[test]
+    fn test_row_key_follows_collation() {
+        let row = |text: &str| vec![Value::Int(1), Value::String(text.to_string())];
+        let insensitive = [(0, false), (1, true)];
+        let sensitive = [(0, false), (1, false)];
+        assert_eq!(
+            row_key(&row("ABC  "), &insensitive),
+            row_key(&row("abc"), &insensitive)
+        );
+        assert_eq!(
+            row_key(&row("abc "), &sensitive),
+            row_key(&row("abc"), &sensitive)
+        );
+        assert_ne!(
+            row_key(&row("ABC"), &sensitive),
+            row_key(&row("abc"), &sensitive)
+        );
+        assert_eq!(row_key(&[Value::Int(1), Value::Null], &insensitive), None);
+
+        let model = DatabaseModel::parse(MODEL_XML).unwrap();
+        let mut column = model.find_table("dbo.Customers").unwrap().columns[1].clone();
+        assert!(ignores_case(&model, &column));
+        column.collation = Some("Latin1_General_100_CS_AS".to_string());
+        assert!(!ignores_case(&model, &column));
+        column.collation = Some("Latin1_General_BIN2".to_string());
+        assert!(!ignores_case(&model, &column));
+    }
+
+    #[test]
+    fn test_subset_with_no_rows() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("full.bacpac");
+        let target = dir.path().join("subset.bacpac");
+        write_package(
+            &source,
+            MODEL_XML,
+            &[
+                (
+                    "Data/dbo.Customers/TableData-000-00000.BCP",
+                    (1..=2).flat_map(customer).collect(),
+                ),
+                (
+                    "Data/sales.Orders/TableData-000-00000.BCP",
+                    [order(10, 1), order(11, 2)].concat(),
+                ),
+            ],
+        );
+
+        let mut archive = BacpacArchive::open(&source).unwrap();
+        let model = archive.read_model().unwrap();
+        let roots = vec![SubsetRoot {
+            table: "sales.Orders".to_string(),
+            filters: Vec::new(),
+            sample_percent: None,
+            max_rows: Some(0),
+        }];
+        let results = subset(&mut archive, &model, &roots, &target, |_| {}).unwrap();
+        assert!(results.is_empty(), "{:?}", results);
+    }
+}
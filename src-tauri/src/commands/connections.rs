@@ -1,146 +1,294 @@
+use crate::commands::databases::DatabaseListCache;
+use crate::connection_string::ConnectionString;
 use crate::db;
-use crate::models::SqlConnection;
+use crate::models::{AuthMethod, ConnectionTestResult, SavedConnection, SqlConnection};
+use crate::vault::Vault;
+use crate::{secrets, sqlserver, utils};
 use chrono::Utc;
+use rusqlite::{named_params, OptionalExtension};
+use tauri::Manager;
+
+const SELECT_CONNECTION: &str = "SELECT id, name, server, port, database_name, username, password, password_is_reference, connection_string, authentication, tenant_id, trust_server_cert, encrypt, connect_timeout, command_timeout, read_only_intent, multi_subnet_failover, strict_encryption, host_name_in_certificate, server_certificate, application_name, extra_options, created_at, updated_at FROM sql_connections";
+
+fn connection_from_row(row: &rusqlite::Row) -> rusqlite::Result<SqlConnection> {
+    Ok(SqlConnection {
+        id: Some(row.get("id")?),
+        name: row.get("name")?,
+        server: row.get("server")?,
+        port: row.get("port")?,
+        database_name: row.get("database_name")?,
+        username: row.get("username")?,
+        password: row.get("password")?,
+        password_is_reference: row.get("password_is_reference")?,
+        connection_string: row.get("connection_string")?,
+        authentication: authentication_from_row(row)?,
+        tenant_id: row.get("tenant_id")?,
+        trust_server_cert: row.get("trust_server_cert")?,
+        encrypt: row.get("encrypt")?,
+        connect_timeout: row.get("connect_timeout")?,
+        command_timeout: row.get("command_timeout")?,
+        read_only_intent: row.get("read_only_intent")?,
+        multi_subnet_failover: row.get("multi_subnet_failover")?,
+        strict_encryption: row.get("strict_encryption")?,
+        host_name_in_certificate: row.get("host_name_in_certificate")?,
+        server_certificate: row.get("server_certificate")?,
+        application_name: row.get("application_name")?,
+        extra_options: row.get("extra_options")?,
+        created_at: Some(row.get("created_at")?),
+        updated_at: Some(row.get("updated_at")?),
+    })
+}
+
+fn authentication_from_row(row: &rusqlite::Row) -> rusqlite::Result<AuthMethod> {
+    let name: String = row.get("authentication")?;
+    AuthMethod::from_name(&name).ok_or_else(|| {
+        rusqlite::Error::InvalidColumnType(
+            row.as_ref()
+                .column_index("authentication")
+                .unwrap_or_default(),
+            format!("authentication '{}'", name),
+            rusqlite::types::Type::Text,
+        )
+    })
+}
+
+/// A stored row, secrets still as they are on disk.
+fn read_connection(conn: &rusqlite::Connection, id: i64) -> Result<SqlConnection, String> {
+    conn.query_row(
+        &format!("{} WHERE id = ?1", SELECT_CONNECTION),
+        [id],
+        connection_from_row,
+    )
+    .optional()
+    .map_err(|e| e.to_string())?
+    .ok_or_else(|| format!("Connection {} not found", id))
+}
+
+/// The frontend view of a connection, stored or as just submitted.
+fn saved_connection(vault: &Vault, connection: SqlConnection) -> SavedConnection {
+    let connection_string = vault
+        .reveal(
+            "connection_string",
+            connection.id.unwrap_or_default(),
+            connection.connection_string,
+        )
+        .unwrap_or(None)
+        .map(|cs| utils::redact_connection_string(&cs));
+
+    SavedConnection {
+        id: connection.id.unwrap_or_default(),
+        name: connection.name,
+        server: connection.server,
+        port: connection.port,
+        database_name: connection.database_name,
+        username: connection.username,
+        has_password: connection.password.is_some_and(|p| !p.is_empty()),
+        password_is_reference: connection.password_is_reference,
+        connection_string,
+        authentication: connection.authentication,
+        tenant_id: connection.tenant_id,
+        trust_server_cert: connection.trust_server_cert,
+        encrypt: connection.encrypt,
+        connect_timeout: connection.connect_timeout,
+        command_timeout: connection.command_timeout,
+        read_only_intent: connection.read_only_intent,
+        multi_subnet_failover: connection.multi_subnet_failover,
+        strict_encryption: connection.strict_encryption,
+        host_name_in_certificate: connection.host_name_in_certificate,
+        server_certificate: connection.server_certificate,
+        application_name: connection.application_name,
+        extra_options: connection.extra_options,
+        created_at: connection.created_at,
+        updated_at: connection.updated_at,
+    }
+}
+
+/// A stored connection with its secrets, for building a job's connection string.
+/// Never return this to the frontend.
+pub(crate) fn load_connection(
+    app_handle: &tauri::AppHandle,
+    id: i64,
+) -> Result<SqlConnection, String> {
+    let conn = db::get_connection(app_handle).map_err(|e| e.to_string())?;
+    let mut connection = read_connection(&conn, id)?;
+
+    let vault = app_handle.state::<Vault>();
+    connection.password = vault
+        .reveal("password", id, connection.password)
+        .map_err(|e| e.to_string())?;
+    connection.connection_string = vault
+        .reveal("connection_string", id, connection.connection_string)
+        .map_err(|e| e.to_string())?;
+
+    Ok(connection)
+}
 
 #[tauri::command]
-pub async fn list_connections(app_handle: tauri::AppHandle) -> Result<Vec<SqlConnection>, String> {
+pub async fn list_connections(
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<SavedConnection>, String> {
     let conn = db::get_connection(&app_handle).map_err(|e| e.to_string())?;
 
     let mut stmt = conn
-        .prepare("SELECT id, name, server, port, database_name, username, password, connection_string, use_windows_auth, trust_server_cert, encrypt, created_at, updated_at FROM sql_connections ORDER BY name")
+        .prepare(&format!("{} ORDER BY name", SELECT_CONNECTION))
         .map_err(|e| e.to_string())?;
 
     let connections = stmt
-        .query_map([], |row| {
-            Ok(SqlConnection {
-                id: Some(row.get(0)?),
-                name: row.get(1)?,
-                server: row.get(2)?,
-                port: row.get(3)?,
-                database_name: row.get(4)?,
-                username: row.get(5)?,
-                password: row.get(6)?,
-                connection_string: row.get(7)?,
-                use_windows_auth: row.get(8)?,
-                trust_server_cert: row.get(9)?,
-                encrypt: row.get(10)?,
-                created_at: Some(row.get(11)?),
-                updated_at: Some(row.get(12)?),
-            })
-        })
+        .query_map([], connection_from_row)
         .map_err(|e| e.to_string())?
         .collect::<Result<Vec<_>, _>>()
         .map_err(|e| e.to_string())?;
 
-    Ok(connections)
+    let vault = app_handle.state::<Vault>();
+    Ok(connections
+        .into_iter()
+        .map(|connection| saved_connection(&vault, connection))
+        .collect())
 }
 
 #[tauri::command]
 pub async fn get_connection(
     app_handle: tauri::AppHandle,
     id: i64,
-) -> Result<SqlConnection, String> {
+) -> Result<SavedConnection, String> {
     let conn = db::get_connection(&app_handle).map_err(|e| e.to_string())?;
+    let connection = read_connection(&conn, id)?;
 
-    let mut stmt = conn
-        .prepare("SELECT id, name, server, port, database_name, username, password, connection_string, use_windows_auth, trust_server_cert, encrypt, created_at, updated_at FROM sql_connections WHERE id = ?1")
-        .map_err(|e| e.to_string())?;
-
-    let connection = stmt
-        .query_row([id], |row| {
-            Ok(SqlConnection {
-                id: Some(row.get(0)?),
-                name: row.get(1)?,
-                server: row.get(2)?,
-                port: row.get(3)?,
-                database_name: row.get(4)?,
-                username: row.get(5)?,
-                password: row.get(6)?,
-                connection_string: row.get(7)?,
-                use_windows_auth: row.get(8)?,
-                trust_server_cert: row.get(9)?,
-                encrypt: row.get(10)?,
-                created_at: Some(row.get(11)?),
-                updated_at: Some(row.get(12)?),
-            })
-        })
-        .map_err(|e| e.to_string())?;
-
-    Ok(connection)
+    Ok(saved_connection(&app_handle.state::<Vault>(), connection))
 }
 
 #[tauri::command]
 pub async fn create_connection(
     app_handle: tauri::AppHandle,
     mut connection: SqlConnection,
-) -> Result<SqlConnection, String> {
-    let conn = db::get_connection(&app_handle).map_err(|e| e.to_string())?;
+) -> Result<SavedConnection, String> {
+    let mut conn = db::get_connection(&app_handle).map_err(|e| e.to_string())?;
     let now = Utc::now().to_rfc3339();
+    validate_connection(&connection)?;
+    describe_connection_string(&mut connection)?;
+    utils::validate_connection_options(&connection).map_err(|e| e.to_string())?;
 
-    conn.execute(
-        "INSERT INTO sql_connections (name, server, port, database_name, username, password, connection_string, use_windows_auth, trust_server_cert, encrypt, created_at, updated_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
-        (
-            &connection.name,
-            &connection.server,
-            &connection.port,
-            &connection.database_name,
-            &connection.username,
-            &connection.password,
-            &connection.connection_string,
-            connection.use_windows_auth,
-            connection.trust_server_cert,
-            connection.encrypt,
-            &now,
-            &now,
-        ),
+    // Secrets are sealed to their row, so they are stored once it has an id
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    tx.execute(
+        "INSERT INTO sql_connections (name, server, port, database_name, username,
+             password_is_reference, authentication, tenant_id, trust_server_cert, encrypt, connect_timeout, command_timeout, read_only_intent,
+             multi_subnet_failover, strict_encryption, host_name_in_certificate, server_certificate,
+             application_name, extra_options, created_at, updated_at)
+         VALUES (:name, :server, :port, :database_name, :username,
+             :password_is_reference, :authentication, :tenant_id, :trust_server_cert, :encrypt, :connect_timeout, :command_timeout, :read_only_intent,
+             :multi_subnet_failover, :strict_encryption, :host_name_in_certificate, :server_certificate,
+             :application_name, :extra_options, :now, :now)",
+        named_params! {
+            ":name": &connection.name,
+            ":server": &connection.server,
+            ":port": &connection.port,
+            ":database_name": &connection.database_name,
+            ":username": &connection.username,
+            ":password_is_reference": connection.password_is_reference,
+            ":authentication": connection.authentication.as_str(),
+            ":tenant_id": &connection.tenant_id,
+            ":trust_server_cert": connection.trust_server_cert,
+            ":encrypt": connection.encrypt,
+            ":connect_timeout": connection.connect_timeout,
+            ":command_timeout": connection.command_timeout,
+            ":read_only_intent": connection.read_only_intent,
+            ":multi_subnet_failover": connection.multi_subnet_failover,
+            ":strict_encryption": connection.strict_encryption,
+            ":host_name_in_certificate": &connection.host_name_in_certificate,
+            ":server_certificate": &connection.server_certificate,
+            ":application_name": &connection.application_name,
+            ":extra_options": &connection.extra_options,
+            ":now": &now,
+        },
     )
     .map_err(|e| e.to_string())?;
+    let id = tx.last_insert_rowid();
+    let (password, connection_string) = conceal_secrets(&app_handle, &tx, id, &connection)?;
+    tx.execute(
+        "UPDATE sql_connections SET password = ?1, connection_string = ?2 WHERE id = ?3",
+        (&password, &connection_string, id),
+    )
+    .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
 
-    let id = conn.last_insert_rowid();
     connection.id = Some(id);
     connection.created_at = Some(now.clone());
     connection.updated_at = Some(now);
 
-    Ok(connection)
+    Ok(saved_connection(&app_handle.state::<Vault>(), connection))
 }
 
+/// Saves changes to a connection. An absent `password` or `connection_string`
+/// keeps the stored one, since the frontend never sees them; an empty one clears it.
+/// Whether the password is a reference only changes along with the password.
 #[tauri::command]
 pub async fn update_connection(
     app_handle: tauri::AppHandle,
     id: i64,
     mut connection: SqlConnection,
-) -> Result<SqlConnection, String> {
+) -> Result<SavedConnection, String> {
     let conn = db::get_connection(&app_handle).map_err(|e| e.to_string())?;
     let now = Utc::now().to_rfc3339();
+    validate_connection(&connection)?;
+    describe_connection_string(&mut connection)?;
+    utils::validate_connection_options(&connection).map_err(|e| e.to_string())?;
+    let keep_password = connection.password.is_none();
+    let keep_connection_string = connection.connection_string.is_none();
+    let (password, connection_string) = conceal_secrets(&app_handle, &conn, id, &connection)?;
 
     conn.execute(
         "UPDATE sql_connections
-         SET name = ?1, server = ?2, port = ?3, database_name = ?4, username = ?5, password = ?6,
-             connection_string = ?7, use_windows_auth = ?8, trust_server_cert = ?9,
-             encrypt = ?10, updated_at = ?11
-         WHERE id = ?12",
-        (
-            &connection.name,
-            &connection.server,
-            &connection.port,
-            &connection.database_name,
-            &connection.username,
-            &connection.password,
-            &connection.connection_string,
-            connection.use_windows_auth,
-            connection.trust_server_cert,
-            connection.encrypt,
-            &now,
-            id,
-        ),
+         SET name = :name, server = :server, port = :port, database_name = :database_name,
+             username = :username,
+             password = CASE WHEN :keep_password THEN password ELSE :password END,
+             password_is_reference = CASE WHEN :keep_password THEN password_is_reference
+                 ELSE :password_is_reference END,
+             connection_string = CASE WHEN :keep_connection_string THEN connection_string
+                 ELSE :connection_string END,
+             authentication = :authentication, tenant_id = :tenant_id,
+             trust_server_cert = :trust_server_cert,
+             encrypt = :encrypt, connect_timeout = :connect_timeout,
+             command_timeout = :command_timeout, read_only_intent = :read_only_intent,
+             multi_subnet_failover = :multi_subnet_failover,
+             strict_encryption = :strict_encryption,
+             host_name_in_certificate = :host_name_in_certificate,
+             server_certificate = :server_certificate, application_name = :application_name,
+             extra_options = :extra_options, updated_at = :now
+         WHERE id = :id",
+        named_params! {
+            ":name": &connection.name,
+            ":server": &connection.server,
+            ":port": &connection.port,
+            ":database_name": &connection.database_name,
+            ":username": &connection.username,
+            ":keep_password": keep_password,
+            ":password": &password,
+            ":password_is_reference": connection.password_is_reference,
+            ":keep_connection_string": keep_connection_string,
+            ":connection_string": &connection_string,
+            ":authentication": connection.authentication.as_str(),
+            ":tenant_id": &connection.tenant_id,
+            ":trust_server_cert": connection.trust_server_cert,
+            ":encrypt": connection.encrypt,
+            ":connect_timeout": connection.connect_timeout,
+            ":command_timeout": connection.command_timeout,
+            ":read_only_intent": connection.read_only_intent,
+            ":multi_subnet_failover": connection.multi_subnet_failover,
+            ":strict_encryption": connection.strict_encryption,
+            ":host_name_in_certificate": &connection.host_name_in_certificate,
+            ":server_certificate": &connection.server_certificate,
+            ":application_name": &connection.application_name,
+            ":extra_options": &connection.extra_options,
+            ":now": &now,
+            ":id": id,
+        },
     )
     .map_err(|e| e.to_string())?;
 
-    connection.id = Some(id);
-    connection.updated_at = Some(now);
-
-    Ok(connection)
+    app_handle.state::<DatabaseListCache>().invalidate(id);
+    let stored = read_connection(&conn, id)?;
+    Ok(saved_connection(&app_handle.state::<Vault>(), stored))
 }
 
 #[tauri::command]
@@ -149,6 +297,228 @@ pub async fn delete_connection(app_handle: tauri::AppHandle, id: i64) -> Result<
 
     conn.execute("DELETE FROM sql_connections WHERE id = ?1", [id])
         .map_err(|e| e.to_string())?;
+    app_handle.state::<DatabaseListCache>().invalidate(id);
 
     Ok(())
 }
+
+/// Opens a session with the connection as entered, before or after it is saved.
+/// With an `id`, secrets left out of the form are taken from the stored connection
+/// as `update_connection` would keep them, but only for the same login; see
+/// `with_stored_secrets`.
+#[tauri::command]
+pub async fn test_connection(
+    app_handle: tauri::AppHandle,
+    mut connection: SqlConnection,
+) -> Result<ConnectionTestResult, String> {
+    validate_connection(&connection)?;
+    if connection.password.is_some() && connection.password_is_reference {
+        return Err(
+            "Save the connection to test a password reference; it is only resolved for saved connections"
+                .to_string(),
+        );
+    }
+    if let Some(id) = connection.id {
+        if connection.password.is_none() || connection.connection_string.is_none() {
+            let stored = load_connection(&app_handle, id)?;
+            connection = with_stored_secrets(connection, stored)?;
+        }
+    }
+    describe_connection_string(&mut connection)?;
+    utils::validate_connection_options(&connection).map_err(|e| e.to_string())?;
+    let connection = secrets::resolve_connection(connection)
+        .await
+        .map_err(|e| format!("{:#}", e))?;
+
+    Ok(sqlserver::test_connection(&connection).await)
+}
+
+/// Fills the secrets left out of a form from the connection it edits. They are
+/// only sent to the server, port and login they were saved for; a form that
+/// changed any of those must bring its own password.
+fn with_stored_secrets(
+    mut connection: SqlConnection,
+    stored: SqlConnection,
+) -> Result<SqlConnection, String> {
+    let field = |value: &Option<String>| value.as_deref().unwrap_or_default().trim().to_string();
+    let same_login = connection.server.trim() == stored.server.trim()
+        && field(&connection.port) == field(&stored.port)
+        && connection.authentication == stored.authentication
+        && field(&connection.username) == field(&stored.username);
+    if !same_login {
+        let stored_secret = stored.password.is_some() || stored.connection_string.is_some();
+        if connection.password.is_none() && stored_secret {
+            return Err("Re-enter the password to test a changed login".to_string());
+        }
+        return Ok(connection);
+    }
+    if connection.password.is_none() {
+        connection.password_is_reference = stored.password_is_reference;
+        connection.password = stored.password;
+    }
+    if connection.connection_string.is_none() {
+        connection.connection_string = stored.connection_string;
+    }
+    Ok(connection)
+}
+
+/// Rejects a password reference without a known source, extra options that
+/// don't parse as a connection-string fragment, and a redacted connection
+/// string sent back unchanged, which would otherwise overwrite the real
+/// password with the mask.
+fn validate_connection(connection: &SqlConnection) -> Result<(), String> {
+    if let (Some(password), true) = (&connection.password, connection.password_is_reference) {
+        secrets::SecretRef::parse(password).map_err(|e| format!("Invalid password: {}", e))?;
+    }
+    if let Some(extra) = connection.extra_options.as_deref() {
+        ConnectionString::parse(extra).map_err(|e| format!("Invalid extra options: {}", e))?;
+    }
+    match &connection.connection_string {
+        Some(cs) if utils::is_redacted_connection_string(cs) => Err(
+            "The connection string contains a masked password; enter the password again or leave the connection string unchanged"
+                .to_string(),
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// Fills the descriptive fields of a connection saved as a connection string,
+/// so it lists like one saved field by field. The string itself stays in charge.
+fn describe_connection_string(connection: &mut SqlConnection) -> Result<(), String> {
+    let Some(cs) = connection
+        .connection_string
+        .as_deref()
+        .filter(|cs| !cs.is_empty())
+    else {
+        return Ok(());
+    };
+    let parsed = utils::parse_connection_string(cs)
+        .map_err(|e| format!("Invalid connection string: {}", e))?;
+    if connection.server.trim().is_empty() {
+        connection.server = parsed.server;
+        connection.port = parsed.port;
+    }
+    if connection
+        .database_name
+        .as_deref()
+        .unwrap_or_default()
+        .is_empty()
+    {
+        connection.database_name = parsed.database_name;
+    }
+    if connection
+        .username
+        .as_deref()
+        .unwrap_or_default()
+        .is_empty()
+    {
+        connection.username = parsed.username;
+    }
+    connection.authentication = parsed.authentication;
+    connection.encrypt = parsed.encrypt;
+    connection.trust_server_cert = parsed.trust_server_cert;
+    connection.connect_timeout = parsed.connect_timeout;
+    connection.command_timeout = parsed.command_timeout;
+    connection.read_only_intent = parsed.read_only_intent;
+    connection.multi_subnet_failover = parsed.multi_subnet_failover;
+    connection.strict_encryption = parsed.strict_encryption;
+    connection.host_name_in_certificate = parsed.host_name_in_certificate;
+    connection.server_certificate = parsed.server_certificate;
+    connection.application_name = parsed.application_name;
+    Ok(())
+}
+
+/// The password and connection string as they should be stored in row `id`.
+fn conceal_secrets(
+    app_handle: &tauri::AppHandle,
+    conn: &rusqlite::Connection,
+    id: i64,
+    connection: &SqlConnection,
+) -> Result<(Option<String>, Option<String>), String> {
+    let vault = app_handle.state::<Vault>();
+    let password = vault
+        .conceal(conn, "password", id, connection.password.clone())
+        .map_err(|e| e.to_string())?;
+    let connection_string = vault
+        .conceal(
+            conn,
+            "connection_string",
+            id,
+            connection.connection_string.clone(),
+        )
+        .map_err(|e| e.to_string())?;
+    Ok((password, connection_string))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stored() -> SqlConnection {
+        SqlConnection {
+            id: Some(7),
+            name: "prod".to_string(),
+            server: "sql.internal".to_string(),
+            port: Some("1433".to_string()),
+            database_name: Some("Sales".to_string()),
+            username: Some("etl".to_string()),
+            password: Some("s3cret".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_stored_secrets_fill_the_same_login() {
+        let form = SqlConnection {
+            database_name: Some("Sales_Archive".to_string()),
+            username: Some(" etl".to_string()),
+            password: None,
+            ..stored()
+        };
+        let tested = with_stored_secrets(form, stored()).unwrap();
+        assert_eq!(tested.password.as_deref(), Some("s3cret"));
+        assert_eq!(tested.database_name.as_deref(), Some("Sales_Archive"));
+    }
+
+    #[test]
+    fn test_stored_secrets_stay_with_their_login() {
+        let changes = [
+            SqlConnection {
+                server: "attacker.example".to_string(),
+                ..stored()
+            },
+            SqlConnection {
+                port: Some("14330".to_string()),
+                ..stored()
+            },
+            SqlConnection {
+                authentication: AuthMethod::ActiveDirectoryPassword,
+                ..stored()
+            },
+            SqlConnection {
+                username: Some("sa".to_string()),
+                ..stored()
+            },
+        ];
+        for changed in changes {
+            let form = SqlConnection {
+                password: None,
+                database_name: Some("Other".to_string()),
+                ..changed
+            };
+            assert_eq!(
+                with_stored_secrets(form, stored()).unwrap_err(),
+                "Re-enter the password to test a changed login"
+            );
+        }
+
+        // A changed login with its own password is tested as entered
+        let form = SqlConnection {
+            server: "staging.example".to_string(),
+            password: Some("typed".to_string()),
+            ..stored()
+        };
+        let tested = with_stored_secrets(form.clone(), stored()).unwrap();
+        assert_eq!(tested, form);
+    }
+}
@@ -0,0 +1,92 @@
+use crate::commands::connections;
+use crate::models::{DatabaseInfo, DatabaseStats};
+use crate::{secrets, sqlserver};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::State;
+
+/// How long a database list is reused before the server is asked again.
+const DATABASE_LIST_TTL: Duration = Duration::from_secs(60);
+
+/// Database lists per saved connection, kept briefly so pickers opened one
+/// after another don't each query the server.
+#[derive(Default)]
+pub struct DatabaseListCache {
+    lists: Mutex<HashMap<i64, (Instant, Vec<DatabaseInfo>)>>,
+}
+
+impl DatabaseListCache {
+    fn get(&self, connection_id: i64) -> Option<Vec<DatabaseInfo>> {
+        match self.lists.lock().unwrap().get(&connection_id) {
+            Some((fetched, databases)) if fetched.elapsed() < DATABASE_LIST_TTL => {
+                Some(databases.clone())
+            }
+            _ => None,
+        }
+    }
+
+    fn insert(&self, connection_id: i64, databases: Vec<DatabaseInfo>) {
+        self.lists
+            .lock()
+            .unwrap()
+            .insert(connection_id, (Instant::now(), databases));
+    }
+
+    /// Drops the list for a connection whose settings changed.
+    pub fn invalidate(&self, connection_id: i64) {
+        self.lists.lock().unwrap().remove(&connection_id);
+    }
+}
+
+/// Databases on the server behind a saved connection. `refresh` skips the cache.
+#[tauri::command]
+pub async fn list_databases(
+    app_handle: tauri::AppHandle,
+    cache: State<'_, DatabaseListCache>,
+    connection_id: i64,
+    refresh: Option<bool>,
+) -> Result<Vec<DatabaseInfo>, String> {
+    if !refresh.unwrap_or(false) {
+        if let Some(databases) = cache.get(connection_id) {
+            return Ok(databases);
+        }
+    }
+
+    let connection = connections::load_connection(&app_handle, connection_id)
+        .map_err(|e| format!("Failed to get connection: {}", e))?;
+    let connection = secrets::resolve_connection(connection)
+        .await
+        .map_err(|e| format!("{:#}", e))?;
+    let mut client = sqlserver::connect(&connection)
+        .await
+        .map_err(|e| format!("Failed to connect to {}: {:#}", connection.server, e))?;
+    let databases = sqlserver::list_databases(&mut client)
+        .await
+        .map_err(|e| format!("Failed to list databases: {:#}", e))?;
+
+    cache.insert(connection_id, databases.clone());
+    Ok(databases)
+}
+
+/// Size, table statistics and export risks of one database, before exporting it.
+#[tauri::command]
+pub async fn get_database_stats(
+    app_handle: tauri::AppHandle,
+    connection_id: i64,
+    database_name: String,
+) -> Result<DatabaseStats, String> {
+    let mut connection = connections::load_connection(&app_handle, connection_id)
+        .map_err(|e| format!("Failed to get connection: {}", e))?;
+    connection.database_name = Some(database_name.clone());
+    let connection = secrets::resolve_connection(connection)
+        .await
+        .map_err(|e| format!("{:#}", e))?;
+    let mut client = sqlserver::connect(&connection)
+        .await
+        .map_err(|e| format!("Failed to connect to {}: {:#}", database_name, e))?;
+
+    sqlserver::database_stats(&mut client)
+        .await
+        .map_err(|e| format!("Failed to read statistics for {}: {:#}", database_name, e))
+}
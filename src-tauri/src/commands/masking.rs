@@ -0,0 +1,120 @@
+use crate::db;
+use crate::models::MaskingRuleSet;
+use chrono::Utc;
+use rusqlite::OptionalExtension;
+
+#[tauri::command]
+pub async fn list_masking_rule_sets(
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<MaskingRuleSet>, String> {
+    let conn = db::get_connection(&app_handle).map_err(|e| e.to_string())?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, name, rules, created_at, updated_at FROM masking_rule_sets ORDER BY name",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map([], rule_set_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    rows.into_iter().map(rule_set_from_row).collect()
+}
+
+type RuleSetRow = (i64, String, String, String, String);
+
+fn rule_set_row(row: &rusqlite::Row) -> rusqlite::Result<RuleSetRow> {
+    Ok((
+        row.get(0)?,
+        row.get(1)?,
+        row.get(2)?,
+        row.get(3)?,
+        row.get(4)?,
+    ))
+}
+
+fn rule_set_from_row(
+    (id, name, rules, created_at, updated_at): RuleSetRow,
+) -> Result<MaskingRuleSet, String> {
+    Ok(MaskingRuleSet {
+        id: Some(id),
+        rules: serde_json::from_str(&rules)
+            .map_err(|e| format!("Rule set '{}' is corrupt: {}", name, e))?,
+        name,
+        created_at: Some(created_at),
+        updated_at: Some(updated_at),
+    })
+}
+
+#[tauri::command]
+pub async fn get_masking_rule_set(
+    app_handle: tauri::AppHandle,
+    name: String,
+) -> Result<MaskingRuleSet, String> {
+    let name = name.trim();
+    let conn = db::get_connection(&app_handle).map_err(|e| e.to_string())?;
+    conn.query_row(
+        "SELECT id, name, rules, created_at, updated_at FROM masking_rule_sets WHERE name = ?1",
+        [name],
+        rule_set_row,
+    )
+    .optional()
+    .map_err(|e| e.to_string())?
+    .ok_or_else(|| format!("Masking rule set '{}' not found", name))
+    .and_then(rule_set_from_row)
+}
+
+/// Creates the rule set, or replaces the rules of the set with the same name.
+#[tauri::command]
+pub async fn save_masking_rule_set(
+    app_handle: tauri::AppHandle,
+    mut rule_set: MaskingRuleSet,
+) -> Result<MaskingRuleSet, String> {
+    rule_set.name = rule_set.name.trim().to_string();
+    if rule_set.name.is_empty() {
+        return Err("Rule set name is required".to_string());
+    }
+    let conn = db::get_connection(&app_handle).map_err(|e| e.to_string())?;
+    let now = Utc::now().to_rfc3339();
+    let rules = serde_json::to_string(&rule_set.rules).map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "INSERT INTO masking_rule_sets (name, rules, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?3)
+         ON CONFLICT(name) DO UPDATE SET rules = excluded.rules, updated_at = excluded.updated_at",
+        (&rule_set.name, &rules, &now),
+    )
+    .map_err(|e| e.to_string())?;
+
+    let (id, created_at): (i64, String) = conn
+        .query_row(
+            "SELECT id, created_at FROM masking_rule_sets WHERE name = ?1",
+            [&rule_set.name],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| e.to_string())?;
+
+    rule_set.id = Some(id);
+    rule_set.created_at = Some(created_at);
+    rule_set.updated_at = Some(now);
+    Ok(rule_set)
+}
+
+#[tauri::command]
+pub async fn delete_masking_rule_set(
+    app_handle: tauri::AppHandle,
+    name: String,
+) -> Result<(), String> {
+    let conn = db::get_connection(&app_handle).map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "DELETE FROM masking_rule_sets WHERE name = ?1",
+        [name.trim()],
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
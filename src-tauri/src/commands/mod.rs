@@ -1,4 +1,8 @@
 pub mod connections;
+pub mod databases;
+pub mod masking;
+pub mod packages;
 pub mod preferences;
 pub mod sqlpackage;
 pub mod system;
+pub mod vault;
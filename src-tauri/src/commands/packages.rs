@@ -0,0 +1,565 @@
+use crate::bacpac::convert::{self, DacpacOptions};
+use crate::bacpac::script::{self, ScriptOptions};
+use crate::bacpac::{
+    browse, builder, dependencies, diff, docs, export, mask, origin, same_file, search, security,
+    sqlite, subset, write_replacing, BacpacArchive, METADATA_ENTRY, MODEL_ENTRY,
+};
+use crate::commands::masking;
+use crate::jobs::JobRegistry;
+use crate::models::{
+    BacpacColumnInfo, BacpacSearchRequest, BacpacSearchResult, BacpacSubsetRequest,
+    BacpacTableInfo, BacpacToDacpacRequest, BacpacToSqliteRequest, CsvToBacpacRequest,
+    DataDictionaryRequest, DataDictionaryResult, DependencyReport, DependencyRequest,
+    InsertScriptRequest, MaskBacpacRequest, ModelObject, SecurityReport, SqliteToBacpacRequest,
+    SubsetTableResult, TableDataExportRequest, TableDataExportResult, TableDiffPage,
+    TableDiffRequest, TableRowsPage, TableRowsRequest,
+};
+use anyhow::Context;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use tauri::{Emitter, Manager};
+
+#[tauri::command]
+pub async fn convert_bacpac_to_dacpac(
+    app_handle: tauri::AppHandle,
+    request: BacpacToDacpacRequest,
+) -> Result<String, String> {
+    let source = PathBuf::from(&request.bacpac_path);
+    let target = PathBuf::from(&request.output_path);
+    if same_file(&source, &target) {
+        return Err("The DACPAC must be written to a different file than the BACPAC".to_string());
+    }
+    if target.exists() && !request.overwrite {
+        return Err(format!("{} already exists", request.output_path));
+    }
+    let options = DacpacOptions {
+        dac_name: request.dac_name,
+        dac_version: request.dac_version,
+    };
+
+    let summary = tokio::task::spawn_blocking(move || {
+        convert::bacpac_to_dacpac(&source, &target, &options, |line| {
+            let _ = app_handle.emit("convert-progress", line);
+        })
+    })
+    .await
+    .map_err(|e| format!("Conversion task failed: {}", e))?
+    .map_err(|e| format!("Conversion failed: {:#}", e))?;
+
+    Ok(format!(
+        "DACPAC created successfully ({} data entries removed)",
+        summary.dropped_data_entries
+    ))
+}
+
+#[tauri::command]
+pub async fn export_bacpac_table_data(
+    app_handle: tauri::AppHandle,
+    request: TableDataExportRequest,
+) -> Result<Vec<TableDataExportResult>, String> {
+    tokio::task::spawn_blocking(move || {
+        let mut archive =
+            BacpacArchive::open(&PathBuf::from(&request.bacpac_path)).map_err(|e| e.to_string())?;
+        let model = archive.read_model().map_err(|e| e.to_string())?;
+        let output_dir = PathBuf::from(&request.output_dir);
+        std::fs::create_dir_all(&output_dir).map_err(|e| e.to_string())?;
+
+        let tables = model
+            .select_tables(&request.tables)
+            .map_err(|e| e.to_string())?;
+
+        let mut results = Vec::new();
+        for table in tables {
+            let _ = app_handle.emit(
+                "data-export-progress",
+                format!("Exporting {}...", table.display_name()),
+            );
+            let (path, rows) =
+                export::export_table(&mut archive, table, request.format, &output_dir)
+                    .map_err(|e| format!("Failed to export {}: {:#}", table.display_name(), e))?;
+            let _ = app_handle.emit(
+                "data-export-progress",
+                format!("Exported {} rows from {}", rows, table.display_name()),
+            );
+            results.push(TableDataExportResult {
+                table: table.display_name(),
+                output_path: path.to_string_lossy().to_string(),
+                rows,
+            });
+        }
+
+        Ok(results)
+    })
+    .await
+    .map_err(|e| format!("Export task failed: {}", e))?
+}
+
+#[tauri::command]
+pub async fn convert_bacpac_to_sqlite(
+    app_handle: tauri::AppHandle,
+    request: BacpacToSqliteRequest,
+) -> Result<String, String> {
+    let source = PathBuf::from(&request.bacpac_path);
+    let target = PathBuf::from(&request.output_path);
+    if same_file(&source, &target) {
+        return Err(
+            "The SQLite database must be written to a different file than the BACPAC".to_string(),
+        );
+    }
+    if target.exists() && !request.overwrite {
+        return Err(format!("{} already exists", request.output_path));
+    }
+
+    let summary = tokio::task::spawn_blocking(move || {
+        let mut archive = BacpacArchive::open(&source).map_err(|e| e.to_string())?;
+        let model = archive.read_model().map_err(|e| e.to_string())?;
+        let tables = model
+            .select_tables(&request.tables)
+            .map_err(|e| e.to_string())?;
+
+        // Built next to the target so a failed run keeps the previous database
+        write_replacing(&target, |path| {
+            sqlite::bacpac_to_sqlite(&mut archive, &model, &tables, path, |line| {
+                let _ = app_handle.emit("sqlite-progress", line);
+            })
+        })
+        .map_err(|e| format!("Conversion failed: {:#}", e))
+    })
+    .await
+    .map_err(|e| format!("Conversion task failed: {}", e))??;
+
+    Ok(format!(
+        "SQLite database created successfully ({} tables, {} rows)",
+        summary.tables, summary.rows
+    ))
+}
+
+#[tauri::command]
+pub async fn list_bacpac_tables(bacpac_path: String) -> Result<Vec<BacpacTableInfo>, String> {
+    tokio::task::spawn_blocking(move || {
+        let mut archive =
+            BacpacArchive::open(&PathBuf::from(&bacpac_path)).map_err(|e| e.to_string())?;
+        let model = archive.read_model().map_err(|e| e.to_string())?;
+
+        model
+            .tables
+            .iter()
+            .map(|table| {
+                Ok(BacpacTableInfo {
+                    schema: table.schema.clone(),
+                    name: table.name.clone(),
+                    columns: table
+                        .data_columns()
+                        .iter()
+                        .map(|column| BacpacColumnInfo {
+                            name: column.name.clone(),
+                            data_type: column.type_declaration(),
+                            nullable: column.nullable,
+                        })
+                        .collect(),
+                    data_size: archive.table_data_size(table).map_err(|e| e.to_string())?,
+                })
+            })
+            .collect()
+    })
+    .await
+    .map_err(|e| format!("Failed to read tables: {}", e))?
+}
+
+#[tauri::command]
+pub async fn get_bacpac_table_rows(request: TableRowsRequest) -> Result<TableRowsPage, String> {
+    tokio::task::spawn_blocking(move || {
+        let mut archive =
+            BacpacArchive::open(&PathBuf::from(&request.bacpac_path)).map_err(|e| e.to_string())?;
+        let model = archive.read_model().map_err(|e| e.to_string())?;
+        let table = model
+            .find_table(&request.table)
+            .ok_or_else(|| format!("Table {} not found in the BACPAC", request.table))?;
+
+        browse::read_page(&mut archive, table, &request).map_err(|e| format!("{:#}", e))
+    })
+    .await
+    .map_err(|e| format!("Failed to read rows: {}", e))?
+}
+
+#[tauri::command]
+pub async fn search_bacpac(
+    app_handle: tauri::AppHandle,
+    request: BacpacSearchRequest,
+) -> Result<BacpacSearchResult, String> {
+    let cancel = app_handle.state::<JobRegistry>().register(&request.job_id);
+    let job_id = request.job_id.clone();
+    let handle = app_handle.clone();
+
+    let result = tokio::task::spawn_blocking(move || {
+        let mut archive =
+            BacpacArchive::open(&PathBuf::from(&request.bacpac_path)).map_err(|e| e.to_string())?;
+        let model = archive.read_model().map_err(|e| e.to_string())?;
+
+        search::search(&mut archive, &model, &request, &cancel, |line| {
+            let _ = handle.emit("search-progress", line);
+        })
+        .map_err(|e| format!("Search failed: {:#}", e))
+    })
+    .await
+    .map_err(|e| format!("Search task failed: {}", e));
+
+    app_handle.state::<JobRegistry>().finish(&job_id);
+    result?
+}
+
+#[tauri::command]
+pub async fn diff_bacpac_table(request: TableDiffRequest) -> Result<TableDiffPage, String> {
+    tokio::task::spawn_blocking(move || {
+        let mut left =
+            BacpacArchive::open(&PathBuf::from(&request.left_path)).map_err(|e| e.to_string())?;
+        let mut right =
+            BacpacArchive::open(&PathBuf::from(&request.right_path)).map_err(|e| e.to_string())?;
+        let left_model = left.read_model().map_err(|e| e.to_string())?;
+        let right_model = right.read_model().map_err(|e| e.to_string())?;
+        let left_table = left_model
+            .find_table(&request.table)
+            .ok_or_else(|| format!("Table {} not found in {}", request.table, request.left_path))?;
+        let right_table = right_model.find_table(&request.table).ok_or_else(|| {
+            format!(
+                "Table {} not found in {}",
+                request.table, request.right_path
+            )
+        })?;
+
+        diff::diff_table(&mut left, left_table, &mut right, right_table, &request)
+            .map_err(|e| format!("{:#}", e))
+    })
+    .await
+    .map_err(|e| format!("Failed to compare table data: {}", e))?
+}
+
+#[tauri::command]
+pub async fn subset_bacpac(
+    app_handle: tauri::AppHandle,
+    request: BacpacSubsetRequest,
+) -> Result<Vec<SubsetTableResult>, String> {
+    let source = PathBuf::from(&request.bacpac_path);
+    let target = PathBuf::from(&request.output_path);
+    if source == target {
+        return Err("The subset must be written to a different file than the source".to_string());
+    }
+    if target.exists() && !request.overwrite {
+        return Err(format!("{} already exists", request.output_path));
+    }
+
+    tokio::task::spawn_blocking(move || {
+        let mut archive = BacpacArchive::open(&source).map_err(|e| e.to_string())?;
+        let model = archive.read_model().map_err(|e| e.to_string())?;
+
+        subset::subset(&mut archive, &model, &request.roots, &target, |line| {
+            let _ = app_handle.emit("subset-progress", line);
+        })
+        .map_err(|e| format!("Subsetting failed: {:#}", e))
+    })
+    .await
+    .map_err(|e| format!("Subsetting task failed: {}", e))?
+}
+
+#[tauri::command]
+pub async fn mask_bacpac(
+    app_handle: tauri::AppHandle,
+    request: MaskBacpacRequest,
+) -> Result<String, String> {
+    let source = PathBuf::from(&request.bacpac_path);
+    let target = PathBuf::from(&request.output_path);
+    if source == target {
+        return Err("The masked BACPAC must be written to a different file".to_string());
+    }
+    if target.exists() && !request.overwrite {
+        return Err(format!("{} already exists", request.output_path));
+    }
+    let rules = match &request.rule_set {
+        Some(_) if !request.rules.is_empty() => {
+            return Err("Choose either a saved rule set or rules, not both".to_string());
+        }
+        Some(name) => {
+            masking::get_masking_rule_set(app_handle.clone(), name.clone())
+                .await?
+                .rules
+        }
+        None => request.rules.clone(),
+    };
+    if rules.is_empty() {
+        return Err("Add at least one masking rule".to_string());
+    }
+    let given_salt = request.salt.clone().filter(|salt| !salt.is_empty());
+    let generated = given_salt.is_none();
+    let salt = given_salt.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let used_salt = salt.clone();
+
+    let summary = tokio::task::spawn_blocking(move || {
+        let mut archive = BacpacArchive::open(&source).map_err(|e| e.to_string())?;
+        let model = archive.read_model().map_err(|e| e.to_string())?;
+
+        mask::mask(&mut archive, &model, &rules, &salt, &target, |line| {
+            let _ = app_handle.emit("mask-progress", line);
+        })
+        .map_err(|e| format!("Masking failed: {:#}", e))
+    })
+    .await
+    .map_err(|e| format!("Masking task failed: {}", e))??;
+
+    let mut message = format!(
+        "Masked BACPAC created successfully ({} columns in {} tables, {} rows)",
+        summary.columns, summary.tables, summary.rows
+    );
+    if generated {
+        message.push_str(&format!(
+            ". Salt: {} (use it again to reproduce this masking)",
+            used_salt
+        ));
+    }
+    Ok(message)
+}
+
+#[tauri::command]
+pub async fn create_bacpac_from_csv(
+    app_handle: tauri::AppHandle,
+    request: CsvToBacpacRequest,
+) -> Result<String, String> {
+    let target = PathBuf::from(&request.output_path);
+    if target.exists() && !request.overwrite {
+        return Err(format!("{} already exists", request.output_path));
+    }
+    let csv_dir = PathBuf::from(&request.csv_dir);
+    let manifest_path = request
+        .manifest_path
+        .as_ref()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| csv_dir.join("manifest.json"));
+    let manifest: builder::CsvManifest = serde_json::from_str(
+        &std::fs::read_to_string(&manifest_path)
+            .map_err(|e| format!("Failed to read {}: {}", manifest_path.display(), e))?,
+    )
+    .map_err(|e| format!("Invalid manifest {}: {}", manifest_path.display(), e))?;
+
+    let summary = tokio::task::spawn_blocking(move || {
+        builder::from_csv(
+            &csv_dir,
+            &manifest,
+            &request.database_name,
+            &target,
+            |line| {
+                let _ = app_handle.emit("build-progress", line);
+            },
+        )
+        .map_err(|e| format!("Failed to build the BACPAC: {:#}", e))
+    })
+    .await
+    .map_err(|e| format!("Build task failed: {}", e))??;
+
+    Ok(format!(
+        "BACPAC created successfully ({} tables, {} rows)",
+        summary.tables, summary.rows
+    ))
+}
+
+#[tauri::command]
+pub async fn create_bacpac_from_sqlite(
+    app_handle: tauri::AppHandle,
+    request: SqliteToBacpacRequest,
+) -> Result<String, String> {
+    let target = PathBuf::from(&request.output_path);
+    if target.exists() && !request.overwrite {
+        return Err(format!("{} already exists", request.output_path));
+    }
+
+    let summary = tokio::task::spawn_blocking(move || {
+        let schema = request
+            .schema
+            .clone()
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| "dbo".to_string());
+        builder::from_sqlite(
+            &PathBuf::from(&request.sqlite_path),
+            &request.tables,
+            &schema,
+            &request.database_name,
+            &target,
+            |line| {
+                let _ = app_handle.emit("build-progress", line);
+            },
+        )
+        .map_err(|e| format!("Failed to build the BACPAC: {:#}", e))
+    })
+    .await
+    .map_err(|e| format!("Build task failed: {}", e))??;
+
+    Ok(format!(
+        "BACPAC created successfully ({} tables, {} rows)",
+        summary.tables, summary.rows
+    ))
+}
+
+#[tauri::command]
+pub async fn generate_insert_script(
+    app_handle: tauri::AppHandle,
+    request: InsertScriptRequest,
+) -> Result<String, String> {
+    let source = PathBuf::from(&request.bacpac_path);
+    let target = PathBuf::from(&request.output_path);
+    if same_file(&source, &target) {
+        return Err("The script must be written to a different file than the BACPAC".to_string());
+    }
+    if target.exists() && !request.overwrite {
+        return Err(format!("{} already exists", request.output_path));
+    }
+
+    let summary = tokio::task::spawn_blocking(move || {
+        let mut archive = BacpacArchive::open(&source).map_err(|e| e.to_string())?;
+        let model = archive.read_model().map_err(|e| e.to_string())?;
+        let tables = if request.tables.is_empty() {
+            model.tables.iter().map(|t| (t, None)).collect()
+        } else {
+            request
+                .tables
+                .iter()
+                .map(|selection| {
+                    model
+                        .find_table(&selection.table)
+                        .map(|t| (t, selection.max_rows))
+                        .ok_or_else(|| format!("Table {} not found in the BACPAC", selection.table))
+                })
+                .collect::<Result<Vec<_>, String>>()?
+        };
+        let options = ScriptOptions {
+            include_schema: request.include_schema,
+            batch_size: request.batch_size,
+        };
+
+        write_replacing(&target, |path| {
+            let file = std::fs::File::create(path)
+                .with_context(|| format!("Failed to create {}", path.display()))?;
+            let mut out = BufWriter::new(file);
+            let summary =
+                script::write_script(&mut archive, &model, &tables, &options, &mut out, |line| {
+                    let _ = app_handle.emit("script-progress", line);
+                })?;
+            out.flush()?;
+            Ok(summary)
+        })
+        .map_err(|e| format!("Script generation failed: {:#}", e))
+    })
+    .await
+    .map_err(|e| format!("Script task failed: {}", e))??;
+
+    Ok(format!(
+        "Script created successfully ({} tables, {} rows)",
+        summary.tables, summary.rows
+    ))
+}
+
+#[tauri::command]
+pub async fn generate_data_dictionary(
+    request: DataDictionaryRequest,
+) -> Result<DataDictionaryResult, String> {
+    tokio::task::spawn_blocking(move || {
+        let source = PathBuf::from(&request.bacpac_path);
+        let mut archive = BacpacArchive::open(&source).map_err(|e| e.to_string())?;
+        let model = archive.read_model().map_err(|e| e.to_string())?;
+
+        let metadata_name = if archive.has_entry(METADATA_ENTRY) {
+            archive
+                .read_entry_string(METADATA_ENTRY)
+                .and_then(|xml| origin::parse_dac_metadata(&xml))
+                .map(|(name, _)| name)
+                .unwrap_or_default()
+        } else {
+            String::new()
+        };
+        let database_name = if metadata_name.is_empty() {
+            source
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        } else {
+            metadata_name
+        };
+
+        let tables = docs::tables_in_schemas(&model, &request.schemas);
+        if tables.is_empty() {
+            return Err("No tables match the selected schemas".to_string());
+        }
+        let er_diagram = docs::er_diagram(&tables);
+
+        let output_dir = PathBuf::from(&request.output_dir);
+        std::fs::create_dir_all(&output_dir).map_err(|e| e.to_string())?;
+        let markdown_path = output_dir.join("data-dictionary.md");
+        let html_path = output_dir.join("data-dictionary.html");
+        for target in [&markdown_path, &html_path] {
+            if target.exists() && !request.overwrite {
+                return Err(format!("{} already exists", target.display()));
+            }
+        }
+        std::fs::write(
+            &markdown_path,
+            docs::markdown(&database_name, &tables, &er_diagram),
+        )
+        .map_err(|e| format!("Failed to write {}: {}", markdown_path.display(), e))?;
+        std::fs::write(&html_path, docs::html(&database_name, &tables, &er_diagram))
+            .map_err(|e| format!("Failed to write {}: {}", html_path.display(), e))?;
+
+        Ok(DataDictionaryResult {
+            markdown_path: markdown_path.to_string_lossy().into_owned(),
+            html_path: html_path.to_string_lossy().into_owned(),
+            er_diagram,
+        })
+    })
+    .await
+    .map_err(|e| format!("Data dictionary task failed: {}", e))?
+}
+
+fn read_dependency_graph(bacpac_path: &str) -> Result<dependencies::DependencyGraph, String> {
+    let mut archive =
+        BacpacArchive::open(&PathBuf::from(bacpac_path)).map_err(|e| e.to_string())?;
+    let xml = archive
+        .read_entry_string(MODEL_ENTRY)
+        .map_err(|e| e.to_string())?;
+    dependencies::DependencyGraph::parse(&xml).map_err(|e| format!("Failed to read model: {:#}", e))
+}
+
+#[tauri::command]
+pub async fn list_model_objects(bacpac_path: String) -> Result<Vec<ModelObject>, String> {
+    tokio::task::spawn_blocking(move || Ok(read_dependency_graph(&bacpac_path)?.objects()))
+        .await
+        .map_err(|e| format!("Model task failed: {}", e))?
+}
+
+#[tauri::command]
+pub async fn get_object_dependencies(
+    request: DependencyRequest,
+) -> Result<DependencyReport, String> {
+    tokio::task::spawn_blocking(move || {
+        let graph = read_dependency_graph(&request.bacpac_path)?;
+        let node = graph
+            .find(&request.object_name)
+            .ok_or_else(|| format!("Object {} not found in the model", request.object_name))?;
+        Ok(DependencyReport {
+            object: graph.object(node),
+            depends_on: graph.depends_on(node, request.max_depth),
+            depended_by: graph.depended_by(node, request.max_depth),
+        })
+    })
+    .await
+    .map_err(|e| format!("Dependency task failed: {}", e))?
+}
+
+#[tauri::command]
+pub async fn get_security_report(bacpac_path: String) -> Result<SecurityReport, String> {
+    tokio::task::spawn_blocking(move || {
+        let mut archive =
+            BacpacArchive::open(&PathBuf::from(&bacpac_path)).map_err(|e| e.to_string())?;
+        let xml = archive
+            .read_entry_string(MODEL_ENTRY)
+            .map_err(|e| e.to_string())?;
+        security::security_report(&xml).map_err(|e| format!("Failed to read model: {:#}", e))
+    })
+    .await
+    .map_err(|e| format!("Security report task failed: {}", e))?
+}
@@ -28,6 +28,21 @@ pub async fn get_preferences(app_handle: tauri::AppHandle) -> Result<Preferences
     for pref in preferences {
         match pref.key.as_str() {
             "theme" => prefs.theme = pref.value,
+            "export_space_margin_percent" => {
+                if let Ok(value) = pref.value.parse() {
+                    prefs.export_space_margin_percent = value;
+                }
+            }
+            "export_min_free_mb" => {
+                if let Ok(value) = pref.value.parse() {
+                    prefs.export_min_free_mb = value;
+                }
+            }
+            "vault_idle_lock_minutes" => {
+                if let Ok(value) = pref.value.parse() {
+                    prefs.vault_idle_lock_minutes = value;
+                }
+            }
             _ => {}
         }
     }
@@ -71,3 +86,27 @@ pub async fn update_theme(
 
     update_preference(app_handle, "theme".to_string(), theme).await
 }
+
+#[tauri::command]
+pub async fn update_export_space_settings(
+    app_handle: tauri::AppHandle,
+    margin_percent: u32,
+    min_free_mb: u64,
+) -> Result<(), String> {
+    if margin_percent > 500 {
+        return Err("Safety margin must be between 0 and 500 percent".to_string());
+    }
+
+    update_preference(
+        app_handle.clone(),
+        "export_space_margin_percent".to_string(),
+        margin_percent.to_string(),
+    )
+    .await?;
+    update_preference(
+        app_handle,
+        "export_min_free_mb".to_string(),
+        min_free_mb.to_string(),
+    )
+    .await
+}
@@ -1,6 +1,11 @@
-use crate::commands::connections;
-use crate::models::{ExportRequest, ImportRequest, ImportWithDetailsRequest, SqlConnection};
-use crate::utils;
+use crate::bacpac::{preflight, BacpacArchive};
+use crate::commands::{connections, preferences};
+use crate::models::{
+    AuthMethod, ExportRequest, ImportPreflight, ImportRequest, ImportWithDetailsRequest,
+    SqlConnection,
+};
+use crate::{disk, secrets, sqlserver, utils};
+use std::path::{Path, PathBuf};
 use tauri::Emitter;
 use tokio::io::{AsyncBufReadExt, BufReader};
 
@@ -10,9 +15,11 @@ pub async fn export_bacpac(
     export_request: ExportRequest,
 ) -> Result<String, String> {
     // Get connection from database
-    let connection = connections::get_connection(app_handle.clone(), export_request.connection_id)
-        .await
+    let connection = connections::load_connection(&app_handle, export_request.connection_id)
         .map_err(|e| format!("Failed to get connection: {}", e))?;
+    let connection = secrets::resolve_connection(connection)
+        .await
+        .map_err(|e| format!("{:#}", e))?;
 
     // Build connection string
     let mut conn_for_export = connection.clone();
@@ -21,25 +28,40 @@ pub async fn export_bacpac(
     }
     let connection_string = utils::build_connection_string(&conn_for_export);
 
-    // Debug output to help troubleshoot connection string issues
-    eprintln!("DEBUG - Connection details:");
-    eprintln!("  Server: {}", conn_for_export.server);
-    eprintln!("  Port: {:?}", conn_for_export.port);
-    eprintln!("  Database: {:?}", conn_for_export.database_name);
-    eprintln!("  Generated connection string: {}", connection_string);
-
     // Get sqlpackage path
     let sqlpackage_path = get_sqlpackage_path(&app_handle)?;
 
+    let prefs = preferences::get_preferences(app_handle.clone()).await?;
+    let floor = prefs.export_min_free_mb.saturating_mul(1024 * 1024);
+    let output_path = PathBuf::from(&export_request.output_path);
+    if !export_request.skip_space_check {
+        check_export_space(
+            &app_handle,
+            &conn_for_export,
+            &output_path,
+            prefs.export_space_margin_percent,
+            floor,
+        )
+        .await?;
+    }
+
+    // sqlpackage writes next to the output and the result is moved into place
+    // only on success, so an existing file survives a failed or aborted export
+    let partial_path = export_partial_path(&output_path)?;
+    let _ = std::fs::remove_file(&partial_path);
+
     // Build command arguments
     let source_cs_arg = format!("/SourceConnectionString:{}", connection_string);
-    let target_file_arg = format!("/TargetFile:{}", export_request.output_path);
+    let target_file_arg = format!("/TargetFile:{}", partial_path.display());
+
+    let (extra_args, _response_file) = connection_args(&conn_for_export)?;
 
     // Execute sqlpackage with streaming output
     let mut child = tokio::process::Command::new(&sqlpackage_path)
         .arg("/Action:Export")
         .arg(&source_cs_arg)
         .arg(&target_file_arg)
+        .args(&extra_args)
         .stdout(std::process::Stdio::piped())
         .stderr(std::process::Stdio::piped())
         .spawn()
@@ -68,17 +90,132 @@ pub async fn export_bacpac(
         }
     });
 
-    // Wait for process to complete
-    let status = child.wait().await
-        .map_err(|e| format!("Failed to wait for sqlpackage: {}", e))?;
+    // Wait for process to complete, stopping it if a volume runs low
+    let watched = vec![output_path.clone(), std::env::temp_dir()];
+    let status = tokio::select! {
+        status = child.wait() => status,
+        reason = disk::watch_free_space(watched, floor) => {
+            let _ = child.kill().await;
+            let _ = tokio::join!(stdout_task, stderr_task);
+            let _ = std::fs::remove_file(&partial_path);
+            return Err(format!(
+                "Export aborted: {}. The partial BACPAC was removed.",
+                reason
+            ));
+        }
+    };
 
     // Wait for output tasks to complete
     let _ = tokio::join!(stdout_task, stderr_task);
 
-    if status.success() {
-        Ok("Export completed successfully!".to_string())
+    let result = match status {
+        Ok(status) if status.success() => std::fs::rename(&partial_path, &output_path)
+            .map_err(|e| format!("Failed to write {}: {}", output_path.display(), e)),
+        Ok(status) => Err(format!("Export failed with exit code: {:?}", status.code())),
+        Err(e) => Err(format!("Failed to wait for sqlpackage: {}", e)),
+    };
+    if result.is_err() {
+        let _ = std::fs::remove_file(&partial_path);
+    }
+    result.map(|_| "Export completed successfully!".to_string())
+}
+
+/// `orders.partial.bacpac` for `orders.bacpac`, keeping the package extension.
+fn export_partial_path(output_path: &Path) -> Result<PathBuf, String> {
+    let stem = output_path
+        .file_stem()
+        .ok_or_else(|| format!("Invalid output path {}", output_path.display()))?;
+    let mut name = stem.to_os_string();
+    name.push(".partial.bacpac");
+    Ok(output_path.with_file_name(name))
+}
+
+/// Table data in a BACPAC is zipped native BCP, which typically compresses to
+/// half its size or less.
+const BACPAC_COMPRESSION_ESTIMATE: u64 = 2;
+
+/// Refuses to start an export whose estimated size won't fit on the output
+/// volume or in sqlpackage's temp directory, which holds the uncompressed table
+/// data while the package is written. Tight fits only produce a warning.
+async fn check_export_space(
+    app_handle: &tauri::AppHandle,
+    connection: &SqlConnection,
+    output_path: &Path,
+    margin_percent: u32,
+    floor: u64,
+) -> Result<(), String> {
+    let temp_dir = std::env::temp_dir();
+    for (location, path) in [("The output volume", output_path), ("The temp directory", &temp_dir)] {
+        let available = disk::available_space(path)
+            .map_err(|e| format!("Failed to read free space for {}: {}", path.display(), e))?;
+        if available < floor {
+            return Err(format!(
+                "{} has only {} free, below the {} minimum",
+                location,
+                disk::format_size(available),
+                disk::format_size(floor)
+            ));
+        }
+    }
+
+    let estimate = async {
+        let mut client = sqlserver::connect(connection).await?;
+        sqlserver::table_data_bytes(&mut client).await
+    }
+    .await;
+    let data_bytes = match estimate {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            let _ = app_handle.emit(
+                "export-progress",
+                format!("Could not estimate the export size ({:#}); skipping the space check", e),
+            );
+            return Ok(());
+        }
+    };
+    let bacpac_bytes = data_bytes / BACPAC_COMPRESSION_ESTIMATE;
+    let _ = app_handle.emit(
+        "export-progress",
+        format!(
+            "Estimated table data {}, BACPAC about {}",
+            disk::format_size(data_bytes),
+            disk::format_size(bacpac_bytes)
+        ),
+    );
+
+    let needs: Vec<(&str, &Path, u64)> = if disk::same_volume(output_path, &temp_dir) {
+        vec![(
+            "The output and temp volume",
+            output_path,
+            data_bytes + bacpac_bytes,
+        )]
     } else {
-        Err(format!("Export failed with exit code: {:?}", status.code()))
+        vec![
+            ("The output volume", output_path, bacpac_bytes),
+            ("The temp directory", &temp_dir, data_bytes),
+        ]
+    };
+
+    let mut problems = Vec::new();
+    for (location, path, required) in needs {
+        let available = disk::available_space(path)
+            .map_err(|e| format!("Failed to read free space for {}: {}", path.display(), e))?;
+        match disk::space_verdict(location, required, available, margin_percent) {
+            disk::SpaceVerdict::Enough => {}
+            disk::SpaceVerdict::Tight(message) => {
+                let _ = app_handle.emit("export-progress", format!("Warning: {}", message));
+            }
+            disk::SpaceVerdict::Insufficient(message) => problems.push(message),
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "Not enough disk space for the export:\n{}\nFree up space, choose another location, or skip the space check.",
+            problems.join("\n")
+        ))
     }
 }
 
@@ -88,22 +225,25 @@ pub async fn import_bacpac(
     import_request: ImportRequest,
 ) -> Result<String, String> {
     // Get connection from database
-    let connection = connections::get_connection(app_handle.clone(), import_request.connection_id)
-        .await
+    let connection = connections::load_connection(&app_handle, import_request.connection_id)
         .map_err(|e| format!("Failed to get connection: {}", e))?;
+    let connection = secrets::resolve_connection(connection)
+        .await
+        .map_err(|e| format!("{:#}", e))?;
+
+    require_preflight(
+        &connection,
+        &import_request.bacpac_path,
+        &import_request.target_database,
+        import_request.acknowledged_warnings.as_deref(),
+    )
+    .await?;
 
     // Build connection string with target database
     let mut conn_for_import = connection.clone();
     conn_for_import.database_name = Some(import_request.target_database.clone());
     let connection_string = utils::build_connection_string(&conn_for_import);
 
-    // Debug output to help troubleshoot connection string issues
-    eprintln!("DEBUG - Connection details:");
-    eprintln!("  Server: {}", conn_for_import.server);
-    eprintln!("  Port: {:?}", conn_for_import.port);
-    eprintln!("  Database: {:?}", conn_for_import.database_name);
-    eprintln!("  Generated connection string: {}", connection_string);
-
     // Get sqlpackage path
     let sqlpackage_path = get_sqlpackage_path(&app_handle)?;
 
@@ -111,11 +251,14 @@ pub async fn import_bacpac(
     let source_file_arg = format!("/SourceFile:{}", import_request.bacpac_path);
     let target_cs_arg = format!("/TargetConnectionString:{}", connection_string);
 
+    let (extra_args, _response_file) = connection_args(&conn_for_import)?;
+
     // Execute sqlpackage with streaming output
     let mut child = tokio::process::Command::new(&sqlpackage_path)
         .arg("/Action:Import")
         .arg(&source_file_arg)
         .arg(&target_cs_arg)
+        .args(&extra_args)
         .stdout(std::process::Stdio::piped())
         .stderr(std::process::Stdio::piped())
         .spawn()
@@ -163,32 +306,17 @@ pub async fn import_bacpac_with_details(
     app_handle: tauri::AppHandle,
     import_request: ImportWithDetailsRequest,
 ) -> Result<String, String> {
-    // Build connection from provided details
-    let connection = SqlConnection {
-        id: None,
-        name: "Temporary Import Connection".to_string(),
-        server: import_request.server,
-        port: import_request.port,
-        database_name: Some(import_request.target_database.clone()),
-        username: import_request.username,
-        password: import_request.password,
-        connection_string: None,
-        use_windows_auth: import_request.use_windows_auth,
-        trust_server_cert: import_request.trust_server_cert,
-        encrypt: import_request.encrypt,
-        created_at: None,
-        updated_at: None,
-    };
+    let connection = details_connection(&import_request);
+    require_preflight(
+        &connection,
+        &import_request.bacpac_path,
+        &import_request.target_database,
+        import_request.acknowledged_warnings.as_deref(),
+    )
+    .await?;
 
     let connection_string = utils::build_connection_string(&connection);
 
-    // Debug output
-    eprintln!("DEBUG - Connection details:");
-    eprintln!("  Server: {}", connection.server);
-    eprintln!("  Port: {:?}", connection.port);
-    eprintln!("  Database: {:?}", connection.database_name);
-    eprintln!("  Generated connection string: {}", connection_string);
-
     // Get sqlpackage path
     let sqlpackage_path = get_sqlpackage_path(&app_handle)?;
 
@@ -196,11 +324,14 @@ pub async fn import_bacpac_with_details(
     let source_file_arg = format!("/SourceFile:{}", import_request.bacpac_path);
     let target_cs_arg = format!("/TargetConnectionString:{}", connection_string);
 
+    let (extra_args, _response_file) = connection_args(&connection)?;
+
     // Execute sqlpackage with streaming output
     let mut child = tokio::process::Command::new(&sqlpackage_path)
         .arg("/Action:Import")
         .arg(&source_file_arg)
         .arg(&target_cs_arg)
+        .args(&extra_args)
         .stdout(std::process::Stdio::piped())
         .stderr(std::process::Stdio::piped())
         .spawn()
@@ -243,6 +374,189 @@ pub async fn import_bacpac_with_details(
     }
 }
 
+#[tauri::command]
+pub async fn preflight_import(
+    app_handle: tauri::AppHandle,
+    import_request: ImportRequest,
+) -> Result<ImportPreflight, String> {
+    let connection = connections::load_connection(&app_handle, import_request.connection_id)
+        .map_err(|e| format!("Failed to get connection: {}", e))?;
+    let connection = secrets::resolve_connection(connection)
+        .await
+        .map_err(|e| format!("{:#}", e))?;
+    run_preflight(
+        &connection,
+        &import_request.bacpac_path,
+        &import_request.target_database,
+    )
+    .await
+}
+
+#[tauri::command]
+pub async fn preflight_import_with_details(
+    import_request: ImportWithDetailsRequest,
+) -> Result<ImportPreflight, String> {
+    run_preflight(
+        &details_connection(&import_request),
+        &import_request.bacpac_path,
+        &import_request.target_database,
+    )
+    .await
+}
+
+/// Connection for an import given by server details rather than a saved connection.
+fn details_connection(import_request: &ImportWithDetailsRequest) -> SqlConnection {
+    SqlConnection {
+        id: None,
+        name: "Temporary Import Connection".to_string(),
+        server: import_request.server.clone(),
+        port: import_request.port.clone(),
+        database_name: Some(import_request.target_database.clone()),
+        username: import_request.username.clone(),
+        password: import_request.password.clone(),
+        connection_string: None,
+        authentication: import_request.authentication,
+        tenant_id: import_request.tenant_id.clone(),
+        trust_server_cert: import_request.trust_server_cert,
+        encrypt: import_request.encrypt,
+        extra_options: None,
+        ..Default::default()
+    }
+}
+
+/// Runs the preflight and refuses the import on errors or on warnings whose
+/// codes are not in `acknowledged`.
+async fn require_preflight(
+    connection: &SqlConnection,
+    bacpac_path: &str,
+    target_database: &str,
+    acknowledged: Option<&[String]>,
+) -> Result<(), String> {
+    let plan = run_preflight(connection, bacpac_path, target_database).await?;
+    if !plan.errors.is_empty() {
+        return Err(format!("Import blocked:\n{}", plan.errors.join("\n")));
+    }
+    let acknowledged = acknowledged.unwrap_or_default();
+    let unacknowledged: Vec<&str> = plan
+        .warnings
+        .iter()
+        .filter(|w| !acknowledged.contains(&w.code))
+        .map(|w| w.message.as_str())
+        .collect();
+    if !unacknowledged.is_empty() {
+        return Err(format!(
+            "Please acknowledge these warnings before importing:\n{}",
+            unacknowledged.join("\n")
+        ));
+    }
+    Ok(())
+}
+
+async fn run_preflight(
+    connection: &SqlConnection,
+    bacpac_path: &str,
+    target_database: &str,
+) -> Result<ImportPreflight, String> {
+    let path = std::path::PathBuf::from(bacpac_path);
+    let package = tokio::task::spawn_blocking(move || {
+        let mut archive = BacpacArchive::open(&path)?;
+        preflight::package_facts(&mut archive)
+    })
+    .await
+    .map_err(|e| format!("Preflight task failed: {}", e))?
+    .map_err(|e| format!("Failed to read BACPAC: {:#}", e))?;
+
+    // Connect to master: the target database may not exist yet
+    let mut server_connection = connection.clone();
+    server_connection.database_name = None;
+    let mut client = sqlserver::connect(&server_connection)
+        .await
+        .map_err(|e| format!("Failed to connect to {}: {:#}", connection.server, e))?;
+
+    let server = sqlserver::server_info(&mut client)
+        .await
+        .map_err(|e| format!("Failed to read server properties: {:#}", e))?;
+    let database_exists = sqlserver::database_exists(&mut client, target_database)
+        .await
+        .map_err(|e| format!("Failed to check for database {}: {:#}", target_database, e))?;
+    let user_objects = if database_exists {
+        sqlserver::user_object_count(&mut client, target_database).await
+    } else {
+        None
+    };
+    let free_bytes = if server.is_azure() {
+        None
+    } else {
+        sqlserver::data_volume_free_bytes(&mut client).await
+    };
+
+    let target = preflight::TargetFacts {
+        server,
+        database_exists,
+        user_objects,
+        free_bytes,
+    };
+    Ok(preflight::evaluate(&package, &target, target_database))
+}
+
+/// Connection settings sqlpackage takes as arguments rather than from the
+/// connection string: the command timeout, an access token and the Entra tenant.
+/// The access token goes in a response file instead of on the command line,
+/// where other local users could read it; keep the file until sqlpackage exits.
+fn connection_args(
+    connection: &SqlConnection,
+) -> Result<(Vec<String>, Option<ResponseFile>), String> {
+    let mut args = Vec::new();
+    let mut response_file = None;
+    if let Some(seconds) = connection.command_timeout {
+        args.push(format!("/p:CommandTimeout={}", seconds));
+    }
+    match connection.authentication {
+        AuthMethod::AccessToken => {
+            if let Some(token) = connection.password.as_deref().filter(|t| !t.is_empty()) {
+                let file = ResponseFile::create(&format!("/AccessToken:{}", token))?;
+                args.push(format!("@{}", file.path.display()));
+                response_file = Some(file);
+            }
+        }
+        AuthMethod::SqlPassword | AuthMethod::Integrated => {}
+        _ => {
+            if let Some(tenant) = connection.tenant_id.as_deref().filter(|t| !t.is_empty()) {
+                args.push(format!("/TenantId:{}", tenant));
+            }
+        }
+    }
+    Ok((args, response_file))
+}
+
+/// A sqlpackage response file readable only by the current user, deleted
+/// when dropped.
+struct ResponseFile {
+    path: PathBuf,
+}
+
+impl ResponseFile {
+    fn create(contents: &str) -> Result<Self, String> {
+        let path = std::env::temp_dir().join(format!("shapac-{}.rsp", uuid::Uuid::new_v4()));
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let file = ResponseFile { path };
+        options
+            .open(&file.path)
+            .and_then(|mut f| std::io::Write::write_all(&mut f, contents.as_bytes()))
+            .map_err(|e| format!("Failed to write the sqlpackage response file: {}", e))?;
+        Ok(file)
+    }
+}
+
+impl Drop for ResponseFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
 fn get_sqlpackage_path(_app_handle: &tauri::AppHandle) -> Result<std::path::PathBuf, String> {
     // Check if sqlpackage is available in system PATH
     let which_cmd = if cfg!(target_os = "windows") {
@@ -1,6 +1,13 @@
+use crate::jobs::JobRegistry;
 use crate::utils;
+use tauri::State;
 
 #[tauri::command]
 pub async fn check_sqlpackage_installed() -> Result<bool, String> {
     Ok(utils::is_sqlpackage_installed())
 }
+
+#[tauri::command]
+pub async fn cancel_job(jobs: State<'_, JobRegistry>, job_id: String) -> Result<bool, String> {
+    Ok(jobs.cancel(&job_id))
+}
@@ -0,0 +1,96 @@
+use crate::commands::preferences;
+use crate::db;
+use crate::models::VaultStatus;
+use crate::vault::{self, Vault};
+use tauri::Manager;
+
+#[tauri::command]
+pub async fn get_vault_status(app_handle: tauri::AppHandle) -> Result<VaultStatus, String> {
+    let conn = db::get_connection(&app_handle).map_err(|e| e.to_string())?;
+    let enabled = vault::is_enabled(&conn).map_err(|e| e.to_string())?;
+    let prefs = preferences::get_preferences(app_handle.clone()).await?;
+
+    Ok(VaultStatus {
+        enabled,
+        unlocked: enabled && app_handle.state::<Vault>().is_unlocked(),
+        idle_lock_minutes: prefs.vault_idle_lock_minutes,
+    })
+}
+
+/// Runs a key-deriving vault operation off the async runtime; Argon2 is slow on purpose.
+async fn with_vault(
+    app_handle: tauri::AppHandle,
+    operation: impl FnOnce(&mut rusqlite::Connection, &Vault) -> anyhow::Result<()> + Send + 'static,
+) -> Result<(), String> {
+    let prefs = preferences::get_preferences(app_handle.clone()).await?;
+    tokio::task::spawn_blocking(move || {
+        let mut conn = db::get_connection(&app_handle).map_err(|e| e.to_string())?;
+        let vault = app_handle.state::<Vault>();
+        vault.set_idle_timeout(prefs.vault_idle_lock_minutes);
+        operation(&mut conn, &vault).map_err(|e| format!("{:#}", e))
+    })
+    .await
+    .map_err(|e| format!("Vault task failed: {}", e))?
+}
+
+/// Turns on encryption of saved passwords and connection strings.
+#[tauri::command]
+pub async fn set_master_password(
+    app_handle: tauri::AppHandle,
+    password: String,
+) -> Result<(), String> {
+    with_vault(app_handle, move |conn, vault| {
+        vault::enable(conn, vault, &password)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn unlock_vault(app_handle: tauri::AppHandle, password: String) -> Result<(), String> {
+    with_vault(app_handle, move |conn, vault| vault.unlock(conn, &password)).await
+}
+
+#[tauri::command]
+pub async fn lock_vault(vault: tauri::State<'_, Vault>) -> Result<(), String> {
+    vault.lock();
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn change_master_password(
+    app_handle: tauri::AppHandle,
+    current_password: String,
+    new_password: String,
+) -> Result<(), String> {
+    with_vault(app_handle, move |conn, vault| {
+        vault::change_password(conn, vault, &current_password, &new_password)
+    })
+    .await
+}
+
+/// Turns encryption off and stores secrets in plain text again.
+#[tauri::command]
+pub async fn remove_master_password(
+    app_handle: tauri::AppHandle,
+    password: String,
+) -> Result<(), String> {
+    with_vault(app_handle, move |conn, vault| {
+        vault::disable(conn, vault, &password)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn set_vault_idle_lock(app_handle: tauri::AppHandle, minutes: u32) -> Result<(), String> {
+    if minutes > 24 * 60 {
+        return Err("Idle lock must be at most 1440 minutes".to_string());
+    }
+    preferences::update_preference(
+        app_handle.clone(),
+        "vault_idle_lock_minutes".to_string(),
+        minutes.to_string(),
+    )
+    .await?;
+    app_handle.state::<Vault>().set_idle_timeout(minutes);
+    Ok(())
+}
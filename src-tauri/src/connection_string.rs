@@ -0,0 +1,220 @@
+//! Reading and writing `key=value;` connection strings with the quoting rules of
+//! Microsoft.Data.SqlClient's `SqlConnectionStringBuilder`.
+
+use anyhow::{bail, Result};
+use std::fmt;
+
+/// Keyword/value pairs in their original order. Keywords keep the spelling they
+/// were given with but are looked up case-insensitively.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConnectionString {
+    pairs: Vec<(String, String)>,
+}
+
+impl ConnectionString {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(input: &str) -> Result<Self> {
+        let mut pairs: Vec<(String, String)> = Vec::new();
+        let mut rest = input;
+
+        loop {
+            rest = rest.trim_start_matches(|c: char| c == ';' || c.is_whitespace());
+            if rest.is_empty() {
+                break;
+            }
+
+            // Keyword up to the first single '='; '==' is a literal '='
+            let mut keyword = String::new();
+            let mut chars = rest.char_indices().peekable();
+            let value_start = loop {
+                match chars.next() {
+                    Some((i, '=')) => {
+                        if let Some((_, '=')) = chars.peek() {
+                            chars.next();
+                            keyword.push('=');
+                        } else {
+                            break i + 1;
+                        }
+                    }
+                    Some((_, ';')) | None => {
+                        bail!("Keyword '{}' has no value", keyword.trim())
+                    }
+                    Some((_, c)) => keyword.push(c),
+                }
+            };
+            let keyword = keyword.trim().to_string();
+            if keyword.is_empty() {
+                bail!("Connection string has a value without a keyword");
+            }
+
+            rest = rest[value_start..].trim_start();
+            let value = match rest.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let (value, after) = read_quoted(&rest[1..], quote).ok_or_else(|| {
+                        anyhow::anyhow!("Value of '{}' has no closing {}", keyword, quote)
+                    })?;
+                    let after = after.trim_start();
+                    if !after.is_empty() && !after.starts_with(';') {
+                        bail!("Unexpected text after the quoted value of '{}'", keyword);
+                    }
+                    rest = after;
+                    value
+                }
+                _ => {
+                    let end = rest.find(';').unwrap_or(rest.len());
+                    let value = rest[..end].trim_end().to_string();
+                    rest = &rest[end..];
+                    value
+                }
+            };
+
+            // As in SqlClient, a repeated keyword overrides the earlier one
+            match pairs
+                .iter_mut()
+                .find(|(k, _)| k.eq_ignore_ascii_case(&keyword))
+            {
+                Some(pair) => pair.1 = value,
+                None => pairs.push((keyword, value)),
+            }
+        }
+
+        Ok(ConnectionString { pairs })
+    }
+
+    /// The value of the first of `keywords` present, so synonyms can be given together.
+    pub fn get(&self, keywords: &[&str]) -> Option<&str> {
+        keywords.iter().find_map(|keyword| {
+            self.pairs
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(keyword))
+                .map(|(_, v)| v.as_str())
+        })
+    }
+
+    /// Sets a keyword, replacing an existing value under the same spelling.
+    pub fn set(&mut self, keyword: &str, value: impl Into<String>) {
+        let value = value.into();
+        match self
+            .pairs
+            .iter_mut()
+            .find(|(k, _)| k.eq_ignore_ascii_case(keyword))
+        {
+            Some(pair) => pair.1 = value,
+            None => self.pairs.push((keyword.to_string(), value)),
+        }
+    }
+
+    /// Removes every keyword in `keywords`, returning the first value found.
+    pub fn remove(&mut self, keywords: &[&str]) -> Option<String> {
+        let found = self.get(keywords).map(|v| v.to_string());
+        self.pairs.retain(|(k, _)| {
+            !keywords
+                .iter()
+                .any(|keyword| k.eq_ignore_ascii_case(keyword))
+        });
+        found
+    }
+
+    pub fn pairs(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.pairs.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    /// Appends all pairs of `other`, as a fragment written after the known keywords.
+    pub fn extend(&mut self, other: ConnectionString) {
+        for (keyword, value) in other.pairs {
+            self.set(&keyword, value);
+        }
+    }
+}
+
+/// Reads a quoted value whose opening quote has been consumed. A doubled quote
+/// stands for one quote character. Returns the value and the text after it.
+fn read_quoted(input: &str, quote: char) -> Option<(String, &str)> {
+    let mut value = String::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == quote {
+            if let Some((_, next)) = chars.peek() {
+                if *next == quote {
+                    chars.next();
+                    value.push(quote);
+                    continue;
+                }
+            }
+            return Some((value, &input[i + c.len_utf8()..]));
+        }
+        value.push(c);
+    }
+    None
+}
+
+fn escape_keyword(keyword: &str) -> String {
+    keyword.replace('=', "==")
+}
+
+/// Quotes a value the way `DbConnectionStringBuilder` does: bare when it has no
+/// quotes, `;`, `=`, whitespace or control characters, in single quotes when it
+/// contains double quotes only, otherwise in double quotes with inner double
+/// quotes doubled.
+pub fn escape_value(value: &str) -> String {
+    let needs_quotes = value.is_empty()
+        || value
+            .chars()
+            .any(|c| matches!(c, ';' | '\'' | '"' | '=') || c.is_whitespace() || c.is_control());
+    if !needs_quotes {
+        value.to_string()
+    } else if value.contains('"') && !value.contains('\'') {
+        format!("'{}'", value)
+    } else {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    }
+}
+
+impl fmt::Display for ConnectionString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (keyword, value)) in self.pairs.iter().enumerate() {
+            if i > 0 {
+                f.write_str(";")?;
+            }
+            write!(f, "{}={}", escape_keyword(keyword), escape_value(value))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_quoting() {
+        let cs = ConnectionString::parse(
+            "Server = tcp:db,1433 ; Password='a;b=\"c\"';User Id=\"it\"\"s\";a==b=1;;",
+        )
+        .unwrap();
+        assert_eq!(cs.get(&["server"]), Some("tcp:db,1433"));
+        assert_eq!(cs.get(&["pwd", "password"]), Some("a;b=\"c\""));
+        assert_eq!(cs.get(&["user id"]), Some("it\"s"));
+        assert_eq!(cs.get(&["a=b"]), Some("1"));
+
+        assert!(ConnectionString::parse("Server=db;Password='open").is_err());
+        assert!(ConnectionString::parse("Server").is_err());
+    }
+
+    #[test]
+    fn test_escape_value() {
+        assert_eq!(escape_value("plain"), "plain");
+        assert_eq!(escape_value("has space"), "\"has space\"");
+        assert_eq!(escape_value(" padded"), "\" padded\"");
+        assert_eq!(escape_value("a;b"), "\"a;b\"");
+        assert_eq!(escape_value("say \"hi\""), "'say \"hi\"'");
+        assert_eq!(escape_value("it's \"x\""), "\"it's \"\"x\"\"\"");
+    }
+}
@@ -1,15 +1,13 @@
-use anyhow::Result;
-use rusqlite::Connection;
+use anyhow::{bail, Context, Result};
+use rusqlite::{Connection, OptionalExtension};
+use std::path::Path;
 use tauri::Manager;
 
 pub fn init_db(app_handle: &tauri::AppHandle) -> Result<()> {
     let app_data_dir = app_handle.path().app_data_dir()?;
     std::fs::create_dir_all(&app_data_dir)?;
 
-    let db_path = app_data_dir.join("shapac.db");
-    let conn = Connection::open(db_path)?;
-
-    create_tables(&conn)?;
+    open(&app_data_dir.join("shapac.db"))?;
     Ok(())
 }
 
@@ -20,54 +18,307 @@ pub fn get_connection(app_handle: &tauri::AppHandle) -> Result<Connection> {
     Ok(conn)
 }
 
-fn create_tables(conn: &Connection) -> Result<()> {
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS sql_connections (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            name TEXT NOT NULL UNIQUE,
-            server TEXT NOT NULL,
-            port TEXT,
-            database_name TEXT,
-            username TEXT,
-            password TEXT,
-            connection_string TEXT,
-            use_windows_auth BOOLEAN DEFAULT 0,
-            trust_server_cert BOOLEAN DEFAULT 1,
-            encrypt BOOLEAN DEFAULT 1,
-            created_at TEXT NOT NULL,
-            updated_at TEXT NOT NULL
-        )",
-        [],
-    )?;
-
-    // Migration: Add port column if it doesn't exist (for existing databases)
-    let column_exists: Result<i32, _> = conn.query_row(
-        "SELECT COUNT(*) FROM pragma_table_info('sql_connections') WHERE name='port'",
-        [],
-        |row| row.get(0),
-    );
-
-    if let Ok(0) = column_exists {
-        conn.execute("ALTER TABLE sql_connections ADD COLUMN port TEXT", [])?;
+/// A schema change. Released migrations are never edited or reordered: a
+/// database's `user_version` is the number of them it has had.
+struct Migration {
+    description: &'static str,
+    sql: &'static str,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        description: "connections and preferences",
+        sql: "CREATE TABLE IF NOT EXISTS sql_connections (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE,
+                server TEXT NOT NULL,
+                database_name TEXT,
+                username TEXT,
+                password TEXT,
+                connection_string TEXT,
+                use_windows_auth BOOLEAN DEFAULT 0,
+                trust_server_cert BOOLEAN DEFAULT 1,
+                encrypt BOOLEAN DEFAULT 1,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS app_preferences (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );
+            INSERT OR IGNORE INTO app_preferences (key, value, created_at, updated_at)
+            VALUES ('theme', 'system', datetime('now'), datetime('now'));",
+    },
+    Migration {
+        description: "connection port",
+        sql: "ALTER TABLE sql_connections ADD COLUMN port TEXT;",
+    },
+    Migration {
+        // Stored as JSON so rules can gain options freely
+        description: "masking rule sets",
+        sql: "CREATE TABLE masking_rule_sets (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE,
+                rules TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );",
+    },
+    Migration {
+        // A single row exists only while encryption is on
+        description: "master password check",
+        sql: "CREATE TABLE master_key (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                salt TEXT NOT NULL,
+                kdf_params TEXT NOT NULL,
+                verifier TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );",
+    },
+    Migration {
+        description: "extra connection options",
+        sql: "ALTER TABLE sql_connections ADD COLUMN extra_options TEXT;",
+    },
+    Migration {
+        description: "timeouts, read-only intent and TLS options",
+        sql: "ALTER TABLE sql_connections ADD COLUMN connect_timeout INTEGER;
+            ALTER TABLE sql_connections ADD COLUMN command_timeout INTEGER;
+            ALTER TABLE sql_connections ADD COLUMN read_only_intent BOOLEAN DEFAULT 0;
+            ALTER TABLE sql_connections ADD COLUMN multi_subnet_failover BOOLEAN DEFAULT 0;
+            ALTER TABLE sql_connections ADD COLUMN strict_encryption BOOLEAN DEFAULT 0;
+            ALTER TABLE sql_connections ADD COLUMN host_name_in_certificate TEXT;
+            ALTER TABLE sql_connections ADD COLUMN server_certificate TEXT;
+            ALTER TABLE sql_connections ADD COLUMN application_name TEXT;",
+    },
+    Migration {
+        // use_windows_auth stays but is no longer read
+        description: "authentication methods",
+        sql: "ALTER TABLE sql_connections
+                ADD COLUMN authentication TEXT NOT NULL DEFAULT 'sql_password';
+            ALTER TABLE sql_connections ADD COLUMN tenant_id TEXT;
+            UPDATE sql_connections SET authentication = 'integrated' WHERE use_windows_auth = 1;",
+    },
+    Migration {
+        // Existing passwords stay literal, whatever they start with
+        description: "password references",
+        sql: "ALTER TABLE sql_connections
+                ADD COLUMN password_is_reference BOOLEAN NOT NULL DEFAULT 0;",
+    },
+];
+
+/// Opens the app database, backing it up and bringing its schema up to date
+/// when it was written by an older version of the app.
+pub(crate) fn open(db_path: &Path) -> Result<Connection> {
+    let mut conn = Connection::open(db_path)?;
+    let version = schema_version(&conn)?;
+    if version > 0 && version < MIGRATIONS.len() {
+        let backup = db_path.with_extension(format!("v{}.db.bak", version));
+        back_up(&conn, &backup)
+            .with_context(|| format!("Could not back up the database to {}", backup.display()))?;
+    }
+    migrate(&mut conn)?;
+    Ok(conn)
+}
+
+/// Applies the migrations a database hasn't had, each in its own transaction.
+pub(crate) fn migrate(conn: &mut Connection) -> Result<()> {
+    let version = schema_version(conn)?;
+    if version > MIGRATIONS.len() {
+        bail!(
+            "shapac.db was written by a newer version of the app (schema version {}, this \
+             version knows up to {}); update the app to use it",
+            version,
+            MIGRATIONS.len()
+        );
+    }
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration.sql).with_context(|| {
+            format!("Migration {} ({}) failed", index + 1, migration.description)
+        })?;
+        tx.pragma_update(None, "user_version", index + 1)?;
+        tx.commit()?;
+    }
+    // A database from before versioning may already be current
+    if stored_version(conn)? != MIGRATIONS.len() {
+        conn.pragma_update(None, "user_version", MIGRATIONS.len())?;
+    }
+    Ok(())
+}
+
+fn stored_version(conn: &Connection) -> Result<usize> {
+    let version: i64 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    Ok(version.max(0) as usize)
+}
+
+/// The number of migrations a database has had. Databases from before
+/// versioning have none recorded; what they already contain tells instead.
+fn schema_version(conn: &Connection) -> Result<usize> {
+    let version = stored_version(conn)?;
+    if version > 0 || !has_table(conn, "sql_connections")? {
+        return Ok(version);
     }
 
-    // Create app_preferences table for storing user preferences
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS app_preferences (
-            key TEXT PRIMARY KEY,
-            value TEXT NOT NULL,
-            created_at TEXT NOT NULL,
-            updated_at TEXT NOT NULL
-        )",
-        [],
-    )?;
-
-    // Initialize default theme preference
-    conn.execute(
-        "INSERT OR IGNORE INTO app_preferences (key, value, created_at, updated_at)
-         VALUES ('theme', 'system', datetime('now'), datetime('now'))",
-        [],
-    )?;
+    // What migrations 2 onwards left behind, in order
+    let applied = [
+        has_column(conn, "sql_connections", "port")?,
+        has_table(conn, "masking_rule_sets")?,
+        has_table(conn, "master_key")?,
+        has_column(conn, "sql_connections", "extra_options")?,
+        has_column(conn, "sql_connections", "connect_timeout")?,
+        has_column(conn, "sql_connections", "authentication")?,
+    ];
+    Ok(1 + applied.iter().take_while(|applied| **applied).count())
+}
+
+fn has_table(conn: &Connection, table: &str) -> Result<bool> {
+    Ok(conn
+        .query_row(
+            "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1",
+            [table],
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some())
+}
+
+fn has_column(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+    Ok(conn
+        .query_row(
+            "SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2",
+            [table, column],
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some())
+}
 
+/// A consistent copy of the database, replacing an earlier backup of the same version.
+fn back_up(conn: &Connection, backup: &Path) -> Result<()> {
+    if backup.exists() {
+        std::fs::remove_file(backup)?;
+    }
+    conn.execute("VACUUM INTO ?1", [backup.to_string_lossy()])?;
     Ok(())
 }
+
+/// Deletes every backup `open` took of the database at `db_path`.
+pub(crate) fn remove_backups(db_path: &Path) -> Result<()> {
+    let (Some(dir), Some(stem)) = (
+        db_path.parent(),
+        db_path.file_stem().and_then(|stem| stem.to_str()),
+    ) else {
+        return Ok(());
+    };
+    let prefix = format!("{}.v", stem);
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with(&prefix) && name.ends_with(".db.bak") {
+            std::fs::remove_file(entry.path())
+                .with_context(|| format!("Could not delete the backup {}", name))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns(conn: &Connection, table: &str) -> Vec<String> {
+        let mut stmt = conn
+            .prepare("SELECT name FROM pragma_table_info(?1)")
+            .unwrap();
+        stmt.query_map([table], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_new_database_gets_every_migration() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn).unwrap();
+
+        assert_eq!(stored_version(&conn).unwrap(), MIGRATIONS.len());
+        let columns = columns(&conn, "sql_connections");
+        for column in [
+            "port",
+            "extra_options",
+            "strict_encryption",
+            "authentication",
+        ] {
+            assert!(columns.iter().any(|c| c == column), "{}", column);
+        }
+        assert!(has_table(&conn, "masking_rule_sets").unwrap());
+        assert!(has_table(&conn, "master_key").unwrap());
+
+        // Running again changes nothing
+        migrate(&mut conn).unwrap();
+        assert_eq!(stored_version(&conn).unwrap(), MIGRATIONS.len());
+    }
+
+    #[test]
+    fn test_unversioned_database_is_adopted_and_backed_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("shapac.db");
+        {
+            // The schema before versioning, with the port column already added
+            let conn = Connection::open(&db_path).unwrap();
+            conn.execute_batch(MIGRATIONS[0].sql).unwrap();
+            conn.execute_batch(MIGRATIONS[1].sql).unwrap();
+            conn.execute(
+                "INSERT INTO sql_connections (name, server, use_windows_auth, created_at, updated_at)
+                 VALUES ('dev', 'localhost', 1, 'now', 'now'), ('prod', 'db', 0, 'now', 'now')",
+                [],
+            )
+            .unwrap();
+        }
+        assert_eq!(
+            schema_version(&Connection::open(&db_path).unwrap()).unwrap(),
+            2
+        );
+
+        let conn = open(&db_path).unwrap();
+        assert_eq!(stored_version(&conn).unwrap(), MIGRATIONS.len());
+        let mut stmt = conn
+            .prepare("SELECT name, authentication FROM sql_connections ORDER BY name")
+            .unwrap();
+        let methods: Vec<(String, String)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(
+            methods,
+            [
+                ("dev".to_string(), "integrated".to_string()),
+                ("prod".to_string(), "sql_password".to_string()),
+            ]
+        );
+
+        let backup = Connection::open(dir.path().join("shapac.v2.db.bak")).unwrap();
+        assert!(!columns(&backup, "sql_connections").contains(&"authentication".to_string()));
+        let rows: i64 = backup
+            .query_row("SELECT COUNT(*) FROM sql_connections", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(rows, 2);
+    }
+
+    #[test]
+    fn test_newer_database_is_refused() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn).unwrap();
+        conn.pragma_update(None, "user_version", MIGRATIONS.len() + 1)
+            .unwrap();
+
+        let message = migrate(&mut conn).unwrap_err().to_string();
+        assert!(message.contains("newer version"), "{}", message);
+    }
+}
@@ -0,0 +1,151 @@
+//! Free-space checks for operations that write large files.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Free bytes on the volume holding `path`, which need not exist yet.
+pub fn available_space(path: &Path) -> io::Result<u64> {
+    fs2::available_space(existing_ancestor(path)?)
+}
+
+fn existing_ancestor(path: &Path) -> io::Result<&Path> {
+    path.ancestors()
+        .find(|p| !p.as_os_str().is_empty() && p.exists())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No existing directory for {}", path.display()),
+            )
+        })
+}
+
+#[cfg(unix)]
+pub fn same_volume(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    let device = |path: &Path| {
+        existing_ancestor(path)
+            .and_then(std::fs::metadata)
+            .map(|m| m.dev())
+    };
+    matches!((device(a), device(b)), (Ok(a), Ok(b)) if a == b)
+}
+
+#[cfg(not(unix))]
+pub fn same_volume(a: &Path, b: &Path) -> bool {
+    // Compare drive letters or UNC shares
+    let root = |path: &Path| {
+        std::path::absolute(path).ok().and_then(|p| {
+            p.components()
+                .next()
+                .map(|c| c.as_os_str().to_ascii_lowercase())
+        })
+    };
+    root(a).is_some() && root(a) == root(b)
+}
+
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SpaceVerdict {
+    Enough,
+    /// Fits, but with less headroom than the safety margin asks for.
+    Tight(String),
+    Insufficient(String),
+}
+
+/// Compares an estimate against free space, asking for `margin_percent` extra
+/// headroom because the estimate is rough.
+pub fn space_verdict(
+    location: &str,
+    required: u64,
+    available: u64,
+    margin_percent: u32,
+) -> SpaceVerdict {
+    let with_margin = required.saturating_add(required.saturating_mul(margin_percent as u64) / 100);
+    if available < required {
+        SpaceVerdict::Insufficient(format!(
+            "{} needs about {} but only {} is free",
+            location,
+            format_size(required),
+            format_size(available)
+        ))
+    } else if available < with_margin {
+        SpaceVerdict::Tight(format!(
+            "{} has {} free for about {}, less than the {}% safety margin",
+            location,
+            format_size(available),
+            format_size(required),
+            margin_percent
+        ))
+    } else {
+        SpaceVerdict::Enough
+    }
+}
+
+/// Resolves once any of `paths` has less than `floor` bytes free, with a message
+/// saying where. Runs until then, so it is meant to be raced against the work.
+pub async fn watch_free_space(paths: Vec<PathBuf>, floor: u64) -> String {
+    loop {
+        for path in &paths {
+            if let Ok(available) = available_space(path) {
+                if available < floor {
+                    return format!(
+                        "only {} left on the volume holding {} (minimum {})",
+                        format_size(available),
+                        path.display(),
+                        format_size(floor)
+                    );
+                }
+            }
+        }
+        tokio::time::sleep(WATCH_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_space_verdict() {
+        const MB: u64 = 1024 * 1024;
+        assert_eq!(
+            space_verdict("Output", 100 * MB, 200 * MB, 20),
+            SpaceVerdict::Enough
+        );
+        assert!(matches!(
+            space_verdict("Output", 100 * MB, 110 * MB, 20),
+            SpaceVerdict::Tight(_)
+        ));
+        assert_eq!(
+            space_verdict("Output", 100 * MB, 50 * MB, 20),
+            SpaceVerdict::Insufficient(
+                "Output needs about 100.0 MB but only 50.0 MB is free".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_available_space_of_missing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("missing").join("export.bacpac");
+        assert!(available_space(&target).unwrap() > 0);
+        assert!(same_volume(&target, dir.path()));
+    }
+}
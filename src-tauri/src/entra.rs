@@ -0,0 +1,230 @@
+//! Microsoft Entra ID access tokens for direct connections.
+//!
+//! sqlpackage signs in through SqlClient, which acquires tokens itself. tiberius
+//! only accepts a ready token, so for the checks we run ourselves the token is
+//! requested here, the same way SqlClient would for each authentication mode.
+
+use crate::models::{AuthMethod, SqlConnection};
+use crate::secrets;
+use anyhow::{anyhow, bail, Context, Result};
+use std::time::Duration;
+
+const SQL_RESOURCE: &str = "https://database.windows.net/";
+const SQL_SCOPE: &str = "https://database.windows.net/.default";
+const DEFAULT_AUTHORITY: &str = "https://login.microsoftonline.com";
+/// The public client SqlClient signs users in with for Active Directory Password.
+const SQLCLIENT_CLIENT_ID: &str = "2fd908ad-0664-4344-b9be-cd3e8b574c38";
+const IMDS_ENDPOINT: &str = "http://169.254.169.254/metadata/identity/oauth2/token";
+const TOKEN_TIMEOUT: Duration = Duration::from_secs(30);
+/// IMDS answers at once on Azure hosts; elsewhere the address goes nowhere.
+const IMDS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The token to sign in with, or `None` for SQL and Windows logins.
+pub async fn access_token(connection: &SqlConnection) -> Result<Option<String>> {
+    let username = connection.username.as_deref().filter(|u| !u.is_empty());
+    let password = connection.password.as_deref().filter(|p| !p.is_empty());
+    let tenant = connection.tenant_id.as_deref().filter(|t| !t.is_empty());
+
+    let token = match connection.authentication {
+        AuthMethod::SqlPassword | AuthMethod::Integrated => return Ok(None),
+        AuthMethod::AccessToken => password
+            .ok_or_else(|| anyhow!("No access token is set for {}", connection.name))?
+            .to_string(),
+        AuthMethod::ActiveDirectoryPassword => {
+            let username = username.ok_or_else(|| anyhow!("No user name is set"))?;
+            let password = password.ok_or_else(|| anyhow!("No password is set"))?;
+            request_token(
+                tenant.unwrap_or("organizations"),
+                &[
+                    ("grant_type", "password"),
+                    ("client_id", SQLCLIENT_CLIENT_ID),
+                    ("username", username),
+                    ("password", password),
+                    ("scope", SQL_SCOPE),
+                ],
+            )
+            .await?
+        }
+        AuthMethod::ActiveDirectoryServicePrincipal => {
+            let tenant = tenant
+                .ok_or_else(|| anyhow!("Set the tenant id to connect with a service principal"))?;
+            let client_id = username.ok_or_else(|| anyhow!("No client id is set"))?;
+            let secret = password.ok_or_else(|| anyhow!("No client secret is set"))?;
+            client_credentials(tenant, client_id, secret).await?
+        }
+        AuthMethod::ActiveDirectoryManagedIdentity => managed_identity(username).await?,
+        AuthMethod::ActiveDirectoryDefault => default_credential(tenant).await?,
+    };
+    Ok(Some(token))
+}
+
+fn http_client() -> Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .timeout(TOKEN_TIMEOUT)
+        .build()
+        .context("could not create the HTTP client")
+}
+
+async fn request_token(tenant: &str, form: &[(&str, &str)]) -> Result<String> {
+    let authority =
+        std::env::var("AZURE_AUTHORITY_HOST").unwrap_or_else(|_| DEFAULT_AUTHORITY.to_string());
+    let url = format!(
+        "{}/{}/oauth2/v2.0/token",
+        authority.trim_end_matches('/'),
+        tenant
+    );
+    let response = http_client()?
+        .post(&url)
+        .form(form)
+        .send()
+        .await
+        .with_context(|| format!("could not reach {}", authority))?;
+    let status = response.status();
+    let body: serde_json::Value = response.json().await.unwrap_or_default();
+    token_from_response(status, &body)
+}
+
+async fn client_credentials(tenant: &str, client_id: &str, secret: &str) -> Result<String> {
+    request_token(
+        tenant,
+        &[
+            ("grant_type", "client_credentials"),
+            ("client_id", client_id),
+            ("client_secret", secret),
+            ("scope", SQL_SCOPE),
+        ],
+    )
+    .await
+}
+
+/// App Service and Functions publish their own endpoint; VMs, AKS and
+/// container instances answer on IMDS.
+async fn managed_identity(client_id: Option<&str>) -> Result<String> {
+    let client = http_client()?;
+    let mut query = vec![("resource", SQL_RESOURCE)];
+    if let Some(client_id) = client_id {
+        query.push(("client_id", client_id));
+    }
+
+    let request = match (
+        std::env::var("IDENTITY_ENDPOINT"),
+        std::env::var("IDENTITY_HEADER"),
+    ) {
+        (Ok(endpoint), Ok(header)) => {
+            query.push(("api-version", "2019-08-01"));
+            client
+                .get(endpoint)
+                .query(&query)
+                .header("X-IDENTITY-HEADER", header)
+        }
+        _ => {
+            query.push(("api-version", "2018-02-01"));
+            client
+                .get(IMDS_ENDPOINT)
+                .query(&query)
+                .header("Metadata", "true")
+                .timeout(IMDS_TIMEOUT)
+        }
+    };
+    let response = request
+        .send()
+        .await
+        .context("no managed identity endpoint answered; is this an Azure host?")?;
+    let status = response.status();
+    let body: serde_json::Value = response.json().await.unwrap_or_default();
+    token_from_response(status, &body)
+}
+
+/// Tries the credentials `Active Directory Default` would: a service principal
+/// from the environment, the Azure CLI login, then a managed identity.
+async fn default_credential(tenant: Option<&str>) -> Result<String> {
+    let mut failures = Vec::new();
+
+    if let (Ok(tenant), Ok(client_id), Ok(secret)) = (
+        std::env::var("AZURE_TENANT_ID"),
+        std::env::var("AZURE_CLIENT_ID"),
+        std::env::var("AZURE_CLIENT_SECRET"),
+    ) {
+        match client_credentials(&tenant, &client_id, &secret).await {
+            Ok(token) => return Ok(token),
+            Err(e) => failures.push(format!("environment: {:#}", e)),
+        }
+    }
+
+    let mut az = format!(
+        "az account get-access-token --resource {} --query accessToken --output tsv",
+        SQL_RESOURCE
+    );
+    if let Some(tenant) = tenant {
+        az.push_str(&format!(" --tenant {}", tenant));
+    }
+    match secrets::run_command(&az).await {
+        Ok(token) => return Ok(token),
+        Err(e) => failures.push(format!("Azure CLI: {:#}", e)),
+    }
+
+    let client_id = std::env::var("AZURE_CLIENT_ID").ok();
+    match managed_identity(client_id.as_deref()).await {
+        Ok(token) => return Ok(token),
+        Err(e) => failures.push(format!("managed identity: {:#}", e)),
+    }
+
+    bail!("no Entra ID credential was found ({})", failures.join("; "))
+}
+
+fn token_from_response(status: reqwest::StatusCode, body: &serde_json::Value) -> Result<String> {
+    if let Some(token) = body["access_token"].as_str() {
+        if status.is_success() {
+            return Ok(token.to_string());
+        }
+    }
+    let reason = body["error_description"]
+        .as_str()
+        .or_else(|| body["error"].as_str())
+        .or_else(|| body["message"].as_str())
+        .unwrap_or("no details");
+    // Entra ID descriptions carry a trace id and timestamp on later lines
+    let reason = reason.lines().next().unwrap_or(reason);
+    bail!("token request failed with {}: {}", status, reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::StatusCode;
+
+    #[test]
+    fn test_token_from_response() {
+        let ok = serde_json::json!({"token_type": "Bearer", "access_token": "eyJ0"});
+        assert_eq!(token_from_response(StatusCode::OK, &ok).unwrap(), "eyJ0");
+
+        let denied = serde_json::json!({
+            "error": "invalid_client",
+            "error_description": "AADSTS7000215: Invalid client secret provided.\r\nTrace ID: 1",
+        });
+        let message = token_from_response(StatusCode::UNAUTHORIZED, &denied)
+            .unwrap_err()
+            .to_string();
+        assert!(message.contains("AADSTS7000215: Invalid client secret provided."));
+        assert!(!message.contains("Trace ID"));
+    }
+
+    #[tokio::test]
+    async fn test_sql_logins_need_no_token() {
+        let connection = SqlConnection {
+            authentication: AuthMethod::Integrated,
+            ..Default::default()
+        };
+        assert_eq!(access_token(&connection).await.unwrap(), None);
+
+        let connection = SqlConnection {
+            authentication: AuthMethod::AccessToken,
+            password: Some("eyJ0".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            access_token(&connection).await.unwrap().as_deref(),
+            Some("eyJ0")
+        );
+    }
+}
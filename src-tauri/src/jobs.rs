@@ -0,0 +1,49 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Cancellation flags for long-running jobs, keyed by an id chosen by the
+/// frontend when it starts the job.
+#[derive(Default)]
+pub struct JobRegistry {
+    jobs: Mutex<HashMap<String, CancellationToken>>,
+}
+
+impl JobRegistry {
+    pub fn register(&self, job_id: &str) -> CancellationToken {
+        let token = CancellationToken::default();
+        self.jobs
+            .lock()
+            .unwrap()
+            .insert(job_id.to_string(), token.clone());
+        token
+    }
+
+    /// Returns false when no job with this id is running.
+    pub fn cancel(&self, job_id: &str) -> bool {
+        match self.jobs.lock().unwrap().get(job_id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn finish(&self, job_id: &str) {
+        self.jobs.lock().unwrap().remove(job_id);
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
@@ -1,18 +1,30 @@
+mod bacpac;
 mod commands;
+mod connection_string;
 mod db;
+mod disk;
+mod entra;
+mod jobs;
 mod models;
+mod secrets;
+mod sqlserver;
 mod utils;
+mod vault;
 
-use commands::{connections, preferences, sqlpackage, system};
+use commands::{connections, databases, masking, packages, preferences, sqlpackage, system};
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
+        .manage(jobs::JobRegistry::default())
+        .manage(vault::Vault::default())
+        .manage(databases::DatabaseListCache::default())
         .setup(|app| {
             // Initialize database
             db::init_db(app.handle())?;
+            vault::spawn_idle_lock(app.handle().clone());
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
@@ -21,13 +33,47 @@ pub fn run() {
             connections::create_connection,
             connections::update_connection,
             connections::delete_connection,
+            connections::test_connection,
+            databases::list_databases,
+            databases::get_database_stats,
             preferences::get_preferences,
             preferences::update_preference,
             preferences::update_theme,
+            preferences::update_export_space_settings,
             sqlpackage::export_bacpac,
             sqlpackage::import_bacpac,
             sqlpackage::import_bacpac_with_details,
+            sqlpackage::preflight_import,
+            sqlpackage::preflight_import_with_details,
+            packages::convert_bacpac_to_dacpac,
+            packages::export_bacpac_table_data,
+            packages::convert_bacpac_to_sqlite,
+            packages::list_bacpac_tables,
+            packages::get_bacpac_table_rows,
+            packages::search_bacpac,
+            packages::diff_bacpac_table,
+            packages::subset_bacpac,
+            packages::mask_bacpac,
+            packages::create_bacpac_from_csv,
+            packages::create_bacpac_from_sqlite,
+            packages::generate_insert_script,
+            packages::generate_data_dictionary,
+            packages::list_model_objects,
+            packages::get_object_dependencies,
+            packages::get_security_report,
+            masking::list_masking_rule_sets,
+            masking::get_masking_rule_set,
+            masking::save_masking_rule_set,
+            masking::delete_masking_rule_set,
             system::check_sqlpackage_installed,
+            system::cancel_job,
+            commands::vault::get_vault_status,
+            commands::vault::set_master_password,
+            commands::vault::unlock_vault,
+            commands::vault::lock_vault,
+            commands::vault::change_master_password,
+            commands::vault::remove_master_password,
+            commands::vault::set_vault_idle_lock,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
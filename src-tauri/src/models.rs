@@ -1,6 +1,79 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// How a connection signs in. Entra ID modes other than `AccessToken` have the
+/// driver acquire a token for `https://database.windows.net/`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthMethod {
+    /// A SQL Server login.
+    #[default]
+    SqlPassword,
+    /// Windows integrated security as the user running the app.
+    Integrated,
+    /// An Entra ID user name and password.
+    ActiveDirectoryPassword,
+    /// An Entra ID application: `username` is the client id, `password` the secret.
+    ActiveDirectoryServicePrincipal,
+    /// The managed identity of the Azure host; `username` selects a user-assigned one.
+    ActiveDirectoryManagedIdentity,
+    /// The first Entra ID credential found: environment, Azure CLI, managed identity.
+    ActiveDirectoryDefault,
+    /// A token acquired elsewhere, kept in `password`.
+    AccessToken,
+}
+
+impl AuthMethod {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AuthMethod::SqlPassword => "sql_password",
+            AuthMethod::Integrated => "integrated",
+            AuthMethod::ActiveDirectoryPassword => "active_directory_password",
+            AuthMethod::ActiveDirectoryServicePrincipal => "active_directory_service_principal",
+            AuthMethod::ActiveDirectoryManagedIdentity => "active_directory_managed_identity",
+            AuthMethod::ActiveDirectoryDefault => "active_directory_default",
+            AuthMethod::AccessToken => "access_token",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            AuthMethod::SqlPassword,
+            AuthMethod::Integrated,
+            AuthMethod::ActiveDirectoryPassword,
+            AuthMethod::ActiveDirectoryServicePrincipal,
+            AuthMethod::ActiveDirectoryManagedIdentity,
+            AuthMethod::ActiveDirectoryDefault,
+            AuthMethod::AccessToken,
+        ]
+        .into_iter()
+        .find(|method| method.as_str() == name)
+    }
+
+    /// Whether `username` means anything for this mode.
+    pub fn uses_username(self) -> bool {
+        matches!(
+            self,
+            AuthMethod::SqlPassword
+                | AuthMethod::ActiveDirectoryPassword
+                | AuthMethod::ActiveDirectoryServicePrincipal
+                | AuthMethod::ActiveDirectoryManagedIdentity
+        )
+    }
+
+    /// Whether `password` holds a secret for this mode: a password, client
+    /// secret or access token.
+    pub fn uses_password(self) -> bool {
+        matches!(
+            self,
+            AuthMethod::SqlPassword
+                | AuthMethod::ActiveDirectoryPassword
+                | AuthMethod::ActiveDirectoryServicePrincipal
+                | AuthMethod::AccessToken
+        )
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
 pub struct SqlConnection {
     pub id: Option<i64>,
     pub name: String,
@@ -9,10 +82,73 @@ pub struct SqlConnection {
     pub database_name: Option<String>,
     pub username: Option<String>,
     pub password: Option<String>,
+    /// The password is an `env:`, `file:`, `cmd:` or `vault:` reference rather
+    /// than the secret itself; resolved only for saved connections.
+    #[serde(default)]
+    pub password_is_reference: bool,
+    pub connection_string: Option<String>,
+    #[serde(default)]
+    pub authentication: AuthMethod,
+    /// Entra ID tenant, for service principals and Entra passwords.
+    #[serde(default)]
+    pub tenant_id: Option<String>,
+    pub trust_server_cert: bool,
+    pub encrypt: bool,
+    /// Seconds to wait while connecting; `None` uses the driver default.
+    #[serde(default)]
+    pub connect_timeout: Option<u32>,
+    /// Seconds a single command may run; `None` uses the default.
+    #[serde(default)]
+    pub command_timeout: Option<u32>,
+    /// Connect with `ApplicationIntent=ReadOnly`, for readable secondaries.
+    #[serde(default)]
+    pub read_only_intent: bool,
+    #[serde(default)]
+    pub multi_subnet_failover: bool,
+    /// TDS 8 encryption (`Encrypt=Strict`); requires `encrypt`.
+    #[serde(default)]
+    pub strict_encryption: bool,
+    #[serde(default)]
+    pub host_name_in_certificate: Option<String>,
+    /// Path to the certificate the server is expected to present.
+    #[serde(default)]
+    pub server_certificate: Option<String>,
+    #[serde(default)]
+    pub application_name: Option<String>,
+    /// Connection string keywords without a field of their own, as a
+    /// `key=value;...` fragment appended when the connection string is built.
+    #[serde(default)]
+    pub extra_options: Option<String>,
+    pub created_at: Option<String>,
+    pub updated_at: Option<String>,
+}
+
+/// A stored connection as the frontend sees it; secrets never leave Rust.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SavedConnection {
+    pub id: i64,
+    pub name: String,
+    pub server: String,
+    pub port: Option<String>,
+    pub database_name: Option<String>,
+    pub username: Option<String>,
+    pub has_password: bool,
+    pub password_is_reference: bool,
+    /// With any password replaced by a mask. `None` while locked.
     pub connection_string: Option<String>,
-    pub use_windows_auth: bool,
+    pub authentication: AuthMethod,
+    pub tenant_id: Option<String>,
     pub trust_server_cert: bool,
     pub encrypt: bool,
+    pub connect_timeout: Option<u32>,
+    pub command_timeout: Option<u32>,
+    pub read_only_intent: bool,
+    pub multi_subnet_failover: bool,
+    pub strict_encryption: bool,
+    pub host_name_in_certificate: Option<String>,
+    pub server_certificate: Option<String>,
+    pub application_name: Option<String>,
+    pub extra_options: Option<String>,
     pub created_at: Option<String>,
     pub updated_at: Option<String>,
 }
@@ -22,6 +158,9 @@ pub struct ExportRequest {
     pub connection_id: i64,
     pub output_path: String,
     pub database_name: String,
+    /// Start even if the free-space estimate says the export won't fit.
+    #[serde(default)]
+    pub skip_space_check: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -29,6 +168,11 @@ pub struct ImportRequest {
     pub connection_id: i64,
     pub bacpac_path: String,
     pub target_database: String,
+    /// Codes of the `preflight_import` warnings the user accepted. The import
+    /// runs the preflight first and refuses to start on errors or on warnings
+    /// not listed here; none are acknowledged when unset.
+    #[serde(default)]
+    pub acknowledged_warnings: Option<Vec<String>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -39,9 +183,15 @@ pub struct ImportWithDetailsRequest {
     pub port: Option<String>,
     pub username: Option<String>,
     pub password: Option<String>,
-    pub use_windows_auth: bool,
+    #[serde(default)]
+    pub authentication: AuthMethod,
+    #[serde(default)]
+    pub tenant_id: Option<String>,
     pub trust_server_cert: bool,
     pub encrypt: bool,
+    /// As for `ImportRequest`, checked against `preflight_import_with_details`.
+    #[serde(default)]
+    pub acknowledged_warnings: Option<Vec<String>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -55,12 +205,597 @@ pub struct AppPreference {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Preferences {
     pub theme: String, // "light" | "dark" | "system"
+    /// Extra free space, as a percentage of the estimate, wanted before an export starts.
+    pub export_space_margin_percent: u32,
+    /// An export is stopped when free space on its volumes drops below this.
+    pub export_min_free_mb: u64,
+    /// Minutes of inactivity before saved connections lock again; 0 never locks.
+    pub vault_idle_lock_minutes: u32,
 }
 
 impl Preferences {
     pub fn default() -> Self {
         Preferences {
             theme: "system".to_string(),
+            export_space_margin_percent: 20,
+            export_min_free_mb: 512,
+            vault_idle_lock_minutes: 15,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BacpacToDacpacRequest {
+    pub bacpac_path: String,
+    pub output_path: String,
+    pub dac_name: Option<String>,
+    pub dac_version: Option<String>,
+    pub overwrite: bool,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DataExportFormat {
+    Csv,
+    Jsonl,
+    Parquet,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TableDataExportRequest {
+    pub bacpac_path: String,
+    /// Tables as `schema.name`; all tables when empty.
+    pub tables: Vec<String>,
+    pub format: DataExportFormat,
+    pub output_dir: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TableDataExportResult {
+    pub table: String,
+    pub output_path: String,
+    pub rows: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BacpacToSqliteRequest {
+    pub bacpac_path: String,
+    pub output_path: String,
+    /// Tables as `schema.name`; all tables when empty.
+    pub tables: Vec<String>,
+    pub overwrite: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BacpacColumnInfo {
+    pub name: String,
+    pub data_type: String,
+    pub nullable: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BacpacTableInfo {
+    pub schema: String,
+    pub name: String,
+    pub columns: Vec<BacpacColumnInfo>,
+    /// Uncompressed size of the table's BCP data in bytes.
+    pub data_size: u64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterOperator {
+    Equals,
+    Contains,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RowFilter {
+    pub column: String,
+    pub operator: FilterOperator,
+    pub value: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TableRowsRequest {
+    pub bacpac_path: String,
+    pub table: String,
+    pub offset: u64,
+    pub limit: u64,
+    /// Columns to return; all columns when empty.
+    pub columns: Vec<String>,
+    pub filters: Vec<RowFilter>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TableRowsPage {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<serde_json::Value>>,
+    pub offset: u64,
+    pub has_more: bool,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchMode {
+    Exact,
+    Substring,
+    Regex,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BacpacSearchRequest {
+    /// Id used to cancel the search with `cancel_job`.
+    pub job_id: String,
+    pub bacpac_path: String,
+    pub value: String,
+    pub mode: MatchMode,
+    pub case_sensitive: bool,
+    /// Wildcard patterns (`*`, `?`) restricting which schemas and tables are searched.
+    pub schema_pattern: Option<String>,
+    pub table_pattern: Option<String>,
+    /// Stop after this many matches.
+    pub max_matches: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchMatch {
+    pub table: String,
+    pub column: String,
+    /// 1-based position of the row within the table's data.
+    pub row: u64,
+    pub value: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BacpacSearchResult {
+    pub matches: Vec<SearchMatch>,
+    pub tables_searched: usize,
+    pub rows_scanned: u64,
+    pub truncated: bool,
+    pub cancelled: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TableDiffRequest {
+    /// The older export.
+    pub left_path: String,
+    /// The newer export.
+    pub right_path: String,
+    pub table: String,
+    pub offset: u64,
+    pub limit: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RowChangeKind {
+    Inserted,
+    Deleted,
+    Changed,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ColumnChange {
+    pub column: String,
+    pub old_value: serde_json::Value,
+    pub new_value: serde_json::Value,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RowDiff {
+    pub kind: RowChangeKind,
+    /// Primary key values, in `key_columns` order.
+    pub key: Vec<serde_json::Value>,
+    /// The whole row for inserted and deleted rows, in `columns` order.
+    pub values: Vec<serde_json::Value>,
+    /// Changed columns of an updated row.
+    pub changes: Vec<ColumnChange>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TableDiffPage {
+    pub key_columns: Vec<String>,
+    /// Columns present in both exports; only these are compared.
+    pub columns: Vec<String>,
+    pub added_columns: Vec<String>,
+    pub removed_columns: Vec<String>,
+    pub inserted: u64,
+    pub deleted: u64,
+    pub changed: u64,
+    pub rows: Vec<RowDiff>,
+    pub offset: u64,
+    pub has_more: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SubsetRoot {
+    pub table: String,
+    /// Rows must match every filter to be kept.
+    pub filters: Vec<RowFilter>,
+    /// Keep roughly this percentage of the matching rows.
+    pub sample_percent: Option<f64>,
+    pub max_rows: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BacpacSubsetRequest {
+    pub bacpac_path: String,
+    pub output_path: String,
+    pub roots: Vec<SubsetRoot>,
+    pub overwrite: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SubsetTableResult {
+    pub table: String,
+    pub rows: u64,
+}
+
+/// How a masked column's values are replaced.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MaskStrategy {
+    Null,
+    /// Replace every value with `value`, which must fit the column.
+    Fixed {
+        value: String,
+    },
+    Hash,
+    FakeName,
+    FakeEmail,
+    FakePhone,
+    Shuffle,
+    /// Replace every digit with a random one, keeping letters and punctuation.
+    KeepFormat,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaskRule {
+    pub table: String,
+    pub column: String,
+    pub strategy: MaskStrategy,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MaskingRuleSet {
+    pub id: Option<i64>,
+    pub name: String,
+    pub rules: Vec<MaskRule>,
+    pub created_at: Option<String>,
+    pub updated_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MaskBacpacRequest {
+    pub bacpac_path: String,
+    pub output_path: String,
+    #[serde(default)]
+    pub rules: Vec<MaskRule>,
+    /// Name of a saved rule set to apply instead of `rules`.
+    #[serde(default)]
+    pub rule_set: Option<String>,
+    /// Secret mixed into hashed and generated values. The same salt maps a
+    /// value to the same replacement in every table; a random one is used,
+    /// and reported back, when absent.
+    pub salt: Option<String>,
+    pub overwrite: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CsvToBacpacRequest {
+    pub csv_dir: String,
+    /// Column-type manifest; defaults to `manifest.json` in `csv_dir`.
+    pub manifest_path: Option<String>,
+    pub database_name: String,
+    pub output_path: String,
+    pub overwrite: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SqliteToBacpacRequest {
+    pub sqlite_path: String,
+    /// SQLite tables to include; all tables when empty.
+    pub tables: Vec<String>,
+    /// SQL Server schema for the tables; `dbo` when absent.
+    pub schema: Option<String>,
+    pub database_name: String,
+    pub output_path: String,
+    pub overwrite: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ScriptTable {
+    pub table: String,
+    /// Only script the first rows of the table.
+    pub max_rows: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InsertScriptRequest {
+    pub bacpac_path: String,
+    pub output_path: String,
+    /// Tables to script; all tables when empty.
+    pub tables: Vec<ScriptTable>,
+    /// Include CREATE SCHEMA/TABLE and foreign key statements.
+    pub include_schema: bool,
+    /// Rows per INSERT statement, at most 1000.
+    pub batch_size: Option<usize>,
+    pub overwrite: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DataDictionaryRequest {
+    pub bacpac_path: String,
+    pub output_dir: String,
+    /// Schemas to document; all schemas when empty.
+    pub schemas: Vec<String>,
+    pub overwrite: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DataDictionaryResult {
+    pub markdown_path: String,
+    pub html_path: String,
+    /// Mermaid source of the ER diagram.
+    pub er_diagram: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelObject {
+    /// Dotted name without brackets, e.g. `dbo.Customers.Email`.
+    pub name: String,
+    /// Model element type without the `Sql` prefix, e.g. `View` or `ComputedColumn`.
+    pub object_type: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DependencySource {
+    /// A reference recorded in the model.
+    Reference,
+    /// A name found in a view, procedure, function or expression body.
+    SqlBody,
+    /// A column, parameter or other part of its parent object.
+    Containment,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DependencyRequest {
+    pub bacpac_path: String,
+    pub object_name: String,
+    /// Levels to follow in each direction; unlimited when absent.
+    pub max_depth: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DependencyEntry {
+    pub name: String,
+    pub object_type: String,
+    pub depth: u32,
+    /// The object this one was reached from.
+    pub via: String,
+    pub source: DependencySource,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DependencyReport {
+    pub object: ModelObject,
+    pub depends_on: Vec<DependencyEntry>,
+    pub depended_by: Vec<DependencyEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SecurityPrincipal {
+    pub name: String,
+    /// `User`, `Role` or `ApplicationRole`.
+    pub principal_type: String,
+    /// How a user authenticates: `Login`, `Password`, `External`,
+    /// `WithoutLogin` or `Unknown`.
+    pub authentication: Option<String>,
+    pub login: Option<String>,
+    pub default_schema: Option<String>,
+    pub owner: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RoleMembership {
+    pub role: String,
+    pub member: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SecurableOwner {
+    pub name: String,
+    /// `Schema`, `Certificate`, `AsymmetricKey` or `SymmetricKey`.
+    pub object_type: String,
+    pub owner: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PermissionStatement {
+    /// `GRANT`, `DENY` or `REVOKE`.
+    pub action: String,
+    pub permission: String,
+    /// `Database`, `Schema`, `Object` or another securable class.
+    pub scope: String,
+    pub securable: Option<String>,
+    pub grantee: String,
+    pub with_grant_option: bool,
+    pub statement: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FindingSeverity {
+    High,
+    Medium,
+    Low,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SecurityFinding {
+    pub severity: FindingSeverity,
+    pub principal: String,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SecurityReport {
+    pub principals: Vec<SecurityPrincipal>,
+    pub role_memberships: Vec<RoleMembership>,
+    pub schemas: Vec<SecurableOwner>,
+    pub keys_and_certificates: Vec<SecurableOwner>,
+    pub permissions: Vec<PermissionStatement>,
+    pub findings: Vec<SecurityFinding>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PreflightWarning {
+    /// Stable identifier the import request lists in `acknowledged_warnings`.
+    pub code: String,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportPreflight {
+    pub source_schema_provider: Option<String>,
+    pub source_server_version: Option<String>,
+    pub target_server_version: String,
+    pub target_edition: String,
+    pub database_exists: bool,
+    /// Uncompressed size of the table data in the BACPAC.
+    pub data_bytes: u64,
+    /// Free space on the server's data volume, when it can be read.
+    pub free_bytes: Option<u64>,
+    pub package_collation: Option<String>,
+    pub server_collation: String,
+    /// Problems that will make the import fail.
+    pub errors: Vec<String>,
+    pub warnings: Vec<PreflightWarning>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VaultStatus {
+    /// A master password is set and secrets are stored encrypted.
+    pub enabled: bool,
+    pub unlocked: bool,
+    /// Minutes of inactivity before locking again; 0 never locks.
+    pub idle_lock_minutes: u32,
+}
+
+/// Why a test connection failed, in terms of what the user should check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionFailure {
+    /// The server name does not resolve.
+    Dns,
+    /// The host resolved but nothing accepted a TCP connection on the port.
+    TcpRefused,
+    /// The TLS handshake failed, usually on an untrusted or mismatched certificate.
+    Tls,
+    /// The server rejected the credentials or the token could not be acquired.
+    LoginFailed,
+    /// The login works but may not open the requested database.
+    DatabaseNotAccessible,
+    Timeout,
+    Other,
+}
+
+/// Where the server runs, from `SERVERPROPERTY('EngineEdition')`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServerEngine {
+    OnPremises,
+    AzureSqlDatabase,
+    AzureManagedInstance,
+    AzureSynapse,
+    AzureSqlEdge,
+}
+
+impl ServerEngine {
+    pub fn from_engine_edition(engine_edition: i32) -> Self {
+        match engine_edition {
+            5 => ServerEngine::AzureSqlDatabase,
+            6 | 11 => ServerEngine::AzureSynapse,
+            8 => ServerEngine::AzureManagedInstance,
+            9 => ServerEngine::AzureSqlEdge,
+            // 1-4: Personal, Standard, Enterprise and Express
+            _ => ServerEngine::OnPremises,
         }
     }
 }
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ConnectionTestResult {
+    Connected {
+        product_version: String,
+        edition: String,
+        engine: ServerEngine,
+        login_name: String,
+        database_name: String,
+        elapsed_ms: u64,
+    },
+    Failed {
+        reason: ConnectionFailure,
+        message: String,
+    },
+}
+
+/// A database on a server, from `sys.databases`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatabaseInfo {
+    pub name: String,
+    /// `state_desc`: ONLINE, RESTORING, OFFLINE and so on.
+    pub state: String,
+    pub recovery_model: String,
+    pub compatibility_level: i32,
+    /// Absent while the database is offline.
+    pub collation: Option<String>,
+    /// Data and log files together; absent when the login may not read
+    /// `sys.master_files`, as on Azure SQL Database.
+    pub size_bytes: Option<u64>,
+    /// master, tempdb, model, msdb and a replication distribution database.
+    pub is_system: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableStats {
+    pub schema: String,
+    pub name: String,
+    pub row_count: u64,
+    /// Pages reserved for the table and its indexes, LOB data included. Zero
+    /// for memory-optimized tables, which live outside the data files.
+    pub reserved_bytes: u64,
+    pub is_memory_optimized: bool,
+}
+
+/// Features that often make an export fail or a package hard to import.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportRiskKind {
+    MemoryOptimizedTables,
+    Filestream,
+    FullTextCatalogs,
+    CrossDatabaseReferences,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportRisk {
+    pub kind: ExportRiskKind,
+    pub message: String,
+    /// The tables, columns, catalogs or modules involved.
+    pub objects: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DatabaseStats {
+    pub database_name: String,
+    /// Allocated size of the data files.
+    pub data_bytes: u64,
+    pub log_bytes: u64,
+    /// Largest first, by reserved space.
+    pub tables: Vec<TableStats>,
+    pub risks: Vec<ExportRisk>,
+}
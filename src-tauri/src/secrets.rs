@@ -0,0 +1,342 @@
+//! Passwords given as references to a secret kept outside the app.
+//!
+//! A saved connection whose password is marked as a reference holds one of:
+//!
+//! - `env:NAME`: the value of an environment variable
+//! - `file:/path/to/secret`: the contents of a file, trailing newline removed
+//! - `cmd:command line`: the first line a shell command prints, as with `pass show`.
+//!   Only when the app was started with `SHAPAC_ALLOW_COMMAND_SECRETS=1`.
+//! - `vault:secret/data/sql/prod#field`: a HashiCorp Vault KV read, using the API
+//!   path (`<mount>/data/<path>` for KV v2). The field defaults to `password`, and
+//!   `VAULT_ADDR`, `VAULT_TOKEN` (or `~/.vault-token`) and `VAULT_NAMESPACE` are
+//!   honoured as by the Vault CLI.
+//!
+//! Passwords not marked as references are literal, whatever they start with.
+//! References are resolved when a job runs on a connection loaded from the
+//! database, never for connection details sent along with a request, and never
+//! inside a saved connection string, whose password is always literal.
+
+use crate::models::SqlConnection;
+use anyhow::{anyhow, bail, Context, Result};
+use std::time::Duration;
+
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+const VAULT_TIMEOUT: Duration = Duration::from_secs(15);
+const DEFAULT_VAULT_ADDR: &str = "http://127.0.0.1:8200";
+const DEFAULT_VAULT_FIELD: &str = "password";
+
+/// Set to `1` to let `cmd:` references run shell commands.
+const ALLOW_COMMANDS_VAR: &str = "SHAPAC_ALLOW_COMMAND_SECRETS";
+
+#[derive(Debug, PartialEq)]
+pub enum SecretRef<'a> {
+    Env(&'a str),
+    File(&'a str),
+    Command(&'a str),
+    Vault { path: &'a str, field: &'a str },
+}
+
+impl<'a> SecretRef<'a> {
+    pub fn parse(value: &'a str) -> Result<Self> {
+        if let Some(name) = value.strip_prefix("env:") {
+            Ok(SecretRef::Env(name.trim()))
+        } else if let Some(path) = value.strip_prefix("file:") {
+            Ok(SecretRef::File(path.trim()))
+        } else if let Some(command) = value.strip_prefix("cmd:") {
+            Ok(SecretRef::Command(command.trim()))
+        } else if let Some(reference) = value.strip_prefix("vault:") {
+            let (path, field) = reference
+                .rsplit_once('#')
+                .unwrap_or((reference, DEFAULT_VAULT_FIELD));
+            Ok(SecretRef::Vault {
+                path: path.trim().trim_matches('/'),
+                field: field.trim(),
+            })
+        } else {
+            // The value may be a mistyped secret; keep it out of the message
+            bail!("a password reference must start with env:, file:, cmd: or vault:")
+        }
+    }
+
+    pub async fn resolve(&self) -> Result<String> {
+        match self {
+            SecretRef::Env(name) => {
+                std::env::var(name).map_err(|_| anyhow!("environment variable {} is not set", name))
+            }
+            SecretRef::File(path) => {
+                let contents = tokio::fs::read_to_string(path)
+                    .await
+                    .with_context(|| format!("could not read secret file {}", path))?;
+                Ok(contents.trim_end_matches(['\r', '\n']).to_string())
+            }
+            SecretRef::Command(command) => {
+                if !commands_allowed() {
+                    bail!(
+                        "cmd: references are off; start the app with {}=1 to allow them",
+                        ALLOW_COMMANDS_VAR
+                    );
+                }
+                run_command(command).await
+            }
+            SecretRef::Vault { path, field } => read_vault(path, field).await,
+        }
+    }
+}
+
+fn commands_allowed() -> bool {
+    std::env::var(ALLOW_COMMANDS_VAR).is_ok_and(|value| value.trim() == "1")
+}
+
+/// Replaces the password reference of a saved connection, as loaded from the
+/// database, with the secret it points to. Other passwords are left alone.
+pub async fn resolve_connection(mut connection: SqlConnection) -> Result<SqlConnection> {
+    if !connection.password_is_reference {
+        return Ok(connection);
+    }
+    if connection.id.is_none() {
+        bail!("Password references can only be used by saved connections");
+    }
+    if let Some(password) = &connection.password {
+        let resolved = async { SecretRef::parse(password)?.resolve().await }
+            .await
+            .with_context(|| {
+                format!(
+                    "Could not resolve the password of connection {}",
+                    connection.name
+                )
+            })?;
+        connection.password = Some(resolved);
+    }
+    connection.password_is_reference = false;
+    Ok(connection)
+}
+
+/// Runs a shell command and returns the first line it prints.
+pub(crate) async fn run_command(command: &str) -> Result<String> {
+    if command.is_empty() {
+        bail!("the cmd: reference has no command");
+    }
+    let mut process = if cfg!(target_os = "windows") {
+        let mut process = tokio::process::Command::new("cmd");
+        process.args(["/C", command]);
+        process
+    } else {
+        let mut process = tokio::process::Command::new("sh");
+        process.args(["-c", command]);
+        process
+    };
+    process.kill_on_drop(true);
+
+    let output = tokio::time::timeout(COMMAND_TIMEOUT, process.output())
+        .await
+        .map_err(|_| {
+            anyhow!(
+                "`{}` did not finish within {} seconds",
+                command,
+                COMMAND_TIMEOUT.as_secs()
+            )
+        })?
+        .with_context(|| format!("could not run `{}`", command))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!(
+            "`{}` failed with {}: {}",
+            command,
+            output.status,
+            stderr.trim()
+        );
+    }
+    let stdout = String::from_utf8(output.stdout)
+        .with_context(|| format!("`{}` printed something that is not UTF-8", command))?;
+    stdout
+        .lines()
+        .next()
+        .filter(|line| !line.is_empty())
+        .map(|line| line.to_string())
+        .ok_or_else(|| anyhow!("`{}` printed nothing", command))
+}
+
+fn vault_token() -> Result<String> {
+    if let Ok(token) = std::env::var("VAULT_TOKEN") {
+        return Ok(token);
+    }
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"));
+    home.map(|home| std::path::PathBuf::from(home).join(".vault-token"))
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty())
+        .ok_or_else(|| anyhow!("no Vault token: set VAULT_TOKEN or run `vault login`"))
+}
+
+async fn read_vault(path: &str, field: &str) -> Result<String> {
+    let address = std::env::var("VAULT_ADDR").unwrap_or_else(|_| DEFAULT_VAULT_ADDR.to_string());
+    let url = format!("{}/v1/{}", address.trim_end_matches('/'), path);
+
+    let client = reqwest::Client::builder()
+        .timeout(VAULT_TIMEOUT)
+        .build()
+        .context("could not create the Vault HTTP client")?;
+    let mut request = client.get(&url).header("X-Vault-Token", vault_token()?);
+    if let Ok(namespace) = std::env::var("VAULT_NAMESPACE") {
+        request = request.header("X-Vault-Namespace", namespace);
+    }
+    let response = request
+        .send()
+        .await
+        .with_context(|| format!("could not reach Vault at {}", address))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let body: serde_json::Value = response.json().await.unwrap_or_default();
+        let errors = body["errors"]
+            .as_array()
+            .map(|errors| {
+                errors
+                    .iter()
+                    .filter_map(|e| e.as_str())
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            })
+            .unwrap_or_default();
+        bail!("Vault returned {} for {}: {}", status, path, errors);
+    }
+
+    let body: serde_json::Value = response
+        .json()
+        .await
+        .context("Vault returned an unreadable response")?;
+    vault_field(&body, field).ok_or_else(|| anyhow!("{} has no field {}", path, field))
+}
+
+/// A field of a KV read, from either a v2 (`data.data`) or a v1 (`data`) engine.
+fn vault_field(body: &serde_json::Value, field: &str) -> Option<String> {
+    let data = &body["data"];
+    let secret = if data["data"].is_object() {
+        &data["data"]
+    } else {
+        data
+    };
+    secret[field].as_str().map(|value| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_references() {
+        assert!(SecretRef::parse("hunter2").is_err());
+        assert_eq!(
+            SecretRef::parse("env:PROD_SQL_PW").unwrap(),
+            SecretRef::Env("PROD_SQL_PW")
+        );
+        assert_eq!(
+            SecretRef::parse("cmd:pass show sql/prod").unwrap(),
+            SecretRef::Command("pass show sql/prod")
+        );
+        assert_eq!(
+            SecretRef::parse("vault:secret/data/sql/prod#sa").unwrap(),
+            SecretRef::Vault {
+                path: "secret/data/sql/prod",
+                field: "sa"
+            }
+        );
+        assert_eq!(
+            SecretRef::parse("vault:/kv/sql/").unwrap(),
+            SecretRef::Vault {
+                path: "kv/sql",
+                field: "password"
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resolve_local_references() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("sql_password");
+        std::fs::write(&file, "from-file\n").unwrap();
+        let reference = format!("file:{}", file.display());
+        assert_eq!(
+            SecretRef::parse(&reference)
+                .unwrap()
+                .resolve()
+                .await
+                .unwrap(),
+            "from-file"
+        );
+
+        let missing = SecretRef::Env("SHAPAC_TEST_UNSET_VARIABLE")
+            .resolve()
+            .await
+            .unwrap_err();
+        assert!(missing.to_string().contains("SHAPAC_TEST_UNSET_VARIABLE"));
+
+        if cfg!(unix) {
+            let command = SecretRef::Command("printf 'from-cmd\\nuser: sa\\n'");
+            if commands_allowed() {
+                assert_eq!(command.resolve().await.unwrap(), "from-cmd");
+            } else {
+                let refused = command.resolve().await.unwrap_err().to_string();
+                assert!(refused.contains(ALLOW_COMMANDS_VAR), "{}", refused);
+            }
+            assert_eq!(
+                run_command("printf 'from-cmd\\nuser: sa\\n'")
+                    .await
+                    .unwrap(),
+                "from-cmd"
+            );
+            assert!(run_command("exit 3").await.is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_only_saved_references_resolve() {
+        let literal = SqlConnection {
+            id: Some(1),
+            password: Some("env:HOME".to_string()),
+            ..Default::default()
+        };
+        let resolved = resolve_connection(literal).await.unwrap();
+        assert_eq!(resolved.password.as_deref(), Some("env:HOME"));
+
+        let ad_hoc = SqlConnection {
+            password: Some("file:/etc/passwd".to_string()),
+            password_is_reference: true,
+            ..Default::default()
+        };
+        assert!(resolve_connection(ad_hoc).await.is_err());
+    }
+
+    #[test]
+    fn test_vault_field() {
+        let v2 = serde_json::json!({"data": {"data": {"password": "kv2"}, "metadata": {}}});
+        let v1 = serde_json::json!({"data": {"password": "kv1"}});
+        assert_eq!(vault_field(&v2, "password").as_deref(), Some("kv2"));
+        assert_eq!(vault_field(&v1, "password").as_deref(), Some("kv1"));
+        assert_eq!(vault_field(&v1, "other"), None);
+    }
+
+    /// Run against a dev server: `vault server -dev -dev-root-token-id=root`, then
+    /// `VAULT_ADDR=http://127.0.0.1:8200 VAULT_TOKEN=root cargo test -- --ignored`.
+    #[tokio::test]
+    #[ignore = "needs a Vault dev server"]
+    async fn test_resolve_from_vault_dev_server() {
+        let address = std::env::var("VAULT_ADDR").unwrap();
+        let token = std::env::var("VAULT_TOKEN").unwrap();
+        reqwest::Client::new()
+            .post(format!("{}/v1/secret/data/shapac-test", address))
+            .header("X-Vault-Token", token)
+            .json(&serde_json::json!({"data": {"password": "from-vault"}}))
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+
+        let reference = SecretRef::parse("vault:secret/data/shapac-test").unwrap();
+        assert_eq!(reference.resolve().await.unwrap(), "from-vault");
+        let missing = SecretRef::parse("vault:secret/data/shapac-test#nope").unwrap();
+        assert!(missing.resolve().await.is_err());
+    }
+}
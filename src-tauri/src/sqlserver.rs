@@ -0,0 +1,1172 @@
+//! Direct connections to SQL Server for the checks sqlpackage doesn't do for us.
+
+use crate::connection_string::ConnectionString;
+use crate::models::{
+    ConnectionFailure, ConnectionTestResult, DatabaseInfo, DatabaseStats, ExportRisk,
+    ExportRiskKind, ServerEngine, SqlConnection, TableStats,
+};
+use crate::{entra, utils};
+use anyhow::{anyhow, bail, Context, Result};
+use std::fmt;
+use std::time::{Duration, Instant};
+use tiberius::{AuthMethod, Client, Config, EncryptionLevel, SqlBrowser};
+use tokio::net::TcpStream;
+use tokio_util::compat::{Compat, TokioAsyncWriteCompatExt};
+
+pub type SqlClient = Client<Compat<TcpStream>>;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A failed attempt to open a session, with the stage it failed at.
+#[derive(Debug)]
+pub struct ConnectError {
+    pub failure: ConnectionFailure,
+    error: anyhow::Error,
+}
+
+impl ConnectError {
+    fn new(failure: ConnectionFailure, error: impl Into<anyhow::Error>) -> Self {
+        ConnectError {
+            failure,
+            error: error.into(),
+        }
+    }
+}
+
+impl fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#}", self.error)
+    }
+}
+
+impl std::error::Error for ConnectError {}
+
+pub async fn connect(connection: &SqlConnection) -> Result<SqlClient> {
+    Ok(open(connection).await?)
+}
+
+async fn open(connection: &SqlConnection) -> Result<SqlClient, ConnectError> {
+    let mut config = tiberius_config(connection)
+        .context("Invalid connection settings")
+        .map_err(|e| ConnectError::new(ConnectionFailure::Other, e))?;
+    if let Some(token) = entra::access_token(connection)
+        .await
+        .context("Could not get an Entra ID access token")
+        .map_err(|e| ConnectError::new(ConnectionFailure::LoginFailed, e))?
+    {
+        config.authentication(AuthMethod::aad_token(token));
+    }
+    let timeout = connection
+        .connect_timeout
+        .filter(|seconds| *seconds > 0)
+        .map_or(CONNECT_TIMEOUT, |seconds| {
+            Duration::from_secs(seconds.into())
+        });
+    // The browser service on UDP 1434 only knows the port of a named instance
+    // when no port was given
+    let server = utils::split_server_name(&connection.server);
+    let use_browser = server.instance.is_some()
+        && connection
+            .port
+            .as_deref()
+            .is_none_or(|p| p.trim().is_empty())
+        && !connection.server.contains(',');
+    tokio::time::timeout(timeout, open_with_config(config, use_browser))
+        .await
+        .map_err(|_| {
+            ConnectError::new(
+                ConnectionFailure::Timeout,
+                anyhow!(
+                    "Timed out after {} seconds connecting to {}",
+                    timeout.as_secs(),
+                    connection.server
+                ),
+            )
+        })?
+}
+
+/// The settings sqlpackage gets, given to tiberius field by field so quotes and
+/// non-ASCII text reach the server unchanged. A saved connection string and extra
+/// options are read with our own parser first. tiberius has no TDS 8
+/// (`Encrypt=Strict` falls back to encrypting after login) and takes the server
+/// certificate as a trusted CA file.
+fn tiberius_config(connection: &SqlConnection) -> Result<Config> {
+    let mut cs = ConnectionString::parse(&utils::build_connection_string(connection))
+        .context("Invalid connection string")?;
+    // tiberius' own setting for servers without TLS at all
+    let plaintext = cs
+        .get(utils::ENCRYPT_KEYWORDS)
+        .is_some_and(|value| value.trim() == "DANGER_PLAINTEXT");
+    if plaintext {
+        cs.remove(utils::ENCRYPT_KEYWORDS);
+    }
+    let fields = utils::parse_connection_string(&cs.to_string())?;
+
+    let mut config = Config::new();
+    let server = utils::split_server_name(&fields.server);
+    config.host(server.host.replace("(local)", "localhost"));
+    if let Some(port) = fields.port.as_deref() {
+        let port = port
+            .trim()
+            .parse::<u16>()
+            .map_err(|_| anyhow!("Invalid port {}", port))?;
+        config.port(port);
+    }
+    if let Some(instance) = server.instance {
+        config.instance_name(instance);
+    }
+    // A database picked for this session wins over the one in a saved string
+    let database = connection
+        .database_name
+        .as_deref()
+        .filter(|db| !db.is_empty())
+        .or(fields.database_name.as_deref());
+    if let Some(database) = database {
+        config.database(database);
+    }
+    config.authentication(match fields.authentication {
+        #[cfg(windows)]
+        crate::models::AuthMethod::Integrated => AuthMethod::Integrated,
+        #[cfg(not(windows))]
+        crate::models::AuthMethod::Integrated => {
+            bail!("Windows integrated authentication is only available on Windows")
+        }
+        _ => AuthMethod::sql_server(
+            fields.username.as_deref().unwrap_or_default(),
+            fields.password.as_deref().unwrap_or_default(),
+        ),
+    });
+    config.encryption(if plaintext {
+        EncryptionLevel::NotSupported
+    } else if fields.encrypt {
+        EncryptionLevel::Required
+    } else {
+        EncryptionLevel::Off
+    });
+    // tiberius always checks the certificate against the host it connects to
+    if let Some(name) = fields.host_name_in_certificate.as_deref() {
+        if !name.trim().eq_ignore_ascii_case(server.host.trim()) {
+            bail!(
+                "HostNameInCertificate is not supported here; connect using the name in the server certificate ({})",
+                name
+            );
+        }
+    }
+    // tiberius refuses a CA file together with TrustServerCertificate
+    if fields.trust_server_cert {
+        config.trust_cert();
+    } else if let Some(certificate) = fields.server_certificate {
+        config.trust_cert_ca(certificate);
+    }
+    if let Some(name) = fields.application_name {
+        config.application_name(name);
+    }
+    config.readonly(fields.read_only_intent);
+    Ok(config)
+}
+
+async fn open_with_config(config: Config, use_browser: bool) -> Result<SqlClient, ConnectError> {
+    let tcp = if use_browser {
+        resolve(&config.get_addr()).await?;
+        TcpStream::connect_named(&config).await.map_err(|e| {
+            let failure = classify_tds_error(&e);
+            ConnectError::new(
+                failure,
+                anyhow::Error::from(e)
+                    .context("Could not look up the instance port with the SQL Server Browser"),
+            )
+        })?
+    } else {
+        open_tcp(&config.get_addr()).await?
+    };
+
+    match Client::connect(config.clone(), tcp.compat_write()).await {
+        Ok(client) => Ok(client),
+        // Azure SQL gateways may redirect to the node hosting the database
+        Err(tiberius::error::Error::Routing { host, port }) => {
+            let mut config = config;
+            config.host(&host);
+            config.port(port);
+            let tcp = open_tcp(&config.get_addr()).await?;
+            Client::connect(config, tcp.compat_write())
+                .await
+                .map_err(|e| ConnectError::new(classify_tds_error(&e), e))
+        }
+        Err(e) => Err(ConnectError::new(classify_tds_error(&e), e)),
+    }
+}
+
+async fn resolve(addr: &str) -> Result<Vec<std::net::SocketAddr>, ConnectError> {
+    let host = addr.rsplit_once(':').map_or(addr, |(host, _)| host);
+    let addrs: Vec<_> = tokio::net::lookup_host(addr)
+        .await
+        .map_err(|e| {
+            ConnectError::new(
+                ConnectionFailure::Dns,
+                anyhow::Error::from(e).context(format!("Could not resolve {}", host)),
+            )
+        })?
+        .collect();
+    if addrs.is_empty() {
+        return Err(ConnectError::new(
+            ConnectionFailure::Dns,
+            anyhow!("{} has no addresses", host),
+        ));
+    }
+    Ok(addrs)
+}
+
+async fn open_tcp(addr: &str) -> Result<TcpStream, ConnectError> {
+    let addrs = resolve(addr).await?;
+    let tcp = TcpStream::connect(&addrs[..]).await.map_err(|e| {
+        let failure = match e.kind() {
+            std::io::ErrorKind::TimedOut => ConnectionFailure::Timeout,
+            _ => ConnectionFailure::TcpRefused,
+        };
+        ConnectError::new(
+            failure,
+            anyhow::Error::from(e).context(format!("Could not connect to {}", addr)),
+        )
+    })?;
+    tcp.set_nodelay(true)
+        .map_err(|e| ConnectError::new(ConnectionFailure::Other, e))?;
+    Ok(tcp)
+}
+
+/// Sorts a tiberius error from the pre-login, TLS or login exchange.
+fn classify_tds_error(error: &tiberius::error::Error) -> ConnectionFailure {
+    use tiberius::error::Error;
+    match error {
+        Error::Tls(_) => ConnectionFailure::Tls,
+        // rustls reports certificate problems as I/O errors from the handshake
+        Error::Io { kind, message }
+            if *kind == std::io::ErrorKind::InvalidData
+                || message.to_ascii_lowercase().contains("certificate") =>
+        {
+            ConnectionFailure::Tls
+        }
+        Error::Io { kind, .. } => match kind {
+            std::io::ErrorKind::ConnectionRefused => ConnectionFailure::TcpRefused,
+            std::io::ErrorKind::TimedOut => ConnectionFailure::Timeout,
+            _ => ConnectionFailure::Other,
+        },
+        Error::Server(token) => match token.code() {
+            // Login failed, untrusted domain, locked, expired or must change
+            // the password, server named by the login not found, firewall
+            18456 | 18452 | 18470 | 18486 | 18487 | 18488 | 40532 | 40615 => {
+                ConnectionFailure::LoginFailed
+            }
+            // Cannot open the database, the default database, or no user in it
+            4060 | 4064 | 916 | 978 => ConnectionFailure::DatabaseNotAccessible,
+            _ => ConnectionFailure::Other,
+        },
+        _ => ConnectionFailure::Other,
+    }
+}
+
+/// Opens a session the way a job would and reports who and where it is, or
+/// why it could not be opened.
+pub async fn test_connection(connection: &SqlConnection) -> ConnectionTestResult {
+    let started = Instant::now();
+    let session = async {
+        let mut client = open(connection).await?;
+        session_info(&mut client)
+            .await
+            .map_err(|e| ConnectError::new(ConnectionFailure::Other, e))
+    };
+    match session.await {
+        Ok(info) => ConnectionTestResult::Connected {
+            engine: ServerEngine::from_engine_edition(info.engine_edition),
+            product_version: info.product_version,
+            edition: info.edition,
+            login_name: info.login_name,
+            database_name: info.database_name,
+            elapsed_ms: started.elapsed().as_millis() as u64,
+        },
+        Err(e) => ConnectionTestResult::Failed {
+            reason: e.failure,
+            message: e.to_string(),
+        },
+    }
+}
+
+struct SessionInfo {
+    product_version: String,
+    edition: String,
+    engine_edition: i32,
+    login_name: String,
+    database_name: String,
+}
+
+async fn session_info(client: &mut SqlClient) -> Result<SessionInfo> {
+    let row = client
+        .simple_query(
+            "SELECT CAST(SERVERPROPERTY('ProductVersion') AS nvarchar(128)), \
+             CAST(SERVERPROPERTY('Edition') AS nvarchar(128)), \
+             CAST(SERVERPROPERTY('EngineEdition') AS int), \
+             SUSER_SNAME(), DB_NAME()",
+        )
+        .await?
+        .into_row()
+        .await?
+        .ok_or_else(|| anyhow!("The server returned no session information"))?;
+
+    let text = |i: usize| row.get::<&str, _>(i).unwrap_or_default().to_string();
+    Ok(SessionInfo {
+        product_version: text(0),
+        edition: text(1),
+        engine_edition: row.get::<i32, _>(2).unwrap_or_default(),
+        login_name: text(3),
+        database_name: text(4),
+    })
+}
+
+/// Version, edition and collation of the server behind a connection.
+#[derive(Debug, Clone)]
+pub struct ServerInfo {
+    pub product_version: String,
+    pub edition: String,
+    /// `SERVERPROPERTY('EngineEdition')`: 5 is Azure SQL Database, 8 Managed Instance.
+    pub engine_edition: i32,
+    pub collation: String,
+}
+
+impl ServerInfo {
+    pub fn major_version(&self) -> Option<u32> {
+        self.product_version.split('.').next()?.parse().ok()
+    }
+
+    /// Azure SQL Database or Managed Instance, which always run the newest engine.
+    pub fn is_azure(&self) -> bool {
+        matches!(self.engine_edition, 5 | 8)
+    }
+
+    pub fn is_express(&self) -> bool {
+        self.engine_edition == 4
+    }
+}
+
+pub async fn server_info(client: &mut SqlClient) -> Result<ServerInfo> {
+    let row = client
+        .simple_query(
+            "SELECT CAST(SERVERPROPERTY('ProductVersion') AS nvarchar(128)), \
+             CAST(SERVERPROPERTY('Edition') AS nvarchar(128)), \
+             CAST(SERVERPROPERTY('EngineEdition') AS int), \
+             CAST(SERVERPROPERTY('Collation') AS nvarchar(128))",
+        )
+        .await?
+        .into_row()
+        .await?
+        .ok_or_else(|| anyhow!("The server returned no version information"))?;
+
+    Ok(ServerInfo {
+        product_version: row.get::<&str, _>(0).unwrap_or_default().to_string(),
+        edition: row.get::<&str, _>(1).unwrap_or_default().to_string(),
+        engine_edition: row.get::<i32, _>(2).unwrap_or_default(),
+        collation: row.get::<&str, _>(3).unwrap_or_default().to_string(),
+    })
+}
+
+pub async fn database_exists(client: &mut SqlClient, database: &str) -> Result<bool> {
+    let row = client
+        .query(
+            "SELECT CASE WHEN DB_ID(@P1) IS NULL THEN 0 ELSE 1 END",
+            &[&database],
+        )
+        .await?
+        .into_row()
+        .await?;
+    Ok(row.and_then(|row| row.get::<i32, _>(0)) == Some(1))
+}
+
+/// The databases the login can see, with their file sizes where the login may
+/// read them.
+pub async fn list_databases(client: &mut SqlClient) -> Result<Vec<DatabaseInfo>> {
+    const COLUMNS: &str = "d.name, d.state_desc, d.recovery_model_desc, \
+         CAST(d.compatibility_level AS int), d.collation_name, \
+         CAST(CASE WHEN d.database_id <= 4 OR d.is_distributor = 1 THEN 1 ELSE 0 END AS bit)";
+    let with_sizes = async {
+        client
+            .simple_query(format!(
+                "SELECT {}, files.bytes FROM sys.databases AS d \
+                 LEFT JOIN (SELECT database_id, SUM(CAST(size AS bigint)) * 8192 AS bytes \
+                     FROM sys.master_files GROUP BY database_id) AS files \
+                 ON files.database_id = d.database_id ORDER BY d.name",
+                COLUMNS
+            ))
+            .await?
+            .into_first_result()
+            .await
+    }
+    .await;
+    // Azure SQL Database has no sys.master_files
+    let rows = match with_sizes {
+        Ok(rows) => rows,
+        Err(_) => {
+            client
+                .simple_query(format!(
+                    "SELECT {}, CAST(NULL AS bigint) FROM sys.databases AS d ORDER BY d.name",
+                    COLUMNS
+                ))
+                .await?
+                .into_first_result()
+                .await?
+        }
+    };
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let text = |i: usize| row.get::<&str, _>(i).map(str::to_string);
+            DatabaseInfo {
+                name: text(0).unwrap_or_default(),
+                state: text(1).unwrap_or_default(),
+                recovery_model: text(2).unwrap_or_default(),
+                compatibility_level: row.get::<i32, _>(3).unwrap_or_default(),
+                collation: text(4),
+                is_system: row.get::<bool, _>(5).unwrap_or_default(),
+                size_bytes: row.get::<i64, _>(6).map(|bytes| bytes.max(0) as u64),
+            }
+        })
+        .collect())
+}
+
+/// Size, tables and export risks of the connected database, from the catalog
+/// views, so `VIEW DATABASE STATE` is not needed.
+pub async fn database_stats(client: &mut SqlClient) -> Result<DatabaseStats> {
+    let sizes = client
+        .simple_query(
+            "SELECT DB_NAME(), \
+             CAST(SUM(CASE WHEN type = 0 THEN CAST(size AS bigint) ELSE 0 END) * 8192 AS bigint), \
+             CAST(SUM(CASE WHEN type = 1 THEN CAST(size AS bigint) ELSE 0 END) * 8192 AS bigint) \
+             FROM sys.database_files",
+        )
+        .await?
+        .into_row()
+        .await?
+        .ok_or_else(|| anyhow!("The server returned no file sizes"))?;
+    let bytes = |i: usize| sizes.get::<i64, _>(i).unwrap_or_default().max(0) as u64;
+    let (data_bytes, log_bytes) = (bytes(1), bytes(2));
+    let database_name = sizes.get::<&str, _>(0).unwrap_or_default().to_string();
+
+    // Rows come from the heap or clustered index only; space from every
+    // allocation unit of every index
+    let tables: Vec<TableStats> = client
+        .simple_query(
+            "SELECT s.name, t.name, CAST(ISNULL(r.row_count, 0) AS bigint), \
+             CAST(ISNULL(a.pages, 0) * 8192 AS bigint), \
+             CAST(ISNULL(OBJECTPROPERTY(t.object_id, 'TableIsMemoryOptimized'), 0) AS bit) \
+             FROM sys.tables AS t \
+             JOIN sys.schemas AS s ON s.schema_id = t.schema_id \
+             OUTER APPLY (SELECT SUM(p.rows) AS row_count FROM sys.partitions AS p \
+                 WHERE p.object_id = t.object_id AND p.index_id IN (0, 1)) AS r \
+             OUTER APPLY (SELECT SUM(CAST(au.total_pages AS bigint)) AS pages \
+                 FROM sys.partitions AS p \
+                 JOIN sys.allocation_units AS au ON au.container_id = p.partition_id \
+                 WHERE p.object_id = t.object_id) AS a \
+             WHERE t.is_ms_shipped = 0 \
+             ORDER BY 4 DESC, 1, 2",
+        )
+        .await?
+        .into_first_result()
+        .await?
+        .into_iter()
+        .map(|row| TableStats {
+            schema: row.get::<&str, _>(0).unwrap_or_default().to_string(),
+            name: row.get::<&str, _>(1).unwrap_or_default().to_string(),
+            row_count: row.get::<i64, _>(2).unwrap_or_default().max(0) as u64,
+            reserved_bytes: row.get::<i64, _>(3).unwrap_or_default().max(0) as u64,
+            is_memory_optimized: row.get::<bool, _>(4).unwrap_or_default(),
+        })
+        .collect();
+
+    let filestream = object_names(
+        client,
+        "SELECT CAST(QUOTENAME(s.name) + N'.' + QUOTENAME(t.name) \
+             + ISNULL(N'.' + QUOTENAME(c.name), N' (FileTable)') AS nvarchar(1000)) \
+         FROM sys.tables AS t \
+         JOIN sys.schemas AS s ON s.schema_id = t.schema_id \
+         LEFT JOIN sys.columns AS c ON c.object_id = t.object_id AND c.is_filestream = 1 \
+         WHERE c.column_id IS NOT NULL \
+             OR OBJECTPROPERTY(t.object_id, 'TableIsFileTable') = 1 \
+         ORDER BY 1",
+    )
+    .await?;
+    let full_text = object_names(
+        client,
+        "SELECT CAST(name AS nvarchar(1000)) FROM sys.fulltext_catalogs ORDER BY 1",
+    )
+    .await?;
+    // Three- and four-part names in module bodies, and synonyms for objects
+    // in other databases
+    let cross_database = object_names(
+        client,
+        "SELECT DISTINCT CAST(QUOTENAME(OBJECT_SCHEMA_NAME(d.referencing_id)) + N'.' \
+             + QUOTENAME(OBJECT_NAME(d.referencing_id)) + N' references ' \
+             + ISNULL(QUOTENAME(d.referenced_server_name) + N'.', N'') \
+             + QUOTENAME(d.referenced_database_name) AS nvarchar(1000)) \
+         FROM sys.sql_expression_dependencies AS d \
+         WHERE d.referenced_database_name IS NOT NULL \
+             AND (d.referenced_database_name <> DB_NAME() OR d.referenced_server_name IS NOT NULL) \
+         UNION \
+         SELECT CAST(QUOTENAME(SCHEMA_NAME(schema_id)) + N'.' + QUOTENAME(name) \
+             + N' references ' + base_object_name AS nvarchar(1000)) \
+         FROM sys.synonyms \
+         WHERE PARSENAME(base_object_name, 4) IS NOT NULL \
+             OR PARSENAME(base_object_name, 3) <> DB_NAME() \
+         ORDER BY 1",
+    )
+    .await?;
+
+    let memory_optimized = tables
+        .iter()
+        .filter(|table| table.is_memory_optimized)
+        .map(|table| format!("[{}].[{}]", table.schema, table.name))
+        .collect();
+    let risks = export_risks(memory_optimized, filestream, full_text, cross_database);
+    Ok(DatabaseStats {
+        database_name,
+        data_bytes,
+        log_bytes,
+        tables,
+        risks,
+    })
+}
+
+async fn object_names(client: &mut SqlClient, sql: &str) -> Result<Vec<String>> {
+    Ok(client
+        .simple_query(sql)
+        .await?
+        .into_first_result()
+        .await?
+        .into_iter()
+        .filter_map(|row| row.get::<&str, _>(0).map(str::to_string))
+        .collect())
+}
+
+fn export_risks(
+    memory_optimized: Vec<String>,
+    filestream: Vec<String>,
+    full_text: Vec<String>,
+    cross_database: Vec<String>,
+) -> Vec<ExportRisk> {
+    [
+        (
+            ExportRiskKind::MemoryOptimizedTables,
+            "Memory-optimized tables need a MEMORY_OPTIMIZED_DATA filegroup on the target, \
+             and Azure SQL Database only has them in the Premium and Business Critical tiers",
+            memory_optimized,
+        ),
+        (
+            ExportRiskKind::Filestream,
+            "FILESTREAM columns and FileTables cannot be exported to a BACPAC",
+            filestream,
+        ),
+        (
+            ExportRiskKind::FullTextCatalogs,
+            "Full-text indexes are repopulated after import, \
+             which can take a long time on large tables",
+            full_text,
+        ),
+        (
+            ExportRiskKind::CrossDatabaseReferences,
+            "References to other databases or linked servers fail export validation \
+             (SQL71562) unless they are removed first",
+            cross_database,
+        ),
+    ]
+    .into_iter()
+    .filter(|(_, _, objects)| !objects.is_empty())
+    .map(|(kind, message, objects)| ExportRisk {
+        kind,
+        message: message.to_string(),
+        objects,
+    })
+    .collect()
+}
+
+/// User objects in an existing database, or `None` when the login can't look.
+pub async fn user_object_count(client: &mut SqlClient, database: &str) -> Option<i32> {
+    let row = client
+        .query(
+            "DECLARE @sql nvarchar(max) = N'SELECT COUNT(*) FROM ' + QUOTENAME(@P1) \
+             + N'.sys.objects WHERE is_ms_shipped = 0'; \
+             EXEC sp_executesql @sql",
+            &[&database],
+        )
+        .await
+        .ok()?
+        .into_row()
+        .await
+        .ok()??;
+    row.get::<i32, _>(0)
+}
+
+/// Free bytes on the volume holding the server's data files, when the login
+/// may see it (`VIEW SERVER STATE`) and the server exposes it.
+pub async fn data_volume_free_bytes(client: &mut SqlClient) -> Option<u64> {
+    let row = client
+        .simple_query(
+            "SELECT TOP 1 CAST(vs.available_bytes AS bigint) \
+             FROM sys.master_files AS mf \
+             CROSS APPLY sys.dm_os_volume_stats(mf.database_id, mf.file_id) AS vs \
+             WHERE mf.database_id = DB_ID('model') AND mf.type = 0",
+        )
+        .await
+        .ok()?
+        .into_row()
+        .await
+        .ok()??;
+    row.get::<i64, _>(0).map(|bytes| bytes.max(0) as u64)
+}
+
+/// Bytes used by table rows (heaps and clustered indexes, LOB pages included)
+/// in the connected database. Falls back to the used space of the data files
+/// when the login lacks `VIEW DATABASE STATE`.
+pub async fn table_data_bytes(client: &mut SqlClient) -> Result<u64> {
+    let partition_stats = async {
+        client
+            .simple_query(
+                "SELECT CAST(SUM(used_page_count) AS bigint) * 8192 \
+                 FROM sys.dm_db_partition_stats WHERE index_id IN (0, 1)",
+            )
+            .await?
+            .into_row()
+            .await
+    }
+    .await;
+    let row = match partition_stats {
+        Ok(row) => row,
+        Err(_) => {
+            client
+                .simple_query(
+                    "SELECT SUM(CAST(FILEPROPERTY(name, 'SpaceUsed') AS bigint)) * 8192 \
+                     FROM sys.database_files WHERE type = 0",
+                )
+                .await?
+                .into_row()
+                .await?
+        }
+    };
+    Ok(row
+        .and_then(|row| row.get::<i64, _>(0))
+        .map(|bytes| bytes.max(0) as u64)
+        .unwrap_or(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn utf16(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    fn b_varchar(text: &str) -> Vec<u8> {
+        let mut bytes = vec![text.encode_utf16().count() as u8];
+        bytes.extend(utf16(text));
+        bytes
+    }
+
+    fn us_varchar(text: &str) -> Vec<u8> {
+        let mut bytes = (text.encode_utf16().count() as u16).to_le_bytes().to_vec();
+        bytes.extend(utf16(text));
+        bytes
+    }
+
+    fn token(kind: u8, body: Vec<u8>) -> Vec<u8> {
+        let mut bytes = vec![kind];
+        bytes.extend((body.len() as u16).to_le_bytes());
+        bytes.extend(body);
+        bytes
+    }
+
+    fn error(code: u32, message: &str) -> Vec<u8> {
+        let mut body = code.to_le_bytes().to_vec();
+        body.extend([1, 14]);
+        body.extend(us_varchar(message));
+        body.extend(b_varchar("mock"));
+        body.extend(b_varchar(""));
+        body.extend(1u32.to_le_bytes());
+        token(0xAA, body)
+    }
+
+    fn login_ack() -> Vec<u8> {
+        let mut body = vec![1];
+        body.extend(0x7400_0004u32.to_be_bytes());
+        body.extend(b_varchar("Microsoft SQL Server"));
+        body.extend(0x1000_0000u32.to_le_bytes());
+        token(0xAD, body)
+    }
+
+    fn done(status: u16, rows: u64) -> Vec<u8> {
+        let mut bytes = vec![0xFD];
+        bytes.extend(status.to_le_bytes());
+        bytes.extend(0u16.to_le_bytes());
+        bytes.extend(rows.to_le_bytes());
+        bytes
+    }
+
+    #[derive(Clone, Copy)]
+    enum Cell<'a> {
+        Text(Option<&'a str>),
+        Int(i32),
+        BigInt(Option<i64>),
+        Bit(bool),
+    }
+
+    /// COLMETADATA, ROW and DONE tokens for one result set; the column types are
+    /// taken from the first row, and are all nvarchar without rows.
+    fn result_set(names: &[&str], rows: &[Vec<Cell>]) -> Vec<u8> {
+        let no_rows = vec![Cell::Text(None); names.len()];
+        let mut bytes = vec![0x81];
+        bytes.extend((names.len() as u16).to_le_bytes());
+        for (name, cell) in names.iter().zip(rows.first().unwrap_or(&no_rows)) {
+            bytes.extend([0, 0, 0, 0, 0x01, 0x00]);
+            match cell {
+                Cell::Text(_) => {
+                    bytes.push(0xE7);
+                    bytes.extend(256u16.to_le_bytes());
+                    bytes.extend(0x00D0_0409u32.to_le_bytes());
+                    bytes.push(52);
+                }
+                Cell::Int(_) => bytes.extend([0x26, 4]),
+                Cell::BigInt(_) => bytes.extend([0x26, 8]),
+                Cell::Bit(_) => bytes.extend([0x68, 1]),
+            }
+            bytes.extend(b_varchar(name));
+        }
+        for row in rows {
+            bytes.push(0xD1);
+            for cell in row {
+                match cell {
+                    Cell::Text(Some(text)) => {
+                        let text = utf16(text);
+                        bytes.extend((text.len() as u16).to_le_bytes());
+                        bytes.extend(text);
+                    }
+                    Cell::Text(None) => bytes.extend(0xFFFFu16.to_le_bytes()),
+                    Cell::Int(value) => {
+                        bytes.push(4);
+                        bytes.extend(value.to_le_bytes());
+                    }
+                    Cell::BigInt(Some(value)) => {
+                        bytes.push(8);
+                        bytes.extend(value.to_le_bytes());
+                    }
+                    Cell::BigInt(None) => bytes.push(0),
+                    Cell::Bit(value) => bytes.extend([1, *value as u8]),
+                }
+            }
+        }
+        bytes.extend(done(0x10, rows.len() as u64));
+        bytes
+    }
+
+    fn failed_batch(code: u32, message: &str) -> Vec<u8> {
+        let mut bytes = error(code, message);
+        bytes.extend(done(0x02, 0));
+        bytes
+    }
+
+    fn prelogin_response() -> Vec<u8> {
+        let mut bytes = vec![0x00, 0, 11, 0, 6, 0x01, 0, 17, 0, 1, 0xFF];
+        bytes.extend([16, 0, 0x10, 0x00, 0, 0]);
+        // Encryption not supported, so the session stays in plain text
+        bytes.push(0x02);
+        bytes
+    }
+
+    async fn read_message(stream: &mut TcpStream) -> u8 {
+        read_message_payload(stream).await.0
+    }
+
+    async fn read_message_payload(stream: &mut TcpStream) -> (u8, Vec<u8>) {
+        let mut message = Vec::new();
+        loop {
+            let mut header = [0u8; 8];
+            stream.read_exact(&mut header).await.unwrap();
+            let length = u16::from_be_bytes([header[2], header[3]]) as usize;
+            let mut payload = vec![0u8; length - 8];
+            stream.read_exact(&mut payload).await.unwrap();
+            message.extend(payload);
+            if header[1] & 0x01 != 0 {
+                return (header[0], message);
+            }
+        }
+    }
+
+    async fn reply(stream: &mut TcpStream, payload: Vec<u8>) {
+        let mut packet = vec![0x04, 0x01];
+        packet.extend(((payload.len() + 8) as u16).to_be_bytes());
+        packet.extend([0, 0, 1, 0]);
+        packet.extend(payload);
+        stream.write_all(&packet).await.unwrap();
+    }
+
+    /// A single-session TDS server answering the login with `login` and each
+    /// SQL batch after it with the next of `batches`.
+    async fn mock_server(login: Vec<u8>, batches: Vec<Vec<u8>>) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            assert_eq!(read_message(&mut stream).await, 0x12);
+            reply(&mut stream, prelogin_response()).await;
+            assert_eq!(read_message(&mut stream).await, 0x10);
+            reply(&mut stream, login).await;
+            for batch in batches {
+                assert_eq!(read_message(&mut stream).await, 0x01);
+                reply(&mut stream, batch).await;
+            }
+        });
+        port
+    }
+
+    /// A server that refuses the login, handing back the LOGIN7 message it got.
+    async fn login_capturing_server() -> (u16, tokio::sync::oneshot::Receiver<Vec<u8>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            assert_eq!(read_message(&mut stream).await, 0x12);
+            reply(&mut stream, prelogin_response()).await;
+            let (kind, login) = read_message_payload(&mut stream).await;
+            assert_eq!(kind, 0x10);
+            sender.send(login).unwrap();
+            reply(&mut stream, failed_batch(18456, "Login failed.")).await;
+        });
+        (port, receiver)
+    }
+
+    /// The `index`th variable field of a LOGIN7 message, the password unscrambled.
+    fn login_field(login: &[u8], index: usize) -> String {
+        let at = 36 + index * 4;
+        let offset = u16::from_le_bytes([login[at], login[at + 1]]) as usize;
+        let chars = u16::from_le_bytes([login[at + 2], login[at + 3]]) as usize;
+        let mut bytes = login[offset..offset + chars * 2].to_vec();
+        if index == 2 {
+            for byte in &mut bytes {
+                *byte = (*byte ^ 0xA5).rotate_left(4);
+            }
+        }
+        let units: Vec<u16> = bytes
+            .chunks(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        String::from_utf16(&units).unwrap()
+    }
+
+    fn logged_in() -> Vec<u8> {
+        let mut bytes = login_ack();
+        bytes.extend(done(0, 0));
+        bytes
+    }
+
+    fn local_connection(port: u16) -> SqlConnection {
+        SqlConnection {
+            server: "127.0.0.1".to_string(),
+            port: Some(port.to_string()),
+            database_name: Some("Sales".to_string()),
+            username: Some("sa".to_string()),
+            password: Some("secret".to_string()),
+            connect_timeout: Some(5),
+            extra_options: Some("Encrypt=DANGER_PLAINTEXT".to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn failure(result: ConnectionTestResult) -> ConnectionFailure {
+        match result {
+            ConnectionTestResult::Failed { reason, .. } => reason,
+            connected => panic!("expected a failure, got {:?}", connected),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connection_reports_the_session() {
+        let session = result_set(
+            &["version", "edition", "engine", "login", "database"],
+            &[vec![
+                Cell::Text(Some("16.0.4135.4")),
+                Cell::Text(Some("Developer Edition (64-bit)")),
+                Cell::Int(3),
+                Cell::Text(Some("sa")),
+                Cell::Text(Some("Sales")),
+            ]],
+        );
+        let port = mock_server(logged_in(), vec![session]).await;
+
+        match test_connection(&local_connection(port)).await {
+            ConnectionTestResult::Connected {
+                product_version,
+                edition,
+                engine,
+                login_name,
+                database_name,
+                ..
+            } => {
+                assert_eq!(product_version, "16.0.4135.4");
+                assert_eq!(edition, "Developer Edition (64-bit)");
+                assert_eq!(engine, ServerEngine::OnPremises);
+                assert_eq!(login_name, "sa");
+                assert_eq!(database_name, "Sales");
+            }
+            failed => panic!("expected a session, got {:?}", failed),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_login_sends_fields_unchanged() {
+        let password = r#"{it's "quoted"}; ünïcode=='"#;
+        for saved_as_string in [false, true] {
+            let (port, login) = login_capturing_server().await;
+            let mut connection = SqlConnection {
+                database_name: Some("Ventes_Été_東京".to_string()),
+                password: Some(password.to_string()),
+                ..local_connection(port)
+            };
+            if saved_as_string {
+                connection.connection_string = Some(utils::build_connection_string(&connection));
+                connection.database_name = None;
+            }
+
+            let result = test_connection(&connection).await;
+            assert_eq!(failure(result), ConnectionFailure::LoginFailed);
+            let login = login.await.unwrap();
+            assert_eq!(login_field(&login, 1), "sa");
+            assert_eq!(login_field(&login, 2), password);
+            assert_eq!(login_field(&login, 8), "Ventes_Été_東京");
+        }
+    }
+
+    #[test]
+    fn test_config_rejects_settings_it_cannot_honour() {
+        let renamed = SqlConnection {
+            encrypt: true,
+            host_name_in_certificate: Some("sql.example.com".to_string()),
+            ..local_connection(1433)
+        };
+        let error = tiberius_config(&renamed).unwrap_err().to_string();
+        assert!(error.contains("HostNameInCertificate"), "{}", error);
+
+        #[cfg(not(windows))]
+        {
+            let integrated = SqlConnection {
+                authentication: crate::models::AuthMethod::Integrated,
+                username: None,
+                password: None,
+                ..local_connection(1433)
+            };
+            let error = tiberius_config(&integrated).unwrap_err().to_string();
+            assert!(error.contains("only available on Windows"), "{}", error);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connection_classifies_login_errors() {
+        let login = failed_batch(18456, "Login failed for user 'sa'.");
+        let port = mock_server(login, vec![]).await;
+        match test_connection(&local_connection(port)).await {
+            ConnectionTestResult::Failed { reason, message } => {
+                assert_eq!(reason, ConnectionFailure::LoginFailed);
+                assert!(
+                    message.contains("Login failed for user 'sa'."),
+                    "{}",
+                    message
+                );
+            }
+            connected => panic!("expected a failure, got {:?}", connected),
+        }
+
+        let login = failed_batch(
+            4060,
+            "Cannot open database \"Sales\" requested by the login.",
+        );
+        let port = mock_server(login, vec![]).await;
+        assert_eq!(
+            failure(test_connection(&local_connection(port)).await),
+            ConnectionFailure::DatabaseNotAccessible
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connection_classifies_network_errors() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+        assert_eq!(
+            failure(test_connection(&local_connection(port)).await),
+            ConnectionFailure::TcpRefused
+        );
+
+        let connection = SqlConnection {
+            server: "shapac-test.invalid".to_string(),
+            ..local_connection(1433)
+        };
+        assert_eq!(
+            failure(test_connection(&connection).await),
+            ConnectionFailure::Dns
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_databases() {
+        let names = [
+            "name",
+            "state",
+            "recovery",
+            "compatibility",
+            "collation",
+            "system",
+            "bytes",
+        ];
+        let rows = |bytes: [Option<i64>; 2]| {
+            vec![
+                vec![
+                    Cell::Text(Some("master")),
+                    Cell::Text(Some("ONLINE")),
+                    Cell::Text(Some("SIMPLE")),
+                    Cell::Int(160),
+                    Cell::Text(Some("SQL_Latin1_General_CP1_CI_AS")),
+                    Cell::Bit(true),
+                    Cell::BigInt(bytes[0]),
+                ],
+                vec![
+                    Cell::Text(Some("Sales")),
+                    Cell::Text(Some("OFFLINE")),
+                    Cell::Text(Some("FULL")),
+                    Cell::Int(150),
+                    Cell::Text(None),
+                    Cell::Bit(false),
+                    Cell::BigInt(bytes[1]),
+                ],
+            ]
+        };
+        let port = mock_server(
+            logged_in(),
+            vec![result_set(&names, &rows([Some(8_388_608), None]))],
+        )
+        .await;
+        let mut client = connect(&local_connection(port)).await.unwrap();
+        let databases = list_databases(&mut client).await.unwrap();
+        assert_eq!(databases.len(), 2);
+        assert_eq!(databases[0].name, "master");
+        assert!(databases[0].is_system);
+        assert_eq!(databases[0].size_bytes, Some(8_388_608));
+        assert_eq!(databases[1].state, "OFFLINE");
+        assert_eq!(databases[1].recovery_model, "FULL");
+        assert_eq!(databases[1].compatibility_level, 150);
+        assert_eq!(databases[1].collation, None);
+        assert!(!databases[1].is_system);
+
+        // Without sys.master_files the list comes back without sizes
+        let port = mock_server(
+            logged_in(),
+            vec![
+                failed_batch(208, "Invalid object name 'sys.master_files'."),
+                result_set(&names, &rows([None, None])),
+            ],
+        )
+        .await;
+        let mut client = connect(&local_connection(port)).await.unwrap();
+        let databases = list_databases(&mut client).await.unwrap();
+        assert_eq!(databases.len(), 2);
+        assert!(databases.iter().all(|db| db.size_bytes.is_none()));
+    }
+
+    #[tokio::test]
+    async fn test_database_stats() {
+        let text = |value| Cell::Text(Some(value));
+        let table = |name, rows, bytes, memory_optimized| {
+            vec![
+                text("dbo"),
+                text(name),
+                Cell::BigInt(Some(rows)),
+                Cell::BigInt(Some(bytes)),
+                Cell::Bit(memory_optimized),
+            ]
+        };
+        let names = ["schema", "name", "rows", "bytes", "memory_optimized"];
+        let batches = vec![
+            result_set(
+                &["database", "data", "log"],
+                &[vec![
+                    text("Sales"),
+                    Cell::BigInt(Some(104_857_600)),
+                    Cell::BigInt(Some(8_388_608)),
+                ]],
+            ),
+            result_set(
+                &names,
+                &[
+                    table("Orders", 120_000, 52_428_800, false),
+                    table("Customers", 5_000, 1_048_576, false),
+                    table("SessionCache", 300, 0, true),
+                ],
+            ),
+            result_set(&["object"], &[vec![text("[dbo].[Documents].[Content]")]]),
+            result_set(&["object"], &[]),
+            result_set(
+                &["object"],
+                &[vec![text("[dbo].[MonthlyReport] references [Warehouse]")]],
+            ),
+        ];
+        let port = mock_server(logged_in(), batches).await;
+        let mut client = connect(&local_connection(port)).await.unwrap();
+
+        let stats = database_stats(&mut client).await.unwrap();
+        assert_eq!(stats.database_name, "Sales");
+        assert_eq!(stats.data_bytes, 104_857_600);
+        assert_eq!(stats.log_bytes, 8_388_608);
+        assert_eq!(stats.tables.len(), 3);
+        assert_eq!(stats.tables[0].name, "Orders");
+        assert_eq!(stats.tables[0].row_count, 120_000);
+
+        let kinds: Vec<_> = stats.risks.iter().map(|risk| risk.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                ExportRiskKind::MemoryOptimizedTables,
+                ExportRiskKind::Filestream,
+                ExportRiskKind::CrossDatabaseReferences,
+            ]
+        );
+        assert_eq!(stats.risks[0].objects, ["[dbo].[SessionCache]"]);
+        assert_eq!(
+            stats.risks[2].objects,
+            ["[dbo].[MonthlyReport] references [Warehouse]"]
+        );
+    }
+
+    #[test]
+    fn test_classify_tds_error() {
+        use tiberius::error::Error;
+        let io = |kind, message: &str| Error::Io {
+            kind,
+            message: message.to_string(),
+        };
+        assert_eq!(
+            classify_tds_error(&io(
+                std::io::ErrorKind::InvalidData,
+                "invalid peer certificate: UnknownIssuer"
+            )),
+            ConnectionFailure::Tls
+        );
+        assert_eq!(
+            classify_tds_error(&io(std::io::ErrorKind::ConnectionRefused, "refused")),
+            ConnectionFailure::TcpRefused
+        );
+        assert_eq!(
+            classify_tds_error(&Error::Tls("handshake failure".to_string())),
+            ConnectionFailure::Tls
+        );
+        assert_eq!(
+            classify_tds_error(&Error::Protocol("bad token".into())),
+            ConnectionFailure::Other
+        );
+    }
+}
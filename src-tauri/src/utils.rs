@@ -1,4 +1,6 @@
-use crate::models::SqlConnection;
+use crate::connection_string::ConnectionString;
+use crate::models::{AuthMethod, SqlConnection};
+use anyhow::{anyhow, bail, Result};
 use std::process::Command;
 
 pub fn is_sqlpackage_installed() -> bool {
@@ -8,9 +10,7 @@ pub fn is_sqlpackage_installed() -> bool {
             .args(&["/C", "sqlpackage", "/version"])
             .output()
     } else {
-        Command::new("sqlpackage")
-            .arg("/version")
-            .output()
+        Command::new("sqlpackage").arg("/version").output()
     };
 
     match result {
@@ -19,6 +19,41 @@ pub fn is_sqlpackage_installed() -> bool {
     }
 }
 
+pub const SERVER_KEYWORDS: &[&str] = &[
+    "Server",
+    "Data Source",
+    "Address",
+    "Addr",
+    "Network Address",
+];
+pub const DATABASE_KEYWORDS: &[&str] = &["Database", "Initial Catalog"];
+const USER_KEYWORDS: &[&str] = &["User Id", "UID", "User"];
+const PASSWORD_KEYWORDS: &[&str] = &["Password", "PWD"];
+const INTEGRATED_SECURITY_KEYWORDS: &[&str] = &["Integrated Security", "Trusted_Connection"];
+const AUTHENTICATION_KEYWORDS: &[&str] = &["Authentication"];
+pub const ENCRYPT_KEYWORDS: &[&str] = &["Encrypt"];
+pub const TRUST_SERVER_CERTIFICATE_KEYWORDS: &[&str] =
+    &["TrustServerCertificate", "Trust Server Certificate"];
+const CONNECT_TIMEOUT_KEYWORDS: &[&str] = &["Connect Timeout", "Connection Timeout", "Timeout"];
+const COMMAND_TIMEOUT_KEYWORDS: &[&str] = &["Command Timeout"];
+const APPLICATION_INTENT_KEYWORDS: &[&str] = &["ApplicationIntent", "Application Intent"];
+const MULTI_SUBNET_FAILOVER_KEYWORDS: &[&str] = &["MultiSubnetFailover", "Multi Subnet Failover"];
+const HOST_NAME_IN_CERTIFICATE_KEYWORDS: &[&str] =
+    &["HostNameInCertificate", "Host Name In Certificate"];
+pub const SERVER_CERTIFICATE_KEYWORDS: &[&str] = &["ServerCertificate", "Server Certificate"];
+const APPLICATION_NAME_KEYWORDS: &[&str] = &["Application Name", "App"];
+
+/// Keywords with a field of their own that extra options may not override, so
+/// a stored password is never sent to another server or login.
+const RESERVED_EXTRA_KEYWORDS: [(&str, &[&str]); 3] = [
+    ("Server", SERVER_KEYWORDS),
+    ("User", USER_KEYWORDS),
+    ("Password", PASSWORD_KEYWORDS),
+];
+
+/// SQL Server limits instance names to 16 characters.
+const MAX_INSTANCE_NAME_LEN: usize = 16;
+
 pub fn build_connection_string(conn: &SqlConnection) -> String {
     // If connection_string is provided directly, use it
     if let Some(cs) = &conn.connection_string {
@@ -28,50 +63,383 @@ pub fn build_connection_string(conn: &SqlConnection) -> String {
     }
 
     // Otherwise, build from individual fields
-    let server_part = if let Some(port) = &conn.port {
-        if !port.is_empty() {
-            format!("{},{}", conn.server, port)
-        } else {
-            conn.server.clone()
-        }
-    } else {
-        conn.server.clone()
+    let server_part = match conn.port.as_deref().filter(|p| !p.is_empty()) {
+        Some(port) => format!("{},{}", conn.server, port),
+        None => conn.server.clone(),
     };
-    let mut parts = vec![format!("Server={}", server_part)];
+    let mut cs = ConnectionString::new();
+    cs.set("Server", server_part);
 
-    if let Some(db) = &conn.database_name {
-        if !db.is_empty() {
-            parts.push(format!("Database={}", db));
+    if let Some(db) = conn.database_name.as_deref().filter(|db| !db.is_empty()) {
+        cs.set("Database", db);
+    }
+
+    // An access token is not a connection string keyword; sqlpackage takes it
+    // as /AccessToken and tiberius as its own authentication method
+    if conn.authentication == AuthMethod::Integrated {
+        cs.set("Integrated Security", "true");
+    }
+    if let Some(keyword) = authentication_keyword(conn.authentication) {
+        cs.set("Authentication", keyword);
+    }
+    if conn.authentication.uses_username() {
+        if let Some(user) = conn.username.as_deref().filter(|u| !u.is_empty()) {
+            cs.set("User Id", user);
+        }
+    }
+    if conn.authentication.uses_password() && conn.authentication != AuthMethod::AccessToken {
+        if let Some(pwd) = conn.password.as_deref().filter(|p| !p.is_empty()) {
+            cs.set("Password", pwd);
         }
     }
 
-    if conn.use_windows_auth {
-        parts.push("Integrated Security=true".to_string());
+    let encrypt = if conn.strict_encryption {
+        "Strict"
+    } else if conn.encrypt {
+        "true"
     } else {
-        if let Some(user) = &conn.username {
-            if !user.is_empty() {
-                parts.push(format!("User Id={}", user));
+        "false"
+    };
+    cs.set("Encrypt", encrypt);
+    cs.set(
+        "TrustServerCertificate",
+        if conn.trust_server_cert {
+            "true"
+        } else {
+            "false"
+        },
+    );
+    let non_empty = |value: &Option<String>| value.clone().filter(|v| !v.is_empty());
+    if let Some(host) = non_empty(&conn.host_name_in_certificate) {
+        cs.set("HostNameInCertificate", host);
+    }
+    if let Some(certificate) = non_empty(&conn.server_certificate) {
+        cs.set("ServerCertificate", certificate);
+    }
+
+    if let Some(seconds) = conn.connect_timeout {
+        cs.set("Connect Timeout", seconds.to_string());
+    }
+    if let Some(seconds) = conn.command_timeout {
+        cs.set("Command Timeout", seconds.to_string());
+    }
+    if conn.read_only_intent {
+        cs.set("ApplicationIntent", "ReadOnly");
+    }
+    if conn.multi_subnet_failover {
+        cs.set("MultiSubnetFailover", "True");
+    }
+    if let Some(name) = non_empty(&conn.application_name) {
+        cs.set("Application Name", name);
+    }
+
+    if let Some(extra) = conn.extra_options.as_deref() {
+        // Checked when the connection is saved
+        if let Ok(mut extra) = ConnectionString::parse(extra) {
+            for (_, keywords) in RESERVED_EXTRA_KEYWORDS {
+                extra.remove(keywords);
             }
+            cs.extend(extra);
         }
-        if let Some(pwd) = &conn.password {
-            if !pwd.is_empty() {
-                parts.push(format!("Password={}", pwd));
+    }
+
+    cs.to_string()
+}
+
+/// Reads a connection string back into connection fields. Keywords without a
+/// field of their own are kept in `extra_options`.
+pub fn parse_connection_string(connection_string: &str) -> Result<SqlConnection> {
+    let mut cs = ConnectionString::parse(connection_string)?;
+
+    let server = cs
+        .remove(SERVER_KEYWORDS)
+        .ok_or_else(|| anyhow!("The connection string has no Server"))?;
+    // A trailing ",port" is split off; anything else stays part of the server
+    let (server, port) = match server.rsplit_once(',') {
+        Some((host, port))
+            if !port.trim().is_empty() && port.trim().chars().all(|c| c.is_ascii_digit()) =>
+        {
+            (host.to_string(), Some(port.trim().to_string()))
+        }
+        _ => (server, None),
+    };
+
+    let integrated = match cs.remove(INTEGRATED_SECURITY_KEYWORDS) {
+        Some(value) => parse_bool("Integrated Security", &value)?,
+        None => false,
+    };
+    // Modes without a field of their own, such as Active Directory Interactive,
+    // stay in extra_options
+    let authentication = match cs
+        .get(AUTHENTICATION_KEYWORDS)
+        .map(authentication_from_keyword)
+    {
+        Some(Some(method)) => {
+            cs.remove(AUTHENTICATION_KEYWORDS);
+            method
+        }
+        _ if integrated => AuthMethod::Integrated,
+        _ => AuthMethod::SqlPassword,
+    };
+    let (encrypt, strict_encryption) = match cs.remove(ENCRYPT_KEYWORDS) {
+        Some(value) if value.trim().eq_ignore_ascii_case("strict") => (true, true),
+        Some(value) => (parse_bool("Encrypt", &value)?, false),
+        None => (true, false),
+    };
+    let trust_server_cert = match cs.remove(TRUST_SERVER_CERTIFICATE_KEYWORDS) {
+        Some(value) => parse_bool("TrustServerCertificate", &value)?,
+        None => false,
+    };
+    let connect_timeout = cs
+        .remove(CONNECT_TIMEOUT_KEYWORDS)
+        .map(|value| parse_seconds("Connect Timeout", &value))
+        .transpose()?;
+    let command_timeout = cs
+        .remove(COMMAND_TIMEOUT_KEYWORDS)
+        .map(|value| parse_seconds("Command Timeout", &value))
+        .transpose()?;
+    let read_only_intent = match cs.remove(APPLICATION_INTENT_KEYWORDS) {
+        Some(value) if value.trim().eq_ignore_ascii_case("ReadOnly") => true,
+        Some(value) if value.trim().eq_ignore_ascii_case("ReadWrite") => false,
+        Some(value) => bail!("Invalid value '{}' for ApplicationIntent", value),
+        None => false,
+    };
+    let multi_subnet_failover = match cs.remove(MULTI_SUBNET_FAILOVER_KEYWORDS) {
+        Some(value) => parse_bool("MultiSubnetFailover", &value)?,
+        None => false,
+    };
+    let non_empty = |value: Option<String>| value.filter(|v| !v.is_empty());
+
+    Ok(SqlConnection {
+        id: None,
+        name: String::new(),
+        server,
+        port,
+        database_name: non_empty(cs.remove(DATABASE_KEYWORDS)),
+        username: non_empty(cs.remove(USER_KEYWORDS)),
+        password: non_empty(cs.remove(PASSWORD_KEYWORDS)),
+        password_is_reference: false,
+        connection_string: None,
+        authentication,
+        tenant_id: None,
+        trust_server_cert,
+        encrypt,
+        connect_timeout,
+        command_timeout,
+        read_only_intent,
+        multi_subnet_failover,
+        strict_encryption,
+        host_name_in_certificate: non_empty(cs.remove(HOST_NAME_IN_CERTIFICATE_KEYWORDS)),
+        server_certificate: non_empty(cs.remove(SERVER_CERTIFICATE_KEYWORDS)),
+        application_name: non_empty(cs.remove(APPLICATION_NAME_KEYWORDS)),
+        extra_options: (!cs.is_empty()).then(|| cs.to_string()),
+        created_at: None,
+        updated_at: None,
+    })
+}
+
+/// The `Authentication` keyword value SqlClient expects for a mode.
+fn authentication_keyword(method: AuthMethod) -> Option<&'static str> {
+    match method {
+        AuthMethod::SqlPassword | AuthMethod::Integrated | AuthMethod::AccessToken => None,
+        AuthMethod::ActiveDirectoryPassword => Some("Active Directory Password"),
+        AuthMethod::ActiveDirectoryServicePrincipal => Some("Active Directory Service Principal"),
+        AuthMethod::ActiveDirectoryManagedIdentity => Some("Active Directory Managed Identity"),
+        AuthMethod::ActiveDirectoryDefault => Some("Active Directory Default"),
+    }
+}
+
+/// Reads an `Authentication` value, which SqlClient accepts with or without spaces.
+fn authentication_from_keyword(value: &str) -> Option<AuthMethod> {
+    let compact: String = value
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_ascii_lowercase();
+    match compact.as_str() {
+        "sqlpassword" => Some(AuthMethod::SqlPassword),
+        "activedirectorypassword" => Some(AuthMethod::ActiveDirectoryPassword),
+        "activedirectoryserviceprincipal" => Some(AuthMethod::ActiveDirectoryServicePrincipal),
+        "activedirectorymanagedidentity" | "activedirectorymsi" => {
+            Some(AuthMethod::ActiveDirectoryManagedIdentity)
+        }
+        "activedirectorydefault" => Some(AuthMethod::ActiveDirectoryDefault),
+        _ => None,
+    }
+}
+
+fn parse_seconds(keyword: &str, value: &str) -> Result<u32> {
+    value.trim().parse().map_err(|_| {
+        anyhow!(
+            "Invalid value '{}' for {}: expected seconds",
+            value,
+            keyword
+        )
+    })
+}
+
+/// The host and optional named instance (`host\INSTANCE`) of a server name,
+/// without any `tcp:` prefix.
+pub struct ServerName<'a> {
+    pub host: &'a str,
+    pub instance: Option<&'a str>,
+}
+
+pub fn split_server_name(server: &str) -> ServerName<'_> {
+    let server = server.trim();
+    let rest = match server.get(..4) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("tcp:") => &server[4..],
+        _ => server,
+    };
+    let (host, instance) = match rest.split_once('\\') {
+        Some((host, instance)) => (host, Some(instance)),
+        None => (rest, None),
+    };
+    ServerName { host, instance }
+}
+
+/// Checks the connection fields that sqlpackage would otherwise reject late or
+/// silently ignore.
+pub fn validate_connection_options(conn: &SqlConnection) -> Result<()> {
+    let port = conn
+        .port
+        .as_deref()
+        .map(str::trim)
+        .filter(|p| !p.is_empty());
+    if conn.server.trim().is_empty() {
+        bail!("A server is required");
+    }
+    let server = split_server_name(&conn.server);
+    if server.host.trim().is_empty() {
+        bail!("The server name '{}' has no host", conn.server);
+    }
+    if let Some(instance) = server.instance {
+        if instance.is_empty() || instance.contains('\\') {
+            bail!("Write a named instance as host\\INSTANCE");
+        }
+        if instance.chars().count() > MAX_INSTANCE_NAME_LEN {
+            bail!(
+                "Instance name '{}' is longer than {} characters",
+                instance,
+                MAX_INSTANCE_NAME_LEN
+            );
+        }
+    }
+    if let Some(port) = port {
+        if port.parse::<u16>().map_or(true, |p| p == 0) {
+            bail!("Port must be a number between 1 and 65535");
+        }
+        if conn.server.contains(',') {
+            bail!("The port is given both in the server name and in the Port field");
+        }
+    }
+
+    let username = conn.username.as_deref().unwrap_or_default().trim();
+    match conn.authentication {
+        AuthMethod::ActiveDirectoryPassword if username.is_empty() => {
+            bail!("Active Directory Password authentication needs a user name")
+        }
+        AuthMethod::ActiveDirectoryServicePrincipal if username.is_empty() => {
+            bail!("Service principal authentication needs the application (client) id as the user name")
+        }
+        _ => {}
+    }
+    if let Some(tenant) = conn.tenant_id.as_deref().filter(|t| !t.is_empty()) {
+        // A GUID or a domain such as contoso.onmicrosoft.com
+        if !tenant
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+        {
+            bail!("Tenant '{}' is not a tenant id or domain name", tenant);
+        }
+    }
+
+    if let Some(extra) = conn.extra_options.as_deref() {
+        let extra = ConnectionString::parse(extra)?;
+        for (field, keywords) in RESERVED_EXTRA_KEYWORDS {
+            if let Some((keyword, _)) = extra
+                .pairs()
+                .find(|(k, _)| keywords.iter().any(|r| k.eq_ignore_ascii_case(r)))
+            {
+                bail!(
+                    "{} can't be set in the extra options; use the {} field",
+                    keyword,
+                    field
+                );
             }
         }
     }
 
-    parts.push(format!("Encrypt={}", if conn.encrypt { "true" } else { "false" }));
-    parts.push(format!(
-        "TrustServerCertificate={}",
-        if conn.trust_server_cert { "true" } else { "false" }
-    ));
+    if conn.strict_encryption && !conn.encrypt {
+        bail!("Strict encryption requires Encrypt to be on");
+    }
+    if conn.strict_encryption && conn.trust_server_cert {
+        bail!("TrustServerCertificate is ignored with strict encryption; turn it off and set HostNameInCertificate or ServerCertificate if needed");
+    }
+    let has = |value: &Option<String>| value.as_deref().is_some_and(|v| !v.trim().is_empty());
+    if !conn.encrypt && (has(&conn.host_name_in_certificate) || has(&conn.server_certificate)) {
+        bail!("HostNameInCertificate and ServerCertificate only apply to encrypted connections");
+    }
+    if let Some(path) = conn
+        .server_certificate
+        .as_deref()
+        .filter(|p| !p.trim().is_empty())
+    {
+        if !std::path::Path::new(path).is_file() {
+            bail!("Server certificate file {} does not exist", path);
+        }
+    }
+    Ok(())
+}
 
-    parts.join(";")
+fn parse_bool(keyword: &str, value: &str) -> Result<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "true" | "yes" | "sspi" | "mandatory" => Ok(true),
+        "false" | "no" | "optional" => Ok(false),
+        _ => bail!("Invalid value '{}' for {}", value, keyword),
+    }
+}
+
+/// Shown in place of passwords in connection strings sent to the frontend.
+pub const REDACTED_PASSWORD: &str = "********";
+
+/// Masks the `Password`/`Pwd` values of a connection string. A string that
+/// can't be parsed is masked as a whole.
+pub fn redact_connection_string(connection_string: &str) -> String {
+    let Ok(parsed) = ConnectionString::parse(connection_string) else {
+        return REDACTED_PASSWORD.to_string();
+    };
+    let mut redacted = ConnectionString::new();
+    for (keyword, value) in parsed.pairs() {
+        if is_password_keyword(keyword) {
+            redacted.set(keyword, REDACTED_PASSWORD);
+        } else {
+            redacted.set(keyword, value);
+        }
+    }
+    redacted.to_string()
+}
+
+/// Whether a connection string still holds a mask from `redact_connection_string`.
+pub fn is_redacted_connection_string(connection_string: &str) -> bool {
+    match ConnectionString::parse(connection_string) {
+        Ok(parsed) => parsed
+            .pairs()
+            .any(|(keyword, value)| is_password_keyword(keyword) && value == REDACTED_PASSWORD),
+        Err(_) => connection_string == REDACTED_PASSWORD,
+    }
+}
+
+fn is_password_keyword(keyword: &str) -> bool {
+    PASSWORD_KEYWORDS
+        .iter()
+        .any(|k| k.eq_ignore_ascii_case(keyword))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
 
     #[test]
     fn test_build_connection_string_with_manual() {
@@ -84,11 +452,10 @@ mod tests {
             username: Some("sa".to_string()),
             password: Some("password".to_string()),
             connection_string: Some("Server=manual;Database=ManualDB".to_string()),
-            use_windows_auth: false,
             trust_server_cert: true,
             encrypt: true,
-            created_at: None,
-            updated_at: None,
+            extra_options: None,
+            ..Default::default()
         };
 
         let result = build_connection_string(&conn);
@@ -106,11 +473,10 @@ mod tests {
             username: Some("sa".to_string()),
             password: Some("password".to_string()),
             connection_string: None,
-            use_windows_auth: false,
             trust_server_cert: true,
             encrypt: true,
-            created_at: None,
-            updated_at: None,
+            extra_options: None,
+            ..Default::default()
         };
 
         let result = build_connection_string(&conn);
@@ -131,11 +497,10 @@ mod tests {
             username: Some("sa".to_string()),
             password: Some("password".to_string()),
             connection_string: None,
-            use_windows_auth: false,
             trust_server_cert: true,
             encrypt: true,
-            created_at: None,
-            updated_at: None,
+            extra_options: None,
+            ..Default::default()
         };
 
         let result = build_connection_string(&conn);
@@ -144,4 +509,313 @@ mod tests {
         assert!(result.contains("User Id=sa"));
         assert!(result.contains("Password=password"));
     }
+
+    #[test]
+    fn test_build_connection_string_entra_modes() {
+        let conn = SqlConnection {
+            server: "sales.database.windows.net".to_string(),
+            username: Some("0b6c1f3e".to_string()),
+            password: Some("secret".to_string()),
+            authentication: AuthMethod::ActiveDirectoryServicePrincipal,
+            encrypt: true,
+            ..Default::default()
+        };
+        let result = build_connection_string(&conn);
+        assert!(result.contains("Authentication=\"Active Directory Service Principal\""));
+        assert!(result.contains("User Id=0b6c1f3e;Password=secret"));
+
+        let token = SqlConnection {
+            authentication: AuthMethod::AccessToken,
+            ..conn.clone()
+        };
+        let result = build_connection_string(&token);
+        assert!(!result.contains("Authentication"));
+        assert!(!result.contains("secret"));
+        assert!(!result.contains("User Id"));
+
+        let managed = SqlConnection {
+            authentication: AuthMethod::ActiveDirectoryManagedIdentity,
+            ..conn
+        };
+        let result = build_connection_string(&managed);
+        assert!(result.contains("User Id=0b6c1f3e"));
+        assert!(!result.contains("secret"));
+    }
+
+    #[test]
+    fn test_redact_connection_string() {
+        let redacted =
+            redact_connection_string("Server=db;User Id=sa;Password=s3cret;Encrypt=true");
+        assert_eq!(
+            redacted,
+            "Server=db;User Id=sa;Password=********;Encrypt=true"
+        );
+        assert!(is_redacted_connection_string(&redacted));
+        assert_eq!(
+            redact_connection_string("Server=db;PWD=s3cret"),
+            "Server=db;PWD=********"
+        );
+        assert!(!is_redacted_connection_string("Server=db;Password=s3cret"));
+    }
+
+    #[test]
+    fn test_password_cannot_inject_keywords() {
+        let conn = SqlConnection {
+            id: None,
+            name: "Test".to_string(),
+            server: "localhost".to_string(),
+            port: None,
+            database_name: Some("TestDB".to_string()),
+            username: Some("sa".to_string()),
+            password: Some("x';Server=evil;\"".to_string()),
+            connection_string: None,
+            trust_server_cert: false,
+            encrypt: true,
+            extra_options: Some("Workstation ID=\"Build Agent\"".to_string()),
+            ..Default::default()
+        };
+
+        let result = build_connection_string(&conn);
+        assert!(result.contains("Password=\"x';Server=evil;\"\"\""));
+        let parsed = parse_connection_string(&result).unwrap();
+        assert_eq!(parsed.server, "localhost");
+        assert_eq!(parsed.password, conn.password);
+        assert_eq!(parsed.extra_options, conn.extra_options);
+    }
+
+    #[test]
+    fn test_parse_connection_string() {
+        let parsed = parse_connection_string(
+            "Data Source=tcp:db.example.com,1433;Initial Catalog=Sales;Trusted_Connection=yes;Connect Timeout=30",
+        )
+        .unwrap();
+        assert_eq!(parsed.server, "tcp:db.example.com");
+        assert_eq!(parsed.port.as_deref(), Some("1433"));
+        assert_eq!(parsed.database_name.as_deref(), Some("Sales"));
+        assert_eq!(parsed.authentication, AuthMethod::Integrated);
+        assert!(parsed.encrypt);
+        assert_eq!(parsed.connect_timeout, Some(30));
+        assert_eq!(parsed.extra_options, None);
+
+        let parsed = parse_connection_string(
+            "Server=ag-listener\\SALES;Encrypt=Strict;HostNameInCertificate=ag.example.com;ApplicationIntent=ReadOnly;MultiSubnetFailover=True;App=Nightly;Command Timeout=0;Packet Size=8192",
+        )
+        .unwrap();
+        assert_eq!(parsed.server, "ag-listener\\SALES");
+        assert!(parsed.encrypt && parsed.strict_encryption);
+        assert_eq!(
+            parsed.host_name_in_certificate.as_deref(),
+            Some("ag.example.com")
+        );
+        assert!(parsed.read_only_intent && parsed.multi_subnet_failover);
+        assert_eq!(parsed.application_name.as_deref(), Some("Nightly"));
+        assert_eq!(parsed.command_timeout, Some(0));
+        assert_eq!(parsed.extra_options.as_deref(), Some("Packet Size=8192"));
+
+        let parsed = parse_connection_string(
+            "Server=tcp:sales.database.windows.net;Authentication=ActiveDirectoryServicePrincipal;User Id=0b6c1f3e;Password=secret",
+        )
+        .unwrap();
+        assert_eq!(
+            parsed.authentication,
+            AuthMethod::ActiveDirectoryServicePrincipal
+        );
+        assert_eq!(parsed.username.as_deref(), Some("0b6c1f3e"));
+        let parsed = parse_connection_string(
+            "Server=sales.database.windows.net;Authentication=Active Directory Interactive",
+        )
+        .unwrap();
+        assert_eq!(parsed.authentication, AuthMethod::SqlPassword);
+        assert_eq!(
+            parsed.extra_options.as_deref(),
+            Some("Authentication=\"Active Directory Interactive\"")
+        );
+
+        assert!(parse_connection_string("Database=Sales").is_err());
+        assert!(parse_connection_string("Server=db;Encrypt=maybe").is_err());
+        assert!(parse_connection_string("Server=db;ApplicationIntent=ReadMostly").is_err());
+        assert!(parse_connection_string("Server=db;Connect Timeout=-1").is_err());
+    }
+
+    #[test]
+    fn test_validate_connection_options() {
+        let valid = |server: &str, port: Option<&str>| {
+            validate_connection_options(&SqlConnection {
+                server: server.to_string(),
+                port: port.map(str::to_string),
+                encrypt: true,
+                ..Default::default()
+            })
+        };
+        assert!(valid("db.example.com", Some("1433")).is_ok());
+        assert!(valid("tcp:db.example.com", None).is_ok());
+        assert!(valid("DBHOST\\SQLEXPRESS", None).is_ok());
+        assert!(valid("tcp:DBHOST\\SQLEXPRESS", Some("50123")).is_ok());
+        assert!(valid("", None).is_err());
+        assert!(valid("tcp:", None).is_err());
+        assert!(valid("DBHOST\\", None).is_err());
+        assert!(valid("DBHOST\\A\\B", None).is_err());
+        assert!(valid("DBHOST\\INSTANCENAMETOOLONG", None).is_err());
+        assert!(valid("db", Some("0")).is_err());
+        assert!(valid("db", Some("70000")).is_err());
+        assert!(valid("db,1433", Some("1433")).is_err());
+
+        let with_extra = |extra: &str| {
+            validate_connection_options(&SqlConnection {
+                server: "db".to_string(),
+                encrypt: true,
+                extra_options: Some(extra.to_string()),
+                ..Default::default()
+            })
+        };
+        assert!(with_extra("Packet Size=8192;Pooling=false").is_ok());
+        for reserved in [
+            "Password=x",
+            "pwd=x",
+            "User Id=sa",
+            "UID=sa",
+            "user=sa",
+            "Server=elsewhere",
+            "Data Source=elsewhere",
+            "Address=elsewhere",
+            "Addr=elsewhere",
+            "Network Address=elsewhere",
+        ] {
+            assert!(with_extra(reserved).is_err(), "{}", reserved);
+        }
+
+        let strict = SqlConnection {
+            server: "db".to_string(),
+            encrypt: true,
+            strict_encryption: true,
+            ..Default::default()
+        };
+        assert!(validate_connection_options(&strict).is_ok());
+        assert!(validate_connection_options(&SqlConnection {
+            trust_server_cert: true,
+            ..strict.clone()
+        })
+        .is_err());
+        assert!(validate_connection_options(&SqlConnection {
+            encrypt: false,
+            ..strict.clone()
+        })
+        .is_err());
+        assert!(validate_connection_options(&SqlConnection {
+            server_certificate: Some("/nonexistent/server.cer".to_string()),
+            ..strict.clone()
+        })
+        .is_err());
+        assert!(validate_connection_options(&SqlConnection {
+            encrypt: false,
+            strict_encryption: false,
+            host_name_in_certificate: Some("db.example.com".to_string()),
+            ..strict
+        })
+        .is_err());
+    }
+
+    fn value() -> impl Strategy<Value = String> {
+        "[^\u{0}]{1,16}"
+    }
+
+    fn extra_pairs() -> impl Strategy<Value = Vec<(String, String)>> {
+        let known = [
+            SERVER_KEYWORDS,
+            DATABASE_KEYWORDS,
+            USER_KEYWORDS,
+            PASSWORD_KEYWORDS,
+            INTEGRATED_SECURITY_KEYWORDS,
+            AUTHENTICATION_KEYWORDS,
+            ENCRYPT_KEYWORDS,
+            TRUST_SERVER_CERTIFICATE_KEYWORDS,
+            CONNECT_TIMEOUT_KEYWORDS,
+            COMMAND_TIMEOUT_KEYWORDS,
+            APPLICATION_INTENT_KEYWORDS,
+            MULTI_SUBNET_FAILOVER_KEYWORDS,
+            HOST_NAME_IN_CERTIFICATE_KEYWORDS,
+            SERVER_CERTIFICATE_KEYWORDS,
+            APPLICATION_NAME_KEYWORDS,
+        ]
+        .concat();
+        let keyword = "[A-Za-z]([A-Za-z _=]{0,10}[A-Za-z])?"
+            .prop_filter("known keyword", move |k| {
+                !known.iter().any(|known| known.eq_ignore_ascii_case(k))
+            });
+        proptest::collection::vec((keyword, value()), 0..4)
+    }
+
+    proptest! {
+        #[test]
+        fn test_build_and_parse_round_trip(
+            server in "[A-Za-z0-9 ._\\\\;='\"-]{1,20}",
+            port in proptest::option::of("[0-9]{1,5}"),
+            database_name in proptest::option::of(value()),
+            username in proptest::option::of(value()),
+            password in proptest::option::of(value()),
+            authentication in prop_oneof![
+                Just(AuthMethod::SqlPassword),
+                Just(AuthMethod::Integrated),
+                Just(AuthMethod::ActiveDirectoryPassword),
+                Just(AuthMethod::ActiveDirectoryServicePrincipal),
+                Just(AuthMethod::ActiveDirectoryManagedIdentity),
+                Just(AuthMethod::ActiveDirectoryDefault),
+            ],
+            trust_server_cert in any::<bool>(),
+            encrypt in any::<bool>(),
+            strict_encryption in any::<bool>(),
+            connect_timeout in proptest::option::of(any::<u32>()),
+            command_timeout in proptest::option::of(any::<u32>()),
+            read_only_intent in any::<bool>(),
+            multi_subnet_failover in any::<bool>(),
+            host_name_in_certificate in proptest::option::of(value()),
+            server_certificate in proptest::option::of(value()),
+            application_name in proptest::option::of(value()),
+            extra in extra_pairs(),
+        ) {
+            let mut extra_options = ConnectionString::new();
+            for (keyword, value) in &extra {
+                extra_options.set(keyword, value.as_str());
+            }
+            let conn = SqlConnection {
+                id: None,
+                name: String::new(),
+                server,
+                port,
+                database_name,
+                username: username.filter(|_| authentication.uses_username()),
+                password: password.filter(|_| authentication.uses_password()),
+                password_is_reference: false,
+                connection_string: None,
+                authentication,
+                tenant_id: None,
+                trust_server_cert,
+                // Strict encryption is always written and read back as encrypted
+                encrypt: encrypt || strict_encryption,
+                connect_timeout,
+                command_timeout,
+                read_only_intent,
+                multi_subnet_failover,
+                strict_encryption,
+                host_name_in_certificate,
+                server_certificate,
+                application_name,
+                extra_options: (!extra_options.is_empty()).then(|| extra_options.to_string()),
+                created_at: None,
+                updated_at: None,
+            };
+
+            let parsed = parse_connection_string(&build_connection_string(&conn)).unwrap();
+            prop_assert_eq!(parsed, conn);
+        }
+
+        #[test]
+        fn test_connection_string_round_trip(extra in extra_pairs()) {
+            let mut cs = ConnectionString::new();
+            for (keyword, value) in &extra {
+                cs.set(keyword, value.as_str());
+            }
+            prop_assert_eq!(ConnectionString::parse(&cs.to_string()).unwrap(), cs);
+        }
+    }
 }
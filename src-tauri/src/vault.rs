@@ -0,0 +1,557 @@
+//! Optional master-password encryption of the secrets stored with connections.
+//!
+//! The key is derived with Argon2id and never stored; the `master_key` table
+//! only keeps the salt, the KDF parameters and a verifier encrypted with the
+//! key. Secrets are sealed with ChaCha20-Poly1305 and stored as
+//! `enc:v2:<hex nonce + ciphertext>`, using the column name and row id as
+//! associated data so a value can't be moved to another column or connection
+//! unnoticed.
+
+use anyhow::{anyhow, Context, Result};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Nonce};
+use chrono::Utc;
+use rusqlite::{Connection, OptionalExtension};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{Emitter, Manager};
+use zeroize::Zeroizing;
+
+const SEALED_PREFIX: &str = "enc:v2:";
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const SALT_LEN: usize = 16;
+const VERIFIER: &[u8] = b"shapac master key";
+const VERIFIER_COLUMN: &str = "master_key";
+/// The `master_key` row holding the verifier.
+const VERIFIER_ROW: i64 = 1;
+
+/// Connection columns holding secrets.
+pub const SECRET_COLUMNS: [&str; 2] = ["password", "connection_string"];
+
+pub const LOCKED_MESSAGE: &str =
+    "Saved connections are locked. Unlock them with the master password first.";
+
+/// Argon2id memory cost in KiB (OWASP's 19 MiB baseline); tests use less to stay fast.
+#[cfg(not(test))]
+const KDF_MEMORY_KIB: u32 = 19 * 1024;
+#[cfg(test)]
+const KDF_MEMORY_KIB: u32 = 1024;
+const KDF_ITERATIONS: u32 = 2;
+const KDF_PARALLELISM: u32 = 1;
+
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+type Key = Zeroizing<[u8; KEY_LEN]>;
+
+/// The unlocked key, held in memory until locked or idle for too long.
+#[derive(Default)]
+pub struct Vault {
+    state: Mutex<VaultState>,
+}
+
+#[derive(Default)]
+struct VaultState {
+    key: Option<Key>,
+    last_used: Option<Instant>,
+    /// `None` never locks automatically.
+    idle_timeout: Option<Duration>,
+}
+
+impl VaultState {
+    fn is_idle(&self) -> bool {
+        match (self.idle_timeout, self.last_used) {
+            (Some(timeout), Some(last_used)) => last_used.elapsed() >= timeout,
+            _ => false,
+        }
+    }
+}
+
+impl Vault {
+    /// The key for sealing or opening secrets, refreshing the idle timer.
+    /// `None` when locked.
+    fn key(&self) -> Option<Key> {
+        let mut state = self.state.lock().unwrap();
+        if state.is_idle() {
+            state.key = None;
+        }
+        state.last_used = Some(Instant::now());
+        state.key.clone()
+    }
+
+    pub fn is_unlocked(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.key.is_some() && !state.is_idle()
+    }
+
+    pub fn lock(&self) {
+        self.state.lock().unwrap().key = None;
+    }
+
+    /// Locks when idle for longer than the timeout. Returns true if it just locked.
+    pub fn expire_if_idle(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.key.is_some() && state.is_idle() {
+            state.key = None;
+            return true;
+        }
+        false
+    }
+
+    /// Minutes of inactivity before locking; 0 disables automatic locking.
+    pub fn set_idle_timeout(&self, minutes: u32) {
+        self.state.lock().unwrap().idle_timeout =
+            (minutes > 0).then(|| Duration::from_secs(minutes as u64 * 60));
+    }
+
+    fn set_key(&self, key: Key) {
+        let mut state = self.state.lock().unwrap();
+        state.key = Some(key);
+        state.last_used = Some(Instant::now());
+    }
+
+    pub fn unlock(&self, conn: &Connection, password: &str) -> Result<()> {
+        let record = MasterKeyRecord::load(conn)?
+            .ok_or_else(|| anyhow!("No master password has been set"))?;
+        let key = record.verify(password)?;
+        self.set_key(key);
+        Ok(())
+    }
+
+    /// Prepares a secret for storage: sealed while a master password is set,
+    /// unchanged otherwise.
+    pub fn conceal(
+        &self,
+        conn: &Connection,
+        column: &str,
+        row_id: i64,
+        value: Option<String>,
+    ) -> Result<Option<String>> {
+        let Some(value) = value.filter(|v| !v.is_empty()) else {
+            return Ok(None);
+        };
+        if !is_enabled(conn)? {
+            return Ok(Some(value));
+        }
+        let key = self.key().ok_or_else(|| anyhow!(LOCKED_MESSAGE))?;
+        Ok(Some(seal(&key, column, row_id, &value)?))
+    }
+
+    /// Recovers a stored secret. Values stored before encryption was turned on
+    /// are returned as they are.
+    pub fn reveal(
+        &self,
+        column: &str,
+        row_id: i64,
+        value: Option<String>,
+    ) -> Result<Option<String>> {
+        match value {
+            Some(value) if is_sealed(&value) => {
+                let key = self.key().ok_or_else(|| anyhow!(LOCKED_MESSAGE))?;
+                Ok(Some(open(&key, column, row_id, &value)?))
+            }
+            other => Ok(other),
+        }
+    }
+}
+
+/// Locks the vault once it has been idle too long and tells the frontend.
+pub fn spawn_idle_lock(app_handle: tauri::AppHandle) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(IDLE_CHECK_INTERVAL);
+        if app_handle.state::<Vault>().expire_if_idle() {
+            let _ = app_handle.emit("vault-locked", ());
+        }
+    });
+}
+
+pub fn is_sealed(value: &str) -> bool {
+    value.starts_with(SEALED_PREFIX)
+}
+
+fn associated_data(column: &str, row_id: i64) -> Vec<u8> {
+    format!("{}:{}", column, row_id).into_bytes()
+}
+
+fn seal(key: &Key, column: &str, row_id: i64, plaintext: &str) -> Result<String> {
+    let cipher = ChaCha20Poly1305::new(key.as_ref().into());
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: plaintext.as_bytes(),
+                aad: &associated_data(column, row_id),
+            },
+        )
+        .map_err(|_| anyhow!("Failed to encrypt {}", column))?;
+
+    let mut sealed = nonce.to_vec();
+    sealed.extend_from_slice(&ciphertext);
+    Ok(format!("{}{}", SEALED_PREFIX, hex::encode(sealed)))
+}
+
+fn open(key: &Key, column: &str, row_id: i64, sealed: &str) -> Result<String> {
+    let bytes = sealed
+        .strip_prefix(SEALED_PREFIX)
+        .and_then(|encoded| hex::decode(encoded).ok())
+        .filter(|bytes| bytes.len() > NONCE_LEN)
+        .ok_or_else(|| anyhow!("Stored {} is not a valid encrypted value", column))?;
+    let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+    let plaintext = ChaCha20Poly1305::new(key.as_ref().into())
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: &associated_data(column, row_id),
+            },
+        )
+        .map_err(|_| anyhow!("Stored {} could not be decrypted", column))?;
+    String::from_utf8(plaintext).with_context(|| format!("Stored {} is not valid UTF-8", column))
+}
+
+struct MasterKeyRecord {
+    salt: Vec<u8>,
+    kdf_params: String,
+    verifier: String,
+}
+
+impl MasterKeyRecord {
+    fn load(conn: &Connection) -> Result<Option<Self>> {
+        let record = conn
+            .query_row(
+                "SELECT salt, kdf_params, verifier FROM master_key WHERE id = 1",
+                [],
+                |row| Ok((row.get::<_, String>(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()?;
+        record
+            .map(|(salt, kdf_params, verifier)| {
+                Ok(MasterKeyRecord {
+                    salt: hex::decode(salt).context("Stored master key salt is corrupt")?,
+                    kdf_params,
+                    verifier,
+                })
+            })
+            .transpose()
+    }
+
+    /// A fresh salt and verifier for `password`, returning the derived key too.
+    fn create(password: &str) -> Result<(Self, Key)> {
+        if password.chars().count() < 8 {
+            return Err(anyhow!("The master password must be at least 8 characters"));
+        }
+        let mut salt = vec![0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let kdf_params = format!(
+            "argon2id$m={},t={},p={}",
+            KDF_MEMORY_KIB, KDF_ITERATIONS, KDF_PARALLELISM
+        );
+        let key = derive_key(password, &salt, &kdf_params)?;
+        let verifier = seal(
+            &key,
+            VERIFIER_COLUMN,
+            VERIFIER_ROW,
+            std::str::from_utf8(VERIFIER).unwrap(),
+        )?;
+        Ok((
+            MasterKeyRecord {
+                salt,
+                kdf_params,
+                verifier,
+            },
+            key,
+        ))
+    }
+
+    fn verify(&self, password: &str) -> Result<Key> {
+        let key = derive_key(password, &self.salt, &self.kdf_params)?;
+        match open(&key, VERIFIER_COLUMN, VERIFIER_ROW, &self.verifier) {
+            Ok(text) if text.as_bytes() == VERIFIER => Ok(key),
+            _ => Err(anyhow!("Wrong master password")),
+        }
+    }
+
+    fn save(&self, conn: &Connection) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+        conn.execute(
+            "INSERT INTO master_key (id, salt, kdf_params, verifier, created_at, updated_at)
+             VALUES (1, ?1, ?2, ?3, ?4, ?4)
+             ON CONFLICT(id) DO UPDATE SET salt = ?1, kdf_params = ?2, verifier = ?3, updated_at = ?4",
+            (hex::encode(&self.salt), &self.kdf_params, &self.verifier, &now),
+        )?;
+        Ok(())
+    }
+}
+
+fn derive_key(password: &str, salt: &[u8], kdf_params: &str) -> Result<Key> {
+    let mut costs = (KDF_MEMORY_KIB, KDF_ITERATIONS, KDF_PARALLELISM);
+    let settings = kdf_params
+        .strip_prefix("argon2id$")
+        .ok_or_else(|| anyhow!("Unsupported key derivation {}", kdf_params))?;
+    for setting in settings.split(',') {
+        let (name, value) = setting
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Invalid key derivation settings {}", kdf_params))?;
+        let value: u32 = value
+            .parse()
+            .with_context(|| format!("Invalid key derivation settings {}", kdf_params))?;
+        match name {
+            "m" => costs.0 = value,
+            "t" => costs.1 = value,
+            "p" => costs.2 = value,
+            _ => return Err(anyhow!("Invalid key derivation settings {}", kdf_params)),
+        }
+    }
+
+    let params = argon2::Params::new(costs.0, costs.1, costs.2, Some(KEY_LEN))
+        .map_err(|e| anyhow!("Invalid key derivation settings: {}", e))?;
+    let mut key = Zeroizing::new([0u8; KEY_LEN]);
+    argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params)
+        .hash_password_into(password.as_bytes(), salt, key.as_mut())
+        .map_err(|e| anyhow!("Key derivation failed: {}", e))?;
+    Ok(key)
+}
+
+pub fn is_enabled(conn: &Connection) -> Result<bool> {
+    Ok(MasterKeyRecord::load(conn)?.is_some())
+}
+
+/// Rewrites every stored secret with `transform`, given its column and row id,
+/// inside the caller's transaction.
+fn rewrite_secrets(
+    conn: &Connection,
+    mut transform: impl FnMut(&str, i64, String) -> Result<String>,
+) -> Result<()> {
+    let rows = {
+        let mut stmt =
+            conn.prepare("SELECT id, password, connection_string FROM sql_connections")?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        rows
+    };
+
+    for (id, password, connection_string) in rows {
+        let stored = [password, connection_string];
+        let mut values = Vec::new();
+        for (column, value) in SECRET_COLUMNS.iter().zip(stored.clone()) {
+            values.push(match value.filter(|v| !v.is_empty()) {
+                Some(value) => Some(transform(column, id, value)?),
+                None => None,
+            });
+        }
+        if values == stored {
+            continue;
+        }
+        conn.execute(
+            "UPDATE sql_connections SET password = ?1, connection_string = ?2 WHERE id = ?3",
+            (&values[0], &values[1], id),
+        )?;
+    }
+    Ok(())
+}
+
+/// Makes sure the secrets a rewrite replaced don't linger in free pages of the
+/// database file. Call with `secure_delete` set before the rewrite.
+fn scrub(conn: &Connection) -> Result<()> {
+    conn.execute_batch("VACUUM")
+        .context("Could not compact the database after rewriting secrets")
+}
+
+/// Deletes the backups taken before schema migrations; they hold secrets as
+/// they were stored before this rewrite.
+fn remove_backups(conn: &Connection) -> Result<()> {
+    match conn.path().filter(|path| !path.is_empty()) {
+        Some(path) => crate::db::remove_backups(Path::new(path)),
+        None => Ok(()),
+    }
+}
+
+/// Turns encryption on, sealing every secret stored so far.
+pub fn enable(conn: &mut Connection, vault: &Vault, password: &str) -> Result<()> {
+    if is_enabled(conn)? {
+        return Err(anyhow!("A master password is already set"));
+    }
+    let (record, key) = MasterKeyRecord::create(password)?;
+
+    conn.pragma_update(None, "secure_delete", "ON")?;
+    let tx = conn.transaction()?;
+    record.save(&tx)?;
+    rewrite_secrets(&tx, |column, id, value| {
+        if is_sealed(&value) {
+            Err(anyhow!(
+                "Stored {} is already encrypted with another key",
+                column
+            ))
+        } else {
+            seal(&key, column, id, &value)
+        }
+    })?;
+    tx.commit()?;
+    scrub(conn)?;
+    remove_backups(conn)?;
+
+    vault.set_key(key);
+    Ok(())
+}
+
+/// Re-encrypts every secret under a key derived from `new_password`.
+pub fn change_password(
+    conn: &mut Connection,
+    vault: &Vault,
+    current_password: &str,
+    new_password: &str,
+) -> Result<()> {
+    let current =
+        MasterKeyRecord::load(conn)?.ok_or_else(|| anyhow!("No master password has been set"))?;
+    let old_key = current.verify(current_password)?;
+    let (record, new_key) = MasterKeyRecord::create(new_password)?;
+
+    conn.pragma_update(None, "secure_delete", "ON")?;
+    let tx = conn.transaction()?;
+    record.save(&tx)?;
+    rewrite_secrets(&tx, |column, id, value| {
+        let plaintext = if is_sealed(&value) {
+            open(&old_key, column, id, &value)?
+        } else {
+            value
+        };
+        seal(&new_key, column, id, &plaintext)
+    })?;
+    tx.commit()?;
+    scrub(conn)?;
+    remove_backups(conn)?;
+
+    vault.set_key(new_key);
+    Ok(())
+}
+
+/// Turns encryption off, storing every secret in plain text again.
+pub fn disable(conn: &mut Connection, vault: &Vault, password: &str) -> Result<()> {
+    let current =
+        MasterKeyRecord::load(conn)?.ok_or_else(|| anyhow!("No master password has been set"))?;
+    let key = current.verify(password)?;
+
+    conn.pragma_update(None, "secure_delete", "ON")?;
+    let tx = conn.transaction()?;
+    rewrite_secrets(&tx, |column, id, value| {
+        if is_sealed(&value) {
+            open(&key, column, id, &value)
+        } else {
+            Ok(value)
+        }
+    })?;
+    tx.execute("DELETE FROM master_key", [])?;
+    tx.commit()?;
+    scrub(conn)?;
+
+    vault.lock();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stored(conn: &Connection) -> (Option<String>, Option<String>) {
+        conn.query_row(
+            "SELECT password, connection_string FROM sql_connections",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_master_password_lifecycle() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("shapac.db");
+        let backup = dir.path().join("shapac.v3.db.bak");
+        std::fs::write(&backup, "Password=secret").unwrap();
+        let mut conn = Connection::open(&db_path).unwrap();
+        crate::db::migrate(&mut conn).unwrap();
+        conn.execute(
+            "INSERT INTO sql_connections (name, server, password, connection_string, created_at, updated_at)
+             VALUES ('dev', 'localhost', 'secret', 'Server=x;Password=secret', 'now', 'now')",
+            [],
+        )
+        .unwrap();
+        let id = conn.last_insert_rowid();
+        let vault = Vault::default();
+
+        assert!(enable(&mut conn, &vault, "short").is_err());
+        enable(&mut conn, &vault, "correct horse").unwrap();
+        assert!(!backup.exists());
+        assert!(db_path.exists());
+        let (password, connection_string) = stored(&conn);
+        assert!(is_sealed(password.as_deref().unwrap()));
+        assert!(is_sealed(connection_string.as_deref().unwrap()));
+        assert_eq!(
+            vault
+                .reveal("password", id, password.clone())
+                .unwrap()
+                .as_deref(),
+            Some("secret")
+        );
+        // Sealed values are bound to their column and row
+        assert!(vault
+            .reveal("connection_string", id, password.clone())
+            .is_err());
+        assert!(vault.reveal("password", id + 1, password.clone()).is_err());
+
+        vault.lock();
+        assert!(vault.reveal("password", id, password.clone()).is_err());
+        assert!(vault
+            .conceal(&conn, "password", id, Some("new".into()))
+            .is_err());
+        assert!(vault.unlock(&conn, "wrong password").is_err());
+        vault.unlock(&conn, "correct horse").unwrap();
+
+        change_password(&mut conn, &vault, "correct horse", "battery staple").unwrap();
+        let (password, _) = stored(&conn);
+        vault.lock();
+        assert!(vault.unlock(&conn, "correct horse").is_err());
+        vault.unlock(&conn, "battery staple").unwrap();
+        assert_eq!(
+            vault.reveal("password", id, password).unwrap().as_deref(),
+            Some("secret")
+        );
+
+        disable(&mut conn, &vault, "battery staple").unwrap();
+        assert_eq!(
+            stored(&conn),
+            (
+                Some("secret".to_string()),
+                Some("Server=x;Password=secret".to_string())
+            )
+        );
+        assert_eq!(
+            vault
+                .conceal(&conn, "password", id, Some("plain".into()))
+                .unwrap()
+                .as_deref(),
+            Some("plain")
+        );
+    }
+
+    #[test]
+    fn test_idle_lock() {
+        let vault = Vault::default();
+        vault.set_key(Zeroizing::new([7u8; KEY_LEN]));
+        vault.set_idle_timeout(1);
+        assert!(!vault.expire_if_idle());
+        vault.state.lock().unwrap().last_used = Some(Instant::now() - Duration::from_secs(61));
+        assert!(!vault.is_unlocked());
+        assert!(vault.expire_if_idle());
+        assert!(vault.key().is_none());
+    }
+}
@@ -7,20 +7,20 @@ import { ImportDialog } from "./components/ImportDialog";
 import { SettingsDialog } from "./components/SettingsDialog";
 import { SqlPackageNotInstalled } from "./components/SqlPackageNotInstalled";
 import { api } from "./utils/api";
-import type { SqlConnection } from "./types";
+import type { SavedConnection } from "./types";
 import "./App.css";
 
 function App() {
   const [sqlPackageInstalled, setSqlPackageInstalled] = useState<boolean | null>(null);
   const [showConnectionForm, setShowConnectionForm] = useState(false);
-  const [editConnection, setEditConnection] = useState<SqlConnection | null>(
+  const [editConnection, setEditConnection] = useState<SavedConnection | null>(
     null
   );
   const [showExportDialog, setShowExportDialog] = useState(false);
   const [showImportDialog, setShowImportDialog] = useState(false);
   const [showSettingsDialog, setShowSettingsDialog] = useState(false);
   const [selectedConnection, setSelectedConnection] =
-    useState<SqlConnection | null>(null);
+    useState<SavedConnection | null>(null);
   const [refreshKey, setRefreshKey] = useState(0);
 
   // Check if SqlPackage is installed on startup
@@ -43,12 +43,12 @@ function App() {
     setShowConnectionForm(true);
   };
 
-  const handleEditConnection = (connection: SqlConnection) => {
+  const handleEditConnection = (connection: SavedConnection) => {
     setEditConnection(connection);
     setShowConnectionForm(true);
   };
 
-  const handleExport = (connection: SqlConnection) => {
+  const handleExport = (connection: SavedConnection) => {
     setSelectedConnection(connection);
     setShowExportDialog(true);
   };
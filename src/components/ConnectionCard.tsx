@@ -1,12 +1,13 @@
 import { useState } from "react";
-import type { SqlConnection } from "../types";
+import type { SavedConnection } from "../types";
 import { buildConnectionString } from "../utils/connectionString";
+import { authMethodLabel } from "../utils/authentication";
 
 interface ConnectionCardProps {
-  connection: SqlConnection;
-  onEdit: (connection: SqlConnection) => void;
+  connection: SavedConnection;
+  onEdit: (connection: SavedConnection) => void;
   onDelete: (id: number) => void;
-  onExport: (connection: SqlConnection) => void;
+  onExport: (connection: SavedConnection) => void;
 }
 
 export function ConnectionCard({
@@ -50,10 +51,12 @@ export function ConnectionCard({
             <span className="value">{connection.username}</span>
           </div>
         )}
-        {connection.use_windows_auth && (
+        {connection.authentication !== "sql_password" && (
           <div className="info-row">
             <span className="label">🔐 Auth</span>
-            <span className="value">Windows Authentication</span>
+            <span className="value">
+              {authMethodLabel(connection.authentication)}
+            </span>
           </div>
         )}
       </div>
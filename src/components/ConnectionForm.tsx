@@ -1,11 +1,43 @@
 import { useState, useEffect } from "react";
 import { useConnections } from "../hooks/useConnections";
-import type { SqlConnection } from "../types";
+import type {
+  AuthMethod,
+  ConnectionFailure,
+  ConnectionTestResult,
+  SavedConnection,
+  ServerEngine,
+  SqlConnection,
+} from "../types";
+import { api } from "../utils/api";
+import {
+  AUTH_METHODS,
+  passwordLabel,
+  usernameLabel,
+  usesTenant,
+} from "../utils/authentication";
+
+const FAILURE_HINTS: Record<ConnectionFailure, string> = {
+  dns: "The server name could not be resolved",
+  tcp_refused: "Nothing accepted the connection; check the port and firewall",
+  tls: "The TLS handshake failed; check the certificate options",
+  login_failed: "The server rejected the credentials",
+  database_not_accessible: "The login cannot open the database",
+  timeout: "The server did not answer in time",
+  other: "The connection failed",
+};
+
+const ENGINE_LABELS: Record<ServerEngine, string> = {
+  on_premises: "SQL Server",
+  azure_sql_database: "Azure SQL Database",
+  azure_managed_instance: "Azure SQL Managed Instance",
+  azure_synapse: "Azure Synapse Analytics",
+  azure_sql_edge: "Azure SQL Edge",
+};
 
 interface ConnectionFormProps {
   isOpen: boolean;
   onClose: () => void;
-  editConnection?: SqlConnection | null;
+  editConnection?: SavedConnection | null;
   onSave?: () => void;
 }
 
@@ -17,6 +49,10 @@ export function ConnectionForm({
 }: ConnectionFormProps) {
   const { createConnection, updateConnection } = useConnections();
   const [activeTab, setActiveTab] = useState<"quick" | "advanced">("advanced");
+  const [testing, setTesting] = useState(false);
+  const [testResult, setTestResult] = useState<ConnectionTestResult | null>(
+    null
+  );
   const [formData, setFormData] = useState<SqlConnection>({
     name: "",
     server: "",
@@ -24,19 +60,17 @@ export function ConnectionForm({
     username: "",
     password: "",
     connection_string: "",
-    use_windows_auth: false,
+    authentication: "sql_password",
     trust_server_cert: true,
     encrypt: true,
   });
 
   useEffect(() => {
+    setTestResult(null);
     if (editConnection) {
-      setFormData(editConnection);
-      setActiveTab(
-        editConnection.connection_string && !editConnection.server
-          ? "quick"
-          : "advanced"
-      );
+      const { has_password: _, ...fields } = editConnection;
+      setFormData({ ...fields, password: "" });
+      setActiveTab(editConnection.connection_string ? "quick" : "advanced");
     } else {
       setFormData({
         name: "",
@@ -45,18 +79,50 @@ export function ConnectionForm({
         username: "",
         password: "",
         connection_string: "",
-        use_windows_auth: false,
+        authentication: "sql_password",
         trust_server_cert: true,
         encrypt: true,
       });
     }
   }, [editConnection, isOpen]);
 
+  // Secrets the user didn't touch are left out so the saved ones are kept
+  const editedFields = (edited: SavedConnection): SqlConnection => ({
+    ...formData,
+    password: formData.password || undefined,
+    connection_string:
+      formData.connection_string === edited.connection_string
+        ? undefined
+        : formData.connection_string,
+  });
+
+  const handleTest = async () => {
+    setTesting(true);
+    setTestResult(null);
+    try {
+      setTestResult(
+        await api.testConnection(
+          editConnection?.id
+            ? { ...editedFields(editConnection), id: editConnection.id }
+            : formData
+        )
+      );
+    } catch (error) {
+      setTestResult({
+        status: "failed",
+        reason: "other",
+        message: String(error),
+      });
+    } finally {
+      setTesting(false);
+    }
+  };
+
   const handleSubmit = async (e: React.FormEvent) => {
     e.preventDefault();
     try {
       if (editConnection?.id) {
-        await updateConnection(editConnection.id, formData);
+        await updateConnection(editConnection.id, editedFields(editConnection));
       } else {
         await createConnection(formData);
       }
@@ -136,7 +202,7 @@ export function ConnectionForm({
                   onChange={(e) =>
                     setFormData({ ...formData, server: e.target.value })
                   }
-                  placeholder="localhost or server.example.com"
+                  placeholder="host, host\INSTANCE or tcp:host"
                 />
               </div>
 
@@ -166,62 +232,264 @@ export function ConnectionForm({
                 />
               </div>
 
+              <div className="form-group">
+                <label htmlFor="authentication">Authentication</label>
+                <select
+                  id="authentication"
+                  value={formData.authentication ?? "sql_password"}
+                  onChange={(e) =>
+                    setFormData({
+                      ...formData,
+                      authentication: e.target.value as AuthMethod,
+                    })
+                  }
+                >
+                  {AUTH_METHODS.map((method) => (
+                    <option key={method.value} value={method.value}>
+                      {method.label}
+                    </option>
+                  ))}
+                </select>
+              </div>
+
+              {usernameLabel(formData.authentication) && (
+                <div className="form-group">
+                  <label htmlFor="username">
+                    {usernameLabel(formData.authentication)}
+                  </label>
+                  <input
+                    id="username"
+                    type="text"
+                    value={formData.username || ""}
+                    onChange={(e) =>
+                      setFormData({ ...formData, username: e.target.value })
+                    }
+                    placeholder={
+                      formData.authentication === "active_directory_password"
+                        ? "user@contoso.com"
+                        : formData.authentication === "sql_password"
+                          ? "SQL Server username"
+                          : "Application (client) ID"
+                    }
+                  />
+                </div>
+              )}
+
+              {passwordLabel(formData.authentication) && (
+                <div className="form-group">
+                  <label htmlFor="password">
+                    {passwordLabel(formData.authentication)}
+                  </label>
+                  <input
+                    id="password"
+                    type={formData.password_is_reference ? "text" : "password"}
+                    value={formData.password || ""}
+                    onChange={(e) =>
+                      setFormData({ ...formData, password: e.target.value })
+                    }
+                    placeholder={
+                      editConnection?.has_password
+                        ? "Leave blank to keep the saved value"
+                        : (passwordLabel(formData.authentication) ?? "")
+                    }
+                  />
+                  <div className="checkbox-group">
+                    <label>
+                      <input
+                        type="checkbox"
+                        checked={formData.password_is_reference ?? false}
+                        onChange={(e) =>
+                          setFormData({
+                            ...formData,
+                            password_is_reference: e.target.checked,
+                          })
+                        }
+                      />
+                      Reference to a secret stored elsewhere
+                    </label>
+                  </div>
+                  {formData.password_is_reference && (
+                    <small
+                      style={{
+                        color: "#666",
+                        marginTop: "4px",
+                        display: "block",
+                      }}
+                    >
+                      Resolved when a job runs on the saved connection: env:NAME,
+                      file:/path, vault:path#field, or cmd:command when the app
+                      runs with SHAPAC_ALLOW_COMMAND_SECRETS=1
+                    </small>
+                  )}
+                </div>
+              )}
+
+              {usesTenant(formData.authentication) && (
+                <div className="form-group">
+                  <label htmlFor="tenant_id">Tenant ID</label>
+                  <input
+                    id="tenant_id"
+                    type="text"
+                    value={formData.tenant_id || ""}
+                    onChange={(e) =>
+                      setFormData({ ...formData, tenant_id: e.target.value })
+                    }
+                    placeholder="Tenant GUID or contoso.onmicrosoft.com"
+                  />
+                </div>
+              )}
+
               <div className="form-group checkbox-group">
                 <label>
                   <input
                     type="checkbox"
-                    checked={formData.use_windows_auth}
+                    checked={formData.encrypt}
+                    onChange={(e) =>
+                      setFormData({ ...formData, encrypt: e.target.checked })
+                    }
+                  />
+                  Encrypt Connection
+                </label>
+              </div>
+
+              <div className="form-group checkbox-group">
+                <label>
+                  <input
+                    type="checkbox"
+                    checked={formData.trust_server_cert}
                     onChange={(e) =>
                       setFormData({
                         ...formData,
-                        use_windows_auth: e.target.checked,
+                        trust_server_cert: e.target.checked,
                       })
                     }
                   />
-                  Use Windows Authentication
+                  Trust Server Certificate
                 </label>
               </div>
 
-              {!formData.use_windows_auth && (
+              {formData.encrypt && (
                 <>
+                  <div className="form-group checkbox-group">
+                    <label>
+                      <input
+                        type="checkbox"
+                        checked={formData.strict_encryption ?? false}
+                        onChange={(e) =>
+                          setFormData({
+                            ...formData,
+                            strict_encryption: e.target.checked,
+                          })
+                        }
+                      />
+                      Strict Encryption (TDS 8, SQL Server 2022 and Azure)
+                    </label>
+                  </div>
+
                   <div className="form-group">
-                    <label htmlFor="username">Username</label>
+                    <label htmlFor="host_name_in_certificate">
+                      Host Name in Certificate
+                    </label>
                     <input
-                      id="username"
+                      id="host_name_in_certificate"
                       type="text"
-                      value={formData.username || ""}
+                      value={formData.host_name_in_certificate || ""}
                       onChange={(e) =>
-                        setFormData({ ...formData, username: e.target.value })
+                        setFormData({
+                          ...formData,
+                          host_name_in_certificate: e.target.value,
+                        })
                       }
-                      placeholder="SQL Server username"
+                      placeholder="Optional - when the certificate names another host"
                     />
                   </div>
 
                   <div className="form-group">
-                    <label htmlFor="password">Password</label>
+                    <label htmlFor="server_certificate">
+                      Server Certificate
+                    </label>
                     <input
-                      id="password"
-                      type="password"
-                      value={formData.password || ""}
+                      id="server_certificate"
+                      type="text"
+                      value={formData.server_certificate || ""}
                       onChange={(e) =>
-                        setFormData({ ...formData, password: e.target.value })
+                        setFormData({
+                          ...formData,
+                          server_certificate: e.target.value,
+                        })
                       }
-                      placeholder="SQL Server password"
+                      placeholder="Optional - path to the certificate the server must present"
                     />
                   </div>
                 </>
               )}
 
+              <div className="form-group">
+                <label htmlFor="application_name">Application Name</label>
+                <input
+                  id="application_name"
+                  type="text"
+                  value={formData.application_name || ""}
+                  onChange={(e) =>
+                    setFormData({ ...formData, application_name: e.target.value })
+                  }
+                  placeholder="Optional - shown in sys.dm_exec_sessions"
+                />
+              </div>
+
+              <div className="form-group">
+                <label htmlFor="connect_timeout">Connect Timeout (seconds)</label>
+                <input
+                  id="connect_timeout"
+                  type="number"
+                  min={0}
+                  value={formData.connect_timeout ?? ""}
+                  onChange={(e) =>
+                    setFormData({
+                      ...formData,
+                      connect_timeout:
+                        e.target.value === ""
+                          ? undefined
+                          : Number(e.target.value),
+                    })
+                  }
+                  placeholder="15 (default)"
+                />
+              </div>
+
+              <div className="form-group">
+                <label htmlFor="command_timeout">Command Timeout (seconds)</label>
+                <input
+                  id="command_timeout"
+                  type="number"
+                  min={0}
+                  value={formData.command_timeout ?? ""}
+                  onChange={(e) =>
+                    setFormData({
+                      ...formData,
+                      command_timeout:
+                        e.target.value === ""
+                          ? undefined
+                          : Number(e.target.value),
+                    })
+                  }
+                  placeholder="Optional - 0 waits indefinitely"
+                />
+              </div>
+
               <div className="form-group checkbox-group">
                 <label>
                   <input
                     type="checkbox"
-                    checked={formData.encrypt}
+                    checked={formData.read_only_intent ?? false}
                     onChange={(e) =>
-                      setFormData({ ...formData, encrypt: e.target.checked })
+                      setFormData({
+                        ...formData,
+                        read_only_intent: e.target.checked,
+                      })
                     }
                   />
-                  Encrypt Connection
+                  Read-Only Intent (export from an Availability Group secondary)
                 </label>
               </div>
 
@@ -229,25 +497,48 @@ export function ConnectionForm({
                 <label>
                   <input
                     type="checkbox"
-                    checked={formData.trust_server_cert}
+                    checked={formData.multi_subnet_failover ?? false}
                     onChange={(e) =>
                       setFormData({
                         ...formData,
-                        trust_server_cert: e.target.checked,
+                        multi_subnet_failover: e.target.checked,
                       })
                     }
                   />
-                  Trust Server Certificate
+                  Multi-Subnet Failover
                 </label>
               </div>
             </>
           )}
+
+          {testResult?.status === "connected" && (
+            <div className="success-message">
+              Connected to {ENGINE_LABELS[testResult.engine]}{" "}
+              {testResult.product_version} ({testResult.edition}) as{" "}
+              {testResult.login_name}, database {testResult.database_name}, in{" "}
+              {testResult.elapsed_ms} ms
+            </div>
+          )}
+          {testResult?.status === "failed" && (
+            <div className="error-message">
+              <strong>{FAILURE_HINTS[testResult.reason]}</strong>
+              <div>{testResult.message}</div>
+            </div>
+          )}
           </div>
 
           <div className="modal-actions">
             <button type="button" className="btn-secondary" onClick={onClose}>
               Cancel
             </button>
+            <button
+              type="button"
+              className="btn-secondary"
+              onClick={handleTest}
+              disabled={testing}
+            >
+              {testing ? "Testing..." : "Test Connection"}
+            </button>
             <button type="submit" className="btn-primary">
               {editConnection ? "Update" : "Create"}
             </button>
@@ -1,11 +1,11 @@
 import { useEffect } from "react";
 import { useConnections } from "../hooks/useConnections";
 import { ConnectionCard } from "./ConnectionCard";
-import type { SqlConnection } from "../types";
+import type { SavedConnection } from "../types";
 
 interface ConnectionListProps {
-  onEdit: (connection: SqlConnection) => void;
-  onExport: (connection: SqlConnection) => void;
+  onEdit: (connection: SavedConnection) => void;
+  onExport: (connection: SavedConnection) => void;
 }
 
 export function ConnectionList({
@@ -0,0 +1,59 @@
+import type { DatabaseStats } from "../types";
+import { formatBytes } from "../utils/format";
+
+/** How many of the largest tables to list. */
+const LARGEST_TABLES = 10;
+
+interface DatabaseStatsPanelProps {
+  stats: DatabaseStats;
+}
+
+export function DatabaseStatsPanel({ stats }: DatabaseStatsPanelProps) {
+  const totalRows = stats.tables.reduce((sum, t) => sum + t.row_count, 0);
+
+  return (
+    <div className="section">
+      <h3 className="section-title">{stats.database_name}</h3>
+      <p className="help-text">
+        Data files {formatBytes(stats.data_bytes)}, log{" "}
+        {formatBytes(stats.log_bytes)}; {stats.tables.length} tables with{" "}
+        {totalRows.toLocaleString()} rows
+      </p>
+
+      {stats.risks.map((risk) => (
+        <div key={risk.kind} className="warning-message">
+          <strong>{risk.message}</strong>
+          <ul>
+            {risk.objects.map((object) => (
+              <li key={object}>{object}</li>
+            ))}
+          </ul>
+        </div>
+      ))}
+
+      {stats.tables.length > 0 && (
+        <table className="stats-table">
+          <thead>
+            <tr>
+              <th>Largest Tables</th>
+              <th>Rows</th>
+              <th>Reserved</th>
+            </tr>
+          </thead>
+          <tbody>
+            {stats.tables.slice(0, LARGEST_TABLES).map((table) => (
+              <tr key={`${table.schema}.${table.name}`}>
+                <td>
+                  {table.schema}.{table.name}
+                  {table.is_memory_optimized && " (memory-optimized)"}
+                </td>
+                <td>{table.row_count.toLocaleString()}</td>
+                <td>{formatBytes(table.reserved_bytes)}</td>
+              </tr>
+            ))}
+          </tbody>
+        </table>
+      )}
+    </div>
+  );
+}
@@ -1,12 +1,16 @@
 import { useState, useEffect } from "react";
 import { useSqlPackage } from "../hooks/useSqlPackage";
-import type { SqlConnection } from "../types";
+import { useDatabases } from "../hooks/useDatabases";
+import { formatBytes } from "../utils/format";
+import { api } from "../utils/api";
+import { DatabaseStatsPanel } from "./DatabaseStatsPanel";
+import type { DatabaseStats, SavedConnection } from "../types";
 import { listen } from "@tauri-apps/api/event";
 
 interface ExportDialogProps {
   isOpen: boolean;
   onClose: () => void;
-  connection: SqlConnection | null;
+  connection: SavedConnection | null;
 }
 
 export function ExportDialog({
@@ -14,8 +18,20 @@ export function ExportDialog({
   onClose,
   connection,
 }: ExportDialogProps) {
-  const { exportBacpac, exporting, progress, error } = useSqlPackage();
+  const { exportBacpac, exportBacpacs, exporting, progress, error } =
+    useSqlPackage();
+  const {
+    databases,
+    loading: loadingDatabases,
+    error: databasesError,
+    refresh: refreshDatabases,
+  } = useDatabases(isOpen ? connection?.id : null);
   const [databaseName, setDatabaseName] = useState("");
+  const [selected, setSelected] = useState<string[]>([]);
+  const [showSystem, setShowSystem] = useState(false);
+  const [stats, setStats] = useState<DatabaseStats | null>(null);
+  const [loadingStats, setLoadingStats] = useState(false);
+  const [statsError, setStatsError] = useState<string | null>(null);
   const [outputMessages, setOutputMessages] = useState<string[]>([]);
   const [showOutput, setShowOutput] = useState(false);
 
@@ -38,6 +54,9 @@ export function ExportDialog({
     if (isOpen) {
       setOutputMessages([]);
       setShowOutput(false);
+      setSelected([]);
+      setStats(null);
+      setStatsError(null);
     }
   }, [isOpen]);
 
@@ -48,7 +67,11 @@ export function ExportDialog({
       databaseName || connection.database_name || "database";
 
     try {
-      await exportBacpac(connection.id, dbName);
+      if (selected.length > 1) {
+        await exportBacpacs(connection.id, selected);
+      } else {
+        await exportBacpac(connection.id, selected[0] ?? dbName);
+      }
       setTimeout(() => {
         onClose();
       }, 2000);
@@ -57,6 +80,35 @@ export function ExportDialog({
     }
   };
 
+  const statsDatabase =
+    selected.length === 1
+      ? selected[0]
+      : selected.length === 0
+        ? databaseName || connection?.database_name
+        : undefined;
+
+  const handleLoadStats = async () => {
+    if (!connection?.id || !statsDatabase) return;
+    setLoadingStats(true);
+    setStatsError(null);
+    try {
+      setStats(await api.getDatabaseStats(connection.id, statsDatabase));
+    } catch (err) {
+      setStats(null);
+      setStatsError(err instanceof Error ? err.message : String(err));
+    } finally {
+      setLoadingStats(false);
+    }
+  };
+
+  const toggleSelected = (name: string, checked: boolean) => {
+    setSelected((prev) =>
+      checked ? [...prev, name] : prev.filter((n) => n !== name)
+    );
+  };
+
+  const listed = databases.filter((db) => showSystem || !db.is_system);
+
   if (!isOpen || !connection) return null;
 
   return (
@@ -79,16 +131,101 @@ export function ExportDialog({
             <input
               id="database-name"
               type="text"
+              list="database-options"
               value={databaseName}
               onChange={(e) => setDatabaseName(e.target.value)}
               placeholder={
                 connection.database_name ||
                 "Enter database name to export"
               }
-              disabled={exporting}
+              disabled={exporting || selected.length > 0}
             />
+            <datalist id="database-options">
+              {databases
+                .filter((db) => db.state === "ONLINE")
+                .map((db) => (
+                  <option key={db.name} value={db.name} />
+                ))}
+            </datalist>
           </div>
 
+          <div className="section">
+            <h3 className="section-title">
+              Databases on Server
+              <button
+                type="button"
+                className="btn-secondary btn-small"
+                onClick={refreshDatabases}
+                disabled={loadingDatabases || exporting}
+              >
+                {loadingDatabases ? "Loading..." : "Refresh"}
+              </button>
+            </h3>
+            {databasesError ? (
+              <p className="help-text">
+                Could not list databases: {databasesError}
+              </p>
+            ) : (
+              <>
+                <div className="form-group checkbox-group">
+                  <label>
+                    <input
+                      type="checkbox"
+                      checked={showSystem}
+                      onChange={(e) => setShowSystem(e.target.checked)}
+                    />
+                    Show system databases
+                  </label>
+                </div>
+                <div className="database-list">
+                  {listed.map((db) => (
+                    <div key={db.name} className="checkbox-group">
+                      <label>
+                        <input
+                          type="checkbox"
+                          checked={selected.includes(db.name)}
+                          onChange={(e) =>
+                            toggleSelected(db.name, e.target.checked)
+                          }
+                          disabled={exporting || db.state !== "ONLINE"}
+                        />
+                        <span>
+                          <strong>{db.name}</strong>
+                          <span className="database-details">
+                            {db.size_bytes !== undefined &&
+                              db.size_bytes !== null &&
+                              `${formatBytes(db.size_bytes)} · `}
+                            {db.state !== "ONLINE" && `${db.state} · `}
+                            {db.recovery_model} · level{" "}
+                            {db.compatibility_level}
+                            {db.collation && ` · ${db.collation}`}
+                          </span>
+                        </span>
+                      </label>
+                    </div>
+                  ))}
+                </div>
+                {selected.length > 0 && (
+                  <p className="help-text">
+                    {selected.length} selected; each is exported to its own
+                    BACPAC in the folder you choose.
+                  </p>
+                )}
+              </>
+            )}
+          </div>
+
+          <button
+            type="button"
+            className="btn-secondary"
+            onClick={handleLoadStats}
+            disabled={exporting || loadingStats || !statsDatabase}
+          >
+            {loadingStats ? "Checking..." : "Check Size and Risks"}
+          </button>
+          {statsError && <div className="error-message">{statsError}</div>}
+          {stats && <DatabaseStatsPanel stats={stats} />}
+
           {progress && !showOutput && (
             <div className={`progress-message ${error ? "error" : "success"}`}>
               {progress}
@@ -132,9 +269,16 @@ export function ExportDialog({
             type="button"
             className="btn-primary"
             onClick={handleExport}
-            disabled={exporting || (!databaseName && !connection.database_name)}
+            disabled={
+              exporting ||
+              (!databaseName && !connection.database_name && !selected.length)
+            }
           >
-            {exporting ? "Exporting..." : "Export"}
+            {exporting
+              ? "Exporting..."
+              : selected.length > 1
+                ? `Export ${selected.length} Databases`
+                : "Export"}
           </button>
         </div>
       </div>
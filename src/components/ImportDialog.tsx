@@ -1,10 +1,17 @@
 import { useState, useEffect } from "react";
 import { useConnections } from "../hooks/useConnections";
 import { useSqlPackage } from "../hooks/useSqlPackage";
+import { useDatabases } from "../hooks/useDatabases";
 import { listen } from "@tauri-apps/api/event";
 import { tempDir, sep } from "@tauri-apps/api/path";
 import { api } from "../utils/api";
-import type { ExportRequest } from "../types";
+import {
+  AUTH_METHODS,
+  passwordLabel,
+  usernameLabel,
+  usesTenant,
+} from "../utils/authentication";
+import type { AuthMethod, ExportRequest } from "../types";
 
 interface ImportDialogProps {
   isOpen: boolean;
@@ -41,13 +48,20 @@ export function ImportDialog({ isOpen, onClose }: ImportDialogProps) {
   );
   const [targetConnectionId, setTargetConnectionId] = useState<number | "">("");
   const [targetDatabase, setTargetDatabase] = useState("");
+  const { databases: targetDatabases } = useDatabases(
+    isOpen && sourceType === "bacpac" && bacpacTargetType === "connection"
+      ? targetConnectionId || null
+      : null
+  );
 
   // Target details (when targetType is "details")
   const [targetServer, setTargetServer] = useState("");
   const [targetPort, setTargetPort] = useState("");
   const [targetUsername, setTargetUsername] = useState("");
   const [targetPassword, setTargetPassword] = useState("");
-  const [targetUseWindowsAuth, setTargetUseWindowsAuth] = useState(false);
+  const [targetAuthentication, setTargetAuthentication] =
+    useState<AuthMethod>("sql_password");
+  const [targetTenantId, setTargetTenantId] = useState("");
   const [targetEncrypt, setTargetEncrypt] = useState(true);
   const [targetTrustServerCert, setTargetTrustServerCert] = useState(true);
 
@@ -73,7 +87,8 @@ export function ImportDialog({ isOpen, onClose }: ImportDialogProps) {
       setTargetPort("");
       setTargetUsername("");
       setTargetPassword("");
-      setTargetUseWindowsAuth(false);
+      setTargetAuthentication("sql_password");
+      setTargetTenantId("");
       setTargetEncrypt(true);
       setTargetTrustServerCert(true);
       setTargetConnectionString("");
@@ -196,7 +211,8 @@ export function ImportDialog({ isOpen, onClose }: ImportDialogProps) {
             targetPort,
             targetUsername,
             targetPassword,
-            targetUseWindowsAuth,
+            targetAuthentication,
+            targetTenantId,
             targetEncrypt,
             targetTrustServerCert,
           );
@@ -241,7 +257,8 @@ export function ImportDialog({ isOpen, onClose }: ImportDialogProps) {
             targetPort,
             targetUsername,
             targetPassword,
-            targetUseWindowsAuth,
+            targetAuthentication,
+            targetTenantId,
             targetEncrypt,
             targetTrustServerCert,
           );
@@ -387,11 +404,19 @@ export function ImportDialog({ isOpen, onClose }: ImportDialogProps) {
                   <input
                     id="target-database"
                     type="text"
+                    list="target-database-options"
                     value={targetDatabase}
                     onChange={(e) => setTargetDatabase(e.target.value)}
                     placeholder="Enter name for the restored database"
                     disabled={importing}
                   />
+                  <datalist id="target-database-options">
+                    {targetDatabases
+                      .filter((db) => !db.is_system)
+                      .map((db) => (
+                        <option key={db.name} value={db.name} />
+                      ))}
+                  </datalist>
                 </div>
 
                 <div className="tabs tabs-small">
@@ -470,46 +495,68 @@ export function ImportDialog({ isOpen, onClose }: ImportDialogProps) {
                       />
                     </div>
 
-                    <div className="form-group checkbox-group">
-                      <label>
+                    <div className="form-group">
+                      <label htmlFor="target-authentication">
+                        Authentication
+                      </label>
+                      <select
+                        id="target-authentication"
+                        value={targetAuthentication}
+                        onChange={(e) =>
+                          setTargetAuthentication(e.target.value as AuthMethod)
+                        }
+                        disabled={importing}
+                      >
+                        {AUTH_METHODS.map((method) => (
+                          <option key={method.value} value={method.value}>
+                            {method.label}
+                          </option>
+                        ))}
+                      </select>
+                    </div>
+
+                    {usernameLabel(targetAuthentication) && (
+                      <div className="form-group">
+                        <label htmlFor="target-username">
+                          {usernameLabel(targetAuthentication)}
+                        </label>
                         <input
-                          type="checkbox"
-                          checked={targetUseWindowsAuth}
-                          onChange={(e) =>
-                            setTargetUseWindowsAuth(e.target.checked)
-                          }
+                          id="target-username"
+                          type="text"
+                          value={targetUsername}
+                          onChange={(e) => setTargetUsername(e.target.value)}
                           disabled={importing}
                         />
-                        Use Windows Authentication
-                      </label>
-                    </div>
+                      </div>
+                    )}
 
-                    {!targetUseWindowsAuth && (
-                      <>
-                        <div className="form-group">
-                          <label htmlFor="target-username">Username</label>
-                          <input
-                            id="target-username"
-                            type="text"
-                            value={targetUsername}
-                            onChange={(e) => setTargetUsername(e.target.value)}
-                            placeholder="SQL Server username"
-                            disabled={importing}
-                          />
-                        </div>
-
-                        <div className="form-group">
-                          <label htmlFor="target-password">Password</label>
-                          <input
-                            id="target-password"
-                            type="password"
-                            value={targetPassword}
-                            onChange={(e) => setTargetPassword(e.target.value)}
-                            placeholder="SQL Server password"
-                            disabled={importing}
-                          />
-                        </div>
-                      </>
+                    {passwordLabel(targetAuthentication) && (
+                      <div className="form-group">
+                        <label htmlFor="target-password">
+                          {passwordLabel(targetAuthentication)}
+                        </label>
+                        <input
+                          id="target-password"
+                          type="password"
+                          value={targetPassword}
+                          onChange={(e) => setTargetPassword(e.target.value)}
+                          disabled={importing}
+                        />
+                      </div>
+                    )}
+
+                    {usesTenant(targetAuthentication) && (
+                      <div className="form-group">
+                        <label htmlFor="target-tenant">Tenant ID</label>
+                        <input
+                          id="target-tenant"
+                          type="text"
+                          value={targetTenantId}
+                          onChange={(e) => setTargetTenantId(e.target.value)}
+                          placeholder="Tenant GUID or contoso.onmicrosoft.com"
+                          disabled={importing}
+                        />
+                      </div>
                     )}
 
                     <div className="form-group checkbox-group">
@@ -597,46 +644,68 @@ export function ImportDialog({ isOpen, onClose }: ImportDialogProps) {
                       />
                     </div>
 
-                    <div className="form-group checkbox-group">
-                      <label>
+                    <div className="form-group">
+                      <label htmlFor="target-authentication">
+                        Authentication
+                      </label>
+                      <select
+                        id="target-authentication"
+                        value={targetAuthentication}
+                        onChange={(e) =>
+                          setTargetAuthentication(e.target.value as AuthMethod)
+                        }
+                        disabled={importing}
+                      >
+                        {AUTH_METHODS.map((method) => (
+                          <option key={method.value} value={method.value}>
+                            {method.label}
+                          </option>
+                        ))}
+                      </select>
+                    </div>
+
+                    {usernameLabel(targetAuthentication) && (
+                      <div className="form-group">
+                        <label htmlFor="target-username">
+                          {usernameLabel(targetAuthentication)}
+                        </label>
                         <input
-                          type="checkbox"
-                          checked={targetUseWindowsAuth}
-                          onChange={(e) =>
-                            setTargetUseWindowsAuth(e.target.checked)
-                          }
+                          id="target-username"
+                          type="text"
+                          value={targetUsername}
+                          onChange={(e) => setTargetUsername(e.target.value)}
                           disabled={importing}
                         />
-                        Use Windows Authentication
-                      </label>
-                    </div>
+                      </div>
+                    )}
 
-                    {!targetUseWindowsAuth && (
-                      <>
-                        <div className="form-group">
-                          <label htmlFor="target-username">Username</label>
-                          <input
-                            id="target-username"
-                            type="text"
-                            value={targetUsername}
-                            onChange={(e) => setTargetUsername(e.target.value)}
-                            placeholder="SQL Server username"
-                            disabled={importing}
-                          />
-                        </div>
-
-                        <div className="form-group">
-                          <label htmlFor="target-password">Password</label>
-                          <input
-                            id="target-password"
-                            type="password"
-                            value={targetPassword}
-                            onChange={(e) => setTargetPassword(e.target.value)}
-                            placeholder="SQL Server password"
-                            disabled={importing}
-                          />
-                        </div>
-                      </>
+                    {passwordLabel(targetAuthentication) && (
+                      <div className="form-group">
+                        <label htmlFor="target-password">
+                          {passwordLabel(targetAuthentication)}
+                        </label>
+                        <input
+                          id="target-password"
+                          type="password"
+                          value={targetPassword}
+                          onChange={(e) => setTargetPassword(e.target.value)}
+                          disabled={importing}
+                        />
+                      </div>
+                    )}
+
+                    {usesTenant(targetAuthentication) && (
+                      <div className="form-group">
+                        <label htmlFor="target-tenant">Tenant ID</label>
+                        <input
+                          id="target-tenant"
+                          type="text"
+                          value={targetTenantId}
+                          onChange={(e) => setTargetTenantId(e.target.value)}
+                          placeholder="Tenant GUID or contoso.onmicrosoft.com"
+                          disabled={importing}
+                        />
+                      </div>
                     )}
 
                     <div className="form-group checkbox-group">
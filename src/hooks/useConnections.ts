@@ -1,9 +1,9 @@
 import { useState, useCallback } from "react";
 import { api } from "../utils/api";
-import type { SqlConnection } from "../types";
+import type { SavedConnection, SqlConnection } from "../types";
 
 export function useConnections() {
-  const [connections, setConnections] = useState<SqlConnection[]>([]);
+  const [connections, setConnections] = useState<SavedConnection[]>([]);
   const [loading, setLoading] = useState(false);
   const [error, setError] = useState<string | null>(null);
 
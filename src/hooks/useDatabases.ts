@@ -0,0 +1,41 @@
+import { useState, useEffect, useCallback } from "react";
+import { api } from "../utils/api";
+import type { DatabaseInfo } from "../types";
+
+/** The databases on a saved connection's server, loaded when the id is set. */
+export function useDatabases(connectionId: number | null | undefined) {
+  const [databases, setDatabases] = useState<DatabaseInfo[]>([]);
+  const [loading, setLoading] = useState(false);
+  const [error, setError] = useState<string | null>(null);
+
+  const load = useCallback(
+    async (refresh = false) => {
+      if (!connectionId) {
+        setDatabases([]);
+        return;
+      }
+      setLoading(true);
+      setError(null);
+      try {
+        setDatabases(await api.listDatabases(connectionId, refresh));
+      } catch (err) {
+        setDatabases([]);
+        setError(err instanceof Error ? err.message : String(err));
+      } finally {
+        setLoading(false);
+      }
+    },
+    [connectionId]
+  );
+
+  useEffect(() => {
+    load();
+  }, [load]);
+
+  return {
+    databases,
+    loading,
+    error,
+    refresh: () => load(true),
+  };
+}
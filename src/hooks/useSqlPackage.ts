@@ -1,12 +1,36 @@
 import { useState } from "react";
 import { open, save } from "@tauri-apps/plugin-dialog";
+import { join } from "@tauri-apps/api/path";
 import { api } from "../utils/api";
 import type {
+  AuthMethod,
   ExportRequest,
+  ImportPreflight,
   ImportRequest,
   ImportWithDetailsRequest,
 } from "../types";
 
+/**
+ * Asks the user to accept the preflight warnings. Returns the acknowledged
+ * warning codes, or null when the user declines; throws on blocking errors.
+ */
+function acknowledgePreflight(plan: ImportPreflight): string[] | null {
+  if (plan.errors.length > 0) {
+    throw new Error(`Import blocked:\n${plan.errors.join("\n")}`);
+  }
+  if (plan.warnings.length === 0) {
+    return [];
+  }
+  const message = plan.warnings.map((w) => `- ${w.message}`).join("\n");
+  const accepted = confirm(
+    `The import check found these warnings:\n${message}\n\nImport anyway?`
+  );
+  if (!accepted) {
+    return null;
+  }
+  return plan.warnings.map((w) => w.code);
+}
+
 export function useSqlPackage() {
   const [exporting, setExporting] = useState(false);
   const [importing, setImporting] = useState(false);
@@ -87,6 +111,47 @@ export function useSqlPackage() {
     }
   };
 
+  /** Exports several databases into one folder, one after another. */
+  const exportBacpacs = async (
+    connectionId: number,
+    databaseNames: string[]
+  ): Promise<void> => {
+    setExporting(true);
+    setError(null);
+    setProgress("Selecting output folder...");
+
+    try {
+      const folder = await selectFolder();
+
+      if (!folder) {
+        setProgress("Export cancelled");
+        return;
+      }
+
+      const date = new Date().toISOString().split("T")[0];
+      for (const [index, databaseName] of databaseNames.entries()) {
+        setProgress(
+          `Exporting ${databaseName} (${index + 1} of ${databaseNames.length})...`
+        );
+        await api.exportBacpac({
+          connection_id: connectionId,
+          output_path: await join(folder, `${databaseName}_${date}.bacpac`),
+          database_name: databaseName,
+        });
+      }
+      setProgress(
+        `Exported ${databaseNames.length} databases to ${folder}`
+      );
+    } catch (err) {
+      const errorMsg = err instanceof Error ? err.message : String(err);
+      setError(errorMsg);
+      setProgress("");
+      throw err;
+    } finally {
+      setExporting(false);
+    }
+  };
+
   const importBacpac = async (
     connectionId: number,
     targetDatabase: string,
@@ -109,13 +174,23 @@ export function useSqlPackage() {
         }
       }
 
-      setProgress("Importing database...");
+      setProgress("Checking the target server...");
 
       const importRequest: ImportRequest = {
         connection_id: connectionId,
         bacpac_path: finalBacpacPath,
         target_database: targetDatabase,
       };
+      const acknowledged = acknowledgePreflight(
+        await api.preflightImport(importRequest)
+      );
+      if (!acknowledged) {
+        setProgress("Import cancelled");
+        return;
+      }
+      importRequest.acknowledged_warnings = acknowledged;
+
+      setProgress("Importing database...");
 
       const result = await api.importBacpac(importRequest);
       setProgress(`Import completed: ${result}`);
@@ -136,13 +211,14 @@ export function useSqlPackage() {
     port: string,
     username: string,
     password: string,
-    useWindowsAuth: boolean,
+    authentication: AuthMethod,
+    tenantId: string,
     encrypt: boolean,
     trustServerCert: boolean
   ): Promise<void> => {
     setImporting(true);
     setError(null);
-    setProgress("Importing database...");
+    setProgress("Checking the target server...");
 
     try {
       const importRequest: ImportWithDetailsRequest = {
@@ -152,11 +228,21 @@ export function useSqlPackage() {
         port: port || undefined,
         username: username || undefined,
         password: password || undefined,
-        use_windows_auth: useWindowsAuth,
+        authentication,
+        tenant_id: tenantId || undefined,
         encrypt,
         trust_server_cert: trustServerCert,
       };
+      const acknowledged = acknowledgePreflight(
+        await api.preflightImportWithDetails(importRequest)
+      );
+      if (!acknowledged) {
+        setProgress("Import cancelled");
+        return;
+      }
+      importRequest.acknowledged_warnings = acknowledged;
 
+      setProgress("Importing database...");
       const result = await api.importBacpacWithDetails(importRequest);
       setProgress(`Import completed: ${result}`);
     } catch (err) {
@@ -175,6 +261,7 @@ export function useSqlPackage() {
     progress,
     error,
     exportBacpac,
+    exportBacpacs,
     importBacpac,
     importBacpacWithDetails,
     selectFolder,
@@ -1,3 +1,13 @@
+/** How a connection signs in; mirrors `AuthMethod` in the backend. */
+export type AuthMethod =
+  | "sql_password"
+  | "integrated"
+  | "active_directory_password"
+  | "active_directory_service_principal"
+  | "active_directory_managed_identity"
+  | "active_directory_default"
+  | "access_token";
+
 export interface SqlConnection {
   id?: number;
   name: string;
@@ -6,24 +16,171 @@ export interface SqlConnection {
   database_name?: string;
   username?: string;
   password?: string;
+  /** The password is an env:, file:, cmd: or vault: reference, resolved only once saved. */
+  password_is_reference?: boolean;
   connection_string?: string;
-  use_windows_auth: boolean;
+  authentication?: AuthMethod;
+  /** Entra ID tenant, for service principals and Entra passwords. */
+  tenant_id?: string;
   trust_server_cert: boolean;
   encrypt: boolean;
+  /** Seconds to wait for a connection; 0 waits indefinitely. */
+  connect_timeout?: number;
+  /** Seconds sqlpackage waits for each command; 0 waits indefinitely. */
+  command_timeout?: number;
+  /** ApplicationIntent=ReadOnly, to export from an Availability Group secondary. */
+  read_only_intent?: boolean;
+  multi_subnet_failover?: boolean;
+  /** Encrypt=Strict (TDS 8): the TLS handshake happens before any login traffic. */
+  strict_encryption?: boolean;
+  host_name_in_certificate?: string;
+  /** Path to the certificate the server must present. */
+  server_certificate?: string;
+  application_name?: string;
+  /** Other connection string keywords, as a `key=value;...` fragment. */
+  extra_options?: string;
   created_at?: string;
   updated_at?: string;
 }
 
+/** A saved connection as returned by the backend; passwords are never sent. */
+export interface SavedConnection {
+  id: number;
+  name: string;
+  server: string;
+  port?: string;
+  database_name?: string;
+  username?: string;
+  has_password: boolean;
+  password_is_reference: boolean;
+  /** Any password is masked; absent while saved connections are locked. */
+  connection_string?: string;
+  authentication: AuthMethod;
+  tenant_id?: string;
+  trust_server_cert: boolean;
+  encrypt: boolean;
+  /** Seconds to wait for a connection; 0 waits indefinitely. */
+  connect_timeout?: number;
+  /** Seconds sqlpackage waits for each command; 0 waits indefinitely. */
+  command_timeout?: number;
+  /** ApplicationIntent=ReadOnly, to export from an Availability Group secondary. */
+  read_only_intent: boolean;
+  multi_subnet_failover: boolean;
+  /** Encrypt=Strict (TDS 8): the TLS handshake happens before any login traffic. */
+  strict_encryption: boolean;
+  host_name_in_certificate?: string;
+  /** Path to the certificate the server must present. */
+  server_certificate?: string;
+  application_name?: string;
+  /** Other connection string keywords, as a `key=value;...` fragment. */
+  extra_options?: string;
+  created_at?: string;
+  updated_at?: string;
+}
+
+/** Why a test connection failed; mirrors `ConnectionFailure` in the backend. */
+export type ConnectionFailure =
+  | "dns"
+  | "tcp_refused"
+  | "tls"
+  | "login_failed"
+  | "database_not_accessible"
+  | "timeout"
+  | "other";
+
+export type ServerEngine =
+  | "on_premises"
+  | "azure_sql_database"
+  | "azure_managed_instance"
+  | "azure_synapse"
+  | "azure_sql_edge";
+
+export type ConnectionTestResult =
+  | {
+      status: "connected";
+      product_version: string;
+      edition: string;
+      engine: ServerEngine;
+      login_name: string;
+      database_name: string;
+      elapsed_ms: number;
+    }
+  | { status: "failed"; reason: ConnectionFailure; message: string };
+
+/** A database on a server, from `sys.databases`. */
+export interface DatabaseInfo {
+  name: string;
+  /** ONLINE, RESTORING, OFFLINE and so on. */
+  state: string;
+  recovery_model: string;
+  compatibility_level: number;
+  collation?: string;
+  /** Data and log files; absent when the login may not read them. */
+  size_bytes?: number;
+  is_system: boolean;
+}
+
+export interface TableStats {
+  schema: string;
+  name: string;
+  row_count: number;
+  /** Pages reserved for the table and its indexes; zero for memory-optimized tables. */
+  reserved_bytes: number;
+  is_memory_optimized: boolean;
+}
+
+export type ExportRiskKind =
+  | "memory_optimized_tables"
+  | "filestream"
+  | "full_text_catalogs"
+  | "cross_database_references";
+
+export interface ExportRisk {
+  kind: ExportRiskKind;
+  message: string;
+  objects: string[];
+}
+
+export interface DatabaseStats {
+  database_name: string;
+  data_bytes: number;
+  log_bytes: number;
+  /** Largest first, by reserved space. */
+  tables: TableStats[];
+  risks: ExportRisk[];
+}
+
 export interface ExportRequest {
   connection_id: number;
   output_path: string;
   database_name: string;
+  skip_space_check?: boolean;
 }
 
 export interface ImportRequest {
   connection_id: number;
   bacpac_path: string;
   target_database: string;
+  acknowledged_warnings?: string[];
+}
+
+export interface PreflightWarning {
+  code: string;
+  message: string;
+}
+
+export interface ImportPreflight {
+  source_schema_provider?: string;
+  source_server_version?: string;
+  target_server_version: string;
+  target_edition: string;
+  database_exists: boolean;
+  data_bytes: number;
+  free_bytes?: number;
+  package_collation?: string;
+  server_collation: string;
+  errors: string[];
+  warnings: PreflightWarning[];
 }
 
 export interface ImportWithDetailsRequest {
@@ -33,9 +190,313 @@ export interface ImportWithDetailsRequest {
   port?: string;
   username?: string;
   password?: string;
-  use_windows_auth: boolean;
+  authentication: AuthMethod;
+  tenant_id?: string;
   trust_server_cert: boolean;
   encrypt: boolean;
+  acknowledged_warnings?: string[];
+}
+
+export interface BacpacToDacpacRequest {
+  bacpac_path: string;
+  output_path: string;
+  dac_name?: string;
+  dac_version?: string;
+  overwrite: boolean;
+}
+
+export type DataExportFormat = "csv" | "jsonl" | "parquet";
+
+export interface TableDataExportRequest {
+  bacpac_path: string;
+  tables: string[];
+  format: DataExportFormat;
+  output_dir: string;
+}
+
+export interface TableDataExportResult {
+  table: string;
+  output_path: string;
+  rows: number;
+}
+
+export interface BacpacToSqliteRequest {
+  bacpac_path: string;
+  output_path: string;
+  tables: string[];
+  overwrite: boolean;
+}
+
+export interface BacpacColumnInfo {
+  name: string;
+  data_type: string;
+  nullable: boolean;
+}
+
+export interface BacpacTableInfo {
+  schema: string;
+  name: string;
+  columns: BacpacColumnInfo[];
+  data_size: number;
+}
+
+export type FilterOperator = "equals" | "contains";
+
+export interface RowFilter {
+  column: string;
+  operator: FilterOperator;
+  value: string;
+}
+
+export interface TableRowsRequest {
+  bacpac_path: string;
+  table: string;
+  offset: number;
+  limit: number;
+  columns: string[];
+  filters: RowFilter[];
+}
+
+export interface TableRowsPage {
+  columns: string[];
+  rows: unknown[][];
+  offset: number;
+  has_more: boolean;
+}
+
+export type MatchMode = "exact" | "substring" | "regex";
+
+export interface BacpacSearchRequest {
+  job_id: string;
+  bacpac_path: string;
+  value: string;
+  mode: MatchMode;
+  case_sensitive: boolean;
+  schema_pattern?: string;
+  table_pattern?: string;
+  max_matches?: number;
+}
+
+export interface SearchMatch {
+  table: string;
+  column: string;
+  row: number;
+  value: string;
+}
+
+export interface BacpacSearchResult {
+  matches: SearchMatch[];
+  tables_searched: number;
+  rows_scanned: number;
+  truncated: boolean;
+  cancelled: boolean;
+}
+
+export interface TableDiffRequest {
+  left_path: string;
+  right_path: string;
+  table: string;
+  offset: number;
+  limit: number;
+}
+
+export type RowChangeKind = "inserted" | "deleted" | "changed";
+
+export interface ColumnChange {
+  column: string;
+  old_value: unknown;
+  new_value: unknown;
+}
+
+export interface RowDiff {
+  kind: RowChangeKind;
+  key: unknown[];
+  values: unknown[];
+  changes: ColumnChange[];
+}
+
+export interface TableDiffPage {
+  key_columns: string[];
+  columns: string[];
+  added_columns: string[];
+  removed_columns: string[];
+  inserted: number;
+  deleted: number;
+  changed: number;
+  rows: RowDiff[];
+  offset: number;
+  has_more: boolean;
+}
+
+export interface SubsetRoot {
+  table: string;
+  filters: RowFilter[];
+  sample_percent?: number;
+  max_rows?: number;
+}
+
+export interface BacpacSubsetRequest {
+  bacpac_path: string;
+  output_path: string;
+  roots: SubsetRoot[];
+  overwrite: boolean;
+}
+
+export interface SubsetTableResult {
+  table: string;
+  rows: number;
+}
+
+export type MaskStrategy =
+  | { kind: "null" }
+  | { kind: "fixed"; value: string }
+  | { kind: "hash" }
+  | { kind: "fake_name" }
+  | { kind: "fake_email" }
+  | { kind: "fake_phone" }
+  | { kind: "shuffle" }
+  | { kind: "keep_format" };
+
+export interface MaskRule {
+  table: string;
+  column: string;
+  strategy: MaskStrategy;
+}
+
+export interface MaskingRuleSet {
+  id?: number;
+  name: string;
+  rules: MaskRule[];
+  created_at?: string;
+  updated_at?: string;
+}
+
+export interface MaskBacpacRequest {
+  bacpac_path: string;
+  output_path: string;
+  rules: MaskRule[];
+  /** Name of a saved rule set to apply instead of `rules`. */
+  rule_set?: string;
+  salt?: string;
+  overwrite: boolean;
+}
+
+export interface CsvToBacpacRequest {
+  csv_dir: string;
+  manifest_path?: string;
+  database_name: string;
+  output_path: string;
+  overwrite: boolean;
+}
+
+export interface SqliteToBacpacRequest {
+  sqlite_path: string;
+  tables: string[];
+  schema?: string;
+  database_name: string;
+  output_path: string;
+  overwrite: boolean;
+}
+
+export interface ScriptTable {
+  table: string;
+  max_rows?: number;
+}
+
+export interface InsertScriptRequest {
+  bacpac_path: string;
+  output_path: string;
+  tables: ScriptTable[];
+  include_schema: boolean;
+  batch_size?: number;
+  overwrite: boolean;
+}
+
+export interface DataDictionaryRequest {
+  bacpac_path: string;
+  output_dir: string;
+  schemas: string[];
+  overwrite: boolean;
+}
+
+export interface DataDictionaryResult {
+  markdown_path: string;
+  html_path: string;
+  er_diagram: string;
+}
+
+export interface ModelObject {
+  name: string;
+  object_type: string;
+}
+
+export type DependencySource = "reference" | "sql_body" | "containment";
+
+export interface DependencyRequest {
+  bacpac_path: string;
+  object_name: string;
+  max_depth?: number;
+}
+
+export interface DependencyEntry {
+  name: string;
+  object_type: string;
+  depth: number;
+  via: string;
+  source: DependencySource;
+}
+
+export interface DependencyReport {
+  object: ModelObject;
+  depends_on: DependencyEntry[];
+  depended_by: DependencyEntry[];
+}
+
+export interface SecurityPrincipal {
+  name: string;
+  principal_type: string;
+  authentication?: string;
+  login?: string;
+  default_schema?: string;
+  owner?: string;
+}
+
+export interface RoleMembership {
+  role: string;
+  member: string;
+}
+
+export interface SecurableOwner {
+  name: string;
+  object_type: string;
+  owner?: string;
+}
+
+export interface PermissionStatement {
+  action: "GRANT" | "DENY" | "REVOKE";
+  permission: string;
+  scope: string;
+  securable?: string;
+  grantee: string;
+  with_grant_option: boolean;
+  statement: string;
+}
+
+export type FindingSeverity = "high" | "medium" | "low";
+
+export interface SecurityFinding {
+  severity: FindingSeverity;
+  principal: string;
+  message: string;
+}
+
+export interface SecurityReport {
+  principals: SecurityPrincipal[];
+  role_memberships: RoleMembership[];
+  schemas: SecurableOwner[];
+  keys_and_certificates: SecurableOwner[];
+  permissions: PermissionStatement[];
+  findings: SecurityFinding[];
 }
 
 export interface OperationProgress {
@@ -46,6 +507,15 @@ export interface OperationProgress {
 
 export interface Preferences {
   theme: "light" | "dark" | "system";
+  export_space_margin_percent: number;
+  export_min_free_mb: number;
+  vault_idle_lock_minutes: number;
+}
+
+export interface VaultStatus {
+  enabled: boolean;
+  unlocked: boolean;
+  idle_lock_minutes: number;
 }
 
 export type Theme = "light" | "dark" | "system";
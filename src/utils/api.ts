@@ -1,33 +1,63 @@
 import { invoke } from "@tauri-apps/api/core";
 import type {
   SqlConnection,
+  SavedConnection,
+  ConnectionTestResult,
+  DatabaseInfo,
+  DatabaseStats,
   ExportRequest,
   ImportRequest,
+  ImportPreflight,
   ImportWithDetailsRequest,
+  BacpacToDacpacRequest,
+  TableDataExportRequest,
+  TableDataExportResult,
+  BacpacToSqliteRequest,
+  BacpacTableInfo,
+  TableRowsRequest,
+  TableRowsPage,
+  BacpacSearchRequest,
+  BacpacSearchResult,
+  TableDiffRequest,
+  TableDiffPage,
+  BacpacSubsetRequest,
+  SubsetTableResult,
+  MaskBacpacRequest,
+  MaskingRuleSet,
+  CsvToBacpacRequest,
+  SqliteToBacpacRequest,
+  InsertScriptRequest,
+  DataDictionaryRequest,
+  DataDictionaryResult,
+  ModelObject,
+  DependencyRequest,
+  DependencyReport,
+  SecurityReport,
   Preferences,
   Theme,
+  VaultStatus,
 } from "../types";
 
 export const api = {
   // Connection CRUD operations
-  listConnections: async (): Promise<SqlConnection[]> => {
+  listConnections: async (): Promise<SavedConnection[]> => {
     return await invoke("list_connections");
   },
 
-  getConnection: async (id: number): Promise<SqlConnection> => {
+  getConnection: async (id: number): Promise<SavedConnection> => {
     return await invoke("get_connection", { id });
   },
 
   createConnection: async (
     connection: SqlConnection
-  ): Promise<SqlConnection> => {
+  ): Promise<SavedConnection> => {
     return await invoke("create_connection", { connection });
   },
 
   updateConnection: async (
     id: number,
     connection: SqlConnection
-  ): Promise<SqlConnection> => {
+  ): Promise<SavedConnection> => {
     return await invoke("update_connection", { id, connection });
   },
 
@@ -35,6 +65,28 @@ export const api = {
     return await invoke("delete_connection", { id });
   },
 
+  /** Tries a connection as entered; with an id, omitted secrets are the saved ones. */
+  testConnection: async (
+    connection: SqlConnection
+  ): Promise<ConnectionTestResult> => {
+    return await invoke("test_connection", { connection });
+  },
+
+  /** Databases on a saved connection's server, cached for a minute unless refreshed. */
+  listDatabases: async (
+    connectionId: number,
+    refresh = false
+  ): Promise<DatabaseInfo[]> => {
+    return await invoke("list_databases", { connectionId, refresh });
+  },
+
+  getDatabaseStats: async (
+    connectionId: number,
+    databaseName: string
+  ): Promise<DatabaseStats> => {
+    return await invoke("get_database_stats", { connectionId, databaseName });
+  },
+
   // SQLPackage operations
   exportBacpac: async (exportRequest: ExportRequest): Promise<string> => {
     return await invoke("export_bacpac", { exportRequest });
@@ -44,12 +96,128 @@ export const api = {
     return await invoke("import_bacpac", { importRequest });
   },
 
+  preflightImport: async (
+    importRequest: ImportRequest
+  ): Promise<ImportPreflight> => {
+    return await invoke("preflight_import", { importRequest });
+  },
+
+  preflightImportWithDetails: async (
+    importRequest: ImportWithDetailsRequest
+  ): Promise<ImportPreflight> => {
+    return await invoke("preflight_import_with_details", { importRequest });
+  },
+
   importBacpacWithDetails: async (
     importRequest: ImportWithDetailsRequest
   ): Promise<string> => {
     return await invoke("import_bacpac_with_details", { importRequest });
   },
 
+  // Package operations
+  convertBacpacToDacpac: async (
+    request: BacpacToDacpacRequest
+  ): Promise<string> => {
+    return await invoke("convert_bacpac_to_dacpac", { request });
+  },
+
+  exportBacpacTableData: async (
+    request: TableDataExportRequest
+  ): Promise<TableDataExportResult[]> => {
+    return await invoke("export_bacpac_table_data", { request });
+  },
+
+  convertBacpacToSqlite: async (
+    request: BacpacToSqliteRequest
+  ): Promise<string> => {
+    return await invoke("convert_bacpac_to_sqlite", { request });
+  },
+
+  listBacpacTables: async (bacpacPath: string): Promise<BacpacTableInfo[]> => {
+    return await invoke("list_bacpac_tables", { bacpacPath });
+  },
+
+  getBacpacTableRows: async (
+    request: TableRowsRequest
+  ): Promise<TableRowsPage> => {
+    return await invoke("get_bacpac_table_rows", { request });
+  },
+
+  searchBacpac: async (
+    request: BacpacSearchRequest
+  ): Promise<BacpacSearchResult> => {
+    return await invoke("search_bacpac", { request });
+  },
+
+  diffBacpacTable: async (request: TableDiffRequest): Promise<TableDiffPage> => {
+    return await invoke("diff_bacpac_table", { request });
+  },
+
+  subsetBacpac: async (
+    request: BacpacSubsetRequest
+  ): Promise<SubsetTableResult[]> => {
+    return await invoke("subset_bacpac", { request });
+  },
+
+  maskBacpac: async (request: MaskBacpacRequest): Promise<string> => {
+    return await invoke("mask_bacpac", { request });
+  },
+
+  createBacpacFromCsv: async (request: CsvToBacpacRequest): Promise<string> => {
+    return await invoke("create_bacpac_from_csv", { request });
+  },
+
+  createBacpacFromSqlite: async (
+    request: SqliteToBacpacRequest
+  ): Promise<string> => {
+    return await invoke("create_bacpac_from_sqlite", { request });
+  },
+
+  generateInsertScript: async (
+    request: InsertScriptRequest
+  ): Promise<string> => {
+    return await invoke("generate_insert_script", { request });
+  },
+
+  generateDataDictionary: async (
+    request: DataDictionaryRequest
+  ): Promise<DataDictionaryResult> => {
+    return await invoke("generate_data_dictionary", { request });
+  },
+
+  listModelObjects: async (bacpacPath: string): Promise<ModelObject[]> => {
+    return await invoke("list_model_objects", { bacpacPath });
+  },
+
+  getObjectDependencies: async (
+    request: DependencyRequest
+  ): Promise<DependencyReport> => {
+    return await invoke("get_object_dependencies", { request });
+  },
+
+  getSecurityReport: async (bacpacPath: string): Promise<SecurityReport> => {
+    return await invoke("get_security_report", { bacpacPath });
+  },
+
+  // Masking rule sets
+  listMaskingRuleSets: async (): Promise<MaskingRuleSet[]> => {
+    return await invoke("list_masking_rule_sets");
+  },
+
+  getMaskingRuleSet: async (name: string): Promise<MaskingRuleSet> => {
+    return await invoke("get_masking_rule_set", { name });
+  },
+
+  saveMaskingRuleSet: async (
+    ruleSet: MaskingRuleSet
+  ): Promise<MaskingRuleSet> => {
+    return await invoke("save_masking_rule_set", { ruleSet });
+  },
+
+  deleteMaskingRuleSet: async (name: string): Promise<void> => {
+    return await invoke("delete_masking_rule_set", { name });
+  },
+
   // Preferences operations
   getPreferences: async (): Promise<Preferences> => {
     return await invoke("get_preferences");
@@ -63,8 +231,57 @@ export const api = {
     return await invoke("update_theme", { theme });
   },
 
+  updateExportSpaceSettings: async (
+    marginPercent: number,
+    minFreeMb: number
+  ): Promise<void> => {
+    return await invoke("update_export_space_settings", {
+      marginPercent,
+      minFreeMb,
+    });
+  },
+
+  // Master password operations
+  getVaultStatus: async (): Promise<VaultStatus> => {
+    return await invoke("get_vault_status");
+  },
+
+  setMasterPassword: async (password: string): Promise<void> => {
+    return await invoke("set_master_password", { password });
+  },
+
+  unlockVault: async (password: string): Promise<void> => {
+    return await invoke("unlock_vault", { password });
+  },
+
+  lockVault: async (): Promise<void> => {
+    return await invoke("lock_vault");
+  },
+
+  changeMasterPassword: async (
+    currentPassword: string,
+    newPassword: string
+  ): Promise<void> => {
+    return await invoke("change_master_password", {
+      currentPassword,
+      newPassword,
+    });
+  },
+
+  removeMasterPassword: async (password: string): Promise<void> => {
+    return await invoke("remove_master_password", { password });
+  },
+
+  setVaultIdleLock: async (minutes: number): Promise<void> => {
+    return await invoke("set_vault_idle_lock", { minutes });
+  },
+
   // System operations
   checkSqlPackageInstalled: async (): Promise<boolean> => {
     return await invoke("check_sqlpackage_installed");
   },
+
+  cancelJob: async (jobId: string): Promise<boolean> => {
+    return await invoke("cancel_job", { jobId });
+  },
 };
@@ -0,0 +1,59 @@
+import type { AuthMethod } from "../types";
+
+export const AUTH_METHODS: { value: AuthMethod; label: string }[] = [
+  { value: "sql_password", label: "SQL Server Authentication" },
+  { value: "integrated", label: "Windows Authentication" },
+  { value: "active_directory_password", label: "Microsoft Entra Password" },
+  {
+    value: "active_directory_service_principal",
+    label: "Microsoft Entra Service Principal",
+  },
+  {
+    value: "active_directory_managed_identity",
+    label: "Microsoft Entra Managed Identity",
+  },
+  { value: "active_directory_default", label: "Microsoft Entra Default" },
+  { value: "access_token", label: "Access Token" },
+];
+
+export function authMethodLabel(method: AuthMethod = "sql_password"): string {
+  return AUTH_METHODS.find((m) => m.value === method)?.label ?? method;
+}
+
+/** What the username field holds for a mode, or null when it has no use. */
+export function usernameLabel(method: AuthMethod = "sql_password"): string | null {
+  switch (method) {
+    case "sql_password":
+    case "active_directory_password":
+      return "Username";
+    case "active_directory_service_principal":
+      return "Client ID";
+    case "active_directory_managed_identity":
+      return "Client ID (user-assigned identity only)";
+    default:
+      return null;
+  }
+}
+
+/** What the password field holds for a mode, or null when it has no use. */
+export function passwordLabel(method: AuthMethod = "sql_password"): string | null {
+  switch (method) {
+    case "sql_password":
+    case "active_directory_password":
+      return "Password";
+    case "active_directory_service_principal":
+      return "Client Secret";
+    case "access_token":
+      return "Access Token";
+    default:
+      return null;
+  }
+}
+
+export function usesTenant(method: AuthMethod = "sql_password"): boolean {
+  return (
+    method === "active_directory_password" ||
+    method === "active_directory_service_principal" ||
+    method === "active_directory_default"
+  );
+}
@@ -1,46 +1,374 @@
-import type { SqlConnection } from "../types";
+import type { AuthMethod, SavedConnection, SqlConnection } from "../types";
+import { passwordLabel, usernameLabel } from "./authentication";
+
+const SERVER_KEYWORDS = [
+  "server",
+  "data source",
+  "address",
+  "addr",
+  "network address",
+];
+const DATABASE_KEYWORDS = ["database", "initial catalog"];
+const USER_KEYWORDS = ["user id", "uid", "user"];
+const PASSWORD_KEYWORDS = ["password", "pwd"];
+const INTEGRATED_SECURITY_KEYWORDS = ["integrated security", "trusted_connection"];
+const AUTHENTICATION_KEYWORDS = ["authentication"];
+const ENCRYPT_KEYWORDS = ["encrypt"];
+const TRUST_SERVER_CERTIFICATE_KEYWORDS = [
+  "trustservercertificate",
+  "trust server certificate",
+];
+const CONNECT_TIMEOUT_KEYWORDS = ["connect timeout", "connection timeout", "timeout"];
+const COMMAND_TIMEOUT_KEYWORDS = ["command timeout"];
+const APPLICATION_INTENT_KEYWORDS = ["applicationintent", "application intent"];
+const MULTI_SUBNET_FAILOVER_KEYWORDS = [
+  "multisubnetfailover",
+  "multi subnet failover",
+];
+const HOST_NAME_IN_CERTIFICATE_KEYWORDS = [
+  "hostnameincertificate",
+  "host name in certificate",
+];
+const SERVER_CERTIFICATE_KEYWORDS = ["servercertificate", "server certificate"];
+const APPLICATION_NAME_KEYWORDS = ["application name", "app"];
+/** Keywords extra options may not set; mirrors `RESERVED_EXTRA_KEYWORDS` in utils.rs. */
+const RESERVED_EXTRA_KEYWORDS = [
+  ...SERVER_KEYWORDS,
+  ...USER_KEYWORDS,
+  ...PASSWORD_KEYWORDS,
+];
+
+/**
+ * Quotes a value the way SqlClient's connection string builder does, so that
+ * `;`, `=`, quotes and whitespace in passwords can't break the string.
+ */
+export function escapeConnectionStringValue(value: string): string {
+  const needsQuotes = value === "" || /[;'"=\s\u0000-\u001f\u007f]/.test(value);
+  if (!needsQuotes) {
+    return value;
+  }
+  if (value.includes('"') && !value.includes("'")) {
+    return `'${value}'`;
+  }
+  return `"${value.replace(/"/g, '""')}"`;
+}
+
+function formatPairs(pairs: [string, string][]): string {
+  return pairs
+    .map(
+      ([keyword, value]) =>
+        `${keyword.replace(/=/g, "==")}=${escapeConnectionStringValue(value)}`,
+    )
+    .join(";");
+}
+
+/**
+ * Splits a connection string into keyword/value pairs, undoing the quoting of
+ * `escapeConnectionStringValue`. A repeated keyword overrides the earlier one.
+ */
+export function parseConnectionStringPairs(input: string): [string, string][] {
+  const pairs: [string, string][] = [];
+  let i = 0;
+  const isSpace = (c: string | undefined) => c !== undefined && /\s/.test(c);
+
+  while (i < input.length) {
+    while (i < input.length && (input[i] === ";" || isSpace(input[i]))) i++;
+    if (i >= input.length) break;
+
+    let keyword = "";
+    for (;;) {
+      const c = input[i];
+      if (c === undefined || c === ";") {
+        throw new Error(`Keyword '${keyword.trim()}' has no value`);
+      }
+      i++;
+      if (c === "=") {
+        if (input[i] === "=") {
+          keyword += "=";
+          i++;
+          continue;
+        }
+        break;
+      }
+      keyword += c;
+    }
+    keyword = keyword.trim();
+    if (!keyword) {
+      throw new Error("Connection string has a value without a keyword");
+    }
+
+    while (isSpace(input[i])) i++;
+    let value = "";
+    const quote = input[i];
+    if (quote === '"' || quote === "'") {
+      i++;
+      for (;;) {
+        if (i >= input.length) {
+          throw new Error(`Value of '${keyword}' has no closing ${quote}`);
+        }
+        if (input[i] === quote) {
+          if (input[i + 1] === quote) {
+            value += quote;
+            i += 2;
+            continue;
+          }
+          i++;
+          break;
+        }
+        value += input[i++];
+      }
+      while (isSpace(input[i])) i++;
+      if (i < input.length && input[i] !== ";") {
+        throw new Error(`Unexpected text after the quoted value of '${keyword}'`);
+      }
+    } else {
+      const end = input.indexOf(";", i);
+      const stop = end === -1 ? input.length : end;
+      value = input.slice(i, stop).trimEnd();
+      i = stop;
+    }
+
+    const existing = pairs.find(
+      ([k]) => k.toLowerCase() === keyword.toLowerCase(),
+    );
+    if (existing) {
+      existing[1] = value;
+    } else {
+      pairs.push([keyword, value]);
+    }
+  }
+  return pairs;
+}
+
+function parseBool(keyword: string, value: string): boolean {
+  switch (value.trim().toLowerCase()) {
+    case "true":
+    case "yes":
+    case "sspi":
+    case "mandatory":
+      return true;
+    case "false":
+    case "no":
+    case "optional":
+      return false;
+    default:
+      throw new Error(`Invalid value '${value}' for ${keyword}`);
+  }
+}
+
+/** `Authentication` keyword values for the Entra ID modes. */
+const AUTHENTICATION_VALUES: Partial<Record<AuthMethod, string>> = {
+  active_directory_password: "Active Directory Password",
+  active_directory_service_principal: "Active Directory Service Principal",
+  active_directory_managed_identity: "Active Directory Managed Identity",
+  active_directory_default: "Active Directory Default",
+};
+
+function authMethodFromKeyword(value: string): AuthMethod | undefined {
+  const compact = value.replace(/\s/g, "").toLowerCase();
+  if (compact === "sqlpassword") return "sql_password";
+  if (compact === "activedirectorymsi") return "active_directory_managed_identity";
+  return (Object.keys(AUTHENTICATION_VALUES) as AuthMethod[]).find(
+    (method) =>
+      AUTHENTICATION_VALUES[method]!.replace(/\s/g, "").toLowerCase() === compact,
+  );
+}
+
+function parseSeconds(keyword: string, value: string): number {
+  if (!/^\s*\d+\s*$/.test(value)) {
+    throw new Error(`Invalid value '${value}' for ${keyword}: expected seconds`);
+  }
+  return Number(value);
+}
+
+/**
+ * Reads a connection string back into connection fields, keeping keywords
+ * without a field of their own in `extra_options`.
+ */
+export function parseConnectionString(
+  connectionString: string,
+): Omit<SqlConnection, "name"> {
+  const pairs = parseConnectionStringPairs(connectionString);
+  // Takes the value of the first synonym present and drops them all
+  const take = (keywords: string[]) => {
+    const found = keywords
+      .map((keyword) => pairs.find(([k]) => k.toLowerCase() === keyword))
+      .find((pair) => pair !== undefined)?.[1];
+    for (let i = pairs.length - 1; i >= 0; i--) {
+      if (keywords.includes(pairs[i][0].toLowerCase())) {
+        pairs.splice(i, 1);
+      }
+    }
+    return found;
+  };
+
+  const serverValue = take(SERVER_KEYWORDS);
+  if (serverValue === undefined) {
+    throw new Error("The connection string has no Server");
+  }
+  const portMatch = /^(.*),\s*(\d+)\s*$/.exec(serverValue);
+  const integrated = take(INTEGRATED_SECURITY_KEYWORDS);
+  // Modes without a field of their own stay in extra_options
+  const authenticationValue = pairs.find(
+    ([k]) => k.toLowerCase() === AUTHENTICATION_KEYWORDS[0],
+  )?.[1];
+  let authentication =
+    authenticationValue !== undefined
+      ? authMethodFromKeyword(authenticationValue)
+      : undefined;
+  if (authentication !== undefined) {
+    take(AUTHENTICATION_KEYWORDS);
+  } else if (
+    integrated !== undefined &&
+    parseBool("Integrated Security", integrated)
+  ) {
+    authentication = "integrated";
+  }
+  const encrypt = take(ENCRYPT_KEYWORDS);
+  const strict = encrypt?.trim().toLowerCase() === "strict";
+  const trust = take(TRUST_SERVER_CERTIFICATE_KEYWORDS);
+  const connectTimeout = take(CONNECT_TIMEOUT_KEYWORDS);
+  const commandTimeout = take(COMMAND_TIMEOUT_KEYWORDS);
+  const intent = take(APPLICATION_INTENT_KEYWORDS)?.trim().toLowerCase();
+  if (intent !== undefined && intent !== "readonly" && intent !== "readwrite") {
+    throw new Error(`Invalid value '${intent}' for ApplicationIntent`);
+  }
+  const multiSubnetFailover = take(MULTI_SUBNET_FAILOVER_KEYWORDS);
+
+  return {
+    server: portMatch ? portMatch[1] : serverValue,
+    port: portMatch ? portMatch[2] : undefined,
+    database_name: take(DATABASE_KEYWORDS) || undefined,
+    username: take(USER_KEYWORDS) || undefined,
+    password: take(PASSWORD_KEYWORDS) || undefined,
+    authentication: authentication ?? "sql_password",
+    encrypt: encrypt === undefined || strict || parseBool("Encrypt", encrypt),
+    strict_encryption: strict,
+    trust_server_cert:
+      trust !== undefined && parseBool("TrustServerCertificate", trust),
+    connect_timeout:
+      connectTimeout === undefined
+        ? undefined
+        : parseSeconds("Connect Timeout", connectTimeout),
+    command_timeout:
+      commandTimeout === undefined
+        ? undefined
+        : parseSeconds("Command Timeout", commandTimeout),
+    read_only_intent: intent === "readonly",
+    multi_subnet_failover:
+      multiSubnetFailover !== undefined &&
+      parseBool("MultiSubnetFailover", multiSubnetFailover),
+    host_name_in_certificate:
+      take(HOST_NAME_IN_CERTIFICATE_KEYWORDS) || undefined,
+    server_certificate: take(SERVER_CERTIFICATE_KEYWORDS) || undefined,
+    application_name: take(APPLICATION_NAME_KEYWORDS) || undefined,
+    extra_options: pairs.length > 0 ? formatPairs(pairs) : undefined,
+  };
+}
 
 /**
  * Builds a SQL Server connection string from connection properties
  */
-export function buildConnectionString(connection: SqlConnection): string {
+export function buildConnectionString(
+  connection: SqlConnection | SavedConnection,
+): string {
   // If connection_string is already provided, use it
   if (connection.connection_string) {
     return connection.connection_string;
   }
 
   // Build connection string from individual properties
-  const parts: string[] = [];
+  const pairs: [string, string][] = [];
 
   // Server and port
   if (connection.port) {
-    parts.push(`Server=${connection.server},${connection.port}`);
+    pairs.push(["Server", `${connection.server},${connection.port}`]);
   } else {
-    parts.push(`Server=${connection.server}`);
+    pairs.push(["Server", connection.server]);
   }
 
   // Database
   if (connection.database_name) {
-    parts.push(`Database=${connection.database_name}`);
+    pairs.push(["Database", connection.database_name]);
   }
 
   // Authentication
-  if (connection.use_windows_auth) {
-    parts.push("Integrated Security=true");
-  } else {
-    if (connection.username) {
-      parts.push(`User Id=${connection.username}`);
-    }
-    if (connection.password) {
-      parts.push(`Password=${connection.password}`);
-    }
+  // An access token is passed to sqlpackage separately, never in the string
+  const authentication = connection.authentication ?? "sql_password";
+  if (authentication === "integrated") {
+    pairs.push(["Integrated Security", "true"]);
+  }
+  const authenticationValue = AUTHENTICATION_VALUES[authentication];
+  if (authenticationValue) {
+    pairs.push(["Authentication", authenticationValue]);
+  }
+  if (usernameLabel(authentication) && connection.username) {
+    pairs.push(["User Id", connection.username]);
+  }
+  if (
+    authentication !== "access_token" &&
+    passwordLabel(authentication) &&
+    "password" in connection &&
+    connection.password
+  ) {
+    pairs.push(["Password", connection.password]);
   }
 
   // Encryption and trust settings
-  parts.push(`Encrypt=${connection.encrypt ? "true" : "false"}`);
-  parts.push(
-    `TrustServerCertificate=${connection.trust_server_cert ? "true" : "false"}`,
-  );
+  pairs.push([
+    "Encrypt",
+    connection.strict_encryption
+      ? "Strict"
+      : connection.encrypt
+        ? "true"
+        : "false",
+  ]);
+  pairs.push([
+    "TrustServerCertificate",
+    connection.trust_server_cert ? "true" : "false",
+  ]);
+  if (connection.host_name_in_certificate) {
+    pairs.push(["HostNameInCertificate", connection.host_name_in_certificate]);
+  }
+  if (connection.server_certificate) {
+    pairs.push(["ServerCertificate", connection.server_certificate]);
+  }
+
+  // Timeouts, routing and identification
+  if (connection.connect_timeout != null) {
+    pairs.push(["Connect Timeout", String(connection.connect_timeout)]);
+  }
+  if (connection.command_timeout != null) {
+    pairs.push(["Command Timeout", String(connection.command_timeout)]);
+  }
+  if (connection.read_only_intent) {
+    pairs.push(["ApplicationIntent", "ReadOnly"]);
+  }
+  if (connection.multi_subnet_failover) {
+    pairs.push(["MultiSubnetFailover", "True"]);
+  }
+  if (connection.application_name) {
+    pairs.push(["Application Name", connection.application_name]);
+  }
+
+  // Keywords without a field of their own, validated when the connection was saved
+  if (connection.extra_options) {
+    for (const [keyword, value] of parseConnectionStringPairs(
+      connection.extra_options,
+    )) {
+      if (RESERVED_EXTRA_KEYWORDS.includes(keyword.toLowerCase())) {
+        continue;
+      }
+      const existing = pairs.find(
+        ([k]) => k.toLowerCase() === keyword.toLowerCase(),
+      );
+      if (existing) {
+        existing[1] = value;
+      } else {
+        pairs.push([keyword, value]);
+      }
+    }
+  }
 
-  return parts.join(";");
+  return formatPairs(pairs);
 }